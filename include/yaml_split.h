@@ -0,0 +1,51 @@
+/*
+ * The C interface to yaml-split's chunker, built with the "ffi" feature:
+ *
+ *     cargo rustc --lib --release --features ffi --crate-type cdylib
+ *
+ * See the documentation of the ffi module for the details of each function.
+ */
+
+#ifndef YAML_SPLIT_H
+#define YAML_SPLIT_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An open YAML stream, and the document last read from it. */
+typedef struct ys_stream ys_stream;
+
+/*
+ * Opens the YAML stream in the file at path, or standard input if path is
+ * NULL. Never returns NULL; if the stream can't be opened, the first call to
+ * ys_next_doc fails.
+ */
+ys_stream *ys_open(const char *path);
+
+/*
+ * Reads the next document, returning 1 if there is one, 0 at the end of the
+ * stream, and -1 if reading fails.
+ */
+int ys_next_doc(ys_stream *stream);
+
+/*
+ * Returns the UTF-8 text of the document last read, which isn't
+ * NUL-terminated, and stores its length in *len unless len is NULL. The text
+ * stays valid until the next call to ys_next_doc or ys_close.
+ */
+const char *ys_doc_bytes(const ys_stream *stream, size_t *len);
+
+/* Returns why the stream failed, or NULL if it hasn't. */
+const char *ys_error(const ys_stream *stream);
+
+/* Closes the stream, which may be NULL. */
+void ys_close(ys_stream *stream);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,101 @@
+//! A single memory budget shared by everything that buffers input.
+//!
+//! Splitting normally streams, holding little more than the current document.
+//! Some options can't: `--reverse` and `--shuffle` hold the entire stream,
+//! `--select` with a position from the end holds the latest documents, JSON
+//! input is read in full before it's split, and the chunker holds a document
+//! until its end even when the document is huge. Each of these holds a
+//! [`Claim`] on the budget for the bytes it buffers, and fails cleanly once the
+//! total would exceed the limit, rather than growing until the system runs out
+//! of memory.
+//!
+//! The budget counts the bytes of buffered YAML and JSON text, not every
+//! allocation, so the process as a whole needs somewhat more than the limit.
+
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+static USED: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the number of bytes that all claims together may hold.
+pub(crate) fn set_limit(limit: usize) {
+	LIMIT.store(limit, Ordering::Relaxed);
+}
+
+/// Parses a size in bytes, with an optional binary suffix of `K`, `M`, or `G`
+/// as in `512M`.
+pub(crate) fn parse_size(text: &str) -> Result<usize, String> {
+	let (digits, shift) = match text.as_bytes().last() {
+		Some(b'K' | b'k') => (&text[..text.len() - 1], 10),
+		Some(b'M' | b'm') => (&text[..text.len() - 1], 20),
+		Some(b'G' | b'g') => (&text[..text.len() - 1], 30),
+		_ => (text, 0),
+	};
+	let size = digits
+		.parse::<usize>()
+		.map_err(|_| format!("invalid size {text:?}"))?;
+	size.checked_mul(1 << shift)
+		.ok_or_else(|| format!("size {text:?} is too large"))
+}
+
+/// A part of the budget held by one buffer, released when dropped.
+#[derive(Default)]
+pub(crate) struct Claim(usize);
+
+impl Claim {
+	/// Grows the claim to `len` bytes, or fails if that would exceed the
+	/// limit. `what` describes the buffering for the error, as in "reading the
+	/// JSON input".
+	pub(crate) fn grow_to(&mut self, len: usize, what: &str) -> io::Result<()> {
+		let Some(more) = len.checked_sub(self.0).filter(|&more| more > 0) else {
+			return Ok(());
+		};
+		let limit = LIMIT.load(Ordering::Relaxed);
+		let used = USED.fetch_add(more, Ordering::Relaxed).saturating_add(more);
+		if used > limit {
+			USED.fetch_sub(more, Ordering::Relaxed);
+			return Err(io::Error::new(
+				io::ErrorKind::OutOfMemory,
+				format!("{what} would exceed the memory limit of {limit} bytes"),
+			));
+		}
+		self.0 = len;
+		Ok(())
+	}
+
+	/// Returns the number of bytes claimed.
+	pub(crate) fn len(&self) -> usize {
+		self.0
+	}
+
+	/// Shrinks the claim to `len` bytes, if it's larger.
+	pub(crate) fn shrink_to(&mut self, len: usize) {
+		if let Some(less) = self.0.checked_sub(len).filter(|&less| less > 0) {
+			USED.fetch_sub(less, Ordering::Relaxed);
+			self.0 = len;
+		}
+	}
+}
+
+impl Drop for Claim {
+	fn drop(&mut self) {
+		self.shrink_to(0);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_sizes() {
+		assert_eq!(parse_size("1000"), Ok(1000));
+		assert_eq!(parse_size("4k"), Ok(4096));
+		assert_eq!(parse_size("512M"), Ok(512 << 20));
+		assert_eq!(parse_size("2G"), Ok(2 << 30));
+		for text in ["", "M", "-1", "1.5M", "1T"] {
+			assert!(parse_size(text).is_err(), "{text:?} should be invalid");
+		}
+	}
+}
@@ -0,0 +1,131 @@
+//! Checkpoints that let a split of a huge stream resume after an interruption.
+//!
+//! A checkpoint file records how many documents have been written so far, and
+//! the offset in the UTF-8 stream just past the last of them. It's a small
+//! text file of `key value` lines, replaced atomically on each update so that
+//! a crash never leaves it half written.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::chunker::Document;
+
+/// How often to record progress while documents are being written.
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// The progress of a split.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Checkpoint {
+	/// The number of documents completely written.
+	pub(crate) documents: usize,
+	/// The offset just past the last document written.
+	pub(crate) offset: u64,
+}
+
+impl Checkpoint {
+	/// Loads the checkpoint at `path`, or returns `None` if it doesn't exist.
+	pub(crate) fn load(path: &Path) -> io::Result<Option<Self>> {
+		let text = match fs::read_to_string(path) {
+			Ok(text) => text,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+			Err(err) => return Err(err),
+		};
+		let invalid = || {
+			io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("{path} is not a checkpoint file", path = path.display()),
+			)
+		};
+		let (mut documents, mut offset) = (None, None);
+		for line in text.lines() {
+			match line.split_once(' ') {
+				Some(("documents", n)) => documents = Some(n.parse().map_err(|_| invalid())?),
+				Some(("offset", n)) => offset = Some(n.parse().map_err(|_| invalid())?),
+				_ => return Err(invalid()),
+			}
+		}
+		match (documents, offset) {
+			(Some(documents), Some(offset)) => Ok(Some(Self { documents, offset })),
+			_ => Err(invalid()),
+		}
+	}
+
+	fn save(&self, path: &Path) -> io::Result<()> {
+		let mut name = path.file_name().unwrap_or_default().to_owned();
+		name.push(".tmp");
+		let tmp = path.with_file_name(name);
+		fs::write(
+			&tmp,
+			format!(
+				"documents {documents}\noffset {offset}\n",
+				documents = self.documents,
+				offset = self.offset,
+			),
+		)?;
+		fs::rename(&tmp, path)
+	}
+}
+
+/// Records progress to a checkpoint file as documents are written.
+pub(crate) struct Checkpointer {
+	path: PathBuf,
+	current: Checkpoint,
+	last_saved: Instant,
+}
+
+impl Checkpointer {
+	/// Creates a checkpointer that continues from `start`.
+	pub(crate) fn new(path: PathBuf, start: Checkpoint) -> Self {
+		Self {
+			path,
+			current: start,
+			last_saved: Instant::now(),
+		}
+	}
+
+	/// Notes that `doc` has been written to `output`, and records progress if
+	/// enough time has passed since the last record.
+	///
+	/// `output` is flushed before each record, so that a checkpoint never
+	/// claims more than has really been written.
+	pub(crate) fn completed(&mut self, output: &mut dyn Write, doc: &Document) -> io::Result<()> {
+		self.current.documents += 1;
+		self.current.offset = doc.source().end;
+		if self.last_saved.elapsed() >= INTERVAL {
+			self.finish(output)?;
+			self.last_saved = Instant::now();
+		}
+		Ok(())
+	}
+
+	/// Flushes `output` and records the final progress.
+	pub(crate) fn finish(&self, output: &mut dyn Write) -> io::Result<()> {
+		output.flush()?;
+		self.current.save(&self.path)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::env;
+	use std::process;
+
+	#[test]
+	fn checkpoint_round_trip() {
+		let path = env::temp_dir().join(format!("yaml-split-checkpoint-{}", process::id()));
+		assert_eq!(Checkpoint::load(&path).unwrap(), None);
+		let checkpoint = Checkpoint {
+			documents: 3,
+			offset: 1234,
+		};
+		checkpoint.save(&path).unwrap();
+		assert_eq!(Checkpoint::load(&path).unwrap(), Some(checkpoint));
+		fs::write(&path, "documents 3\n").unwrap();
+		assert!(Checkpoint::load(&path).is_err());
+		fs::remove_file(&path).unwrap();
+	}
+}
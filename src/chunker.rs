@@ -12,24 +12,53 @@
 //! I would love to have the time and energy someday to implement a true
 //! streaming YAML parser, whatever that looks like.
 
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt::{self, Display};
 use std::io::{self, Read};
 use std::mem;
+use std::ops::{Range, RangeInclusive};
 
-mod parser;
+use crate::budget::Claim;
+use crate::encoding::SourceMap;
+pub use crate::libyaml::Mark;
 
-use self::parser::{
-	Parser, YAML_DOCUMENT_END_EVENT, YAML_DOCUMENT_START_EVENT, YAML_MAPPING_START_EVENT,
-	YAML_SCALAR_EVENT, YAML_SEQUENCE_START_EVENT, YAML_STREAM_END_EVENT,
+use crate::libyaml::{
+	Event, Parser, YAML_ALIAS_EVENT, YAML_DOCUMENT_END_EVENT, YAML_DOCUMENT_START_EVENT,
+	YAML_MAPPING_END_EVENT, YAML_MAPPING_START_EVENT, YAML_PLAIN_SCALAR_STYLE, YAML_SCALAR_EVENT,
+	YAML_SEQUENCE_END_EVENT, YAML_SEQUENCE_START_EVENT, YAML_STREAM_END_EVENT,
 };
+use crate::node::{self, Node};
 
 /// An iterator over individual raw documents in a UTF-8-encoded YAML stream.
-pub(super) struct Chunker<R>
+pub struct Chunker<R>
 where
 	R: Read,
 {
 	parser: Parser<ChunkReader<R>>,
+	peeked: Option<Option<io::Result<Document>>>,
 	last_document: Option<Document>,
 	current_document_kind: Option<DocumentKind>,
+	current_document_empty: bool,
+	current_document_tag: Option<String>,
+	current_document_start: u64,
+	current_document_original_start: u64,
+	current_document_start_mark: Mark,
+	current_document_features: Option<Features>,
+	collect_features: bool,
+	current_document_stats: Option<Stats>,
+	collect_stats: bool,
+	comments: Option<CommentPlacement>,
+	/// The offset where the content of the current document ends so far,
+	/// for placing the comments after it.
+	current_content_end: u64,
+	/// Whether each collection open in the current document is in flow
+	/// style, whose end is part of the content.
+	open_collections: Vec<bool>,
+	/// Whether the last document ended with a `...` marker.
+	after_end_marker: bool,
 	stream_ended: bool,
 }
 
@@ -44,23 +73,129 @@ where
 	/// individual documents. However, `Chunker` requires a UTF-8 stream without
 	/// BOMs. Consider using the [`encoding`](super::encoding) module to
 	/// re-encode non-UTF-8 streams.
-	pub(super) fn new(reader: R) -> Self {
+	pub fn new(reader: R) -> Self {
 		Self {
 			parser: Parser::new(ChunkReader::new(reader)),
+			peeked: None,
 			last_document: None,
 			current_document_kind: None,
+			current_document_empty: false,
+			current_document_tag: None,
+			current_document_start: 0,
+			current_document_original_start: 0,
+			current_document_start_mark: Mark::default(),
+			current_document_features: None,
+			collect_features: false,
+			current_document_stats: None,
+			collect_stats: false,
+			comments: None,
+			current_content_end: 0,
+			open_collections: vec![],
+			after_end_marker: false,
 			stream_ended: false,
 		}
 	}
+
+	/// Configures the chunker to report offsets in the original input of an
+	/// [`Encoder`](super::encoding::Encoder) that produced the stream, using
+	/// the encoder's source map.
+	pub fn with_source_map(mut self, map: SourceMap) -> Self {
+		let reader = self.parser.reader_mut();
+		reader.source_map = map;
+		reader.source_offset = map.start();
+		self
+	}
+
+	/// Configures the chunker to report document offsets as if the reader
+	/// began `offset` bytes into the stream, after `documents` earlier
+	/// documents, as when resuming from the middle of a file.
+	pub fn starting_at(mut self, documents: usize, offset: u64) -> Self {
+		let reader = self.parser.reader_mut();
+		reader.base_offset = offset;
+		reader.documents = documents;
+		self
+	}
+
+	/// Configures the chunker to fail on any document longer than `limit`
+	/// bytes, with a [`LimitExceeded`] error.
+	///
+	/// When capturing content, the chunker fails as soon as it has read enough
+	/// of a document to know that it's too long, so a huge document never has
+	/// to fit in memory.
+	pub fn max_document_len(mut self, limit: usize) -> Self {
+		self.parser.reader_mut().limit = Some(limit);
+		self
+	}
+
+	/// Configures the chunker to cut any document longer than the limit set by
+	/// [`max_document_len`](Self::max_document_len) down to the limit, rather
+	/// than failing; see [`Document::full_len`].
+	///
+	/// As with failing, the chunker never holds much more of a long document
+	/// than the part that it keeps.
+	pub fn truncate_long_documents(mut self) -> Self {
+		self.parser.reader_mut().truncate = true;
+		self
+	}
+
+	/// Configures the chunker to discard the content of each document rather
+	/// than capturing it.
+	///
+	/// Documents produced by a discarding chunker have empty content, but are
+	/// otherwise identical to those produced by a capturing chunker. This is
+	/// useful when only the boundaries of documents are of interest, as it
+	/// avoids buffering the raw stream.
+	pub fn discard_content(mut self) -> Self {
+		self.parser.reader_mut().capture = false;
+		self
+	}
+
+	/// Configures the chunker to collect the anchors, aliases, and tags that
+	/// appear in each document; see [`Document::features`].
+	pub fn collect_features(mut self) -> Self {
+		self.collect_features = true;
+		self
+	}
+
+	/// Configures the chunker to count the nodes in each document; see
+	/// [`Document::stats`].
+	pub fn collect_stats(mut self) -> Self {
+		self.collect_stats = true;
+		self
+	}
+
+	/// Configures the chunker to place the comments between documents
+	/// according to `placement`, rather than leaving them wherever the
+	/// parser's document boundaries happen to fall.
+	///
+	/// Without a placement, comments before the first document or after a
+	/// `...` marker are dropped, and those before a `---` marker stay with
+	/// the document before them.
+	pub fn place_comments(mut self, placement: CommentPlacement) -> Self {
+		self.comments = Some(placement);
+		self.parser.reader_mut().placing_comments = true;
+		self
+	}
 }
 
-impl<R> Iterator for Chunker<R>
+impl<R> Chunker<R>
 where
 	R: Read,
 {
-	type Item = io::Result<Document>;
+	/// Returns the next document without consuming it, reading it from the
+	/// stream if it hasn't been read already.
+	///
+	/// The document stays buffered until the next call to `next`, which
+	/// returns it.
+	pub fn peek(&mut self) -> Option<&io::Result<Document>> {
+		if self.peeked.is_none() {
+			let next = self.read_document();
+			self.peeked = Some(next);
+		}
+		self.peeked.as_ref().unwrap().as_ref()
+	}
 
-	fn next(&mut self) -> Option<Self::Item> {
+	fn read_document(&mut self) -> Option<io::Result<Document>> {
 		if self.stream_ended {
 			return None;
 		}
@@ -68,9 +203,22 @@ where
 		loop {
 			let event = match self.parser.next_event() {
 				Ok(event) => event,
+				Err(err) if err.get_ref().is_some_and(|err| err.is::<LimitExceeded>()) => {
+					return Some(Err(err))
+				}
 				Err(err) => return Some(Err(io::Error::new(io::ErrorKind::InvalidData, err))),
 			};
 
+			if let Some(features) = self.current_document_features.as_mut() {
+				features.record(&event);
+			}
+			if let Some(stats) = self.current_document_stats.as_mut() {
+				stats.record(&event);
+			}
+			if self.comments.is_some() {
+				self.record_content_end(&event);
+			}
+
 			// Note that while we chunk on DOCUMENT_END events, we don't emit
 			// the chunk until the next DOCUMENT_START or STREAM_END. The parser
 			// sometimes sees valid documents in non-YAML inputs, and only fails
@@ -80,70 +228,736 @@ where
 			match event.event_type() {
 				YAML_DOCUMENT_START_EVENT => {
 					let offset = event.start_offset();
-					self.parser.reader_mut().trim_to_offset(offset);
+					let mut start = offset;
+					let mut start_mark = event.start_mark();
+					match self.comments {
+						Some(CommentPlacement::AttachPrevious) if self.last_document.is_some() => {
+							self.attach_gap(offset, start_mark);
+						}
+						Some(CommentPlacement::AttachPrevious | CommentPlacement::AttachNext) => {
+							let reader = self.parser.reader_mut();
+							start = reader.gap_start(offset, self.after_end_marker);
+							start_mark = reader.mark_before(start, offset, start_mark);
+							// The rest of the line with a `...` marker still
+							// belongs to the document that it ends.
+							self.attach_gap(start, start_mark);
+						}
+						_ => {}
+					}
+					self.parser.reader_mut().trim_to_offset(start);
 					self.current_document_kind = None;
+					self.current_document_start = start;
+					self.current_document_original_start = self.parser.reader_mut().source_offset;
+					self.current_document_start_mark = start_mark;
+					self.current_document_features = self.collect_features.then(Features::default);
+					self.current_document_stats = self.collect_stats.then(Stats::default);
 					if let Some(doc) = self.last_document.take() {
 						return Some(Ok(doc));
 					}
 				}
 				YAML_SCALAR_EVENT => {
+					if self.current_document_kind.is_none() {
+						let (value, style) = event.scalar();
+						self.current_document_empty = value.is_empty()
+							&& style == YAML_PLAIN_SCALAR_STYLE
+							&& event.anchor().is_none()
+							&& event.tag().is_none();
+						self.current_document_tag = event.tag().map(Cow::into_owned);
+					}
 					self.current_document_kind
 						.get_or_insert(DocumentKind::Scalar);
 				}
 				YAML_SEQUENCE_START_EVENT | YAML_MAPPING_START_EVENT => {
+					if self.current_document_kind.is_none() {
+						self.current_document_tag = event.tag().map(Cow::into_owned);
+					}
 					self.current_document_kind
-						.get_or_insert(DocumentKind::Collection);
+						.get_or_insert(match event.event_type() {
+							YAML_SEQUENCE_START_EVENT => DocumentKind::Sequence,
+							_ => DocumentKind::Mapping,
+						});
 				}
 				YAML_DOCUMENT_END_EVENT => {
-					let chunk = self.parser.reader_mut().take_to_offset(event.end_offset());
+					let mut offset = event.end_offset();
+					let mut end_mark = event.end_mark();
+					let reader = self.parser.reader_mut();
+					let len = offset - self.current_document_start;
+					let limit = reader.limit.filter(|&limit| len > limit as u64);
+					if limit.is_some() && !reader.truncate {
+						return Some(Err(reader.limit_exceeded()));
+					}
+					// An implicit end falls wherever the next document starts,
+					// so the comments before that belong to the next document
+					// unless they're attached to this one.
+					self.after_end_marker = event.start_offset() != offset;
+					if !self.after_end_marker
+						&& limit.is_none() && matches!(
+						self.comments,
+						Some(CommentPlacement::AttachNext | CommentPlacement::Strip)
+					) {
+						let end = reader.body_end(self.current_content_end, offset);
+						end_mark = reader.mark_before(end, offset, end_mark);
+						offset = end;
+					}
+					let chunk = reader.take_to_offset(offset);
+					let base = reader.base_offset;
 					self.last_document = Some(Document {
 						content: String::from_utf8(chunk).unwrap(),
 						kind: self.current_document_kind.take().unwrap(),
+						empty: mem::take(&mut self.current_document_empty),
+						tag: self.current_document_tag.take(),
+						full_len: None,
+						name: None,
+						id: None,
+						input: None,
+						index: Some(reader.documents - 1),
+						source: base + self.current_document_start..base + offset,
+						original: base + self.current_document_original_start
+							..base + reader.source_offset,
+						position: Some(self.current_document_start_mark..end_mark),
+						features: self.current_document_features.take(),
+						stats: self.current_document_stats.take(),
+						value: OnceCell::new(),
 					});
+					if let (Some(limit), Some(doc)) = (limit, self.last_document.as_mut()) {
+						doc.truncate(limit, len);
+					}
 				}
 				YAML_STREAM_END_EVENT => {
 					self.stream_ended = true;
+					// With no next document, the last one keeps the comments
+					// after it.
+					if matches!(
+						self.comments,
+						Some(CommentPlacement::AttachPrevious | CommentPlacement::AttachNext)
+					) {
+						self.attach_gap(event.start_offset(), event.start_mark());
+					}
 					return self.last_document.take().map(Ok);
 				}
 				_ => {}
 			};
 		}
 	}
+
+	/// Tracks where the content of the current document ends, leaving out
+	/// the events that don't mark any text of their own: empty scalars, which
+	/// are marked where the next token starts, and the ends of block
+	/// collections, which are marked where the next line with less
+	/// indentation starts.
+	fn record_content_end(&mut self, event: &Event) {
+		let end = event.end_offset();
+		let content = match event.event_type() {
+			YAML_DOCUMENT_START_EVENT => {
+				self.current_content_end = end;
+				self.open_collections.clear();
+				return;
+			}
+			YAML_SEQUENCE_START_EVENT | YAML_MAPPING_START_EVENT => {
+				self.open_collections.push(event.is_flow());
+				true
+			}
+			YAML_SEQUENCE_END_EVENT | YAML_MAPPING_END_EVENT => {
+				self.open_collections.pop().unwrap_or_default()
+			}
+			YAML_SCALAR_EVENT => {
+				let (value, style) = event.scalar();
+				!value.is_empty()
+					|| style != YAML_PLAIN_SCALAR_STYLE
+					|| event.anchor().is_some()
+					|| event.tag().is_some()
+			}
+			YAML_ALIAS_EVENT => true,
+			_ => false,
+		};
+		if content {
+			self.current_content_end = self.current_content_end.max(end);
+		}
+	}
+
+	/// Appends the text before `offset` to the last document, which then ends
+	/// at `offset` and `mark`, unless it was cut short.
+	fn attach_gap(&mut self, offset: u64, mark: Mark) {
+		let Some(doc) = self.last_document.as_mut() else {
+			return;
+		};
+		let reader = self.parser.reader_mut();
+		if doc.full_len.is_some() || offset <= reader.captured_start_offset {
+			return;
+		}
+		let gap = reader.take_gap(offset);
+		doc.content.push_str(&String::from_utf8(gap).unwrap());
+		doc.source.end = reader.base_offset + offset;
+		doc.original.end = reader.base_offset + reader.source_offset;
+		if let Some(position) = doc.position.as_mut() {
+			position.end = mark;
+		}
+	}
+}
+
+/// The contract shared by the backends that find the documents in a UTF-8
+/// YAML stream.
+///
+/// [`Chunker`] finds them by parsing the stream, which checks that it's
+/// well-formed and learns the most about each document. Another backend may
+/// find them some other way, like from their markers alone, but yields the
+/// same documents in order, with the same content, indexes, and locations.
+pub(crate) trait Chunking: Iterator<Item = io::Result<Document>> {
+	/// Configures the backend to fail on any document longer than `limit`
+	/// bytes, with a [`LimitExceeded`] error, or to cut it down to the limit
+	/// if `truncate` is set.
+	fn limit_document_len(self, limit: usize, truncate: bool) -> Self
+	where
+		Self: Sized;
+}
+
+impl<R> Chunking for Chunker<R>
+where
+	R: Read,
+{
+	fn limit_document_len(self, limit: usize, truncate: bool) -> Self {
+		let chunker = self.max_document_len(limit);
+		match truncate {
+			true => chunker.truncate_long_documents(),
+			false => chunker,
+		}
+	}
+}
+
+/// Where a [`Chunker`] puts the comments between documents; see
+/// [`Chunker::place_comments`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommentPlacement {
+	/// Comments end the document before them, and those before the first
+	/// document start it.
+	AttachPrevious,
+	/// Comments start the document after them, and those after the last
+	/// document end it.
+	AttachNext,
+	/// Comments between documents are dropped.
+	Strip,
+}
+
+impl<R> Iterator for Chunker<R>
+where
+	R: Read,
+{
+	type Item = io::Result<Document>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		match self.peeked.take() {
+			Some(peeked) => peeked,
+			None => self.read_document(),
+		}
+	}
+}
+
+/// The error for a document longer than the limit set by
+/// [`Chunker::max_document_len`].
+#[derive(Debug)]
+pub struct LimitExceeded {
+	/// The one-based number of the document in the stream.
+	pub index: usize,
+	/// The byte offset of the document in the original input.
+	pub offset: u64,
+	/// The limit that the document exceeded, in bytes.
+	pub limit: usize,
+}
+
+impl Display for LimitExceeded {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"document {index} at byte {offset} is longer than the limit of {limit} bytes",
+			index = self.index,
+			offset = self.offset,
+			limit = self.limit,
+		)
+	}
 }
 
+impl Error for LimitExceeded {}
+
 /// A UTF-8 encoded YAML document.
-pub(super) struct Document {
+pub struct Document {
 	content: String,
 	kind: DocumentKind,
+	empty: bool,
+	tag: Option<String>,
+	full_len: Option<u64>,
+	name: Option<String>,
+	id: Option<String>,
+	input: Option<Input>,
+	index: Option<usize>,
+	source: Range<u64>,
+	original: Range<u64>,
+	position: Option<Range<Mark>>,
+	features: Option<Features>,
+	stats: Option<Stats>,
+	value: OnceCell<Node>,
+}
+
+/// The input that a document came from, in a run that reads more than one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct Input {
+	/// The name of the input for display to users, typically its path.
+	pub(super) name: String,
+	/// The zero-based position of the input among all of the run's inputs.
+	pub(super) index: usize,
 }
 
-/// The type of content contained in a YAML document.
-pub(super) enum DocumentKind {
+/// The type of content at the root of a YAML document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocumentKind {
+	/// A scalar, including the null scalar of an empty document.
 	Scalar,
-	Collection,
+	/// A sequence.
+	Sequence,
+	/// A mapping.
+	Mapping,
+}
+
+impl DocumentKind {
+	/// Returns a short lowercase name for the kind, for display to users.
+	pub fn name(self) -> &'static str {
+		match self {
+			DocumentKind::Scalar => "scalar",
+			DocumentKind::Sequence => "sequence",
+			DocumentKind::Mapping => "mapping",
+		}
+	}
+}
+
+/// The anchors defined, aliases used, and tags present in a document.
+///
+/// Tags are resolved through the document's `%TAG` directives, so `!!str` is
+/// reported as `tag:yaml.org,2002:str`. Non-specific tags like `!` are
+/// reported as written.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Features {
+	/// The names of the anchors defined in the document.
+	pub anchors: BTreeSet<String>,
+	/// The names of the anchors that aliases in the document refer to.
+	pub aliases: BTreeSet<String>,
+	/// The tags of the nodes in the document.
+	pub tags: BTreeSet<String>,
+}
+
+impl Features {
+	fn record(&mut self, event: &Event) {
+		match event.event_type() {
+			YAML_ALIAS_EVENT => {
+				if let Some(anchor) = event.anchor() {
+					self.aliases.insert(anchor.into_owned());
+				}
+			}
+			YAML_SCALAR_EVENT | YAML_SEQUENCE_START_EVENT | YAML_MAPPING_START_EVENT => {
+				if let Some(anchor) = event.anchor() {
+					self.anchors.insert(anchor.into_owned());
+				}
+				if let Some(tag) = event.tag() {
+					self.tags.insert(tag.into_owned());
+				}
+			}
+			_ => {}
+		}
+	}
+}
+
+/// Counts of the nodes in a document, by kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+	/// The number of mappings.
+	pub mappings: usize,
+	/// The number of sequences.
+	pub sequences: usize,
+	/// The number of scalars.
+	pub scalars: usize,
+	/// The number of aliases.
+	pub aliases: usize,
+	/// The greatest number of collections that enclose any node, counting the
+	/// node itself if it's a collection. A scalar document has a depth of 0.
+	pub max_depth: usize,
+	depth: usize,
+}
+
+impl Stats {
+	/// Returns the total number of nodes, including aliases.
+	pub fn nodes(&self) -> usize {
+		self.mappings + self.sequences + self.scalars + self.aliases
+	}
+
+	fn record(&mut self, event: &Event) {
+		match event.event_type() {
+			YAML_ALIAS_EVENT => self.aliases += 1,
+			YAML_SCALAR_EVENT => self.scalars += 1,
+			YAML_SEQUENCE_START_EVENT | YAML_MAPPING_START_EVENT => {
+				match event.event_type() {
+					YAML_SEQUENCE_START_EVENT => self.sequences += 1,
+					_ => self.mappings += 1,
+				}
+				self.depth += 1;
+				self.max_depth = self.max_depth.max(self.depth);
+			}
+			YAML_SEQUENCE_END_EVENT | YAML_MAPPING_END_EVENT => self.depth -= 1,
+			_ => {}
+		}
+	}
 }
 
 impl Document {
+	/// Creates a document from text that was not read directly from a stream,
+	/// such as the output of a transformation, derived from the given byte
+	/// range of the stream.
+	pub(super) fn new(content: String, kind: DocumentKind, source: Range<u64>) -> Self {
+		Self {
+			content,
+			kind,
+			empty: false,
+			tag: None,
+			full_len: None,
+			name: None,
+			id: None,
+			input: None,
+			index: None,
+			original: source.clone(),
+			source,
+			position: None,
+			features: None,
+			stats: None,
+			value: OnceCell::new(),
+		}
+	}
+
+	/// Creates the document at the zero-based `index` of a stream that was
+	/// read without parsing it, as by the [`lexical`](crate::lexical) chunker.
+	pub(super) fn unparsed(
+		content: String,
+		kind: DocumentKind,
+		empty: bool,
+		index: usize,
+		source: Range<u64>,
+		original: Range<u64>,
+		position: Range<Mark>,
+	) -> Self {
+		Self {
+			empty,
+			index: Some(index),
+			original,
+			position: Some(position),
+			..Self::new(content, kind, source)
+		}
+	}
+
+	/// Creates a document from text derived from this document, such as part
+	/// of its content, with the same location in the stream.
+	pub(super) fn derive(&self, content: String, kind: DocumentKind) -> Self {
+		Self {
+			content,
+			kind,
+			empty: false,
+			tag: None,
+			full_len: None,
+			name: None,
+			id: None,
+			input: self.input.clone(),
+			index: None,
+			source: self.source(),
+			original: self.original(),
+			position: self.position(),
+			features: None,
+			stats: None,
+			value: OnceCell::new(),
+		}
+	}
+
+	/// Extends the location of this document to the end of `other`, for a
+	/// document derived from every document in a range.
+	pub(super) fn spanning(self, other: &Document) -> Self {
+		Self {
+			source: self.source.start..other.source.end,
+			original: self.original.start..other.original.end,
+			position: self
+				.position
+				.zip(other.position())
+				.map(|(start, end)| start.start..end.end),
+			..self
+		}
+	}
+
+	/// Extends the location of this document back to the start of `earlier`,
+	/// for a document that takes in an earlier one.
+	pub(super) fn extended_back(self, earlier: &Document) -> Self {
+		Self {
+			source: earlier.source.start..self.source.end,
+			original: earlier.original.start..self.original.end,
+			position: self
+				.position
+				.zip(earlier.position())
+				.map(|(end, start)| start.start..end.end),
+			..self
+		}
+	}
+
+	/// Sets the zero-based position of the document in the stream it was read
+	/// from.
+	pub(super) fn with_index(self, index: usize) -> Self {
+		Self {
+			index: Some(index),
+			..self
+		}
+	}
+
+	/// Sets the lines and columns of the stream that the document spans.
+	pub(super) fn with_position(self, position: Range<Mark>) -> Self {
+		Self {
+			position: Some(position),
+			..self
+		}
+	}
+
+	/// Sets a name that identifies the document in output, in place of its
+	/// position in the stream.
+	pub(super) fn with_name(self, name: String) -> Self {
+		Self {
+			name: Some(name),
+			..self
+		}
+	}
+
+	/// Sets an identifier for the document that's derived from its content.
+	pub(super) fn with_id(self, id: String) -> Self {
+		Self {
+			id: Some(id),
+			..self
+		}
+	}
+
+	/// Sets the input that the document came from.
+	pub(super) fn with_input(self, input: Input) -> Self {
+		Self {
+			input: Some(input),
+			..self
+		}
+	}
+
+	/// Replaces the text of the document, keeping its other properties.
+	pub(super) fn with_content(self, content: String) -> Self {
+		Self {
+			content,
+			value: OnceCell::new(),
+			..self
+		}
+	}
+
 	/// Returns the original text of the document.
-	pub(super) fn content(&self) -> &str {
+	pub fn content(&self) -> &str {
 		&self.content
 	}
 
+	/// Returns the root node of the document, with every alias replaced by a
+	/// copy of the node it refers to.
+	///
+	/// The document is parsed on the first call, and the result is kept for
+	/// later calls, so that options that each need the parsed document share
+	/// a single parse.
+	pub(super) fn value(&self) -> io::Result<&Node> {
+		if let Some(root) = self.value.get() {
+			return Ok(root);
+		}
+		let mut root = self.root()?;
+		root.expand_aliases()?;
+		Ok(self.value.get_or_init(|| root))
+	}
+
+	/// Parses the content of the document into its root node, as written.
+	///
+	/// This fails with [`io::ErrorKind::InvalidData`] for a document that was
+	/// cut to a size limit, whose content no longer says what the document
+	/// held, or for content that doesn't hold exactly one document.
+	pub(super) fn root(&self) -> io::Result<Node> {
+		let invalid = |problem: &str| {
+			let doc = match self.index {
+				Some(index) => format!("document {}", index + 1),
+				None => "a document".to_owned(),
+			};
+			io::Error::new(io::ErrorKind::InvalidData, format!("{doc} {problem}"))
+		};
+		if self.full_len.is_some() {
+			return Err(invalid("was cut to the size limit, so it can't be parsed"));
+		}
+		let mut roots = node::parse(&self.content)?;
+		match (roots.pop(), roots.is_empty()) {
+			(Some(root), true) => Ok(root),
+			_ => Err(invalid("doesn't hold exactly one YAML document")),
+		}
+	}
+
+	/// Returns the type of content at the root of the document.
+	pub fn kind(&self) -> DocumentKind {
+		self.kind
+	}
+
+	/// Returns whether the document was read from a stream with nothing at its
+	/// root, as in `---` followed by another marker, where a YAML parser
+	/// would see a null scalar.
+	///
+	/// A root of `~`, `null`, or `""` isn't empty, since it's written out.
+	/// Documents derived from others are never empty.
+	pub fn is_empty(&self) -> bool {
+		self.empty
+	}
+
+	/// Returns the tag of the document's root node, if it was read from a
+	/// stream with an explicit tag on its root.
+	///
+	/// Tags are resolved as for [`Features`]. Documents derived from others
+	/// have no tag, even if their content gives one.
+	pub fn tag(&self) -> Option<&str> {
+		self.tag.as_deref()
+	}
+
+	/// Returns the byte range of the UTF-8 stream that the document was read
+	/// from, or derived from in the case of a transformed document.
+	///
+	/// The range for a document read directly from a stream begins at its
+	/// first directive or marker, or at its content if it has neither, and
+	/// matches its content exactly.
+	pub fn source(&self) -> Range<u64> {
+		self.source.clone()
+	}
+
+	/// Returns the byte range of the original input that the document was
+	/// read or derived from.
+	///
+	/// This is the same as [`Document::source`] unless the chunker was given
+	/// a source map for a stream that was transcoded to UTF-8.
+	pub fn original(&self) -> Range<u64> {
+		self.original.clone()
+	}
+
+	/// Returns the lines and columns of the stream that the document was read
+	/// or derived from, if they're known.
+	///
+	/// Like the byte range of a document, this begins at its first directive
+	/// or marker, and ends after its last line break or its `...` marker.
+	pub fn position(&self) -> Option<Range<Mark>> {
+		self.position.clone()
+	}
+
+	/// Returns the one-based numbers of the first and last lines of the stream
+	/// that the document was read or derived from, if they're known.
+	///
+	/// The lines are those of [`Document::source`], so the first is that of
+	/// the document's first directive or marker, and the last is the one that
+	/// its last line break or `...` marker is on.
+	pub fn lines(&self) -> Option<RangeInclusive<u64>> {
+		let Range { start, end } = self.position.as_ref()?;
+		let last = match end.column {
+			0 if end.line > start.line => end.line,
+			_ => end.line + 1,
+		};
+		Some(start.line + 1..=last)
+	}
+
+	/// Returns the zero-based position of the document in the stream it was
+	/// read from, counting any documents skipped by
+	/// [`Chunker::starting_at`].
+	///
+	/// Documents derived from others have no index of their own, though
+	/// documents that were only reformatted keep theirs.
+	pub fn index(&self) -> Option<usize> {
+		self.index
+	}
+
+	/// Returns the anchors, aliases, and tags that appear in the document, if
+	/// the chunker collected them.
+	///
+	/// Documents derived from parts of other documents never have features,
+	/// though documents that were only reformatted keep them.
+	pub fn features(&self) -> Option<&Features> {
+		self.features.as_ref()
+	}
+
+	/// Returns the counts of the nodes in the document, if the chunker
+	/// collected them.
+	///
+	/// Like [`features`](Self::features), these are only kept for documents
+	/// that weren't derived from parts of others.
+	pub fn stats(&self) -> Option<Stats> {
+		self.stats
+	}
+
+	/// Returns the length in bytes of a document that was cut short, as by
+	/// [`Chunker::truncate_long_documents`].
+	pub fn full_len(&self) -> Option<u64> {
+		self.full_len
+	}
+
+	/// Cuts the content of a document that was `full_len` bytes long to at
+	/// most `limit` bytes, at a character boundary.
+	pub(super) fn truncate(&mut self, limit: usize, full_len: u64) {
+		let mut len = limit.min(self.content.len());
+		while !self.content.is_char_boundary(len) {
+			len -= 1;
+		}
+		self.content.truncate(len);
+		self.full_len = Some(full_len);
+		self.value = OnceCell::new();
+	}
+
+	/// Returns the name of the document, if it has one.
+	pub(super) fn name(&self) -> Option<&str> {
+		self.name.as_deref()
+	}
+
+	/// Returns the content-derived identifier of the document, if it has one.
+	pub(super) fn id(&self) -> Option<&str> {
+		self.id.as_deref()
+	}
+
+	/// Returns the input that the document came from, if it was set by a run
+	/// that reads more than one.
+	pub(super) fn input(&self) -> Option<&Input> {
+		self.input.as_ref()
+	}
+
 	/// Returns true if the content of the document is a scalar rather than a
 	/// collection (sequence or mapping).
-	pub(super) fn is_scalar(&self) -> bool {
+	pub fn is_scalar(&self) -> bool {
 		matches!(self.kind, DocumentKind::Scalar)
 	}
 }
 
 /// A reader that captures bytes read from a source and provides them in chunks.
+///
+/// The reader also tracks the offset in the original input of the start of the
+/// capture buffer, per its source map. When the source map isn't the identity,
+/// the reader keeps a buffer even when not capturing, since it can't translate
+/// the length of bytes that it no longer has.
+///
+/// When truncating long documents, the reader moves the start of a long
+/// document into a separate prefix buffer, and drops the bytes after it that
+/// the parser is already done with, counting their length in the original
+/// input as skipped.
 struct ChunkReader<R>
 where
 	R: Read,
 {
 	reader: R,
+	capture: bool,
 	captured: Vec<u8>,
 	captured_start_offset: u64,
+	source_map: SourceMap,
+	source_offset: u64,
+	base_offset: u64,
+	documents: usize,
+	limit: Option<usize>,
+	truncate: bool,
+	placing_comments: bool,
+	prefix: Option<Vec<u8>>,
+	skipped_source_len: u64,
+	claim: Claim,
 }
 
 impl<R> ChunkReader<R>
@@ -153,29 +967,176 @@ where
 	fn new(reader: R) -> Self {
 		Self {
 			reader,
+			capture: true,
 			captured: vec![],
 			captured_start_offset: 0,
+			source_map: SourceMap::IDENTITY,
+			source_offset: 0,
+			base_offset: 0,
+			documents: 0,
+			limit: None,
+			truncate: false,
+			placing_comments: false,
+			prefix: None,
+			skipped_source_len: 0,
+			claim: Claim::default(),
 		}
 	}
 
+	fn buffering(&self) -> bool {
+		self.capture || self.placing_comments || !self.source_map.is_identity()
+	}
+
+	/// Returns the number of bytes held in buffers.
+	fn held(&self) -> usize {
+		self.captured.len() + self.prefix.as_ref().map_or(0, Vec::len)
+	}
+
+	/// Keeps the first `limit` bytes of the document in the capture buffer as
+	/// its prefix, if there's no prefix yet, and drops everything but what the
+	/// parser may not have parsed yet.
+	fn drop_overflow(&mut self, limit: usize) {
+		let end = self.captured.len() - LIBYAML_READ_AHEAD;
+		if self.prefix.is_none() {
+			let mut len = limit;
+			while self.captured.get(len).is_some_and(|b| b & 0xC0 == 0x80) {
+				len -= 1;
+			}
+			self.prefix = Some(self.captured[..len].to_vec());
+		}
+		self.skipped_source_len += self.source_map.source_len(&self.captured[..end]);
+		self.captured.drain(..end);
+		self.captured_start_offset += end as u64;
+		self.claim.shrink_to(self.held());
+	}
+
+	/// Returns the error for a current document that's longer than the limit.
+	fn limit_exceeded(&self) -> io::Error {
+		io::Error::new(
+			io::ErrorKind::InvalidData,
+			LimitExceeded {
+				index: self.documents + 1,
+				offset: self.base_offset + self.source_offset,
+				limit: self.limit.unwrap_or_default(),
+			},
+		)
+	}
+
 	/// Trims from the start of the capture buffer so the next chunk will begin
 	/// at the specified reader offset.
 	fn trim_to_offset(&mut self, offset: u64) {
 		let trim_len = usize::try_from(offset - self.captured_start_offset).unwrap();
 		self.captured_start_offset = offset;
+		if !self.buffering() {
+			self.source_offset += trim_len as u64;
+			return;
+		}
+		self.source_offset += mem::take(&mut self.skipped_source_len);
+		self.source_offset += self.source_map.source_len(&self.captured[..trim_len]);
+		self.prefix = None;
 		self.captured.drain(..trim_len);
+		self.claim.shrink_to(self.held());
 	}
 
 	/// Takes the chunk from the start of the capture buffer up to the specified
 	/// reader offset, leaving bytes beyond the offset in the capture buffer.
 	fn take_to_offset(&mut self, offset: u64) -> Vec<u8> {
 		let take_len = usize::try_from(offset - self.captured_start_offset).unwrap();
-		let tail = self.captured.split_off(take_len);
 		self.captured_start_offset = offset;
-		mem::replace(&mut self.captured, tail)
+		self.documents += 1;
+		if !self.buffering() {
+			self.source_offset += take_len as u64;
+			return vec![];
+		}
+		let tail = self.captured.split_off(take_len);
+		let chunk = mem::replace(&mut self.captured, tail);
+		self.source_offset += mem::take(&mut self.skipped_source_len);
+		self.source_offset += self.source_map.source_len(&chunk);
+		let chunk = self.prefix.take().unwrap_or(chunk);
+		self.claim.shrink_to(self.held());
+		match self.capture {
+			true => chunk,
+			false => vec![],
+		}
+	}
+
+	/// Takes the text from the start of the capture buffer up to the
+	/// specified reader offset, like [`take_to_offset`](Self::take_to_offset),
+	/// but as part of the last document rather than a new one.
+	fn take_gap(&mut self, offset: u64) -> Vec<u8> {
+		let gap = self.take_to_offset(offset);
+		self.documents -= 1;
+		gap
+	}
+
+	/// Returns the captured bytes between two reader offsets, if the capture
+	/// buffer still holds all of them.
+	fn captured_between(&self, start: u64, end: u64) -> Option<&[u8]> {
+		if !self.buffering() || self.prefix.is_some() || start < self.captured_start_offset {
+			return None;
+		}
+		let start = usize::try_from(start - self.captured_start_offset).ok()?;
+		let end = usize::try_from(end - self.captured_start_offset).ok()?;
+		self.captured.get(start..end)
+	}
+
+	/// Returns the offset where a document whose content ends at
+	/// `content_end` should end, to leave the lines after the content to the
+	/// next document, which starts at `end`.
+	fn body_end(&self, content_end: u64, end: u64) -> u64 {
+		let at_line_start = content_end <= self.captured_start_offset
+			|| self.captured_between(content_end - 1, content_end) == Some(b"\n");
+		if at_line_start {
+			return content_end.clamp(self.captured_start_offset, end);
+		}
+		match self.captured_between(content_end, end) {
+			Some(rest) => rest
+				.iter()
+				.position(|&b| b == b'\n')
+				.map_or(end, |i| content_end + i as u64 + 1),
+			None => end,
+		}
+	}
+
+	/// Returns the offset where the next document should start to take the
+	/// lines before the document's first token at `offset`: the start of the
+	/// capture buffer, or the line after a `...` marker that it starts with.
+	fn gap_start(&self, offset: u64, after_end_marker: bool) -> u64 {
+		let start = self.captured_start_offset;
+		let Some(gap) = self.captured_between(start, offset) else {
+			return offset;
+		};
+		match after_end_marker {
+			true => gap
+				.iter()
+				.position(|&b| b == b'\n')
+				.map_or(offset, |i| start + i as u64 + 1),
+			false => start,
+		}
+	}
+
+	/// Returns the mark for `start`, the start of a line at or before `offset`,
+	/// where the parser's mark is `mark`.
+	fn mark_before(&self, start: u64, offset: u64, mark: Mark) -> Mark {
+		let Some(lines) = self
+			.captured_between(start, offset)
+			.filter(|_| start < offset)
+		else {
+			return mark;
+		};
+		let newlines = lines.iter().filter(|&&b| b == b'\n').count() as u64;
+		Mark {
+			line: mark.line - newlines,
+			column: 0,
+		}
 	}
 }
 
+/// An upper bound on the bytes that libyaml reads beyond the last event it
+/// returned, with room to spare: its raw and decoded input buffers hold 16 KiB
+/// and 48 KiB.
+const LIBYAML_READ_AHEAD: usize = 128 * 1024;
+
 impl<R> Read for ChunkReader<R>
 where
 	R: Read,
@@ -186,16 +1147,66 @@ where
 		// might do this. As consolation, note that we only read back bytes that
 		// we know were freshly written, unless of course the source is broken
 		// and lies about how many bytes it read.
+		self.check_limit()?;
 		let len = self.reader.read(buf)?;
-		self.captured.extend_from_slice(&buf[..len]);
+		self.capture(&buf[..len])?;
+		Ok(len)
+	}
+
+	fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+		self.check_limit()?;
+		let len = self.reader.read_vectored(bufs)?;
+		let mut rest = len;
+		for buf in bufs.iter() {
+			let n = rest.min(buf.len());
+			self.capture(&buf[..n])?;
+			rest -= n;
+		}
 		Ok(len)
 	}
 }
 
+impl<R> ChunkReader<R>
+where
+	R: Read,
+{
+	/// Fails if the document being read is certainly longer than the limit,
+	/// or drops what it can of it when truncating.
+	fn check_limit(&mut self) -> io::Result<()> {
+		// The capture buffer holds the current document from its start, along
+		// with whatever libyaml has read ahead of its parsing. Once the buffer
+		// exceeds the limit by more than libyaml can read ahead, the document
+		// is certainly too long, even if libyaml hasn't reached its end.
+		// When truncating instead, the parser is done with everything but
+		// what it may have read ahead, so the rest can go.
+		if let Some(limit) = self.limit {
+			if self.buffering() && self.captured.len() > limit.saturating_add(LIBYAML_READ_AHEAD) {
+				match self.truncate {
+					true => self.drop_overflow(limit),
+					false => return Err(self.limit_exceeded()),
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Adds freshly read bytes to the capture buffer, if buffering.
+	fn capture(&mut self, bytes: &[u8]) -> io::Result<()> {
+		if self.buffering() {
+			self.captured.extend_from_slice(bytes);
+			self.claim
+				.grow_to(self.held(), "reading the current document")?;
+		}
+		Ok(())
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
 
+	use crate::encoding::Encoder;
+
 	#[test]
 	fn chunker_normal_usage() {
 		const INPUT: &str = r"---
@@ -221,6 +1232,324 @@ test: true
 
 		let scalars = docs.iter().map(|doc| doc.is_scalar()).collect::<Vec<_>>();
 		assert_eq!(&scalars, &[false, true, false]);
+
+		let kinds = docs.iter().map(|doc| doc.kind()).collect::<Vec<_>>();
+		assert_eq!(
+			&kinds,
+			&[
+				DocumentKind::Mapping,
+				DocumentKind::Scalar,
+				DocumentKind::Sequence
+			]
+		);
+	}
+
+	#[test]
+	fn chunker_original_offsets() {
+		const INPUT: &str = "\u{FEFF}a: é\n--- 😀\n";
+		let input = INPUT
+			.encode_utf16()
+			.flat_map(u16::to_le_bytes)
+			.collect::<Vec<_>>();
+		for discard in [false, true] {
+			let (reader, map) = Encoder::from_reader_with_map(&input[..]).unwrap();
+			let mut chunker = Chunker::new(reader).with_source_map(map);
+			if discard {
+				chunker = chunker.discard_content();
+			}
+			let docs = chunker.collect::<Result<Vec<_>, io::Error>>().unwrap();
+			let ranges = docs.iter().map(Document::original).collect::<Vec<_>>();
+			assert_eq!(&ranges, &[2..12, 12..26]);
+		}
+	}
+
+	#[test]
+	fn chunker_positions() {
+		const INPUT: &str = "a: é\n--- b\n...\n%YAML 1.2\n---\nc: [d]\n";
+		let docs = Chunker::new(INPUT.as_bytes())
+			.collect::<Result<Vec<_>, io::Error>>()
+			.unwrap();
+		let positions = docs
+			.iter()
+			.map(|doc| {
+				let position = doc.position().unwrap();
+				format!("{}..{}", position.start, position.end)
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(&positions, &["1:1..2:1", "2:1..3:4", "4:1..7:1"]);
+		let lines = docs
+			.iter()
+			.map(|doc| doc.lines().unwrap())
+			.collect::<Vec<_>>();
+		assert_eq!(&lines, &[1..=1, 2..=3, 4..=6]);
+		let indexes = docs.iter().map(Document::index).collect::<Vec<_>>();
+		assert_eq!(&indexes, &[Some(0), Some(1), Some(2)]);
+
+		let mut resumed = Chunker::new("--- x\n".as_bytes()).starting_at(4, 100);
+		assert_eq!(resumed.next().unwrap().unwrap().index(), Some(4));
+	}
+
+	#[test]
+	fn chunker_place_comments() {
+		const INPUT: &str = "# lead\na: 1\n# before b\n--- b\n...\n# after end\n--- [c]\n# tail\n";
+		let chunks = |input: &str, placement| {
+			Chunker::new(input.as_bytes())
+				.place_comments(placement)
+				.map(|doc| doc.unwrap().content)
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(
+			chunks(INPUT, CommentPlacement::AttachPrevious),
+			[
+				"# lead\na: 1\n# before b\n",
+				"--- b\n...\n# after end\n",
+				"--- [c]\n# tail\n"
+			]
+		);
+		assert_eq!(
+			chunks(INPUT, CommentPlacement::AttachNext),
+			[
+				"# lead\na: 1\n",
+				"# before b\n--- b\n...\n",
+				"# after end\n--- [c]\n# tail\n"
+			]
+		);
+		assert_eq!(
+			chunks(INPUT, CommentPlacement::Strip),
+			["a: 1\n", "--- b\n...", "--- [c]\n"]
+		);
+		for (input, first) in [
+			("a: |\n  x\n# c\n--- b\n", "a: |\n  x\n"),
+			("a:\n# c\n--- b\n", "a:\n"),
+			("- [x]  # x\n# c\n--- b\n", "- [x]  # x\n"),
+			("k: v\n\n# c\n\n--- b\n", "k: v\n"),
+		] {
+			let docs = chunks(input, CommentPlacement::AttachNext);
+			assert_eq!(docs[0], first, "{input:?}");
+			assert_eq!(docs[0].clone() + &docs[1], input);
+		}
+
+		let docs = Chunker::new(INPUT.as_bytes())
+			.place_comments(CommentPlacement::AttachNext)
+			.discard_content()
+			.collect::<Result<Vec<_>, io::Error>>()
+			.unwrap();
+		let sources = docs.iter().map(Document::source).collect::<Vec<_>>();
+		assert_eq!(&sources, &[0..12, 12..33, 33..INPUT.len() as u64]);
+		let lines = docs
+			.iter()
+			.map(|doc| doc.lines().unwrap())
+			.collect::<Vec<_>>();
+		assert_eq!(&lines, &[1..=2, 3..=5, 6..=8]);
+		let indexes = docs.iter().map(Document::index).collect::<Vec<_>>();
+		assert_eq!(&indexes, &[Some(0), Some(1), Some(2)]);
+	}
+
+	#[test]
+	fn chunker_features() {
+		const INPUT: &str = "base: &b {x: !!int 1}\nuse: *b\n--- !Ref &s [*s]\n--- plain\n";
+		let docs = Chunker::new(INPUT.as_bytes())
+			.collect_features()
+			.collect::<Result<Vec<_>, io::Error>>()
+			.unwrap();
+		let set = |items: &[&str]| items.iter().map(|&s| s.to_owned()).collect();
+		let features = docs.iter().map(|doc| doc.features().unwrap().clone());
+		assert_eq!(
+			features.collect::<Vec<_>>(),
+			[
+				Features {
+					anchors: set(&["b"]),
+					aliases: set(&["b"]),
+					tags: set(&["tag:yaml.org,2002:int"]),
+				},
+				Features {
+					anchors: set(&["s"]),
+					aliases: set(&["s"]),
+					tags: set(&["!Ref"]),
+				},
+				Features::default(),
+			]
+		);
+		assert!(Chunker::new(INPUT.as_bytes()).all(|doc| doc.unwrap().features().is_none()));
+	}
+
+	#[test]
+	fn chunker_stats() {
+		const INPUT: &str = "a: &x [1, {b: *x}]\n--- 1\n--- []\n";
+		let docs = Chunker::new(INPUT.as_bytes())
+			.collect_stats()
+			.collect::<Result<Vec<_>, io::Error>>()
+			.unwrap();
+		let stats = docs.iter().map(|doc| {
+			let stats = doc.stats().unwrap();
+			(
+				stats.nodes(),
+				stats.mappings,
+				stats.sequences,
+				stats.max_depth,
+			)
+		});
+		assert_eq!(
+			stats.collect::<Vec<_>>(),
+			[(7, 2, 1, 3), (1, 0, 0, 0), (1, 0, 1, 1)]
+		);
+	}
+
+	#[test]
+	fn chunker_empty_documents() {
+		const INPUT: &str = "---\n--- # none\n...\n--- ~\n--- ''\n--- !!null\n--- &a\n";
+		let empty = Chunker::new(INPUT.as_bytes())
+			.map(|doc| doc.unwrap().is_empty())
+			.collect::<Vec<_>>();
+		assert_eq!(empty, [true, true, false, false, false, false]);
+	}
+
+	#[test]
+	fn chunker_root_tags() {
+		const INPUT: &str = "--- !Ref x\n--- !!map {a: !b c}\n--- [!x y]\n\
+			%TAG !e! tag:example.com,2000:\n--- !e!Stack\n- 1\n--- ! z\n";
+		let tags = Chunker::new(INPUT.as_bytes())
+			.map(|doc| doc.unwrap().tag().map(str::to_owned))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			tags,
+			[
+				Some("!Ref".to_owned()),
+				Some("tag:yaml.org,2002:map".to_owned()),
+				None,
+				Some("tag:example.com,2000:Stack".to_owned()),
+				Some("!".to_owned()),
+			]
+		);
+	}
+
+	#[test]
+	fn chunker_keeps_directives() {
+		const INPUT: &str = "a\n...\n%YAML 1.2\n# c\n%TAG !e! tag:example.com,2000:\n\
+			--- !e!x b\n...\n%TAG !e! tag:other.com,2000:\n--- !e!y c\n";
+		for placement in [None, Some(CommentPlacement::AttachNext)] {
+			let mut chunker = Chunker::new(INPUT.as_bytes());
+			if let Some(placement) = placement {
+				chunker = chunker.place_comments(placement);
+			}
+			let docs = chunker.collect::<Result<Vec<_>, io::Error>>().unwrap();
+			assert!(docs[1].content.starts_with("%YAML 1.2\n# c\n%TAG"));
+			assert!(docs[2].content.starts_with("%TAG !e! tag:other.com"));
+			for doc in &docs {
+				let reparsed = Chunker::new(doc.content.as_bytes())
+					.collect::<Result<Vec<_>, io::Error>>()
+					.unwrap();
+				assert_eq!(reparsed.len(), 1);
+				assert_eq!(reparsed[0].tag(), doc.tag(), "{:?}", doc.content);
+			}
+		}
+	}
+
+	#[test]
+	fn chunk_reader_read_vectored() {
+		let mut reader = ChunkReader::new("abcdefgh".as_bytes());
+		let (mut a, mut b) = ([0; 3], [0; 8]);
+		let mut bufs = [io::IoSliceMut::new(&mut a), io::IoSliceMut::new(&mut b)];
+		assert_eq!(reader.read_vectored(&mut bufs).unwrap(), 8);
+		assert_eq!((&a, &b[..5]), (b"abc", &b"defgh"[..]));
+		assert_eq!(reader.take_to_offset(6), b"abcdef");
+		assert_eq!(reader.take_to_offset(8), b"gh");
+	}
+
+	#[test]
+	fn chunker_peek() {
+		let mut chunker = Chunker::new("a\n--- b\n--- [\n".as_bytes());
+		let content = |doc: Option<&io::Result<Document>>| match doc {
+			Some(Ok(doc)) => Some(doc.content().to_owned()),
+			Some(Err(_)) => Some("error".to_owned()),
+			None => None,
+		};
+		assert_eq!(content(chunker.peek()), Some("a\n".to_owned()));
+		assert_eq!(content(chunker.peek()), Some("a\n".to_owned()));
+		assert_eq!(content(chunker.next().as_ref()), Some("a\n".to_owned()));
+		assert_eq!(content(chunker.next().as_ref()), Some("--- b\n".to_owned()));
+		assert_eq!(content(chunker.peek()), Some("error".to_owned()));
+		assert_eq!(content(chunker.next().as_ref()), Some("error".to_owned()));
+
+		let mut chunker = Chunker::new("".as_bytes());
+		assert!(chunker.peek().is_none());
+		assert!(chunker.next().is_none());
+	}
+
+	#[test]
+	fn document_value() {
+		use crate::node::Content;
+
+		let mut docs = Chunker::new("a: &x [1]\nb: *x\n--- [\n".as_bytes());
+		let doc = docs.next().unwrap().unwrap();
+		let root = doc.value().unwrap();
+		assert!(std::ptr::eq(root, doc.value().unwrap()));
+		let Content::Mapping(entries, _) = &root.content else {
+			panic!("root is not a mapping");
+		};
+		assert_eq!(entries[0].1.content, entries[1].1.content);
+
+		let doc = doc.with_content("c\n".to_owned());
+		let root = doc.value().unwrap();
+		assert!(matches!(&root.content, Content::Scalar(value, _) if value == "c"));
+		assert!(docs.next().unwrap().is_err());
+	}
+
+	#[test]
+	fn chunker_max_document_len() {
+		let small = "---\na: 1\n";
+		let large = format!("---\nb: {}\n", "x".repeat(300_000));
+		let input = format!("{small}{large}{small}");
+		for discard in [false, true] {
+			let mut chunker = Chunker::new(input.as_bytes()).max_document_len(small.len());
+			if discard {
+				chunker = chunker.discard_content();
+			}
+			assert!(chunker.next().unwrap().is_ok());
+			let err = chunker.next().unwrap().err().unwrap();
+			let err = err.into_inner().unwrap();
+			let err = err.downcast_ref::<LimitExceeded>().unwrap();
+			assert_eq!((err.index, err.offset), (2, small.len() as u64));
+		}
+	}
+
+	#[test]
+	fn chunker_truncate_long_documents() {
+		let small = "---\na: 1\n";
+		let large = format!("---\nbb: é{}\n", "x".repeat(300_000));
+		let input = format!("{small}{large}{small}");
+		let docs = Chunker::new(input.as_bytes())
+			.max_document_len(small.len())
+			.truncate_long_documents()
+			.collect::<io::Result<Vec<_>>>()
+			.unwrap();
+		let docs = docs
+			.iter()
+			.map(|doc| (doc.content(), doc.full_len(), doc.original()))
+			.collect::<Vec<_>>();
+		let (start, end) = (small.len() as u64, (small.len() + large.len()) as u64);
+		assert_eq!(
+			docs,
+			[
+				(small, None, 0..start),
+				("---\nbb: ", Some(large.len() as u64), start..end),
+				(small, None, end..end + start),
+			]
+		);
+	}
+
+	#[test]
+	fn chunker_discard_content() {
+		const INPUT: &str = "---\ntest: true\n---\n12345\n";
+
+		let chunker = Chunker::new(INPUT.as_bytes()).discard_content();
+		let docs = chunker.collect::<Result<Vec<_>, io::Error>>().unwrap();
+
+		let contents = docs.iter().map(|doc| doc.content()).collect::<Vec<_>>();
+		assert_eq!(&contents, &["", ""]);
+
+		let scalars = docs.iter().map(|doc| doc.is_scalar()).collect::<Vec<_>>();
+		assert_eq!(&scalars, &[false, true]);
 	}
 
 	#[test]
@@ -0,0 +1,146 @@
+//! The `yaml-split` command line interface, which the binary runs.
+
+use std::fmt::{Debug, Formatter};
+use std::io;
+use std::process::{ExitCode, Termination};
+
+use clap::{CommandFactory, Parser, Subcommand};
+
+use crate::{cmd, config, diagnostics, interrupt, pipecheck};
+
+/// Runs the command with the process's arguments, and returns its exit code.
+pub fn main() -> ExitCode {
+	match run() {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(err) => {
+			eprintln!("Error: {err:?}");
+			err.report()
+		}
+	}
+}
+
+fn run() -> Result<(), CleanExit> {
+	config::load_into_env(&Cli::command())?;
+	let cli = Cli::parse();
+	diagnostics::set_quiet(cli.quiet);
+	pipecheck::set_exit(cli.pipe_exit);
+	match cli.command.unwrap_or(Command::Split(cli.split)) {
+		Command::Split(args) => cmd::split::run(*args)?,
+		Command::Count(args) => cmd::count::run(args)?,
+		Command::Extract(args) => cmd::extract::run(args)?,
+		Command::Diff(args) => cmd::diff::run(args)?,
+		Command::Join(args) => cmd::join::run(args)?,
+		Command::Convert(args) => cmd::convert::run(args)?,
+		Command::Validate(args) => cmd::validate::run(args)?,
+		Command::Transcode(args) => cmd::transcode::run(args)?,
+		Command::Browse(args) => cmd::browse::run(args)?,
+		Command::Bench(args) => cmd::bench::run(args)?,
+		Command::Mangen(args) => cmd::mangen::run(args)?,
+	};
+	Ok(())
+}
+
+/// Shows the splits between documents in a YAML stream
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+pub(crate) struct Cli {
+	#[command(subcommand)]
+	command: Option<Command>,
+
+	#[command(flatten)]
+	split: Box<cmd::split::Args>,
+
+	#[clap(
+		short,
+		long,
+		global = true,
+		env = "YAML_SPLIT_QUIET",
+		value_parser = clap::builder::BoolishValueParser::new(),
+		help = "Suppress warnings, printing only output and errors"
+	)]
+	quiet: bool,
+
+	#[clap(
+		long = "pipe-exit",
+		global = true,
+		value_enum,
+		value_name = "HOW",
+		env = "YAML_SPLIT_PIPE_EXIT",
+		default_value = "signal",
+		help = "How to exit when the reader of the output goes away"
+	)]
+	pipe_exit: pipecheck::PipeExit,
+}
+
+#[derive(Subcommand)]
+enum Command {
+	/// Show the splits between documents (the default)
+	Split(Box<cmd::split::Args>),
+	/// Print the number of documents
+	Count(cmd::count::Args),
+	/// Print a single document
+	Extract(cmd::extract::Args),
+	/// Compare the documents of two streams
+	Diff(cmd::diff::Args),
+	/// Combine files into a single stream
+	Join(cmd::join::Args),
+	/// Convert the documents to JSON
+	Convert(cmd::convert::Args),
+	/// Check that the input is valid YAML
+	Validate(cmd::validate::Args),
+	/// Re-encode the input as UTF-8
+	Transcode(cmd::transcode::Args),
+	/// Browse documents interactively
+	Browse(cmd::browse::Args),
+	/// Measure splitting throughput for a file
+	Bench(cmd::bench::Args),
+	/// Render manual pages for packagers
+	#[command(hide = true)]
+	Mangen(cmd::mangen::Args),
+}
+
+struct CleanExit(io::Error);
+
+impl From<io::Error> for CleanExit {
+	fn from(value: io::Error) -> Self {
+		Self(value)
+	}
+}
+
+impl Termination for CleanExit {
+	fn report(self) -> ExitCode {
+		match self.0.kind() {
+			io::ErrorKind::Interrupted => ExitCode::from(interrupt::EXIT_CODE),
+			io::ErrorKind::TimedOut => ExitCode::from(interrupt::TIMEOUT_EXIT_CODE),
+			_ => ExitCode::FAILURE,
+		}
+	}
+}
+
+impl Debug for CleanExit {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn cli_invalid_values_name_the_flag() {
+		for (flag, value, reason) in [
+			("--get", "no-dot", "must start with '.'"),
+			("--timeout", "1500ms", "invalid duration"),
+			("--grep", "(", "unclosed '('"),
+			("--tag", "", "can't be empty"),
+		] {
+			let Err(err) = Cli::try_parse_from(["yaml-split", flag, value]) else {
+				panic!("{flag} {value:?} should be invalid");
+			};
+			let message = err.to_string();
+			assert!(message.contains(flag), "{message}");
+			assert!(message.contains(reason), "{message}");
+		}
+	}
+}
@@ -0,0 +1,431 @@
+//! Implementations of yaml-split's subcommands.
+//!
+//! Each submodule defines the arguments for one subcommand along with a `run`
+//! function that executes it. The helpers here cover the input and output
+//! handling that most subcommands share.
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, IoSlice, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::chunker::{Chunker, Document};
+#[cfg(feature = "compression")]
+use crate::compression;
+use crate::encoding::Encoder;
+use crate::json;
+use crate::lexical::LexicalChunker;
+use crate::pager::Pager;
+use crate::parallel;
+use crate::pipecheck;
+use crate::transform::Documents;
+
+pub(crate) mod bench;
+pub(crate) mod browse;
+pub(crate) mod convert;
+pub(crate) mod count;
+pub(crate) mod diff;
+pub(crate) mod extract;
+pub(crate) mod join;
+pub(crate) mod mangen;
+pub(crate) mod split;
+pub(crate) mod transcode;
+pub(crate) mod validate;
+
+/// Opens the named input file, or standard input if no file is given or the
+/// file is named `-`.
+///
+/// When no file is given and standard input is a terminal, the user probably
+/// forgot to provide an input, so this fails with a hint rather than blocking
+/// while waiting on the terminal. Naming `-` explicitly still allows a user to
+/// type or paste a stream interactively.
+pub(crate) fn open_input(path: Option<&Path>) -> io::Result<Box<dyn BufRead>> {
+	Ok(match path {
+		None if io::stdin().is_terminal() => {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"no input file given, and standard input is a terminal\n\n\
+				Pass a file to read, pipe a stream into yaml-split, or use - to type a stream.\n\
+				For more information, try '--help'.",
+			))
+		}
+		Some(path) if path == Path::new("-") => open_stdin()?,
+		None => open_stdin()?,
+		Some(path) => {
+			let file = BufReader::new(File::open(path)?);
+			#[cfg(feature = "compression")]
+			let mut file = file;
+			#[cfg(feature = "compression")]
+			if let Some(format) = compression::detect(&mut file, Some(path))? {
+				return compression::decompress(file, format);
+			}
+			Box::new(file)
+		}
+	})
+}
+
+/// Opens standard input, decompressing it if it's compressed.
+fn open_stdin() -> io::Result<Box<dyn BufRead>> {
+	let stdin = io::stdin().lock();
+	#[cfg(feature = "compression")]
+	let mut stdin = stdin;
+	// What's peeked stays in the buffer that every handle to stdin shares.
+	#[cfg(feature = "compression")]
+	if let Some(format) = compression::detect(&mut stdin, None)? {
+		return compression::decompress(io::stdin(), format);
+	}
+	Ok(Box::new(stdin))
+}
+
+/// Opens the named input file, or standard input if no file is given, and
+/// re-encodes its contents as UTF-8.
+pub(crate) fn open_utf8_input(path: Option<&Path>) -> io::Result<impl Read> {
+	Encoder::from_reader(open_input(path)?)
+}
+
+/// Returns a chunker over the documents in the named input file, or standard
+/// input if no file is given.
+///
+/// The chunker reports the original offsets of its documents in the input, as
+/// well as their offsets in the input's re-encoding as UTF-8.
+pub(crate) fn chunk_input(path: Option<&Path>) -> io::Result<Chunker<impl Read>> {
+	let (reader, map) = Encoder::from_reader_with_map(open_input(path)?)?;
+	Ok(Chunker::new(reader).with_source_map(map))
+}
+
+/// Returns a chunker over the documents in the named input file, or standard
+/// input if no file is given, that finds documents from their markers alone.
+///
+/// As with [`chunk_input`], the chunker reports offsets in both the original
+/// input and its re-encoding as UTF-8.
+pub(crate) fn chunk_input_lexically(
+	path: Option<&Path>,
+) -> io::Result<LexicalChunker<impl BufRead>> {
+	let (reader, map) = Encoder::from_reader_with_map(open_input(path)?)?;
+	Ok(LexicalChunker::new(BufReader::new(reader), map))
+}
+
+/// The backends that can find the documents in a YAML stream.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum Backend {
+	/// Parse the stream with libyaml, which checks that it's well-formed.
+	Libyaml,
+	/// Find documents from their markers alone without parsing them, for
+	/// trusted input.
+	Lexical,
+}
+
+impl Backend {
+	/// Returns the name of the backend, as `--backend` takes it.
+	pub(crate) fn name(self) -> &'static str {
+		match self {
+			Backend::Libyaml => "libyaml",
+			Backend::Lexical => "lexical",
+		}
+	}
+}
+
+/// Writes every one of `parts` to `w` in order, in as few vectored writes as
+/// the writer allows, so that framing around a document's content needn't be
+/// copied together with it first.
+pub(crate) fn write_all_vectored(w: &mut dyn Write, parts: &[&[u8]]) -> io::Result<()> {
+	let mut parts = parts.to_vec();
+	parts.retain(|part| !part.is_empty());
+	let mut first = 0;
+	while first < parts.len() {
+		let slices = parts[first..]
+			.iter()
+			.map(|part| IoSlice::new(part))
+			.collect::<Vec<_>>();
+		let mut n = match w.write_vectored(&slices) {
+			Ok(0) => {
+				return Err(io::Error::new(
+					io::ErrorKind::WriteZero,
+					"failed to write whole buffer",
+				))
+			}
+			Ok(n) => n,
+			Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+			Err(err) => return Err(err),
+		};
+		while first < parts.len() && n >= parts[first].len() {
+			n -= parts[first].len();
+			first += 1;
+		}
+		if n > 0 {
+			parts[first] = &parts[first][n..];
+		}
+	}
+	Ok(())
+}
+
+/// Returns a writer for standard output that exits silently on broken pipes.
+pub(crate) fn stdout() -> pipecheck::Writer<io::StdoutLock<'static>> {
+	pipecheck::Writer::new(io::stdout().lock())
+}
+
+/// The policies for when a command flushes its output.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum Flush {
+	/// After every document, so each is visible downstream as soon as it's
+	/// written.
+	PerDoc,
+	/// Only when the output buffer fills, and at the end.
+	Block,
+}
+
+impl Flush {
+	/// Applies the policy after a document has been written in full.
+	pub(crate) fn document_written(self, output: &mut dyn Write) -> io::Result<()> {
+		match self {
+			Flush::PerDoc => output.flush(),
+			Flush::Block => Ok(()),
+		}
+	}
+}
+
+/// Writes every document as an item of a single JSON array, converting
+/// documents on `jobs` threads, and returns the number of documents written.
+pub(crate) fn write_json_array(
+	docs: Documents,
+	output: &mut dyn Write,
+	flush: Flush,
+	jobs: usize,
+) -> io::Result<usize> {
+	let mut count = 0;
+	for json in parallel::map(docs, jobs, to_json) {
+		let json = json?;
+		let separator = if count == 0 { "[\n" } else { ",\n" };
+		write!(output, "{separator}  {json}")?;
+		flush.document_written(output)?;
+		count += 1;
+	}
+	match count {
+		0 => writeln!(output, "[]")?,
+		_ => writeln!(output, "\n]")?,
+	}
+	Ok(count)
+}
+
+/// Writes every document as a line of JSON, converting documents on `jobs`
+/// threads, and returns the number of documents written.
+pub(crate) fn write_json_lines(
+	docs: Documents,
+	output: &mut dyn Write,
+	flush: Flush,
+	jobs: usize,
+) -> io::Result<usize> {
+	let mut count = 0;
+	for json in parallel::map(docs, jobs, to_json) {
+		writeln!(output, "{}", json?)?;
+		flush.document_written(output)?;
+		count += 1;
+	}
+	Ok(count)
+}
+
+/// Converts a document to JSON on a single line.
+fn to_json(doc: Document) -> io::Result<String> {
+	json::to_json(&doc.root()?)
+}
+
+/// The choices for when to color output for display in a terminal.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub(crate) enum ColorChoice {
+	/// Color output when writing to a terminal, unless `NO_COLOR` is set.
+	Auto,
+	Always,
+	Never,
+}
+
+impl ColorChoice {
+	/// Returns whether to color output written to `output`.
+	pub(crate) fn enabled_for(self, output: &OutputArgs) -> bool {
+		match self {
+			ColorChoice::Always => true,
+			ColorChoice::Never => false,
+			ColorChoice::Auto => {
+				output.is_terminal() && env::var_os("NO_COLOR").map_or(true, |v| v.is_empty())
+			}
+		}
+	}
+}
+
+/// Arguments that choose where a subcommand writes its output.
+#[derive(clap::Args)]
+pub(crate) struct OutputArgs {
+	#[clap(
+		short,
+		long,
+		value_name = "FILE",
+		help = "Write to a file instead of standard output"
+	)]
+	output: Option<PathBuf>,
+
+	#[clap(
+		long,
+		requires = "output",
+		help = "Append to the output file instead of replacing its contents"
+	)]
+	append: bool,
+
+	#[clap(
+		long,
+		env = "YAML_SPLIT_PAGER",
+		value_parser = clap::builder::BoolishValueParser::new(),
+		help = "Page output written to a terminal through $PAGER or less"
+	)]
+	pager: bool,
+}
+
+impl OutputArgs {
+	/// Returns the output file, if output is to be appended to it.
+	pub(crate) fn append_path(&self) -> Option<&Path> {
+		self.output.as_deref().filter(|_| self.append)
+	}
+
+	/// Returns whether the output destination is an interactive terminal.
+	pub(crate) fn is_terminal(&self) -> bool {
+		self.output.is_none() && io::stdout().is_terminal()
+	}
+
+	/// Opens the output destination.
+	///
+	/// Standard output is piped through a pager if paging was requested and
+	/// standard output is a terminal. Otherwise, it's fully buffered like a
+	/// file, rather than flushed at the end of every line.
+	///
+	/// A file destination is created if it does not exist, and is otherwise
+	/// truncated unless appending was requested. Unlike standard output, write
+	/// errors to a file (including broken pipes, in the case of a FIFO) are
+	/// always reported. Callers must flush the returned writer to observe any
+	/// errors in writing its final contents.
+	///
+	/// To protect against data loss, opening fails if the output file is the
+	/// same as any of the `inputs`.
+	pub(crate) fn open<'a, I>(&self, inputs: I) -> io::Result<Box<dyn Write>>
+	where
+		I: IntoIterator<Item = &'a Path>,
+	{
+		let Some(path) = &self.output else {
+			if self.pager && io::stdout().is_terminal() {
+				if let Some(pager) = Pager::spawn() {
+					return Ok(Box::new(pager));
+				}
+			}
+			return Ok(Box::new(BufWriter::new(stdout())));
+		};
+		Ok(Box::new(BufWriter::new(self.open_file(path, inputs)?)))
+	}
+
+	/// Opens the output destination like [`OutputArgs::open`], but compresses
+	/// everything written to it in `format`.
+	///
+	/// Compressed output is never paged, and to keep it from garbling the
+	/// screen, opening fails if standard output would be a terminal.
+	#[cfg(feature = "compression")]
+	pub(crate) fn open_compressed<'a, I>(
+		&self,
+		inputs: I,
+		format: compression::Format,
+	) -> io::Result<Box<dyn Write>>
+	where
+		I: IntoIterator<Item = &'a Path>,
+	{
+		use compression::{Compressor, Target};
+		let Some(path) = &self.output else {
+			if io::stdout().is_terminal() {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidInput,
+					"refusing to write compressed output to a terminal; use --output or a pipe",
+				));
+			}
+			return Ok(Box::new(pipecheck::Writer::new(Compressor::new(
+				Target::Stdout,
+				format,
+			))));
+		};
+		let file = self.open_file(path, inputs)?;
+		Ok(Box::new(Compressor::new(Target::File(file), format)))
+	}
+
+	/// Opens the output file at `path`, as long as it isn't any of `inputs`.
+	fn open_file<'a, I>(&self, path: &Path, inputs: I) -> io::Result<File>
+	where
+		I: IntoIterator<Item = &'a Path>,
+	{
+		if let Ok(output) = fs::canonicalize(path) {
+			for input in inputs {
+				if fs::canonicalize(input).is_ok_and(|input| input == output) {
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidInput,
+						format!(
+							"refusing to write output to the input file {path}",
+							path = path.display()
+						),
+					));
+				}
+			}
+		}
+		OpenOptions::new()
+			.write(true)
+			.create(true)
+			.append(self.append)
+			.truncate(!self.append)
+			.open(path)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A writer that accepts at most a few bytes per call.
+	struct Trickle(Vec<u8>);
+
+	impl Write for Trickle {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			let n = buf.len().min(3);
+			self.0.extend_from_slice(&buf[..n]);
+			Ok(n)
+		}
+
+		fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+			let mut n = 0;
+			for buf in bufs {
+				let len = buf.len().min(5 - n);
+				self.0.extend_from_slice(&buf[..len]);
+				n += len;
+			}
+			Ok(n)
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn json_of_truncated_documents_fails() {
+		let docs = Chunker::new("- a\n".as_bytes())
+			.max_document_len(0)
+			.truncate_long_documents();
+		let mut output = vec![];
+		let err = write_json_lines(Box::new(docs), &mut output, Flush::Block, 1)
+			.err()
+			.unwrap();
+		assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+		assert!(output.is_empty());
+	}
+
+	#[test]
+	fn write_all_vectored_parts() {
+		let mut w = Trickle(vec![]);
+		let parts: [&[u8]; 5] = [b">>>|", b"", b"a: 1\nb: 2\n", b"|<<<", b"\n"];
+		write_all_vectored(&mut w, &parts).unwrap();
+		assert_eq!(w.0, b">>>|a: 1\nb: 2\n|<<<\n");
+		let mut out = vec![];
+		write_all_vectored(&mut out, &[]).unwrap();
+		assert!(out.is_empty());
+	}
+}
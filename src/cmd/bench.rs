@@ -0,0 +1,115 @@
+//! A built-in benchmark for end-to-end splitting throughput.
+//!
+//! Performance claims are only as good as the data they're measured on, and
+//! the shape of a YAML stream (many small documents vs. a few huge ones, flow
+//! vs. block style, the source encoding) matters a great deal. `bench` lets
+//! anyone measure the splitter against their own files without setting up a
+//! Rust toolchain and a benchmark harness.
+//!
+//! The input is read into memory once up front, so the numbers reflect the
+//! cost of encoding and chunking the stream rather than the speed of the disk.
+
+use std::fs;
+use std::io::{self, Write};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::chunker::Chunker;
+use crate::encoding::Encoder;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+	#[clap(help = "The file to split")]
+	file: PathBuf,
+
+	#[clap(
+		short = 'n',
+		long,
+		env = "YAML_SPLIT_ITERATIONS",
+		default_value = "5",
+		help = "The number of runs to take the best result from"
+	)]
+	iterations: NonZeroUsize,
+}
+
+/// The ways of running the chunker that a benchmark compares.
+#[derive(Clone, Copy)]
+enum Mode {
+	/// Captures the full content of every document, as normal splitting does.
+	Capture,
+	/// Discards document content, measuring the cost of parsing alone.
+	Discard,
+}
+
+impl Mode {
+	const ALL: [Mode; 2] = [Mode::Capture, Mode::Discard];
+
+	fn name(self) -> &'static str {
+		match self {
+			Mode::Capture => "capture",
+			Mode::Discard => "no-capture",
+		}
+	}
+}
+
+/// The best observed result of several runs of a single mode.
+struct Measurement {
+	mode: Mode,
+	elapsed: Duration,
+	docs: usize,
+}
+
+pub(crate) fn run(args: Args) -> io::Result<()> {
+	let Args { file, iterations } = args;
+	let input = fs::read(&file)?;
+
+	let mut results = Vec::with_capacity(Mode::ALL.len());
+	for mode in Mode::ALL {
+		let runs = (0..iterations.get())
+			.map(|_| measure(&input, mode))
+			.collect::<io::Result<Vec<_>>>()?;
+		results.extend(runs.into_iter().min_by_key(|run| run.elapsed));
+	}
+
+	let mut output = super::stdout();
+	writeln!(
+		output,
+		"{path}: {len} bytes, best of {iterations} runs",
+		path = file.display(),
+		len = input.len(),
+	)?;
+	for result in results {
+		let secs = result.elapsed.as_secs_f64();
+		writeln!(
+			output,
+			"{mode:<10}  {millis:>10.3} ms  {mbps:>10.2} MB/s  {dps:>12.0} docs/s  ({docs} docs)",
+			mode = result.mode.name(),
+			millis = secs * 1e3,
+			mbps = input.len() as f64 / secs / 1e6,
+			dps = result.docs as f64 / secs,
+			docs = result.docs,
+		)?;
+	}
+	Ok(())
+}
+
+fn measure(input: &[u8], mode: Mode) -> io::Result<Measurement> {
+	let start = Instant::now();
+	let chunker = Chunker::new(Encoder::from_reader(input)?);
+	let chunker = match mode {
+		Mode::Capture => chunker,
+		Mode::Discard => chunker.discard_content(),
+	};
+	let mut docs = 0;
+	for doc in chunker {
+		// Touch the content so that capture can't be optimized away.
+		io::sink().write_all(doc?.content().as_bytes())?;
+		docs += 1;
+	}
+	Ok(Measurement {
+		mode,
+		elapsed: start.elapsed(),
+		docs,
+	})
+}
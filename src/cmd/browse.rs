@@ -0,0 +1,329 @@
+//! The `browse` subcommand, an interactive terminal browser for documents.
+//!
+//! `browse` loads every document of the stream into memory, then lists them
+//! with a preview of the current document. From the list, documents can be
+//! searched for, selected, and written out to individual files. It's a front
+//! end over the same chunker that powers `split` and `extract`, for when it's
+//! easier to look around a stream than to work out the right indexes up front.
+
+use std::io;
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+	#[clap(
+		name = "inputfile",
+		help = "A file to read from instead of standard input"
+	)]
+	inputfile: Option<PathBuf>,
+}
+
+#[cfg(not(unix))]
+pub(crate) fn run(_: Args) -> io::Result<()> {
+	Err(io::Error::new(
+		io::ErrorKind::Unsupported,
+		"browse is only supported on Unix-like systems",
+	))
+}
+
+#[cfg(unix)]
+pub(crate) fn run(args: Args) -> io::Result<()> {
+	let mut entries = vec![];
+	for (index, doc) in super::chunk_input(args.inputfile.as_deref())?.enumerate() {
+		let doc = doc?;
+		entries.push(Entry {
+			index,
+			kind: doc.kind().name(),
+			summary: summarize(doc.content()),
+			content: doc.content().to_owned(),
+		});
+	}
+	if entries.is_empty() {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			"the input contains no documents to browse",
+		));
+	}
+
+	let mut terminal = Terminal::open()?;
+	let mut browser = Browser::new(entries);
+	loop {
+		browser.draw(&mut terminal)?;
+		if browser.handle(terminal.read_key()?) == Flow::Quit {
+			return Ok(());
+		}
+	}
+}
+
+#[cfg(unix)]
+use self::tui::*;
+
+#[cfg(unix)]
+mod tui {
+	use std::collections::BTreeSet;
+	use std::fmt::Write as _;
+	use std::fs::{self, OpenOptions};
+	use std::io::{self, Write};
+	use std::path::Path;
+
+	use crate::highlight;
+	pub(super) use crate::terminal::{Key, Terminal};
+
+	/// A single document in the browser's list.
+	pub(super) struct Entry {
+		pub(super) index: usize,
+		pub(super) kind: &'static str,
+		pub(super) summary: String,
+		pub(super) content: String,
+	}
+
+	/// Whether the browser should keep running after handling a key.
+	#[derive(PartialEq, Eq)]
+	pub(super) enum Flow {
+		Continue,
+		Quit,
+	}
+
+	/// What the bottom line of the screen is being used for.
+	enum Prompt {
+		None,
+		Search(String),
+		WriteDir(String),
+	}
+
+	pub(super) struct Browser {
+		entries: Vec<Entry>,
+		selected: BTreeSet<usize>,
+		cursor: usize,
+		scroll: usize,
+		list_height: usize,
+		query: Option<String>,
+		prompt: Prompt,
+		status: String,
+	}
+
+	const HELP: &str = "j/k move  space select  / search  n/N next/prev  w write  q quit";
+
+	impl Browser {
+		pub(super) fn new(entries: Vec<Entry>) -> Self {
+			Self {
+				entries,
+				selected: BTreeSet::new(),
+				cursor: 0,
+				scroll: 0,
+				list_height: 1,
+				query: None,
+				prompt: Prompt::None,
+				status: HELP.to_owned(),
+			}
+		}
+
+		pub(super) fn handle(&mut self, key: Key) -> Flow {
+			match &mut self.prompt {
+				Prompt::None => return self.handle_list(key),
+				Prompt::Search(input) | Prompt::WriteDir(input) => match key {
+					Key::Char(c) => input.push(c),
+					Key::Backspace => {
+						input.pop();
+					}
+					Key::Escape | Key::Interrupt => {
+						self.prompt = Prompt::None;
+						self.status = HELP.to_owned();
+					}
+					Key::Enter => match std::mem::replace(&mut self.prompt, Prompt::None) {
+						Prompt::Search(query) => {
+							self.query = (!query.is_empty()).then_some(query);
+							self.find(true, false);
+						}
+						Prompt::WriteDir(dir) => self.write_selected(Path::new(&dir)),
+						Prompt::None => unreachable!(),
+					},
+					_ => {}
+				},
+			}
+			Flow::Continue
+		}
+
+		fn handle_list(&mut self, key: Key) -> Flow {
+			let last = self.entries.len() - 1;
+			match key {
+				Key::Char('q') | Key::Escape | Key::Interrupt => return Flow::Quit,
+				Key::Up | Key::Char('k') => self.cursor = self.cursor.saturating_sub(1),
+				Key::Down | Key::Char('j') => self.cursor = (self.cursor + 1).min(last),
+				Key::PageUp => self.cursor = self.cursor.saturating_sub(self.list_height),
+				Key::PageDown => self.cursor = (self.cursor + self.list_height).min(last),
+				Key::Home | Key::Char('g') => self.cursor = 0,
+				Key::End | Key::Char('G') => self.cursor = last,
+				Key::Char(' ') => {
+					if !self.selected.remove(&self.cursor) {
+						self.selected.insert(self.cursor);
+					}
+					self.cursor = (self.cursor + 1).min(last);
+				}
+				Key::Char('/') => self.prompt = Prompt::Search(String::new()),
+				Key::Char('n') => self.find(false, false),
+				Key::Char('N') => self.find(false, true),
+				Key::Char('w') => self.prompt = Prompt::WriteDir(".".to_owned()),
+				_ => {}
+			}
+			Flow::Continue
+		}
+
+		/// Moves the cursor to the next (or previous) document matching the
+		/// current query, optionally including the current document.
+		fn find(&mut self, include_current: bool, backward: bool) {
+			let Some(query) = &self.query else {
+				self.status = "no search in progress; press / to search".to_owned();
+				return;
+			};
+			let query = query.to_lowercase();
+			let len = self.entries.len();
+			let start = if include_current { 0 } else { 1 };
+			let found = (start..=len).map(|step| match backward {
+				false => (self.cursor + step) % len,
+				true => (self.cursor + len * 2 - step) % len,
+			});
+			let found = found
+				.take(len)
+				.find(|&i| self.entries[i].content.to_lowercase().contains(&query));
+			match found {
+				Some(i) => {
+					self.cursor = i;
+					self.status =
+						format!("match for {query:?} in document {}", self.entries[i].index);
+				}
+				None => self.status = format!("no matches for {query:?}"),
+			}
+		}
+
+		/// Writes the selected documents, or the current document if none are
+		/// selected, to individual files in `dir`.
+		fn write_selected(&mut self, dir: &Path) {
+			let targets = match self.selected.is_empty() {
+				true => vec![self.cursor],
+				false => self.selected.iter().copied().collect(),
+			};
+			let result = fs::create_dir_all(dir).and_then(|_| {
+				for &i in &targets {
+					let entry = &self.entries[i];
+					let path = dir.join(format!("doc-{index:04}.yaml", index = entry.index));
+					let mut file = OpenOptions::new()
+						.write(true)
+						.create_new(true)
+						.open(&path)?;
+					file.write_all(entry.content.as_bytes())?;
+				}
+				Ok(())
+			});
+			self.status = match result {
+				Ok(()) => format!(
+					"wrote {n} document(s) to {dir}",
+					n = targets.len(),
+					dir = dir.display()
+				),
+				Err(err) => format!("failed to write documents: {err}"),
+			};
+		}
+
+		pub(super) fn draw(&mut self, terminal: &mut Terminal) -> io::Result<()> {
+			let (rows, cols) = terminal.size();
+			self.list_height = ((rows.saturating_sub(3)) / 2).max(1);
+			if self.cursor < self.scroll {
+				self.scroll = self.cursor;
+			} else if self.cursor >= self.scroll + self.list_height {
+				self.scroll = self.cursor + 1 - self.list_height;
+			}
+
+			let mut frame = String::from("\x1b[H\x1b[2J");
+			let header = format!(
+				"  {index:>6}  {kind:<8}  {bytes:>9}  summary",
+				index = "index",
+				kind = "kind",
+				bytes = "bytes"
+			);
+			let _ = write!(frame, "\x1b[7m{:<cols$}\x1b[0m", truncate(&header, cols));
+
+			for (row, i) in (self.scroll..self.entries.len())
+				.take(self.list_height)
+				.enumerate()
+			{
+				let entry = &self.entries[i];
+				let line = format!(
+					"{sel} {index:>6}  {kind:<8}  {bytes:>9}  {summary}",
+					sel = if self.selected.contains(&i) { '*' } else { ' ' },
+					index = entry.index,
+					kind = entry.kind,
+					bytes = entry.content.len(),
+					summary = entry.summary,
+				);
+				let line = truncate(&line, cols);
+				let _ = write!(frame, "\x1b[{row};1H", row = row + 2);
+				if i == self.cursor {
+					let _ = write!(frame, "\x1b[7m{line:<cols$}\x1b[0m");
+				} else {
+					frame.push_str(line);
+				}
+			}
+
+			let preview_top = self.list_height + 2;
+			let _ = write!(
+				frame,
+				"\x1b[{preview_top};1H\x1b[2m{:─<cols$}\x1b[0m",
+				truncate(
+					&format!("── document {} ", self.entries[self.cursor].index),
+					cols
+				)
+			);
+			let preview_height = rows.saturating_sub(preview_top + 1);
+			let preview = self.entries[self.cursor]
+				.content
+				.lines()
+				.take(preview_height)
+				.map(|line| truncate(line, cols))
+				.collect::<Vec<_>>();
+			let mut highlighted = vec![];
+			highlight::write_highlighted(&mut highlighted, &preview.join("\n"))?;
+			for (row, line) in String::from_utf8_lossy(&highlighted).lines().enumerate() {
+				let _ = write!(frame, "\x1b[{row};1H{line}", row = preview_top + 1 + row);
+			}
+
+			let status = match &self.prompt {
+				Prompt::None => format!(
+					"[{pos}/{len}]  {status}",
+					pos = self.cursor + 1,
+					len = self.entries.len(),
+					status = self.status
+				),
+				Prompt::Search(input) => format!("/{input}"),
+				Prompt::WriteDir(input) => format!("write to directory: {input}"),
+			};
+			let _ = write!(frame, "\x1b[{rows};1H{}", truncate(&status, cols));
+
+			terminal.write_all(frame.as_bytes())?;
+			terminal.flush()
+		}
+	}
+
+	/// Returns the first meaningful line of a document, for display in a list.
+	pub(super) fn summarize(content: &str) -> String {
+		content
+			.lines()
+			.map(|line| line.strip_prefix("--- ").unwrap_or(line).trim())
+			.find(|line| {
+				!line.is_empty()
+					&& !line.starts_with('#')
+					&& !line.starts_with('%')
+					&& !matches!(*line, "---" | "...")
+			})
+			.unwrap_or_default()
+			.to_owned()
+	}
+
+	/// Truncates `text` to at most `cols` characters.
+	fn truncate(text: &str, cols: usize) -> &str {
+		match text.char_indices().nth(cols) {
+			Some((i, _)) => &text[..i],
+			None => text,
+		}
+	}
+}
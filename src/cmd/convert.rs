@@ -0,0 +1,68 @@
+//! The `convert` subcommand, which writes the documents of a stream as JSON.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use super::Flush;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+	#[clap(
+		name = "inputfile",
+		help = "A file to read from instead of standard input"
+	)]
+	inputfile: Option<PathBuf>,
+
+	#[clap(
+		long,
+		value_enum,
+		env = "YAML_SPLIT_CONVERT_TO",
+		default_value = "jsonl",
+		help = "The format to convert the documents to"
+	)]
+	to: Format,
+
+	#[clap(
+		short,
+		long,
+		value_name = "N",
+		env = "YAML_SPLIT_JOBS",
+		default_value_t = 1,
+		value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+		help = "The number of threads to convert documents on"
+	)]
+	jobs: usize,
+
+	#[clap(
+		long,
+		value_enum,
+		value_name = "POLICY",
+		env = "YAML_SPLIT_FLUSH",
+		default_value = "block",
+		help = "When to flush output to its destination"
+	)]
+	flush: Flush,
+
+	#[command(flatten)]
+	output: super::OutputArgs,
+}
+
+/// The formats that convert can write documents in.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Format {
+	/// Newline-delimited JSON, with one document per line.
+	Jsonl,
+	/// A single JSON array, with one item per document.
+	JsonArray,
+}
+
+pub(crate) fn run(args: Args) -> io::Result<()> {
+	let input = args.inputfile.as_deref();
+	let mut output = args.output.open(input)?;
+	let docs = Box::new(super::chunk_input(input)?);
+	match args.to {
+		Format::Jsonl => super::write_json_lines(docs, &mut output, args.flush, args.jobs)?,
+		Format::JsonArray => super::write_json_array(docs, &mut output, args.flush, args.jobs)?,
+	};
+	output.flush()
+}
@@ -0,0 +1,51 @@
+//! The `count` subcommand, which prints the number of documents in a stream.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use super::Backend;
+use crate::transform::Documents;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+	#[clap(
+		name = "inputfile",
+		help = "A file to read from instead of standard input"
+	)]
+	inputfile: Option<PathBuf>,
+
+	#[clap(
+		short,
+		long,
+		help = "Print each document's index and size in bytes before the count"
+	)]
+	verbose: bool,
+
+	#[clap(
+		long,
+		value_enum,
+		default_value_t = Backend::Libyaml,
+		env = "YAML_SPLIT_BACKEND",
+		help = "How to find the documents in the stream"
+	)]
+	backend: Backend,
+}
+
+pub(crate) fn run(args: Args) -> io::Result<()> {
+	let mut output = super::stdout();
+	let input = args.inputfile.as_deref();
+	let docs: Documents = match args.backend {
+		Backend::Libyaml => Box::new(super::chunk_input(input)?.discard_content()),
+		Backend::Lexical => Box::new(super::chunk_input_lexically(input)?),
+	};
+	let mut count = 0usize;
+	for doc in docs {
+		let doc = doc?;
+		if args.verbose {
+			let original = doc.original();
+			writeln!(output, "{count}\t{}", original.end - original.start)?;
+		}
+		count += 1;
+	}
+	writeln!(output, "{count}")
+}
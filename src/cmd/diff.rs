@@ -0,0 +1,174 @@
+//! The `diff` subcommand, which compares the documents of two streams.
+//!
+//! Documents are paired up by their position in each stream, or by the values
+//! at one or more key paths, like `.kind` and `.metadata.name` for Kubernetes
+//! resources. A pair differs if the documents have different data once
+//! parsed, so a change in formatting, comments, or anchors alone doesn't
+//! count, though a change in the order of keys does.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::chunker::Document;
+use crate::json;
+use crate::node::Content;
+use crate::query::Query;
+use crate::textdiff;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+	#[clap(help = "The stream to compare from")]
+	old: PathBuf,
+
+	#[clap(help = "The stream to compare to")]
+	new: PathBuf,
+
+	#[clap(
+		long,
+		value_name = "PATH",
+		help = "Pair documents by the scalar at a path like .metadata.name, instead of by position"
+	)]
+	key: Vec<Query>,
+
+	#[clap(
+		short,
+		long,
+		help = "Show the lines that differ in each changed document"
+	)]
+	unified: bool,
+
+	#[clap(
+		long,
+		value_name = "N",
+		env = "YAML_SPLIT_CONTEXT",
+		default_value_t = 3,
+		help = "The number of unchanged lines to show around each change with --unified"
+	)]
+	context: usize,
+
+	#[clap(long = "exit-code", help = "Fail if any documents differ")]
+	exit_code: bool,
+
+	#[command(flatten)]
+	output: super::OutputArgs,
+}
+
+pub(crate) fn run(args: Args) -> io::Result<()> {
+	let inputs = [args.old.as_path(), args.new.as_path()];
+	let mut output = args.output.open(inputs)?;
+	let old = read_stream(&args.old, &args.key)?;
+	let new = read_stream(&args.new, &args.key)?;
+
+	let mut new_by_label = HashMap::new();
+	for (i, (label, _)) in new.iter().enumerate() {
+		new_by_label.insert(label.as_str(), i);
+	}
+	let mut paired = vec![false; new.len()];
+	let mut differences = 0;
+	for (label, old_doc) in &old {
+		let Some(&i) = new_by_label.get(label.as_str()) else {
+			writeln!(output, "removed: {label}")?;
+			differences += 1;
+			continue;
+		};
+		paired[i] = true;
+		let new_doc = &new[i].1;
+		if data(old_doc)? == data(new_doc)? {
+			continue;
+		}
+		writeln!(output, "changed: {label}")?;
+		differences += 1;
+		if args.unified {
+			writeln!(output, "--- {}", args.old.display())?;
+			writeln!(output, "+++ {}", args.new.display())?;
+			let hunks = textdiff::unified(
+				old_doc.content(),
+				new_doc.content(),
+				first_line(old_doc),
+				first_line(new_doc),
+				args.context,
+			);
+			output.write_all(hunks.as_bytes())?;
+		}
+	}
+	for ((label, _), _) in new.iter().zip(paired).filter(|(_, paired)| !paired) {
+		writeln!(output, "added: {label}")?;
+		differences += 1;
+	}
+	output.flush()?;
+
+	match differences {
+		n if n > 0 && args.exit_code => Err(io::Error::new(
+			io::ErrorKind::Other,
+			format!("{n} documents differ"),
+		)),
+		_ => Ok(()),
+	}
+}
+
+/// Returns the documents of the stream at `path`, each with the label that
+/// pairs it with a document of the other stream.
+///
+/// Without `keys`, a document's label is its one-based number in the stream.
+/// Otherwise, it's the values of the keys joined by slashes, which must be
+/// unique within the stream.
+fn read_stream(path: &Path, keys: &[Query]) -> io::Result<Vec<(String, Document)>> {
+	let mut docs = vec![];
+	let mut labels = HashMap::new();
+	for (i, doc) in super::chunk_input(Some(path))?.enumerate() {
+		let doc = doc?;
+		let n = i + 1;
+		if keys.is_empty() {
+			docs.push((format!("document {n}"), doc));
+			continue;
+		}
+		let mut values = vec![];
+		for key in keys {
+			match key.get(doc.value()?).map(|node| &node.content) {
+				Some(Content::Scalar(value, _)) => values.push(value.as_str()),
+				Some(_) => {
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						format!(
+							"{key} in document {n} of {path} is not a scalar",
+							path = path.display()
+						),
+					))
+				}
+				None => {
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						format!(
+							"document {n} of {path} has no value at {key}",
+							path = path.display()
+						),
+					))
+				}
+			}
+		}
+		let label = values.join("/");
+		if let Some(first) = labels.insert(label.clone(), n) {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!(
+					"documents {first} and {n} of {path} are both {label}",
+					path = path.display(),
+				),
+			));
+		}
+		docs.push((label, doc));
+	}
+	Ok(docs)
+}
+
+/// Returns the data of a document in a form that ignores formatting.
+fn data(doc: &Document) -> io::Result<String> {
+	json::to_json(doc.value()?)
+}
+
+/// Returns the one-based line number of the start of a document in its file.
+fn first_line(doc: &Document) -> usize {
+	doc.position()
+		.map_or(1, |position| position.start.line as usize + 1)
+}
@@ -0,0 +1,60 @@
+//! The `extract` subcommand, which prints a single document from a stream.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::index::Index;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+	#[clap(help = "The zero-based index of the document to print")]
+	index: usize,
+
+	#[clap(
+		name = "inputfile",
+		help = "A file to read from instead of standard input"
+	)]
+	inputfile: Option<PathBuf>,
+
+	#[clap(
+		long = "index-in",
+		value_name = "FILE",
+		requires = "inputfile",
+		help = "Find the document through an index from split --index-out"
+	)]
+	index_in: Option<PathBuf>,
+
+	#[command(flatten)]
+	output: super::OutputArgs,
+}
+
+pub(crate) fn run(args: Args) -> io::Result<()> {
+	let input = args.inputfile.as_deref();
+	let mut output = args.output.open(input)?;
+	if let (Some(path), Some(input)) = (&args.index_in, input) {
+		let index = Index::load(path)?;
+		let Some(content) = index.read(&mut File::open(input)?, args.index)? else {
+			return Err(out_of_range(args.index, index.len()));
+		};
+		output.write_all(content.as_bytes())?;
+		return output.flush();
+	}
+	let mut count = 0usize;
+	for doc in super::chunk_input(input)? {
+		let doc = doc?;
+		if count == args.index {
+			output.write_all(doc.content().as_bytes())?;
+			return output.flush();
+		}
+		count += 1;
+	}
+	Err(out_of_range(args.index, count))
+}
+
+fn out_of_range(index: usize, count: usize) -> io::Error {
+	io::Error::new(
+		io::ErrorKind::InvalidInput,
+		format!("document index {index} is out of range for a stream of {count} documents"),
+	)
+}
@@ -0,0 +1,133 @@
+//! The `join` subcommand, which combines files into a single YAML stream.
+//!
+//! Joining is the inverse of splitting. Each file is appended to the stream
+//! as-is by a [`DocumentWriter`], which adds only the markers needed to keep
+//! the documents of each file separate from those around it.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics;
+use crate::encoding::Encoder;
+use crate::writer::DocumentWriter;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+	#[clap(
+		required_unless_present = "dir",
+		conflicts_with = "dir",
+		help = "The files to join, in order"
+	)]
+	files: Vec<PathBuf>,
+
+	#[clap(
+		long,
+		value_name = "DIR",
+		help = "Join the .yaml and .yml files in a directory"
+	)]
+	dir: Option<PathBuf>,
+
+	#[clap(
+		long,
+		value_enum,
+		env = "YAML_SPLIT_SORT",
+		default_value = "name",
+		help = "The order to join the files of a directory in"
+	)]
+	sort: SortOrder,
+
+	#[clap(
+		long = "strip-bom",
+		help = "Remove any byte order mark from the start of each file"
+	)]
+	strip_bom: bool,
+
+	#[command(flatten)]
+	output: super::OutputArgs,
+}
+
+/// The orders for joining the files of a directory.
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum SortOrder {
+	/// By file name.
+	Name,
+	/// By modification time, oldest first, then by file name.
+	Mtime,
+}
+
+pub(crate) fn run(args: Args) -> io::Result<()> {
+	let files = match &args.dir {
+		None => args.files,
+		Some(dir) => {
+			let files = list_dir(dir, args.sort)?;
+			write_manifest(dir, &files);
+			files
+		}
+	};
+
+	let output = args.output.open(files.iter().map(PathBuf::as_path))?;
+	let mut writer = match args.output.append_path() {
+		Some(path) => DocumentWriter::appending(output, path)?,
+		None => DocumentWriter::new(output),
+	};
+	for path in &files {
+		let mut content = String::new();
+		Encoder::from_reader(BufReader::new(File::open(path)?))?.read_to_string(&mut content)?;
+		if args.strip_bom {
+			content = content.trim_start_matches('\u{FEFF}').to_owned();
+		}
+		writer.write_document(&content)?;
+	}
+	writer.flush()
+}
+
+/// Returns the paths of the YAML files directly inside `dir`, in the order
+/// they should be joined.
+///
+/// Hidden files are skipped, so that editor swap files and the like aren't
+/// picked up by accident.
+fn list_dir(dir: &Path, sort: SortOrder) -> io::Result<Vec<PathBuf>> {
+	let mut files = vec![];
+	for entry in fs::read_dir(dir)? {
+		let entry = entry?;
+		let path = entry.path();
+		let hidden = entry.file_name().to_string_lossy().starts_with('.');
+		let yaml = path
+			.extension()
+			.is_some_and(|ext| ext == "yaml" || ext == "yml");
+		if yaml && !hidden && entry.file_type()?.is_file() {
+			let mtime = match sort {
+				SortOrder::Name => None,
+				SortOrder::Mtime => Some(entry.metadata()?.modified()?),
+			};
+			files.push((mtime, path));
+		}
+	}
+	if files.is_empty() {
+		return Err(io::Error::new(
+			io::ErrorKind::NotFound,
+			format!("no .yaml or .yml files found in {}", dir.display()),
+		));
+	}
+	files.sort();
+	Ok(files.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Lists the files joined from a directory on standard error, unless
+/// informational output is suppressed.
+fn write_manifest(dir: &Path, files: &[PathBuf]) {
+	if diagnostics::is_quiet() {
+		return;
+	}
+	let mut stderr = io::stderr().lock();
+	let _ = writeln!(
+		stderr,
+		"Joining {n} files from {dir}:",
+		n = files.len(),
+		dir = dir.display()
+	);
+	for path in files {
+		let _ = writeln!(stderr, "  {}", path.display());
+	}
+}
@@ -0,0 +1,92 @@
+//! The hidden `mangen` subcommand, which renders manual pages for the CLI.
+//!
+//! The pages are generated from the same clap definitions that drive argument
+//! parsing, so packagers can ship documentation that always matches the
+//! binary. By default, only the top-level page is written to standard output.
+//! With `--out-dir`, a page is written for the top-level command along with
+//! one for each subcommand, in the style of `git(1)` and `git-commit(1)`.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use clap::CommandFactory;
+use clap_mangen::Man;
+
+use crate::cli::Cli;
+use crate::{interrupt, pipecheck};
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+	#[clap(
+		long,
+		value_name = "DIR",
+		help = "Write pages for every command into this directory"
+	)]
+	out_dir: Option<PathBuf>,
+}
+
+/// The exit codes that yaml-split can produce, for the EXIT STATUS section.
+const EXIT_CODES: &[(u8, &str)] = &[
+	(0, "Success."),
+	(
+		1,
+		"An error occurred while reading, splitting, or writing the stream.",
+	),
+	(2, "The command line arguments were invalid."),
+	(
+		interrupt::EXIT_CODE,
+		"The split was interrupted by SIGINT, after writing every document it had read.",
+	),
+	(
+		interrupt::TIMEOUT_EXIT_CODE,
+		"The split stopped when the time given by --timeout passed.",
+	),
+	(
+		pipecheck::EXIT_CODE,
+		"The reader of the output went away, with --pipe-exit signal.",
+	),
+];
+
+pub(crate) fn run(args: Args) -> io::Result<()> {
+	let mut cmd = Cli::command().disable_help_subcommand(true);
+	cmd.build();
+
+	let Some(out_dir) = args.out_dir else {
+		return render(cmd, &mut super::stdout());
+	};
+
+	let mut pending = vec![cmd];
+	while let Some(cmd) = pending.pop() {
+		pending.extend(
+			cmd.get_subcommands()
+				.filter(|sub| !sub.is_hide_set())
+				.cloned(),
+		);
+		let path = out_dir.join(Man::new(cmd.clone()).get_filename());
+		let mut file = File::create(path)?;
+		render(cmd, &mut file)?;
+		file.flush()?;
+	}
+	Ok(())
+}
+
+/// Renders the page for a single command, including the standard clap_mangen
+/// sections and an EXIT STATUS section.
+fn render(cmd: clap::Command, w: &mut dyn Write) -> io::Result<()> {
+	let has_subcommands = cmd.get_subcommands().any(|sub| !sub.is_hide_set());
+	let man = Man::new(cmd).source(format!("yaml-split {}", env!("CARGO_PKG_VERSION")));
+	man.render_title(w)?;
+	man.render_name_section(w)?;
+	man.render_synopsis_section(w)?;
+	man.render_description_section(w)?;
+	man.render_options_section(w)?;
+	if has_subcommands {
+		man.render_subcommands_section(w)?;
+	}
+	writeln!(w, ".SH \"EXIT STATUS\"")?;
+	for (code, description) in EXIT_CODES {
+		write!(w, ".TP\n{code}\n{description}\n")?;
+	}
+	Ok(())
+}
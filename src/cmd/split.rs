@@ -0,0 +1,2484 @@
+//! The `split` subcommand, which shows the boundaries between documents.
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, VecDeque};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{Backend, ColorChoice, Flush};
+use crate::budget::{self, Claim};
+use crate::checkpoint::{Checkpoint, Checkpointer};
+use crate::chunker::{
+	Chunker, Chunking, CommentPlacement, Document, DocumentKind, Features, Input, LimitExceeded,
+	Stats,
+};
+#[cfg(feature = "compression")]
+use crate::compression;
+use crate::config;
+use crate::dedup::{self, DedupState};
+use crate::diagnostics::{self, warning};
+use crate::dump;
+use crate::encoding::Encoding;
+use crate::hash;
+use crate::highlight;
+use crate::index::{self, Index};
+use crate::inputs::{self, Glob};
+use crate::interrupt;
+use crate::json;
+use crate::libyaml::EmitterOptions;
+use crate::memory;
+use crate::node::{Content, CORE_TAG_PREFIX};
+use crate::output::{Place, Template};
+use crate::pager;
+use crate::query::{Condition, Query};
+use crate::rate::{self, Rate};
+use crate::regex::Regex;
+use crate::select::{self, Selection};
+use crate::transform::{self, Documents, Layout};
+use crate::writer::DocumentWriter;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+	#[clap(
+		name = "inputfile",
+		help = "A file to read from instead of standard input"
+	)]
+	inputfile: Option<PathBuf>,
+
+	#[command(flatten)]
+	output: super::OutputArgs,
+
+	#[clap(
+		long,
+		value_name = "FILE",
+		num_args = 2..,
+		conflicts_with_all = ["inputfile", "checkpoint", "total_counts"],
+		help = "Read documents from each file in turn, instead of from one input"
+	)]
+	zip: Vec<PathBuf>,
+
+	#[clap(
+		long = "zip-pad",
+		requires = "zip",
+		help = "Pad --zip inputs that end early with empty documents, instead of failing"
+	)]
+	zip_pad: bool,
+
+	#[clap(
+		long,
+		value_enum,
+		value_name = "POLICY",
+		env = "YAML_SPLIT_NUMBERING",
+		default_value = "continue",
+		help = "How --annotate numbers the documents of --zip inputs"
+	)]
+	numbering: Numbering,
+
+	#[clap(
+		long,
+		value_name = "DIR",
+		conflicts_with_all = [
+			"inputfile",
+			"zip",
+			"checkpoint",
+			"in_place",
+			"index_out",
+			"total_counts",
+			"events",
+			"tokens",
+		],
+		help = "Read every YAML file under DIR in turn, instead of one input"
+	)]
+	recursive: Option<PathBuf>,
+
+	#[clap(
+		long,
+		value_name = "GLOB",
+		help = "Only read the files under --recursive or a glob input that match GLOB"
+	)]
+	include: Vec<Glob>,
+
+	#[clap(
+		long,
+		value_name = "GLOB",
+		help = "Skip the files and directories under --recursive or a glob input that match GLOB"
+	)]
+	exclude: Vec<Glob>,
+
+	#[clap(
+		long,
+		value_enum,
+		default_value_t = Backend::Libyaml,
+		env = "YAML_SPLIT_BACKEND",
+		help = "How to find the documents in YAML input"
+	)]
+	backend: Backend,
+
+	#[clap(
+		long = "cross-check",
+		conflicts_with_all = ["zip", "checkpoint", "report", "comments", "events", "tokens"],
+		help = "Find the documents with both backends too, and fail wherever they disagree"
+	)]
+	cross_check: bool,
+
+	#[clap(
+		long,
+		hide = true,
+		conflicts_with_all = ["checkpoint", "report", "tag", "events", "tokens", "comments"],
+		help = "The same as --backend lexical, which replaces it"
+	)]
+	lexical: bool,
+
+	#[clap(
+		long,
+		value_enum,
+		env = "YAML_SPLIT_FROM",
+		default_value = "yaml",
+		help = "The format of the input"
+	)]
+	from: InputFormat,
+
+	#[clap(
+		long,
+		value_enum,
+		env = "YAML_SPLIT_TO",
+		default_value = "chunks",
+		help = "The format of the output"
+	)]
+	to: OutputFormat,
+
+	#[clap(
+		long,
+		env = "YAML_SPLIT_RAW",
+		value_parser = clap::builder::BoolishValueParser::new(),
+		help = "Write each document exactly as it was read, without chunk markers"
+	)]
+	raw: bool,
+
+	#[clap(
+		long,
+		value_name = "TEXT",
+		env = "YAML_SPLIT_DELIMITER",
+		help = "The text to write between documents with --raw"
+	)]
+	delimiter: Option<String>,
+
+	#[clap(
+		long,
+		conflicts_with_all = [
+			"get",
+			"report",
+			"events",
+			"tokens",
+			"in_place",
+			"output_dir",
+			"exec",
+		],
+		help = "Write each document as it was read and end it with a NUL byte, for xargs -0"
+	)]
+	print0: bool,
+
+	#[clap(
+		long,
+		value_enum,
+		value_name = "ALGORITHM",
+		help = "Include a digest of each document in its chunk header"
+	)]
+	hash: Option<hash::Algorithm>,
+
+	#[clap(
+		long,
+		help = "Include a stable ID derived from each document's content in its chunk header"
+	)]
+	ids: bool,
+
+	#[clap(
+		long,
+		help = "Include the lines and columns each document spans in its chunk header"
+	)]
+	positions: bool,
+
+	#[clap(
+		long = "dedup-state",
+		value_name = "FILE",
+		help = "Skip documents recorded in a state file by earlier runs, and record new ones"
+	)]
+	dedup_state: Option<PathBuf>,
+
+	#[clap(
+		long,
+		value_name = "FILE",
+		conflicts_with_all = ["explode_seq", "by_key", "implode", "reverse", "shuffle"],
+		help = "Record progress to a file, so an interrupted split can be resumed"
+	)]
+	checkpoint: Option<PathBuf>,
+
+	#[clap(
+		long = "index-out",
+		value_name = "FILE",
+		conflicts_with_all = ["zip", "checkpoint"],
+		help = "Write an index of the input's documents, for extract --index-in"
+	)]
+	index_out: Option<PathBuf>,
+
+	#[clap(
+		long = "in-place",
+		requires = "inputfile",
+		conflicts_with_all = ["zip", "checkpoint", "get", "report", "events", "tokens"],
+		help = "Write each document to its own file next to the input, and list the files"
+	)]
+	in_place: bool,
+
+	#[clap(
+		long = "in-place-layout",
+		value_enum,
+		value_name = "LAYOUT",
+		env = "YAML_SPLIT_IN_PLACE_LAYOUT",
+		default_value = "dir",
+		help = "Where --in-place puts the file for each document"
+	)]
+	in_place_layout: InPlaceLayout,
+
+	#[clap(
+		long = "output-dir",
+		value_name = "DIR",
+		conflicts_with_all = ["checkpoint", "get", "report", "events", "tokens", "in_place"],
+		help = "Write each document to its own file in DIR, as doc-0001.yaml onward, and list the files"
+	)]
+	output_dir: Option<PathBuf>,
+
+	#[cfg(feature = "compression")]
+	#[clap(
+		long,
+		value_enum,
+		value_name = "FORMAT",
+		help = "Compress each file for --in-place or --output-dir, or else the whole output"
+	)]
+	compress: Option<Compress>,
+
+	#[clap(
+		long = "filename-template",
+		value_name = "TEMPLATE",
+		help = "Name the files for --in-place or --output-dir like cm-{index:04}.yaml"
+	)]
+	filename_template: Option<Template>,
+
+	#[clap(
+		long = "name-from-path",
+		value_name = "PATH",
+		help = "Name each document after the scalar at a path like .metadata.name"
+	)]
+	name_from_path: Vec<Query>,
+
+	#[clap(
+		long = "name-from-regex",
+		value_name = "REGEX",
+		help = "Name documents without every --name-from-path after a match in their text"
+	)]
+	name_from_regex: Option<Regex>,
+
+	#[clap(
+		long = "replace-input",
+		requires = "in_place",
+		help = "Replace the input with a list of the files written by --in-place"
+	)]
+	replace_input: bool,
+
+	#[clap(
+		long,
+		value_name = "COMMAND",
+		conflicts_with_all = ["checkpoint", "get", "report", "events", "tokens", "in_place", "output_dir"],
+		help = "Run a shell command for each document, with the document on its standard input"
+	)]
+	exec: Option<OsString>,
+
+	#[clap(
+		long,
+		conflicts_with_all = [
+			"checkpoint",
+			"get",
+			"report",
+			"events",
+			"tokens",
+			"in_place",
+			"output_dir",
+			"exec",
+		],
+		help = "End the output with a document giving the number, size, and digests of the others"
+	)]
+	trailer: bool,
+
+	#[clap(
+		long,
+		requires = "checkpoint",
+		help = "Continue from the progress recorded in the --checkpoint file"
+	)]
+	resume: bool,
+
+	#[clap(
+		long,
+		value_name = "PATH",
+		help = "Print the scalar at a path like .metadata.name from each document"
+	)]
+	get: Option<Query>,
+
+	#[clap(
+		long,
+		value_name = "VALUE",
+		requires = "get",
+		help = "The value to print for documents without the --get path"
+	)]
+	default: Option<String>,
+
+	#[clap(
+		long,
+		value_name = "POSITIONS",
+		conflicts_with_all = ["checkpoint", "events", "tokens"],
+		help = "Keep only the documents at positions from zero like 0,3,10-20,-1"
+	)]
+	select: Option<Selection>,
+
+	#[clap(
+		long,
+		value_name = "PATH",
+		help = "Keep only documents with a node at a path like .spec.template"
+	)]
+	has: Vec<Query>,
+
+	#[clap(
+		long = "where",
+		value_name = "CONDITION",
+		help = "Keep only documents with a scalar like .kind=ConfigMap or .kind!=Secret"
+	)]
+	where_: Vec<Condition>,
+
+	#[clap(
+		long,
+		value_name = "TAG",
+		value_parser = resolve_tag,
+		help = "Keep only documents whose root has a tag like !Deployment or !!map"
+	)]
+	tag: Vec<String>,
+
+	#[clap(
+		long,
+		value_name = "REGEX",
+		conflicts_with_all = ["events", "tokens"],
+		help = "Keep only documents whose text matches a pattern like ^kind: Secret$"
+	)]
+	grep: Vec<Regex>,
+
+	#[clap(
+		long = "grep-v",
+		value_name = "REGEX",
+		conflicts_with_all = ["events", "tokens"],
+		help = "Keep only documents whose text doesn't match a pattern"
+	)]
+	grep_v: Vec<Regex>,
+
+	#[clap(
+		long,
+		conflicts_with_all = ["zip", "checkpoint", "get", "report"],
+		help = "Print the parser's events as JSON lines instead of splitting the input"
+	)]
+	events: bool,
+
+	#[clap(
+		long,
+		conflicts_with_all = ["zip", "checkpoint", "get", "report", "events"],
+		help = "Print the scanner's tokens as JSON lines instead of splitting the input"
+	)]
+	tokens: bool,
+
+	#[clap(
+		long,
+		value_enum,
+		value_name = "KIND",
+		conflicts_with_all = ["get", "checkpoint"],
+		help = "Print a report on each document instead of its content"
+	)]
+	report: Option<Report>,
+
+	#[clap(
+		long,
+		value_enum,
+		env = "YAML_SPLIT_COLOR",
+		default_value = "auto",
+		help = "When to highlight documents and color the chunk markers"
+	)]
+	color: ColorChoice,
+
+	#[clap(
+		long = "explode-seq",
+		help = "Split each document whose root is a sequence into one document per item"
+	)]
+	explode_seq: bool,
+
+	#[clap(
+		long,
+		conflicts_with = "explode_seq",
+		help = "Combine all documents into one whose root is a sequence of them"
+	)]
+	implode: bool,
+
+	#[clap(
+		long = "by-key",
+		help = "Split each document whose root is a mapping into one document per key"
+	)]
+	by_key: bool,
+
+	#[clap(long, help = "Output documents in reverse order")]
+	reverse: bool,
+
+	#[clap(
+		long,
+		conflicts_with = "reverse",
+		help = "Output documents in a pseudo-random order chosen by --seed"
+	)]
+	shuffle: bool,
+
+	#[clap(
+		long,
+		value_name = "N",
+		env = "YAML_SPLIT_SEED",
+		default_value_t = 0,
+		help = "The seed for --shuffle, which always gives the same order for the same seed"
+	)]
+	seed: u64,
+
+	#[clap(
+		long,
+		value_enum,
+		value_name = "POLICY",
+		env = "YAML_SPLIT_FLUSH",
+		default_value = "block",
+		help = "When to flush output to its destination"
+	)]
+	flush: Flush,
+
+	#[clap(
+		long,
+		value_name = "RATE",
+		help = "Write documents no faster than RATE, like 10/s or 1MB/s"
+	)]
+	rate: Option<Rate>,
+
+	#[clap(
+		long = "empty-docs",
+		value_enum,
+		value_name = "POLICY",
+		env = "YAML_SPLIT_EMPTY_DOCS",
+		default_value = "emit",
+		help = "What to do with documents that have nothing at their root"
+	)]
+	empty_docs: EmptyDocs,
+
+	#[clap(
+		long = "comment-only-docs",
+		value_enum,
+		value_name = "POLICY",
+		env = "YAML_SPLIT_COMMENT_ONLY_DOCS",
+		default_value = "emit",
+		help = "What to do with documents that have nothing but comments"
+	)]
+	comment_only_docs: CommentOnlyDocs,
+
+	#[clap(
+		long,
+		value_enum,
+		value_name = "POLICY",
+		conflicts_with_all = ["checkpoint", "events", "tokens"],
+		help = "Which document gets the comments between two documents"
+	)]
+	comments: Option<Comments>,
+
+	#[clap(
+		long,
+		value_enum,
+		value_name = "POLICY",
+		env = "YAML_SPLIT_MARKERS",
+		default_value = "keep",
+		help = "What to do with the --- and ... markers of each document"
+	)]
+	markers: Markers,
+
+	#[clap(
+		long = "assert-sorted",
+		value_name = "PATH",
+		conflicts_with_all = ["events", "tokens"],
+		help = "Fail unless documents are in order of the scalar at a path like .metadata.name"
+	)]
+	assert_sorted: Option<Query>,
+
+	#[clap(
+		long,
+		help = "Print the number of documents, time taken, and memory used to standard error"
+	)]
+	stats: bool,
+
+	#[clap(
+		long,
+		value_name = "DURATION",
+		value_parser = interrupt::parse_duration,
+		env = "YAML_SPLIT_TIMEOUT",
+		help = "Stop cleanly once the run has taken DURATION, like 90s or 10m"
+	)]
+	timeout: Option<Duration>,
+
+	#[cfg(all(target_os = "linux", feature = "sandbox"))]
+	#[clap(
+		long,
+		help = "Deny opening files and using the network once the input is open"
+	)]
+	sandbox: bool,
+
+	#[clap(
+		long = "memory-limit",
+		value_name = "SIZE",
+		value_parser = budget::parse_size,
+		env = "YAML_SPLIT_MEMORY_LIMIT",
+		help = "Fail instead of buffering more than SIZE bytes of input, like 512M"
+	)]
+	memory_limit: Option<usize>,
+
+	#[clap(
+		long,
+		conflicts_with_all = ["reverse", "shuffle", "implode", "dedup_state", "total_counts"],
+		help = "Guarantee bounded memory use however long the input, for endless streams"
+	)]
+	streaming: bool,
+
+	#[clap(
+		long = "max-doc-bytes",
+		value_name = "N",
+		env = "YAML_SPLIT_MAX_DOC_BYTES",
+		help = "Fail on any input document larger than N bytes"
+	)]
+	max_doc_bytes: Option<usize>,
+
+	#[clap(
+		long,
+		value_enum,
+		value_name = "POLICY",
+		env = "YAML_SPLIT_OVERSIZE",
+		default_value = "error",
+		help = "What to do with documents larger than --max-doc-bytes"
+	)]
+	oversize: Oversize,
+
+	#[clap(
+		long = "warn-doc-bytes",
+		value_name = "N",
+		env = "YAML_SPLIT_WARN_DOC_BYTES",
+		help = "Warn about each input document larger than N bytes"
+	)]
+	warn_doc_bytes: Option<usize>,
+
+	#[clap(
+		short,
+		long,
+		value_name = "N",
+		env = "YAML_SPLIT_JOBS",
+		default_value_t = 1,
+		value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+		help = "The number of threads for work on each document, like --pretty or --to jsonl"
+	)]
+	jobs: usize,
+
+	#[clap(
+		long,
+		help = "Re-serialize documents in block style with consistent indentation"
+	)]
+	pretty: bool,
+
+	#[clap(
+		long,
+		value_name = "N",
+		env = "YAML_SPLIT_INDENT",
+		default_value_t = 2,
+		value_parser = clap::value_parser!(i32).range(2..=9),
+		help = "The number of spaces to indent by with --pretty"
+	)]
+	indent: i32,
+
+	#[clap(
+		long,
+		conflicts_with = "pretty",
+		help = "Re-serialize documents in flow style, on a single line where possible"
+	)]
+	minify: bool,
+
+	#[clap(
+		long,
+		conflicts_with_all = ["pretty", "minify"],
+		help = "Re-serialize documents in canonical form, with every tag explicit"
+	)]
+	canonical: bool,
+
+	#[clap(
+		long = "strip-comments",
+		help = "Remove comments from documents, keeping everything else as-is"
+	)]
+	strip_comments: bool,
+
+	#[clap(
+		long,
+		help = "Start each document with a comment giving its position in the input"
+	)]
+	annotate: bool,
+
+	#[clap(
+		long = "number-lines",
+		conflicts_with_all = ["get", "report", "events", "tokens"],
+		help = "Number the lines of each document from 1, like nl, for reviewing it"
+	)]
+	number_lines: bool,
+
+	#[clap(
+		long = "total-counts",
+		help = "Count the documents in an input file first, for --annotate, --exec, or {total}"
+	)]
+	total_counts: bool,
+}
+
+/// The formats that split can read documents from.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum InputFormat {
+	/// A YAML stream.
+	Yaml,
+	/// Concatenated JSON values, or a single JSON array of values.
+	Json,
+	/// Newline-delimited JSON, with one value per line.
+	Ndjson,
+}
+
+/// The formats that split can write documents in.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+	/// Each document between chunk markers.
+	Chunks,
+	/// A single JSON array, with one item per document.
+	JsonArray,
+	/// Newline-delimited JSON, with one document per line.
+	Jsonl,
+}
+
+impl OutputFormat {
+	fn name(self) -> &'static str {
+		match self {
+			OutputFormat::Chunks => "chunks",
+			OutputFormat::JsonArray => "json-array",
+			OutputFormat::Jsonl => "jsonl",
+		}
+	}
+}
+
+/// The policies for numbering documents from more than one input.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Numbering {
+	/// Number documents across the whole output, whatever their input.
+	Continue,
+	/// Number documents from one within each input.
+	PerInput,
+}
+
+/// The formats that `--compress` can write.
+#[cfg(feature = "compression")]
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Compress {
+	/// gzip, in files ending in `.gz`.
+	Gz,
+	/// Zstandard, in files ending in `.zst`.
+	Zst,
+}
+
+#[cfg(feature = "compression")]
+impl Compress {
+	fn format(self) -> compression::Format {
+		match self {
+			Compress::Gz => compression::Format::Gzip,
+			Compress::Zst => compression::Format::Zstd,
+		}
+	}
+}
+
+/// Where `--in-place` writes the documents of an input like `foo.yaml`.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum InPlaceLayout {
+	/// In a new directory named after the input, as `foo.d/0001.yaml` onward.
+	Dir,
+	/// Beside the input, as `foo-1.yaml` onward.
+	Siblings,
+}
+
+/// The policies for documents with nothing at their root, like the first in
+/// `---\n---\n`.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum EmptyDocs {
+	/// Output them like any other document.
+	Emit,
+	/// Leave them out of the output.
+	Skip,
+	/// Fail at the first one.
+	Error,
+}
+
+/// The policies for documents with nothing but comments, like the first in
+/// `---\n# note\n---\na: 1\n`.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum CommentOnlyDocs {
+	/// Output them like any other empty document.
+	Emit,
+	/// Move their comments to the start of the next document.
+	Attach,
+	/// Leave them out of the output.
+	Skip,
+}
+
+/// The policies for comments between documents, like the one in
+/// `a: 1\n# note\n---\nb: 2\n`.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Comments {
+	/// Keep them with the document before them.
+	AttachPrevious,
+	/// Keep them with the document after them.
+	AttachNext,
+	/// Leave them out of the output.
+	Strip,
+}
+
+impl Comments {
+	fn placement(self) -> CommentPlacement {
+		match self {
+			Comments::AttachPrevious => CommentPlacement::AttachPrevious,
+			Comments::AttachNext => CommentPlacement::AttachNext,
+			Comments::Strip => CommentPlacement::Strip,
+		}
+	}
+}
+
+/// The policies for the `---` and `...` markers of each document.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Markers {
+	/// Leave them as they are in the input.
+	Keep,
+	/// Remove them, except for a `---` that ends directives.
+	Strip,
+	/// Start every document with `---`.
+	Normalize,
+}
+
+/// The policies for documents larger than `--max-doc-bytes`.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Oversize {
+	/// Fail at the first one.
+	Error,
+	/// Leave them out of the output, with a warning.
+	Skip,
+	/// Cut them to the limit and mark where they end, with a warning.
+	Truncate,
+}
+
+/// The reports that split can print about each document.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Report {
+	/// The anchors defined, aliases used, and tags present.
+	Tags,
+	/// The number of nodes of each kind, and the maximum nesting depth.
+	Stats,
+}
+
+impl Report {
+	/// Configures a chunker to collect what the report needs.
+	fn configure<R: Read>(self, chunker: Chunker<R>) -> Chunker<R> {
+		match self {
+			Report::Tags => chunker.collect_features(),
+			Report::Stats => chunker.collect_stats(),
+		}
+	}
+}
+
+/// The document size limit with `--streaming`, if `--max-doc-bytes` isn't
+/// given.
+const STREAMING_MAX_DOC_BYTES: usize = 64 << 20;
+
+pub(crate) fn run(mut args: Args) -> io::Result<()> {
+	let started = Instant::now();
+	check_raw(&mut args)?;
+	check_backend(&mut args)?;
+	if let Some(timeout) = args.timeout {
+		interrupt::set_timeout(timeout)?;
+	}
+	if let Some(limit) = args.memory_limit {
+		budget::set_limit(limit);
+	}
+	if args.streaming {
+		if args.from == InputFormat::Json {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"--streaming can't read --from json, which is read in full; use --from ndjson",
+			));
+		}
+		args.max_doc_bytes.get_or_insert(STREAMING_MAX_DOC_BYTES);
+	}
+	let tree = find_inputs(&args)?;
+	let tree_paths = match &tree {
+		None => vec![],
+		Some((root, files)) => files.iter().map(|file| root.join(file)).collect(),
+	};
+	if args.cross_check && tree.is_some() {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"--cross-check only reads a single input file",
+		));
+	}
+	let input = args.inputfile.as_deref().filter(|_| tree.is_none());
+	let zipped = args.zip.iter().chain(&tree_paths).map(PathBuf::as_path);
+	let inputs = input.into_iter().chain(zipped);
+	// With files of their own, documents are compressed one file at a time.
+	#[cfg(feature = "compression")]
+	let mut output = match args.compress {
+		Some(compress) if !args.in_place && args.output_dir.is_none() => {
+			args.output.open_compressed(inputs, compress.format())?
+		}
+		_ => args.output.open(inputs)?,
+	};
+	#[cfg(not(feature = "compression"))]
+	let mut output = args.output.open(inputs)?;
+	let color = args.color.enabled_for(&args.output);
+	if args.events || args.tokens {
+		let input = super::open_utf8_input(input)?;
+		match args.events {
+			true => dump::events(input, &mut output)?,
+			false => dump::tokens(input, &mut output)?,
+		};
+		return output.flush();
+	}
+	let named = !args.name_from_path.is_empty() || args.name_from_regex.is_some();
+	if args.filename_template.is_some() && !args.in_place && args.output_dir.is_none() {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"--filename-template requires --in-place or --output-dir",
+		));
+	}
+	if args.output_dir.is_some() && args.to != OutputFormat::Chunks {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!(
+				"--output-dir writes documents as YAML, not --to {}",
+				args.to.name()
+			),
+		));
+	}
+	let mut files = match (args.in_place, input) {
+		(false, _) => match &args.output_dir {
+			None => None,
+			Some(dir) => {
+				let template = args.filename_template.as_ref();
+				let files = OutputFiles::in_dir(dir, template, named)?;
+				Some(match &tree {
+					None => files,
+					Some((_, inputs)) => files.mirroring(inputs),
+				})
+			}
+		},
+		(true, Some(path)) if path != Path::new("-") && args.to == OutputFormat::Chunks => {
+			let template = args.filename_template.as_ref();
+			let layout = args.in_place_layout;
+			Some(OutputFiles::in_place(path, layout, template, named)?)
+		}
+		(true, _) => {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"--in-place only supports splitting an input file into YAML documents",
+			))
+		}
+	};
+	#[cfg(feature = "compression")]
+	if let (Some(files), Some(compress)) = (&mut files, args.compress) {
+		files.compress = Some(compress.format());
+	}
+	if args.comments.is_some() && args.from != InputFormat::Yaml {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"--comments only applies to YAML input, not --from json or ndjson",
+		));
+	}
+	if args.exec.is_some() && args.to != OutputFormat::Chunks {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!(
+				"--exec passes documents to the command as YAML, not --to {}",
+				args.to.name()
+			),
+		));
+	}
+	if (args.raw || args.print0) && args.to != OutputFormat::Chunks {
+		let option = if args.raw { "--raw" } else { "--print0" };
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!(
+				"{option} writes documents as YAML, not --to {}",
+				args.to.name()
+			),
+		));
+	}
+	if args.number_lines && args.to != OutputFormat::Chunks {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!(
+				"--number-lines only applies to YAML output, not --to {}",
+				args.to.name()
+			),
+		));
+	}
+	if args.markers != Markers::Keep && args.to != OutputFormat::Chunks {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!(
+				"--markers only applies to YAML output, not --to {}",
+				args.to.name()
+			),
+		));
+	}
+	if args.total_counts
+		&& !args.annotate
+		&& args.exec.is_none()
+		&& args.filename_template.is_none()
+	{
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"--total-counts requires --annotate, --exec, or --filename-template",
+		));
+	}
+	let mut start = Checkpoint::default();
+	let mut docs = match &args.checkpoint {
+		None if tree.is_some() => {
+			let (format, limit, oversize) = (args.from, args.max_doc_bytes, args.oversize);
+			let (report, backend, comments) = (args.report, args.backend, args.comments);
+			let checks = args.input_checks();
+			concat(tree_paths.clone(), move |path| {
+				let docs = read_input(
+					format,
+					Some(path),
+					limit,
+					oversize,
+					report,
+					backend,
+					comments,
+				)?;
+				Ok(check_input(docs, &checks, input_name(Some(path))))
+			})
+		}
+		None if !args.zip.is_empty() => {
+			let mut streams = vec![];
+			for (index, path) in args.zip.iter().enumerate() {
+				let docs = read_input(
+					args.from,
+					Some(path),
+					args.max_doc_bytes,
+					args.oversize,
+					args.report,
+					args.backend,
+					args.comments,
+				)?;
+				let name = input_name(Some(path));
+				let docs = check_input(docs, &args.input_checks(), name.clone());
+				let input = Input { name, index };
+				streams.push((input.clone(), from_input(docs, input)));
+			}
+			zip(streams, args.zip_pad)
+		}
+		None => {
+			let docs = read_input(
+				args.from,
+				input,
+				args.max_doc_bytes,
+				args.oversize,
+				args.report,
+				args.backend,
+				args.comments,
+			)?;
+			match args.cross_check {
+				true => cross_check(docs, &args, input)?,
+				false => docs,
+			}
+		}
+		Some(path) => {
+			if args.from != InputFormat::Yaml
+				|| args.to != OutputFormat::Chunks
+				|| args.get.is_some()
+			{
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidInput,
+					"--checkpoint only supports splitting YAML input into chunks",
+				));
+			}
+			if args.resume {
+				start = Checkpoint::load(path)?.unwrap_or_default();
+			}
+			resume_input(input, start, args.max_doc_bytes, args.oversize)?
+		}
+	};
+	let mut checkpointer = args
+		.checkpoint
+		.clone()
+		.map(|path| Checkpointer::new(path, start));
+	let index = match &args.index_out {
+		None => None,
+		Some(_) => Some(Rc::new(RefCell::new(Index::new(indexed_len(input)?)))),
+	};
+	if let Some(index) = &index {
+		docs = index::record(docs, Rc::clone(index));
+	}
+	if args.zip.is_empty() && tree.is_none() {
+		let checks = args.input_checks();
+		docs = check_input(docs, &checks, input_name(input));
+	}
+	interrupt::install();
+	docs = interrupt::stop_on_request(docs);
+	let dedup = match &args.dedup_state {
+		None => None,
+		Some(path) => Some(Rc::new(RefCell::new(DedupState::load(path)?))),
+	};
+	docs = transform_documents(docs, &args, named, dedup.as_ref());
+	let total = match args.total_counts {
+		true => Some(count_documents(&args, input, named)?),
+		false => None,
+	};
+	if let Some(files) = &mut files {
+		files.total = total;
+		files.source = input
+			.filter(|path| *path != Path::new("-"))
+			.map(|path| path.to_string_lossy().into_owned());
+	}
+	if args.number_lines {
+		docs = transform::number_lines(docs);
+	}
+	if args.annotate {
+		let name = input_name(input);
+		let per_input = args.numbering == Numbering::PerInput;
+		docs = transform::annotate(docs, name, start.documents, total, per_input);
+	}
+	if args.trailer {
+		docs = transform::trailer(docs, args.hash.unwrap_or(hash::Algorithm::Sha256));
+	}
+	// Throttling output that then sits in a buffer would only make it arrive
+	// late and in bursts, so a rate limit always flushes every document.
+	let mut flush = args.flush;
+	if let Some(rate) = args.rate {
+		docs = rate::throttle(docs, rate);
+		flush = Flush::PerDoc;
+	}
+	#[cfg(all(target_os = "linux", feature = "sandbox"))]
+	if args.sandbox {
+		if args.exec.is_some() {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"--sandbox denies running the --exec command",
+			));
+		}
+		#[cfg(feature = "compression")]
+		if args.compress.is_some() {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"--sandbox denies running the compressor for --compress",
+			));
+		}
+		// Any file's path will do to allow writing in the files' directory.
+		let files_writable = files.as_ref().map(|files| files.dir.join("-"));
+		let writable = [
+			&args.checkpoint,
+			&args.dedup_state,
+			&args.index_out,
+			&files_writable,
+		];
+		let mut allowed = crate::sandbox::Allowed {
+			writable: writable.iter().filter_map(|path| path.as_deref()).collect(),
+			// The inputs of a tree are only opened in turn.
+			readable: tree_paths.iter().map(PathBuf::as_path).collect(),
+			..Default::default()
+		};
+		if args.replace_input {
+			allowed.writable.extend(input);
+		}
+		// Mirroring a tree creates a directory for each input's files.
+		if let Some(files) = files.as_ref().filter(|files| !files.mirrored.is_empty()) {
+			allowed.trees.push(&files.dir);
+		}
+		crate::sandbox::enter(&allowed)?;
+	}
+	let count = match (&args.get, args.to) {
+		_ if args.report.is_some() => write_report(docs, &mut output, flush, args.report.unwrap())?,
+		_ if args.exec.is_some() => {
+			let command = args.exec.clone().unwrap();
+			write_exec(docs, &mut output, flush, command, &input_name(input), total)?
+		}
+		_ if files.is_some() => write_files(docs, &mut output, flush, files.as_mut().unwrap())?,
+		(Some(query), _) => write_values(docs, &mut output, flush, query, args.default.as_deref())?,
+		(None, OutputFormat::Chunks) if args.raw || args.print0 => {
+			let (delimiter, terminator) = match args.print0 {
+				true => ("", "\0"),
+				false => (args.delimiter.as_deref().unwrap_or_default(), ""),
+			};
+			// Only documents written as they are make a YAML stream, which
+			// one appended to has to continue.
+			let appending = args
+				.output
+				.append_path()
+				.filter(|_| delimiter.is_empty() && terminator.is_empty());
+			#[cfg(feature = "compression")]
+			let appending = appending.filter(|_| args.compress.is_none());
+			let checkpointer = checkpointer.as_mut();
+			write_raw(
+				docs,
+				&mut output,
+				flush,
+				delimiter,
+				terminator,
+				appending,
+				checkpointer,
+			)?
+		}
+		(None, OutputFormat::Chunks) => write_chunks(
+			docs,
+			&mut output,
+			flush,
+			color,
+			args.hash,
+			args.positions,
+			checkpointer.as_mut(),
+		)?,
+		(None, OutputFormat::JsonArray) => {
+			super::write_json_array(docs, &mut output, flush, args.jobs)?
+		}
+		(None, OutputFormat::Jsonl) => {
+			super::write_json_lines(docs, &mut output, flush, args.jobs)?
+		}
+	};
+	if let Some(checkpointer) = &checkpointer {
+		checkpointer.finish(&mut output)?;
+	}
+	// The trailer is always written, but isn't one of the input's documents.
+	let count = count - usize::from(args.trailer);
+	let skipped = dedup.is_some()
+		|| args.select.is_some()
+		|| !args.has.is_empty()
+		|| !args.where_.is_empty()
+		|| !args.tag.is_empty()
+		|| !args.grep.is_empty()
+		|| !args.grep_v.is_empty()
+		|| args.empty_docs == EmptyDocs::Skip
+		|| args.comment_only_docs == CommentOnlyDocs::Skip
+		|| args.oversize == Oversize::Skip;
+	if count == 0 && !skipped && !interrupt::requested() {
+		warning!("the input contains no documents");
+	}
+	output.flush()?;
+	if let Some(state) = &dedup {
+		state.borrow().save()?;
+	}
+	if let (Some(index), Some(path)) = (&index, &args.index_out) {
+		if !interrupt::requested() {
+			index.borrow().save(path)?;
+		}
+	}
+	if let Some(files) = files.filter(|_| args.replace_input) {
+		if count > 0 && !interrupt::requested() {
+			files.replace_input()?;
+		}
+	}
+	// Like a warning, the summary is informational, so --quiet drops it and
+	// failing to write it doesn't fail the split.
+	if args.stats && !diagnostics::is_quiet() {
+		let _ = writeln!(
+			io::stderr().lock(),
+			"Stats: {count} documents in {secs:.3} s, {usage}",
+			secs = started.elapsed().as_secs_f64(),
+			usage = memory::usage(),
+		);
+	}
+	match interrupt::requested() {
+		true => Err(interrupt::error(count)),
+		false => Ok(()),
+	}
+}
+
+/// The options that check the documents of each input on its own.
+#[derive(Clone)]
+struct InputChecks {
+	max_doc_bytes: Option<usize>,
+	oversize: Oversize,
+	warn_doc_bytes: Option<usize>,
+	comment_only_docs: CommentOnlyDocs,
+	empty_docs: EmptyDocs,
+	assert_sorted: Option<Query>,
+}
+
+impl Args {
+	fn input_checks(&self) -> InputChecks {
+		InputChecks {
+			max_doc_bytes: self.max_doc_bytes,
+			oversize: self.oversize,
+			warn_doc_bytes: self.warn_doc_bytes,
+			comment_only_docs: self.comment_only_docs,
+			empty_docs: self.empty_docs,
+			assert_sorted: self.assert_sorted.clone(),
+		}
+	}
+}
+
+/// Applies the options that select, restructure, and rewrite documents, in
+/// the order that splitting applies them, filtering out documents that
+/// `dedup` has already seen.
+fn transform_documents(
+	mut docs: Documents,
+	args: &Args,
+	named: bool,
+	dedup: Option<&Rc<RefCell<DedupState>>>,
+) -> Documents {
+	if let Some(selection) = &args.select {
+		docs = select::select(docs, selection.clone());
+	}
+	if !args.tag.is_empty() {
+		docs = transform::tagged(docs, args.tag.clone());
+	}
+	if args.strip_comments {
+		docs = transform::strip_comments(docs, args.jobs);
+	}
+	if args.explode_seq {
+		docs = transform::explode_sequences(docs);
+	}
+	if args.by_key {
+		docs = transform::split_by_key(docs);
+	}
+	if args.reverse {
+		docs = transform::reverse(docs);
+	}
+	if args.shuffle {
+		docs = transform::shuffle(docs, args.seed);
+	}
+	if args.implode {
+		docs = transform::implode(docs);
+	}
+	if !args.has.is_empty() {
+		docs = transform::having(docs, args.has.clone());
+	}
+	if !args.where_.is_empty() {
+		docs = transform::satisfying(docs, args.where_.clone());
+	}
+	if !args.grep.is_empty() {
+		docs = transform::matching(docs, args.grep.clone(), false);
+	}
+	if !args.grep_v.is_empty() {
+		docs = transform::matching(docs, args.grep_v.clone(), true);
+	}
+	if named {
+		let regex = args.name_from_regex.clone();
+		docs = transform::named(docs, args.name_from_path.clone(), regex);
+	}
+	if args.pretty {
+		let options = EmitterOptions {
+			indent: args.indent,
+			..EmitterOptions::default()
+		};
+		docs = transform::reserialize(docs, Layout::Block, options, args.jobs);
+	}
+	if args.minify {
+		let options = EmitterOptions {
+			width: None,
+			..EmitterOptions::default()
+		};
+		docs = transform::reserialize(docs, Layout::Flow, options, args.jobs);
+	}
+	if args.canonical {
+		let options = EmitterOptions {
+			canonical: true,
+			..EmitterOptions::default()
+		};
+		docs = transform::reserialize(docs, Layout::Preserve, options, args.jobs);
+	}
+	match args.markers {
+		Markers::Keep => {}
+		Markers::Strip => docs = transform::strip_markers(docs),
+		Markers::Normalize => docs = transform::normalize_markers(docs),
+	}
+	if let Some(state) = dedup {
+		docs = dedup::filter(docs, Rc::clone(state));
+	}
+	if args.ids {
+		docs = transform::assign_ids(docs, args.jobs);
+	}
+	docs
+}
+
+/// Checks that `--backend lexical` goes with the other options, taking the
+/// deprecated `--lexical` as the same.
+///
+/// As with [`check_raw`], a lexical default from the config file is dropped
+/// where it doesn't apply, for libyaml.
+fn check_backend(args: &mut Args) -> io::Result<()> {
+	if args.lexical {
+		warning!("--lexical is deprecated; use --backend lexical instead");
+		args.backend = Backend::Lexical;
+	}
+	if args.backend == Backend::Libyaml {
+		return Ok(());
+	}
+	let conflicts = [
+		("--from json or ndjson", args.from != InputFormat::Yaml),
+		("--checkpoint", args.checkpoint.is_some()),
+		("--report", args.report.is_some()),
+		("--tag", !args.tag.is_empty()),
+		("--events", args.events),
+		("--tokens", args.tokens),
+		("--comments", args.comments.is_some()),
+	];
+	let Some((option, _)) = conflicts.into_iter().find(|&(_, given)| given) else {
+		return Ok(());
+	};
+	if !args.lexical && config::has_default("backend") {
+		args.backend = Backend::Libyaml;
+		return Ok(());
+	}
+	Err(io::Error::new(
+		io::ErrorKind::InvalidInput,
+		format!("--backend lexical can't be used with {option}"),
+	))
+}
+
+/// Checks that `--raw` and `--delimiter` go with the other options, which clap
+/// can't do for them since either can be a default from the config file.
+///
+/// A default doesn't apply where the flag couldn't be given, so it's dropped
+/// there instead of failing.
+fn check_raw(args: &mut Args) -> io::Result<()> {
+	let conflicts = [
+		("--get", args.get.is_some()),
+		("--report", args.report.is_some()),
+		("--events", args.events),
+		("--tokens", args.tokens),
+		("--in-place", args.in_place),
+		("--output-dir", args.output_dir.is_some()),
+		("--exec", args.exec.is_some()),
+	];
+	let conflict = conflicts.into_iter().find(|&(_, given)| given);
+	if args.raw && config::has_default("raw") {
+		args.raw = conflict.is_none() && args.to == OutputFormat::Chunks;
+	}
+	if let (true, Some((option, _))) = (args.raw, conflict) {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!("--raw can't be used with {option}"),
+		));
+	}
+	if args.delimiter.is_some() && (!args.raw || args.print0) {
+		if config::has_default("delimiter") {
+			args.delimiter = None;
+		} else {
+			let problem = match args.print0 {
+				true => "can't be used with --print0",
+				false => "requires --raw",
+			};
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				format!("--delimiter {problem}"),
+			));
+		}
+	}
+	Ok(())
+}
+
+/// Applies the options that check the documents of the input `name`.
+///
+/// Messages number each document by its place in the input, so skipping one
+/// doesn't change the numbers of those after it.
+fn check_input(mut docs: Documents, checks: &InputChecks, name: String) -> Documents {
+	if let (Some(limit), Oversize::Skip | Oversize::Truncate) =
+		(checks.max_doc_bytes, checks.oversize)
+	{
+		docs = handle_oversize(docs, checks.oversize, limit, name.clone());
+	}
+	if let Some(limit) = checks.warn_doc_bytes {
+		docs = warn_large(docs, limit, name.clone());
+	}
+	match checks.comment_only_docs {
+		CommentOnlyDocs::Emit => {}
+		CommentOnlyDocs::Attach => docs = transform::attach_comment_only(docs),
+		CommentOnlyDocs::Skip => docs = transform::skip_comment_only(docs),
+	}
+	if checks.empty_docs != EmptyDocs::Emit {
+		docs = handle_empty(docs, checks.empty_docs, name.clone());
+	}
+	if let Some(query) = &checks.assert_sorted {
+		docs = assert_sorted(docs, query.clone(), name);
+	}
+	docs
+}
+
+/// Returns the directory that `--recursive` or a glob input reads, with the
+/// paths of the files to read relative to it, or `None` if the input is a
+/// single file.
+fn find_inputs(args: &Args) -> io::Result<Option<(PathBuf, Vec<PathBuf>)>> {
+	let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+	// A file whose name only looks like a glob is read as it is.
+	let glob_input = args
+		.inputfile
+		.as_deref()
+		.filter(|path| !path.exists())
+		.and_then(Path::to_str)
+		.filter(|text| inputs::is_glob(text));
+	let (root, pattern) = match (&args.recursive, glob_input) {
+		(Some(dir), _) => (dir.clone(), None),
+		(None, Some(text)) => {
+			let (root, glob) = inputs::split_glob(text).map_err(invalid)?;
+			(root, Some(glob))
+		}
+		(None, None) if args.include.is_empty() && args.exclude.is_empty() => return Ok(None),
+		(None, None) => {
+			return Err(invalid(
+				"--include and --exclude require --recursive or a glob input".to_owned(),
+			))
+		}
+	};
+	if let Some(text) = glob_input {
+		let single = [
+			("--checkpoint", args.checkpoint.is_some()),
+			("--in-place", args.in_place),
+			("--index-out", args.index_out.is_some()),
+			("--total-counts", args.total_counts),
+			("--events", args.events),
+			("--tokens", args.tokens),
+		];
+		if let Some((option, _)) = single.iter().find(|(_, given)| *given) {
+			return Err(invalid(format!(
+				"{option} reads a single input file, not a glob like {text}"
+			)));
+		}
+	}
+	// Only YAML files are read from a directory unless told otherwise.
+	let yaml = ["*.yaml".parse().unwrap(), "*.yml".parse().unwrap()];
+	let include = match (&pattern, args.include.is_empty()) {
+		(None, true) => &yaml[..],
+		_ => &args.include[..],
+	};
+	let files = inputs::find(&root, pattern.as_ref(), include, &args.exclude)?;
+	if files.is_empty() {
+		return Err(io::Error::new(
+			io::ErrorKind::NotFound,
+			match glob_input {
+				Some(text) => format!("no files match {text}"),
+				None => format!("no input files found in {}", root.display()),
+			},
+		));
+	}
+	Ok(Some((root, files)))
+}
+
+/// Returns the documents of each of `paths` in turn, each recorded as from
+/// its input, opening each with `open` only once the documents of the one
+/// before it have all been read.
+fn concat<F>(paths: Vec<PathBuf>, open: F) -> Documents
+where
+	F: Fn(&Path) -> io::Result<Documents> + 'static,
+{
+	Box::new(
+		paths
+			.into_iter()
+			.enumerate()
+			.flat_map(move |(index, path)| {
+				let docs = open(&path).unwrap_or_else(|err| {
+					let err = io::Error::new(err.kind(), format!("{}: {err}", path.display()));
+					Box::new(std::iter::once(Err(err)))
+				});
+				let name = input_name(Some(&path));
+				from_input(docs, Input { name, index })
+			}),
+	)
+}
+
+/// Records `input` as the input of each document.
+fn from_input(docs: Documents, input: Input) -> Documents {
+	Box::new(docs.map(move |doc| doc.map(|doc| doc.with_input(input.clone()))))
+}
+
+/// Alternates documents from each of the `streams`, taking one from
+/// each in turn until all of them end.
+///
+/// With `pad`, an empty document stands in for each stream that ends before
+/// the others. Otherwise, a stream ending early is an error.
+fn zip(streams: Vec<(Input, Documents)>, pad: bool) -> Documents {
+	let mut streams = streams;
+	let mut round = VecDeque::new();
+	let mut rounds = 0;
+	Box::new(std::iter::from_fn(move || {
+		if round.is_empty() {
+			let next = streams
+				.iter_mut()
+				.map(|(_, docs)| docs.next())
+				.collect::<Vec<_>>();
+			if next.iter().all(Option::is_none) {
+				return None;
+			}
+			for (doc, (input, _)) in next.into_iter().zip(&streams) {
+				round.push_back(match doc {
+					Some(doc) => doc,
+					None if pad => {
+						let doc = Document::new("---\n".to_owned(), DocumentKind::Scalar, 0..0);
+						Ok(doc.with_input(input.clone()))
+					}
+					None => Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						format!(
+							"{name} ended after {rounds} documents, before the other inputs",
+							name = input.name
+						),
+					)),
+				});
+			}
+			rounds += 1;
+		}
+		round.pop_front()
+	}))
+}
+
+/// Resolves a tag given on the command line the way the parser resolves tags
+/// in a document without `%TAG` directives, so that `!!map` matches
+/// `tag:yaml.org,2002:map`. A verbatim tag like `!<tag:example.com,2000:a>`
+/// is unwrapped, and any other tag is matched as written.
+fn resolve_tag(text: &str) -> Result<String, String> {
+	if let Some(verbatim) = text.strip_prefix("!<").and_then(|t| t.strip_suffix('>')) {
+		return Ok(verbatim.to_owned());
+	}
+	match text.strip_prefix("!!") {
+		Some(suffix) => Ok(format!("tag:yaml.org,2002:{suffix}")),
+		None if text.is_empty() => Err("a tag can't be empty".to_owned()),
+		None => Ok(text.to_owned()),
+	}
+}
+
+/// Returns the name of the input for display to users.
+fn input_name(input: Option<&Path>) -> String {
+	match input {
+		None => "standard input".to_owned(),
+		Some(path) if path == Path::new("-") => "standard input".to_owned(),
+		Some(path) => path.display().to_string(),
+	}
+}
+
+/// Warns about each document of the input `name` whose content is larger than
+/// `limit` bytes.
+fn warn_large(docs: Documents, limit: usize, name: String) -> Documents {
+	Box::new(docs.map(move |doc| {
+		let doc = doc?;
+		let len = doc.content().len();
+		if len > limit {
+			warning!(
+				"{location}: document {n} is {len} bytes, more than the limit of {limit}",
+				location = location(&name, &doc),
+				n = number(&doc),
+			);
+		}
+		Ok(doc)
+	}))
+}
+
+/// Applies `policy` to each empty document of the input `name`.
+fn handle_empty(docs: Documents, policy: EmptyDocs, name: String) -> Documents {
+	Box::new(docs.filter_map(move |doc| match (doc, policy) {
+		(Ok(doc), EmptyDocs::Skip) if doc.is_empty() => None,
+		(Ok(doc), EmptyDocs::Error) if doc.is_empty() => Some(Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!(
+				"{location}: document {n} is empty",
+				location = location(&name, &doc),
+				n = number(&doc),
+			),
+		))),
+		(doc, _) => Some(doc),
+	}))
+}
+
+/// Fails at the first document whose scalar at `query` sorts before that of
+/// the document before it in the input `name`.
+///
+/// Scalars are compared as text, byte by byte, so `10` sorts before `9`. A
+/// document without a scalar at `query` is an error too, since it has no
+/// place in the order.
+fn assert_sorted(docs: Documents, query: Query, name: String) -> Documents {
+	let mut last: Option<(String, String, usize)> = None;
+	Box::new(docs.map(move |doc| {
+		let doc = doc?;
+		let n = number(&doc);
+		let location = location(&name, &doc);
+		let value = match query.get(doc.value()?).map(|node| &node.content) {
+			Some(Content::Scalar(value, _)) => value.clone(),
+			Some(_) => {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidData,
+					format!("{location}: {query} in document {n} is not a scalar"),
+				))
+			}
+			None => {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidData,
+					format!("{location}: document {n} has no value at {query}"),
+				))
+			}
+		};
+		if let Some((prev, prev_location, prev_n)) = &last {
+			if value < *prev {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidData,
+					format!(
+						"{location}: document {n} is out of order by {query}: \
+						 {value:?} sorts before {prev:?} of document {prev_n} at {prev_location}"
+					),
+				));
+			}
+		}
+		last = Some((value, location, n));
+		Ok(doc)
+	}))
+}
+
+/// Applies `policy` to each document of the input `name` that was cut to
+/// `limit` bytes on input.
+///
+/// Truncated documents end with a comment that gives their full length, so
+/// they can't be mistaken for whole ones.
+fn handle_oversize(docs: Documents, policy: Oversize, limit: usize, name: String) -> Documents {
+	Box::new(docs.filter_map(move |doc| {
+		let doc = match doc {
+			Ok(doc) => doc,
+			Err(err) => return Some(Err(err)),
+		};
+		let Some(full_len) = doc.full_len() else {
+			return Some(Ok(doc));
+		};
+		let location = location(&name, &doc);
+		let n = number(&doc);
+		if policy == Oversize::Skip {
+			warning!(
+				"{location}: skipped document {n}, which is {full_len} bytes, more than the limit of {limit}"
+			);
+			return None;
+		}
+		warning!(
+			"{location}: truncated document {n} from {full_len} bytes to the limit of {limit}"
+		);
+		let mut content = doc.content().to_owned();
+		if !content.is_empty() && !content.ends_with('\n') {
+			content.push('\n');
+		}
+		content.push_str(&format!("# yaml-split: truncated from {full_len} bytes\n"));
+		Some(Ok(doc.with_content(content)))
+	}))
+}
+
+/// Returns the number of a document in its input, counting from 1, for
+/// messages.
+///
+/// Every document that's read from an input knows its place in it.
+fn number(doc: &Document) -> usize {
+	doc.index().map_or(0, |index| index + 1)
+}
+
+/// Returns where a document starts in the input `name`, for messages.
+fn location(name: &str, doc: &Document) -> String {
+	match doc.position() {
+		Some(position) => format!("{name}:{}", position.start),
+		None => format!("{name} at byte {}", doc.original().start),
+	}
+}
+
+/// Writes each document between chunk markers, and returns the number of
+/// documents written.
+///
+/// If `hash` is given, each header includes the digest of the document's
+/// content as written. If `positions` is set, each header includes the lines
+/// and columns of the input that the document spans, where they're known. If
+/// `checkpointer` is given, it's told about each document once it's written.
+fn write_chunks(
+	docs: Documents,
+	output: &mut dyn Write,
+	flush: Flush,
+	color: bool,
+	hash: Option<hash::Algorithm>,
+	positions: bool,
+	mut checkpointer: Option<&mut Checkpointer>,
+) -> io::Result<usize> {
+	let mut count = 0;
+	for doc in docs {
+		let doc = doc?;
+		count += 1;
+		let content = doc.content();
+		let mut info = format!("{len} bytes", len = content.len());
+		if let Some(hash) = hash {
+			let digest = hash.hex_digest(content.as_bytes());
+			info = format!("{info}, {name}:{digest}", name = hash.name());
+		}
+		if let Some(id) = doc.id() {
+			info = format!("{info}, id {id}");
+		}
+		if let Some(input) = doc.input() {
+			info = format!(
+				"{info}, input {index} {name:?}",
+				index = input.index + 1,
+				name = input.name
+			);
+		}
+		if let Some(position) = doc.position().filter(|_| positions) {
+			info = format!(
+				"{info}, at {start}..{end}",
+				start = position.start,
+				end = position.end
+			);
+		}
+		let header = match doc.name() {
+			None => format!(">>> START CHUNK ({info}) >>>|"),
+			Some(name) => format!(">>> START CHUNK {name:?} ({info}) >>>|"),
+		};
+		const FOOTER: &str = "|<<< END CHUNK <<<";
+		if color {
+			highlight::write_styled(output, highlight::FRAME_SGR, &header)?;
+			highlight::write_highlighted(output, content)?;
+			highlight::write_styled(output, highlight::FRAME_SGR, FOOTER)?;
+			writeln!(output)?;
+		} else {
+			let parts = [
+				header.as_bytes(),
+				content.as_bytes(),
+				FOOTER.as_bytes(),
+				b"\n",
+			];
+			super::write_all_vectored(output, &parts)?;
+		}
+		flush.document_written(output)?;
+		if let Some(checkpointer) = checkpointer.as_deref_mut() {
+			checkpointer.completed(output, &doc)?;
+		}
+	}
+	Ok(count)
+}
+
+/// Writes each document as it is, with `delimiter` between each one and the
+/// next and `terminator` after each one, and returns the number of documents
+/// written.
+///
+/// If `appending` is given, the documents continue the YAML stream at that
+/// path, so they're written like `join --append` writes them, with whatever
+/// markers and line breaks keep them apart from the stream's documents.
+///
+/// If `checkpointer` is given, it's told about each document once it's
+/// written.
+fn write_raw(
+	docs: Documents,
+	output: &mut dyn Write,
+	flush: Flush,
+	delimiter: &str,
+	terminator: &str,
+	appending: Option<&Path>,
+	mut checkpointer: Option<&mut Checkpointer>,
+) -> io::Result<usize> {
+	let mut stream = match appending {
+		Some(path) => Some(DocumentWriter::appending(vec![], path)?),
+		None => None,
+	};
+	let mut count = 0;
+	for doc in docs {
+		let doc = doc?;
+		if let Some(stream) = &mut stream {
+			stream.write_document(doc.content())?;
+			output.write_all(&std::mem::take(stream.get_mut()))?;
+		} else {
+			let delimiter = if count == 0 { "" } else { delimiter };
+			let parts = [
+				delimiter.as_bytes(),
+				doc.content().as_bytes(),
+				terminator.as_bytes(),
+			];
+			super::write_all_vectored(output, &parts)?;
+		}
+		count += 1;
+		flush.document_written(output)?;
+		if let Some(checkpointer) = checkpointer.as_deref_mut() {
+			checkpointer.completed(output, &doc)?;
+		}
+	}
+	Ok(count)
+}
+
+/// The files that `--in-place` or `--output-dir` writes documents to, one
+/// per document.
+struct OutputFiles {
+	/// The directory that the files go in.
+	dir: PathBuf,
+	/// The names of the files.
+	template: Template,
+	/// The input file that the files are next to, with the directory that
+	/// they go in relative to the input's, for `--in-place`.
+	input: Option<(PathBuf, PathBuf)>,
+	/// The paths of the files written so far, relative to the input's
+	/// directory.
+	written: Vec<PathBuf>,
+	/// For each input, the directory that its files go in relative to `dir`
+	/// and the number of them written so far, when mirroring a tree of
+	/// inputs.
+	mirrored: Vec<(PathBuf, usize)>,
+	/// For each input that documents record, the number of them written so
+	/// far.
+	counts: Vec<usize>,
+	/// The number of documents in the input, if they were counted first.
+	total: Option<usize>,
+	/// The path of the input, for documents that don't record their own.
+	source: Option<String>,
+	/// The format to compress each file in, if any, which adds its extension
+	/// to the file's name.
+	#[cfg(feature = "compression")]
+	compress: Option<compression::Format>,
+}
+
+impl OutputFiles {
+	/// Prepares to write the documents of `input` next to it, creating the
+	/// directory for them if the layout calls for one.
+	///
+	/// Files are named by `template` if one is given, or else after the
+	/// documents' names if they're `named`, or else after the layout. To
+	/// avoid mixing documents with stale ones from an earlier split, this
+	/// fails if the directory already exists.
+	fn in_place(
+		input: &Path,
+		layout: InPlaceLayout,
+		template: Option<&Template>,
+		named: bool,
+	) -> io::Result<Self> {
+		let Some(stem) = input.file_stem() else {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				format!("--in-place can't name files after {}", input.display()),
+			));
+		};
+		let stem = stem.to_string_lossy();
+		let suffix = match input.extension() {
+			Some(extension) => format!(".{}", extension.to_string_lossy()),
+			None => ".yaml".to_owned(),
+		};
+		let parent = input.parent().unwrap_or(Path::new(""));
+		// Numbers in a directory of their own are padded to four digits, so
+		// that the files sort in order.
+		let (relative, numbered) = match layout {
+			InPlaceLayout::Dir => (
+				PathBuf::from(format!("{stem}.d")),
+				Template::numbered("", 4, &suffix),
+			),
+			InPlaceLayout::Siblings => (
+				PathBuf::new(),
+				Template::numbered(&format!("{stem}-"), 0, &suffix),
+			),
+		};
+		let dir = parent.join(&relative);
+		if layout == InPlaceLayout::Dir {
+			fs::create_dir(&dir).map_err(|err| {
+				io::Error::new(err.kind(), format!("{dir}: {err}", dir = dir.display()))
+			})?;
+		}
+		Ok(Self {
+			dir,
+			template: match (template, named) {
+				(Some(template), _) => template.clone(),
+				(None, true) => Template::named(&suffix),
+				(None, false) => numbered,
+			},
+			input: Some((input.to_owned(), relative)),
+			written: vec![],
+			mirrored: vec![],
+			counts: vec![],
+			total: None,
+			source: None,
+			#[cfg(feature = "compression")]
+			compress: None,
+		})
+	}
+
+	/// Prepares to write documents to `dir`, creating it and any missing
+	/// parents, in files named by `template`, or `{name}.yaml` if the
+	/// documents are `named`, or else `doc-0001.yaml` onward.
+	///
+	/// The directory may already exist, but writing a document still fails
+	/// rather than overwrite an existing file.
+	fn in_dir(dir: &Path, template: Option<&Template>, named: bool) -> io::Result<Self> {
+		fs::create_dir_all(dir).map_err(|err| {
+			io::Error::new(err.kind(), format!("{dir}: {err}", dir = dir.display()))
+		})?;
+		Ok(Self {
+			dir: dir.to_owned(),
+			template: match (template, named) {
+				(Some(template), _) => template.clone(),
+				(None, true) => Template::named(".yaml"),
+				(None, false) => Template::numbered("doc-", 4, ".yaml"),
+			},
+			input: None,
+			written: vec![],
+			mirrored: vec![],
+			counts: vec![],
+			total: None,
+			source: None,
+			#[cfg(feature = "compression")]
+			compress: None,
+		})
+	}
+
+	/// Configures the files to mirror the tree of `inputs`, given relative to
+	/// the directory they were found in, so that the documents of each input
+	/// go in a directory named after it without its extension.
+	///
+	/// Each directory is only created once it has a document, and the files
+	/// in each are numbered from one.
+	fn mirroring(mut self, inputs: &[PathBuf]) -> Self {
+		self.mirrored = inputs
+			.iter()
+			.map(|input| (input.with_extension(""), 0))
+			.collect();
+		self
+	}
+
+	/// Writes `doc`, numbered `n` from one, to a new file, which must not
+	/// already exist, and returns its path.
+	fn write(&mut self, n: usize, doc: &Document) -> io::Result<PathBuf> {
+		let (dir, n) = match doc.input().filter(|_| !self.mirrored.is_empty()) {
+			None => (self.dir.clone(), n),
+			Some(input) => {
+				let (relative, count) = &mut self.mirrored[input.index];
+				let dir = self.dir.join(relative);
+				if *count == 0 {
+					fs::create_dir_all(&dir).map_err(|err| {
+						io::Error::new(err.kind(), format!("{dir}: {err}", dir = dir.display()))
+					})?;
+				}
+				*count += 1;
+				(dir, *count)
+			}
+		};
+		let file_index = match doc.input() {
+			None => n,
+			Some(input) => {
+				if self.counts.len() <= input.index {
+					self.counts.resize(input.index + 1, 0);
+				}
+				self.counts[input.index] += 1;
+				self.counts[input.index]
+			}
+		};
+		let place = Place {
+			index: n,
+			file_index,
+			total: self.total,
+			source: self.source.as_deref(),
+		};
+		let name = self.template.render(place, doc)?;
+		#[cfg(feature = "compression")]
+		let name = match self.compress {
+			Some(format) => format!("{name}.{}", format.extension()),
+			None => name,
+		};
+		let path = dir.join(&name);
+		let content = doc.content();
+		let file = OpenOptions::new()
+			.write(true)
+			.create_new(true)
+			.open(&path)
+			.map_err(|err| {
+				io::Error::new(err.kind(), format!("{path}: {err}", path = path.display()))
+			})?;
+		#[cfg(feature = "compression")]
+		let mut file: Box<dyn Write> = match self.compress {
+			Some(format) => Box::new(compression::Compressor::new(
+				compression::Target::File(file),
+				format,
+			)),
+			None => Box::new(file),
+		};
+		#[cfg(not(feature = "compression"))]
+		let mut file = file;
+		file.write_all(content.as_bytes())?;
+		if !content.is_empty() && !content.ends_with('\n') {
+			file.write_all(b"\n")?;
+		}
+		file.flush()?;
+		if let Some((_, relative)) = &self.input {
+			self.written.push(relative.join(name));
+		}
+		Ok(path)
+	}
+
+	/// Replaces the input atomically with a YAML sequence of the files that
+	/// its documents were written to.
+	fn replace_input(&self) -> io::Result<()> {
+		let Some((input, _)) = &self.input else {
+			return Ok(());
+		};
+		let mut text = "# Split by yaml-split into the files below.\n".to_owned();
+		for name in &self.written {
+			text.push_str("- ");
+			json::write_string(&mut text, &name.to_string_lossy());
+			text.push('\n');
+		}
+		let mut name = input.file_name().unwrap_or_default().to_owned();
+		name.push(".tmp");
+		let tmp = input.with_file_name(name);
+		fs::write(&tmp, text)?;
+		fs::rename(&tmp, input)
+	}
+}
+
+/// Writes each document to its own file for `--in-place` or `--output-dir`,
+/// listing the path of each file on its own line of the output, and returns
+/// the number of documents written.
+fn write_files(
+	docs: Documents,
+	output: &mut dyn Write,
+	flush: Flush,
+	files: &mut OutputFiles,
+) -> io::Result<usize> {
+	let mut count = 0;
+	for doc in docs {
+		let doc = doc?;
+		count += 1;
+		let path = files.write(count, &doc)?;
+		writeln!(output, "{}", path.display())?;
+		flush.document_written(output)?;
+	}
+	Ok(count)
+}
+
+/// Runs `command` through the shell once for each document, with the document
+/// on its standard input, and returns the number of documents read.
+///
+/// What each run writes to its standard output is copied to the output in
+/// turn. Each run's environment describes its document, so that a wrapper
+/// script can log and route it without parsing it first:
+///
+/// - `YS_DOC_INDEX`: the one-based position of the document in the output
+/// - `YS_DOC_BYTES`: the length of the document in bytes
+/// - `YS_SOURCE`: the name of the document's input, which is `source` unless
+///   the document records its own
+/// - `YS_DOC_HASH`: the SHA-256 digest of the document in hex
+/// - `YS_TOTAL_DOCS`: the `total` number of documents, when it's known
+///
+/// A run that fails ends the split.
+fn write_exec(
+	docs: Documents,
+	output: &mut dyn Write,
+	flush: Flush,
+	command: OsString,
+	source: &str,
+	total: Option<usize>,
+) -> io::Result<usize> {
+	let mut count = 0;
+	for doc in docs {
+		let doc = doc?;
+		count += 1;
+		let content = doc.content();
+		let mut cmd = pager::shell_command(command.clone());
+		cmd.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.env("YS_DOC_INDEX", count.to_string())
+			.env("YS_DOC_BYTES", content.len().to_string())
+			.env("YS_SOURCE", doc.input().map_or(source, |input| &input.name))
+			.env(
+				"YS_DOC_HASH",
+				hash::Algorithm::Sha256.hex_digest(content.as_bytes()),
+			);
+		// A total inherited from an outer run would describe another stream.
+		match total {
+			Some(total) => cmd.env("YS_TOTAL_DOCS", total.to_string()),
+			None => cmd.env_remove("YS_TOTAL_DOCS"),
+		};
+		let mut child = cmd.spawn()?;
+		let mut stdin = child.stdin.take().expect("stdin is piped");
+		let run = thread::scope(|scope| {
+			// Writing from another thread keeps a command that writes before
+			// reading all of its input from blocking on a full pipe.
+			let writer = scope.spawn(move || stdin.write_all(content.as_bytes()));
+			let run = child.wait_with_output()?;
+			match writer.join().expect("writing to the command doesn't panic") {
+				// A command needn't read all of its input.
+				Err(err) if err.kind() != io::ErrorKind::BrokenPipe => Err(err),
+				_ => Ok(run),
+			}
+		})?;
+		output.write_all(&run.stdout)?;
+		if !run.status.success() {
+			return Err(io::Error::new(
+				io::ErrorKind::Other,
+				format!(
+					"the --exec command failed on document {count}: {}",
+					run.status
+				),
+			));
+		}
+		flush.document_written(output)?;
+	}
+	Ok(count)
+}
+
+/// Writes a report on each document on its own line, and returns the number of
+/// documents read.
+///
+/// Reports come from what the chunker collected while reading the input, or
+/// from reading a document's content again where it has nothing collected, as
+/// in JSON input or after splitting documents apart.
+fn write_report(
+	docs: Documents,
+	output: &mut dyn Write,
+	flush: Flush,
+	report: Report,
+) -> io::Result<usize> {
+	let mut count = 0;
+	for doc in docs {
+		let mut doc = doc?;
+		count += 1;
+		let collected = match report {
+			Report::Tags => doc.features().is_some(),
+			Report::Stats => doc.stats().is_some(),
+		};
+		if !collected {
+			let content = doc.content().as_bytes();
+			let reread = report.configure(Chunker::new(content)).next();
+			doc = reread.ok_or_else(|| {
+				io::Error::new(
+					io::ErrorKind::InvalidData,
+					format!("document {count} doesn't hold a YAML document to report on"),
+				)
+			})??;
+		}
+		let line = match report {
+			Report::Tags => tags_report(doc.features().expect("features were collected")),
+			Report::Stats => stats_report(doc.stats().expect("stats were collected")),
+		};
+		writeln!(output, "document {count}: {line}")?;
+		flush.document_written(output)?;
+	}
+	Ok(count)
+}
+
+fn tags_report(features: &Features) -> String {
+	let list = |sigil: &str, items: &BTreeSet<String>| {
+		let items = items
+			.iter()
+			.map(|item| format!("{sigil}{item}"))
+			.collect::<Vec<_>>();
+		format!("[{}]", items.join(", "))
+	};
+	let tags = features
+		.tags
+		.iter()
+		.map(|tag| match tag.strip_prefix(CORE_TAG_PREFIX) {
+			Some(name) => format!("!!{name}"),
+			None if tag.starts_with('!') => tag.clone(),
+			None => format!("!<{tag}>"),
+		});
+	format!(
+		"anchors {anchors}, aliases {aliases}, tags {tags}",
+		anchors = list("&", &features.anchors),
+		aliases = list("*", &features.aliases),
+		tags = list("", &tags.collect()),
+	)
+}
+
+fn stats_report(stats: Stats) -> String {
+	format!(
+		"nodes {nodes} (mappings {mappings}, sequences {sequences}, scalars {scalars}, aliases {aliases}), depth {depth}",
+		nodes = stats.nodes(),
+		mappings = stats.mappings,
+		sequences = stats.sequences,
+		scalars = stats.scalars,
+		aliases = stats.aliases,
+		depth = stats.max_depth,
+	)
+}
+
+/// Writes the scalar at the end of `query` in each document on its own line,
+/// and returns the number of documents read.
+fn write_values(
+	docs: Documents,
+	output: &mut dyn Write,
+	flush: Flush,
+	query: &Query,
+	default: Option<&str>,
+) -> io::Result<usize> {
+	let mut count = 0;
+	for doc in docs {
+		let doc = doc?;
+		count += 1;
+		let value = match (query.get(doc.value()?), default) {
+			(Some(node), _) => match &node.content {
+				Content::Scalar(value, _) => value.as_str(),
+				_ => {
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						format!("{query} in document {count} is not a scalar"),
+					))
+				}
+			},
+			(None, Some(default)) => default,
+			(None, None) => {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidData,
+					format!("document {count} has no value at {query}"),
+				))
+			}
+		};
+		writeln!(output, "{value}")?;
+		flush.document_written(output)?;
+	}
+	Ok(count)
+}
+
+/// Returns the documents of the input in the given format, collecting what
+/// `report` needs from YAML documents and placing their comments by
+/// `comments`, and finding YAML documents with `backend`.
+fn read_input(
+	format: InputFormat,
+	input: Option<&Path>,
+	limit: Option<usize>,
+	oversize: Oversize,
+	report: Option<Report>,
+	backend: Backend,
+	comments: Option<Comments>,
+) -> io::Result<Documents> {
+	let truncate = oversize != Oversize::Error;
+	let docs: Documents = match format {
+		InputFormat::Yaml if backend == Backend::Lexical => {
+			let chunker = super::chunk_input_lexically(input)?;
+			return Ok(Box::new(with_limit(chunker, limit, oversize)));
+		}
+		InputFormat::Yaml => {
+			let mut chunker = with_limit(super::chunk_input(input)?, limit, oversize);
+			if let Some(comments) = comments {
+				chunker = chunker.place_comments(comments.placement());
+			}
+			return Ok(match report {
+				Some(report) => Box::new(report.configure(chunker)),
+				None => Box::new(chunker),
+			});
+		}
+		InputFormat::Json => {
+			let mut text = vec![];
+			let mut claim = Claim::default();
+			let mut reader = super::open_utf8_input(input)?;
+			// Read in pieces, so that the claim fails before the text is
+			// entirely in memory.
+			while Read::by_ref(&mut reader)
+				.take(64 * 1024)
+				.read_to_end(&mut text)?
+				> 0
+			{
+				claim.grow_to(text.len(), "reading the JSON input")?;
+			}
+			let text = String::from_utf8(text)
+				.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+			let docs = json::documents(&text)?;
+			// The documents copy the text, so keep the claim until they've
+			// all been taken.
+			Box::new(docs.into_iter().map(move |doc| {
+				let _ = &claim;
+				Ok(doc)
+			}))
+		}
+		InputFormat::Ndjson => {
+			let reader = BufReader::new(super::open_utf8_input(input)?);
+			return Ok(Box::new(json::lines(reader, limit, truncate)));
+		}
+	};
+	let Some(limit) = limit else {
+		return Ok(docs);
+	};
+	// JSON values are never larger in the original input than in the
+	// document, which only adds a line break.
+	Ok(Box::new(docs.enumerate().map(move |(i, doc)| {
+		let mut doc = doc?;
+		let len = doc.content().len() - 1;
+		if len > limit && truncate {
+			doc.truncate(limit, len as u64);
+		} else if len > limit {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				LimitExceeded {
+					index: i + 1,
+					offset: doc.original().start,
+					limit,
+				},
+			));
+		}
+		Ok(doc)
+	})))
+}
+
+/// Applies the limit from --max-doc-bytes to a chunker, if there is one,
+/// along with the policy for documents beyond it.
+fn with_limit<C: Chunking>(chunker: C, limit: Option<usize>, oversize: Oversize) -> C {
+	match limit {
+		None => chunker,
+		Some(limit) => chunker.limit_document_len(limit, oversize != Oversize::Error),
+	}
+}
+
+/// Returns the documents of a UTF-8 YAML input file from the offset in a
+/// checkpoint onward.
+fn resume_input(
+	input: Option<&Path>,
+	start: Checkpoint,
+	limit: Option<usize>,
+	oversize: Oversize,
+) -> io::Result<Documents> {
+	let Some(path) = input.filter(|path| *path != Path::new("-")) else {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"--checkpoint requires an input file",
+		));
+	};
+	#[cfg(feature = "compression")]
+	reject_compressed(path, "--checkpoint")?;
+	let mut file = File::open(path)?;
+	let mut prefix = vec![];
+	Read::by_ref(&mut file)
+		.take(Encoding::DETECT_LEN as u64)
+		.read_to_end(&mut prefix)?;
+	if !matches!(Encoding::detect(&prefix), Encoding::Utf8) {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"--checkpoint requires a UTF-8 input file",
+		));
+	}
+	file.seek(SeekFrom::Start(start.offset))?;
+	let chunker = Chunker::new(BufReader::new(file)).starting_at(start.documents, start.offset);
+	Ok(Box::new(with_limit(chunker, limit, oversize)))
+}
+
+/// Returns the length of the input file for --index-out, which can't index
+/// standard input since there would be nothing to seek in later.
+fn indexed_len(input: Option<&Path>) -> io::Result<u64> {
+	let meta = input
+		.filter(|path| *path != Path::new("-"))
+		.and_then(|path| fs::metadata(path).ok())
+		.filter(|meta| meta.is_file());
+	#[cfg(feature = "compression")]
+	if let (Some(path), Some(_)) = (input, &meta) {
+		reject_compressed(path, "--index-out")?;
+	}
+	match meta {
+		Some(meta) => Ok(meta.len()),
+		None => Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"--index-out requires an input file",
+		)),
+	}
+}
+
+/// Fails if the input file at `path` is compressed, for an option that needs
+/// the offsets of its documents as it's stored.
+#[cfg(feature = "compression")]
+fn reject_compressed(path: &Path, option: &str) -> io::Result<()> {
+	match crate::compression::is_compressed(path)? {
+		true => Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!("{option} can't read compressed input; decompress it first"),
+		)),
+		false => Ok(()),
+	}
+}
+
+/// Returns whether `input` names a regular file, which can be read twice.
+fn is_regular_file(input: Option<&Path>) -> bool {
+	input
+		.filter(|path| *path != Path::new("-"))
+		.is_some_and(|path| fs::metadata(path).is_ok_and(|meta| meta.is_file()))
+}
+
+/// Checks the documents that `--backend` found in the YAML file `input`
+/// against those that the other backend finds, in a pass of its own over the
+/// file alongside the first.
+fn cross_check(docs: Documents, args: &Args, input: Option<&Path>) -> io::Result<Documents> {
+	if !is_regular_file(input) || args.from != InputFormat::Yaml {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"--cross-check requires a YAML input file that can be read twice",
+		));
+	}
+	let (limit, oversize) = (args.max_doc_bytes, args.oversize);
+	let (other, others): (_, Documents) = match args.backend {
+		Backend::Libyaml => {
+			let chunker = super::chunk_input_lexically(input)?;
+			(
+				Backend::Lexical,
+				Box::new(with_limit(chunker, limit, oversize)),
+			)
+		}
+		Backend::Lexical => {
+			let chunker = super::chunk_input(input)?;
+			(
+				Backend::Libyaml,
+				Box::new(with_limit(chunker, limit, oversize)),
+			)
+		}
+	};
+	let backend = args.backend.name();
+	Ok(transform::cross_checked(
+		docs,
+		backend,
+		others,
+		other.name(),
+	))
+}
+
+/// Counts the documents that splitting will produce, in a separate pass over
+/// the input that must be repeated for the real split.
+///
+/// The pass reads and checks the input and applies the same options as the
+/// split, with a dedup state of its own that isn't saved, so it counts exactly
+/// the documents that the split writes. Its warnings are left to the split.
+fn count_documents(args: &Args, input: Option<&Path>, named: bool) -> io::Result<usize> {
+	if !is_regular_file(input) {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"--total-counts requires an input file that can be read twice",
+		));
+	}
+	let quiet = diagnostics::is_quiet();
+	diagnostics::set_quiet(true);
+	let count = count_checked_documents(args, input, named);
+	diagnostics::set_quiet(quiet);
+	count
+}
+
+/// Counts the documents of `input` for [`count_documents`].
+fn count_checked_documents(args: &Args, input: Option<&Path>, named: bool) -> io::Result<usize> {
+	let docs = read_input(
+		args.from,
+		input,
+		args.max_doc_bytes,
+		args.oversize,
+		None,
+		args.backend,
+		args.comments,
+	)?;
+	let docs = check_input(docs, &args.input_checks(), input_name(input));
+	let dedup = match &args.dedup_state {
+		None => None,
+		Some(path) => Some(Rc::new(RefCell::new(DedupState::load(path)?))),
+	};
+	let mut count = 0;
+	for doc in transform_documents(docs, args, named, dedup.as_ref()) {
+		doc?;
+		count += 1;
+	}
+	Ok(count)
+}
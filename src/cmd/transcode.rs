@@ -0,0 +1,23 @@
+//! The `transcode` subcommand, which re-encodes a stream as UTF-8.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+	#[clap(
+		name = "inputfile",
+		help = "A file to read from instead of standard input"
+	)]
+	inputfile: Option<PathBuf>,
+
+	#[command(flatten)]
+	output: super::OutputArgs,
+}
+
+pub(crate) fn run(args: Args) -> io::Result<()> {
+	let mut output = args.output.open(args.inputfile.as_deref())?;
+	let mut input = super::open_utf8_input(args.inputfile.as_deref())?;
+	io::copy(&mut input, &mut output)?;
+	output.flush()
+}
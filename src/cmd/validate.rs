@@ -0,0 +1,20 @@
+//! The `validate` subcommand, which checks that a stream parses as YAML.
+
+use std::io;
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub(crate) struct Args {
+	#[clap(
+		name = "inputfile",
+		help = "A file to read from instead of standard input"
+	)]
+	inputfile: Option<PathBuf>,
+}
+
+pub(crate) fn run(args: Args) -> io::Result<()> {
+	for doc in super::chunk_input(args.inputfile.as_deref())?.discard_content() {
+		doc?;
+	}
+	Ok(())
+}
@@ -0,0 +1,383 @@
+//! Compression of the inputs and outputs of yaml-split, like the `.yaml.gz`
+//! files that CI systems keep as artifacts.
+//!
+//! A compressed input is recognized by the magic bytes at its start, or for a
+//! file, by an extension like `.gz`, and decompressed before encoding
+//! detection, so a compressed UTF-16 stream works like any other. Rather than
+//! link an encoder and decoder for every format, yaml-split pipes data through
+//! the system's own `gzip`, `bzip2`, `xz`, or `zstd`, which is already
+//! installed wherever these files are made.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio};
+use std::thread::{self, JoinHandle};
+
+/// A compression format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Format {
+	Gzip,
+	Bzip2,
+	Xz,
+	Zstd,
+}
+
+impl Format {
+	/// Returns the format of a stream that starts with `prefix`, if it starts
+	/// with the magic bytes of one.
+	pub(crate) fn detect(prefix: &[u8]) -> Option<Self> {
+		const MAGIC: [(&[u8], Format); 4] = [
+			(b"\x1F\x8B", Format::Gzip),
+			(b"BZh", Format::Bzip2),
+			(b"\xFD7zXZ\x00", Format::Xz),
+			(b"\x28\xB5\x2F\xFD", Format::Zstd),
+		];
+		MAGIC
+			.iter()
+			.find(|(magic, _)| prefix.starts_with(magic))
+			.map(|&(_, format)| format)
+	}
+
+	/// Returns the format that the extension of `path` names, if any.
+	pub(crate) fn from_path(path: &Path) -> Option<Self> {
+		match path.extension()?.to_str()? {
+			"gz" => Some(Format::Gzip),
+			"bz2" => Some(Format::Bzip2),
+			"xz" => Some(Format::Xz),
+			"zst" => Some(Format::Zstd),
+			_ => None,
+		}
+	}
+
+	/// Returns the extension of files in the format, without its dot.
+	pub(crate) fn extension(self) -> &'static str {
+		match self {
+			Format::Gzip => "gz",
+			Format::Bzip2 => "bz2",
+			Format::Xz => "xz",
+			Format::Zstd => "zst",
+		}
+	}
+
+	/// Returns the program that compresses the format with `-c`, and
+	/// decompresses it with `-dc`.
+	fn program(self) -> &'static str {
+		match self {
+			Format::Gzip => "gzip",
+			Format::Bzip2 => "bzip2",
+			Format::Xz => "xz",
+			Format::Zstd => "zstd",
+		}
+	}
+}
+
+/// Returns the format that `input` is compressed in, judging by its start or
+/// else by the extension of `path`, without consuming any of it.
+pub(crate) fn detect(input: &mut dyn BufRead, path: Option<&Path>) -> io::Result<Option<Format>> {
+	Ok(Format::detect(input.fill_buf()?).or_else(|| path.and_then(Format::from_path)))
+}
+
+/// Returns the error for a compression program that couldn't be started.
+fn spawn_error(format: Format, err: io::Error) -> io::Error {
+	let program = format.program();
+	io::Error::new(
+		err.kind(),
+		format!("{program} is needed for compressed data, but could not be run: {err}"),
+	)
+}
+
+/// Returns a reader of `input` decompressed from `format`.
+pub(crate) fn decompress<R>(input: R, format: Format) -> io::Result<Box<dyn BufRead>>
+where
+	R: Read + Send + 'static,
+{
+	Ok(Box::new(io::BufReader::new(Decompressor::spawn(
+		input, format,
+	)?)))
+}
+
+/// Returns whether the file at `path` is compressed, for the options that need
+/// to seek through the input as it's stored.
+pub(crate) fn is_compressed(path: &Path) -> io::Result<bool> {
+	let mut prefix = vec![];
+	File::open(path)?.take(8).read_to_end(&mut prefix)?;
+	Ok(Format::detect(&prefix)
+		.or_else(|| Format::from_path(path))
+		.is_some())
+}
+
+/// A reader of the output of a running decompressor, which is fed its input on
+/// a thread of its own.
+///
+/// The decompressor's failure is reported as an error at the end of its
+/// output, after whatever it prints to standard error. Dropping the reader
+/// before then stops the decompressor.
+struct Decompressor {
+	child: Child,
+	stdout: ChildStdout,
+	feeder: Option<JoinHandle<io::Result<()>>>,
+	format: Format,
+}
+
+impl Decompressor {
+	fn spawn<R>(mut input: R, format: Format) -> io::Result<Self>
+	where
+		R: Read + Send + 'static,
+	{
+		let mut child = Command::new(format.program())
+			.arg("-dc")
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.stderr(Stdio::inherit())
+			.spawn()
+			.map_err(|err| spawn_error(format, err))?;
+		let mut stdin = child.stdin.take().expect("stdin is piped");
+		let stdout = child.stdout.take().expect("stdout is piped");
+		let feeder = thread::spawn(move || -> io::Result<()> {
+			io::copy(&mut input, &mut stdin)?;
+			stdin.flush()
+		});
+		Ok(Self {
+			child,
+			stdout,
+			feeder: Some(feeder),
+			format,
+		})
+	}
+
+	/// Waits for the decompressor to exit once its output ends, and fails if
+	/// it didn't succeed.
+	fn finish(&mut self) -> io::Result<()> {
+		let Some(feeder) = self.feeder.take() else {
+			return Ok(());
+		};
+		let fed = feeder.join().expect("feeder thread panicked");
+		check_status(self.child.wait()?, self.format, "decompress the input")?;
+		match fed {
+			// The decompressor may stop reading once it has everything it needs.
+			Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+			fed => fed,
+		}
+	}
+}
+
+impl Read for Decompressor {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let len = self.stdout.read(buf)?;
+		if len == 0 && !buf.is_empty() {
+			self.finish()?;
+		}
+		Ok(len)
+	}
+}
+
+impl Drop for Decompressor {
+	fn drop(&mut self) {
+		if self.feeder.is_some() {
+			// The feeder ends on its own once the decompressor's input closes.
+			let _ = self.child.kill();
+			let _ = self.child.wait();
+		}
+	}
+}
+
+/// Fails if a compression program exited with `status` after failing to
+/// `what`, as when its input was corrupt.
+fn check_status(status: ExitStatus, format: Format, what: &str) -> io::Result<()> {
+	#[cfg(unix)]
+	if std::os::unix::process::ExitStatusExt::signal(&status) == Some(libc::SIGPIPE) {
+		// Its output went away, as when piping into head.
+		return Err(io::ErrorKind::BrokenPipe.into());
+	}
+	match status.success() {
+		true => Ok(()),
+		false => Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!(
+				"{program} failed to {what} ({status})",
+				program = format.program()
+			),
+		)),
+	}
+}
+
+/// Where a [`Compressor`] writes its output.
+pub(crate) enum Target {
+	/// The standard output of yaml-split, which nothing else writes to.
+	Stdout,
+	/// A file, from its current position.
+	File(File),
+}
+
+/// A writer that compresses what's written to it.
+///
+/// Since a compressed stream can only be read through once it ends, flushing
+/// a compressor ends the stream so far, and writing after that starts another
+/// stream that follows it. Each format decompresses a sequence of streams as
+/// though they were one, so flushing after each document makes every one
+/// readable as soon as it's written, at the cost of running the compression
+/// program once per document.
+///
+/// A compressor that's flushed without ever being written to still writes an
+/// empty stream, so that an empty output can be decompressed like any other.
+pub(crate) struct Compressor {
+	format: Format,
+	target: Target,
+	/// The running compression program and its input, until the next flush.
+	running: Option<(Child, BufWriter<ChildStdin>)>,
+	/// Whether any stream has been started.
+	started: bool,
+}
+
+impl Compressor {
+	/// Returns a compressor that writes `format` to `target`.
+	pub(crate) fn new(target: Target, format: Format) -> Self {
+		Self {
+			format,
+			target,
+			running: None,
+			started: false,
+		}
+	}
+
+	/// Returns the input of the running compression program, starting it if
+	/// it isn't running.
+	fn input(&mut self) -> io::Result<&mut BufWriter<ChildStdin>> {
+		if self.running.is_none() {
+			let stdout = match &self.target {
+				Target::Stdout => Stdio::inherit(),
+				Target::File(file) => Stdio::from(file.try_clone()?),
+			};
+			let mut cmd = Command::new(self.format.program());
+			cmd.arg("-c");
+			if self.format == Format::Zstd {
+				cmd.arg("-q");
+			}
+			let mut child = cmd
+				.stdin(Stdio::piped())
+				.stdout(stdout)
+				.stderr(Stdio::inherit())
+				.spawn()
+				.map_err(|err| spawn_error(self.format, err))?;
+			let stdin = child.stdin.take().expect("stdin is piped");
+			self.running = Some((child, BufWriter::new(stdin)));
+			self.started = true;
+		}
+		Ok(&mut self.running.as_mut().expect("the program is running").1)
+	}
+
+	/// Ends the current stream, and waits for the compression program to
+	/// finish writing it.
+	fn finish(&mut self) -> io::Result<()> {
+		let Some((mut child, stdin)) = self.running.take() else {
+			return Ok(());
+		};
+		let written = stdin.into_inner().map(drop).map_err(|err| err.into_error());
+		check_status(child.wait()?, self.format, "compress the output")?;
+		written
+	}
+
+	/// Returns the error to report for `err` in writing to the compression
+	/// program, which is the program's own failure if it exited early.
+	fn failed(&mut self, err: io::Error) -> io::Error {
+		match self.finish() {
+			Err(reason) if err.kind() == io::ErrorKind::BrokenPipe => reason,
+			_ => err,
+		}
+	}
+}
+
+impl Write for Compressor {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		match self.input()?.write(buf) {
+			Ok(len) => Ok(len),
+			Err(err) => Err(self.failed(err)),
+		}
+	}
+
+	fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+		match self.input()?.write_all(buf) {
+			Ok(()) => Ok(()),
+			Err(err) => Err(self.failed(err)),
+		}
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		if !self.started {
+			self.input()?;
+		}
+		self.finish()
+	}
+}
+
+impl Drop for Compressor {
+	fn drop(&mut self) {
+		let _ = self.finish();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn detect_formats() {
+		assert_eq!(Format::detect(b"\x1F\x8B\x08\x00"), Some(Format::Gzip));
+		assert_eq!(Format::detect(b"BZh91AY"), Some(Format::Bzip2));
+		assert_eq!(Format::detect(b"\xFD7zXZ\x00\x00"), Some(Format::Xz));
+		assert_eq!(Format::detect(b"\x28\xB5\x2F\xFD\x00"), Some(Format::Zstd));
+		assert_eq!(Format::detect(b"a: 1\n"), None);
+		assert_eq!(Format::detect(b""), None);
+		assert_eq!(
+			Format::from_path(Path::new("dump.yaml.gz")),
+			Some(Format::Gzip)
+		);
+		assert_eq!(Format::from_path(Path::new("a.zst")), Some(Format::Zstd));
+		assert_eq!(Format::from_path(Path::new("a.yaml")), None);
+	}
+
+	#[test]
+	fn decompress_gzip() {
+		// The output of `printf 'a: 1\n--- b\n' | gzip -n`.
+		const GZIP: &[u8] = &[
+			0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x4b, 0xb4, 0x52, 0x30,
+			0xe4, 0xd2, 0xd5, 0xd5, 0x55, 0x48, 0xe2, 0x02, 0x00, 0x8e, 0x48, 0x82, 0xb5, 0x0b,
+			0x00, 0x00, 0x00,
+		];
+		let read = |input: &'static [u8]| {
+			let format = detect(&mut &input[..], None).unwrap();
+			let mut text = String::new();
+			decompress(input, format.unwrap())
+				.unwrap()
+				.read_to_string(&mut text)
+				.map(|_| text)
+		};
+		assert_eq!(read(GZIP).unwrap(), "a: 1\n--- b\n");
+		assert!(read(&GZIP[..20]).is_err());
+		assert_eq!(detect(&mut &b"a: 1\n"[..], None).unwrap(), None);
+	}
+
+	#[test]
+	fn compress_gzip() {
+		let path = std::env::temp_dir().join(format!("yaml-split-gzip-{}", std::process::id()));
+		let compressed = |writes: &[&str]| {
+			let file = File::create(&path).unwrap();
+			let mut compressor = Compressor::new(Target::File(file), Format::Gzip);
+			for text in writes {
+				compressor.write_all(text.as_bytes()).unwrap();
+				compressor.flush().unwrap();
+			}
+			compressor.flush().unwrap();
+			let mut text = String::new();
+			decompress(File::open(&path).unwrap(), Format::Gzip)
+				.unwrap()
+				.read_to_string(&mut text)
+				.unwrap();
+			text
+		};
+		assert_eq!(compressed(&["a: 1\n", "---\nb: 2\n"]), "a: 1\n---\nb: 2\n");
+		assert_eq!(compressed(&[]), "");
+		std::fs::remove_file(&path).unwrap();
+	}
+}
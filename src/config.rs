@@ -0,0 +1,283 @@
+//! Defaults for command line flags from a config file and the environment.
+//!
+//! Any flag that supports a default declares an environment variable named
+//! `YAML_SPLIT_<FLAG>`, which clap consults whenever the flag is absent from
+//! the command line. The config file feeds into that same mechanism: each
+//! `flag = value` entry sets the matching environment variable, unless the
+//! environment already sets it. The resulting order of precedence, from
+//! highest to lowest, is the command line, the environment, the config file,
+//! and finally the built-in default.
+//!
+//! The config file lives at `$XDG_CONFIG_HOME/yaml-split/config.toml`, falling
+//! back to `~/.config/yaml-split/config.toml`, or wherever `YAML_SPLIT_CONFIG`
+//! points. Only a small subset of TOML is supported: top-level `key = value`
+//! pairs whose values are strings, integers, floats, or booleans, along with
+//! comments. That's everything a flag can express, and keeping the parser
+//! small avoids pulling a full TOML implementation into the binary.
+
+use std::collections::HashSet;
+use std::env;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The prefix shared by every environment variable that sets a flag default.
+const ENV_PREFIX: &str = "YAML_SPLIT_";
+
+/// Loads the config file, if one exists, and exports its entries as
+/// environment variables for the flags defined by `cmd` and its subcommands.
+pub(crate) fn load_into_env(cmd: &clap::Command) -> io::Result<()> {
+	let Some(path) = config_path() else {
+		return Ok(());
+	};
+	let text = match fs::read_to_string(&path) {
+		Ok(text) => text,
+		Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+		Err(err) => return Err(err),
+	};
+
+	let known = known_env_vars(cmd);
+	let entries = parse(&text).map_err(|err| err.in_file(&path))?;
+	for (line, key, value) in entries {
+		let var = env_var_for_key(&key);
+		if !known.contains(&OsString::from(&var)) {
+			let err = ConfigError::new(line, format!("unknown setting `{key}`"));
+			return Err(err.in_file(&path));
+		}
+		if env::var_os(&var).is_none() {
+			env::set_var(var, value);
+		}
+	}
+	Ok(())
+}
+
+/// Returns the path of the config file to load, if a location can be found.
+fn config_path() -> Option<PathBuf> {
+	if let Some(path) = env::var_os("YAML_SPLIT_CONFIG") {
+		return Some(PathBuf::from(path));
+	}
+	let config_home = match env::var_os("XDG_CONFIG_HOME") {
+		Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+		_ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+	};
+	Some(config_home.join("yaml-split").join("config.toml"))
+}
+
+/// Returns whether the environment or the config file sets a default for the
+/// flag with the long name `flag`.
+///
+/// clap treats a value from the environment like one from the command line,
+/// so a default for a flag that only goes with some others, like `delimiter`
+/// with `raw`, would otherwise be an error everywhere else. Commands check
+/// such flags themselves, and drop their defaults where they don't apply.
+pub(crate) fn has_default(flag: &str) -> bool {
+	env::var_os(env_var_for_key(flag)).is_some()
+}
+
+/// Returns the environment variable that sets the default for a config key.
+fn env_var_for_key(key: &str) -> String {
+	ENV_PREFIX.to_owned() + &key.to_ascii_uppercase().replace('-', "_")
+}
+
+/// Collects the environment variables of every argument in `cmd` and its
+/// subcommands.
+fn known_env_vars(cmd: &clap::Command) -> HashSet<OsString> {
+	let mut known = HashSet::new();
+	let mut pending = vec![cmd];
+	while let Some(cmd) = pending.pop() {
+		known.extend(
+			cmd.get_arguments()
+				.filter_map(|arg| arg.get_env())
+				.map(Into::into),
+		);
+		pending.extend(cmd.get_subcommands());
+	}
+	known
+}
+
+/// Parses the supported subset of TOML into a list of line numbers, keys, and
+/// values, with values rendered in the form that clap expects from the
+/// environment.
+fn parse(text: &str) -> Result<Vec<(usize, String, String)>, ConfigError> {
+	let mut entries = vec![];
+	for (i, line) in text.lines().enumerate() {
+		let line_number = i + 1;
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+		if line.starts_with('[') {
+			return Err(ConfigError::new(
+				line_number,
+				"tables are not supported; settings must be at the top level",
+			));
+		}
+		let Some((key, rest)) = line.split_once('=') else {
+			return Err(ConfigError::new(line_number, "expected `key = value`"));
+		};
+		let key = key.trim();
+		if key.is_empty()
+			|| !key
+				.bytes()
+				.all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+		{
+			return Err(ConfigError::new(
+				line_number,
+				format!("invalid key `{key}`"),
+			));
+		}
+		match parse_value(rest.trim()) {
+			Ok(value) => entries.push((line_number, key.to_owned(), value)),
+			Err(msg) => return Err(ConfigError::new(line_number, msg)),
+		}
+	}
+	Ok(entries)
+}
+
+/// Parses a single TOML value, along with any trailing comment.
+fn parse_value(text: &str) -> Result<String, String> {
+	let (value, rest) = match text.chars().next() {
+		Some('"') => parse_basic_string(&text[1..])?,
+		Some('\'') => match text[1..].split_once('\'') {
+			Some((value, rest)) => (value.to_owned(), rest),
+			None => return Err("unterminated string".into()),
+		},
+		Some(_) => {
+			let end = text.find(|c: char| c.is_whitespace() || c == '#');
+			let (bare, rest) = text.split_at(end.unwrap_or(text.len()));
+			if !is_bare_value(bare) {
+				return Err(format!("unsupported value `{bare}`"));
+			}
+			(bare.replace('_', ""), rest)
+		}
+		None => return Err("missing value".into()),
+	};
+	match rest.trim_start() {
+		rest if rest.is_empty() || rest.starts_with('#') => Ok(value),
+		rest => Err(format!("unexpected `{rest}` after value")),
+	}
+}
+
+/// Parses the remainder of a double-quoted string after its opening quote,
+/// returning the unescaped string and the text after the closing quote.
+fn parse_basic_string(text: &str) -> Result<(String, &str), String> {
+	let mut value = String::new();
+	let mut chars = text.char_indices();
+	while let Some((i, c)) = chars.next() {
+		match c {
+			'"' => return Ok((value, &text[i + 1..])),
+			'\\' => value.push(match chars.next() {
+				Some((_, 'n')) => '\n',
+				Some((_, 't')) => '\t',
+				Some((_, 'r')) => '\r',
+				Some((_, '0')) => '\0',
+				Some((_, '"')) => '"',
+				Some((_, '\\')) => '\\',
+				Some((_, c)) => return Err(format!("unsupported escape `\\{c}`")),
+				None => break,
+			}),
+			c => value.push(c),
+		}
+	}
+	Err("unterminated string".into())
+}
+
+/// Returns whether `text` is a TOML boolean, integer, or float.
+fn is_bare_value(text: &str) -> bool {
+	matches!(text, "true" | "false")
+		|| text.replace('_', "").parse::<i64>().is_ok()
+		|| text.replace('_', "").parse::<f64>().is_ok()
+}
+
+/// An error in the syntax or content of a config file.
+#[derive(Debug)]
+struct ConfigError {
+	line: usize,
+	description: String,
+}
+
+impl ConfigError {
+	fn new(line: usize, description: impl Into<String>) -> Self {
+		Self {
+			line,
+			description: description.into(),
+		}
+	}
+
+	fn in_file(self, path: &Path) -> io::Error {
+		io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("{path}:{self}", path = path.display()),
+		)
+	}
+}
+
+impl Error for ConfigError {}
+
+impl Display for ConfigError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"{line}: {description}",
+			line = self.line,
+			description = self.description
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_supported_values() {
+		const INPUT: &str = r#"
+# A comment on its own line.
+quiet = true
+iterations = 1_000 # A trailing comment.
+name = "with \"escapes\"\t"
+literal = 'C:\raw'
+ratio = 0.5
+"#;
+
+		let entries = parse(INPUT).unwrap();
+		let entries = entries
+			.iter()
+			.map(|(line, key, value)| (*line, key.as_str(), value.as_str()))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			&entries,
+			&[
+				(3, "quiet", "true"),
+				(4, "iterations", "1000"),
+				(5, "name", "with \"escapes\"\t"),
+				(6, "literal", "C:\\raw"),
+				(7, "ratio", "0.5"),
+			]
+		);
+	}
+
+	#[test]
+	fn parse_rejects_unsupported_syntax() {
+		for (input, line) in [
+			("[section]\nkey = 1", 1),
+			("ok = 1\nnot a setting", 2),
+			("key = [1, 2]", 1),
+			("key = \"open", 1),
+			("key = 'a' 'b'", 1),
+			("ke y = 1", 1),
+		] {
+			let err = parse(input).unwrap_err();
+			assert_eq!(err.line, line, "wrong line for {input:?}");
+		}
+	}
+
+	#[test]
+	fn env_var_names() {
+		assert_eq!(env_var_for_key("max-doc-bytes"), "YAML_SPLIT_MAX_DOC_BYTES");
+		assert_eq!(env_var_for_key("quiet"), "YAML_SPLIT_QUIET");
+	}
+}
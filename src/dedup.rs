@@ -0,0 +1,127 @@
+//! Deduplication of documents across runs, through a persistent state file.
+//!
+//! The state file is plain text, with the SHA-256 digest of one previously
+//! emitted document per line. New digests are only appended to it, so it stays
+//! readable by other tools and survives being shared by runs that overlap.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::hash;
+use crate::transform::Documents;
+
+/// The digests of every document seen so far, from previous runs recorded in
+/// the state file as well as from this one.
+pub(crate) struct DedupState {
+	path: PathBuf,
+	seen: HashSet<String>,
+	added: Vec<String>,
+}
+
+impl DedupState {
+	/// Loads the state file at `path`, which need not exist yet.
+	pub(crate) fn load(path: &Path) -> io::Result<Self> {
+		let text = match fs::read_to_string(path) {
+			Ok(text) => text,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+			Err(err) => return Err(err),
+		};
+		let mut seen = HashSet::new();
+		for (i, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() {
+				continue;
+			}
+			if line.len() != 64 || !line.bytes().all(|b| b.is_ascii_hexdigit()) {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidData,
+					format!(
+						"{path} is not a dedup state file: line {n} is not a SHA-256 digest",
+						path = path.display(),
+						n = i + 1,
+					),
+				));
+			}
+			seen.insert(line.to_ascii_lowercase());
+		}
+		Ok(Self {
+			path: path.to_owned(),
+			seen,
+			added: vec![],
+		})
+	}
+
+	/// Records the digest of `content`, and returns whether it's new.
+	fn insert(&mut self, content: &str) -> bool {
+		let digest = hash::Algorithm::Sha256.hex_digest(content.as_bytes());
+		let new = self.seen.insert(digest.clone());
+		if new {
+			self.added.push(digest);
+		}
+		new
+	}
+
+	/// Appends the digests of the documents seen since loading to the state
+	/// file, creating it if necessary.
+	///
+	/// This should only be called once the documents have been written
+	/// successfully, so that a failed run doesn't cause them to be skipped by
+	/// the next one.
+	pub(crate) fn save(&self) -> io::Result<()> {
+		if self.added.is_empty() {
+			return Ok(());
+		}
+		let mut text = String::new();
+		for digest in &self.added {
+			text.push_str(digest);
+			text.push('\n');
+		}
+		OpenOptions::new()
+			.create(true)
+			.append(true)
+			.open(&self.path)?
+			.write_all(text.as_bytes())
+	}
+}
+
+/// Drops every document whose content `state` has already seen, including
+/// repeats of a document within the stream.
+pub(crate) fn filter(docs: Documents, state: Rc<RefCell<DedupState>>) -> Documents {
+	Box::new(docs.filter(move |doc| match doc {
+		Ok(doc) => state.borrow_mut().insert(doc.content()),
+		Err(_) => true,
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::env;
+	use std::process;
+
+	use crate::chunker::Chunker;
+
+	fn run(path: &Path, input: &'static str) -> Vec<String> {
+		let state = Rc::new(RefCell::new(DedupState::load(path).unwrap()));
+		let docs = filter(Box::new(Chunker::new(input.as_bytes())), Rc::clone(&state))
+			.map(|doc| doc.unwrap().content().to_owned())
+			.collect();
+		state.borrow().save().unwrap();
+		docs
+	}
+
+	#[test]
+	fn dedup_across_runs() {
+		let path = env::temp_dir().join(format!("yaml-split-dedup-{}", process::id()));
+		let _ = fs::remove_file(&path);
+		assert_eq!(run(&path, "--- a\n--- b\n--- a\n"), ["--- a\n", "--- b\n"]);
+		assert_eq!(run(&path, "--- b\n--- c\n"), ["--- c\n"]);
+		assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+		fs::remove_file(&path).unwrap();
+	}
+}
@@ -0,0 +1,43 @@
+//! Reporting of non-fatal warnings to standard error.
+//!
+//! Warnings describe conditions that the user may want to know about, but that
+//! don't prevent yaml-split from producing its output. They're written in the
+//! same style as the errors reported when the program exits, and can be
+//! suppressed entirely with `--quiet` for use in scripts that treat any output
+//! on standard error as a failure.
+
+use std::fmt::Arguments;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Suppresses (or re-enables) all future warnings.
+pub(crate) fn set_quiet(quiet: bool) {
+	QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Returns whether warnings and other informational output are suppressed.
+pub(crate) fn is_quiet() -> bool {
+	QUIET.load(Ordering::Relaxed)
+}
+
+/// Writes a warning to standard error, unless warnings are suppressed.
+///
+/// Errors in writing the warning are ignored, as there is nowhere left to
+/// report them.
+pub(crate) fn write_warning(args: Arguments<'_>) {
+	if !is_quiet() {
+		let _ = writeln!(io::stderr().lock(), "Warning: {args}");
+	}
+}
+
+/// Writes a formatted warning to standard error, unless warnings are
+/// suppressed.
+macro_rules! warning {
+	($($arg:tt)*) => {
+		$crate::diagnostics::write_warning(format_args!($($arg)*))
+	};
+}
+
+pub(crate) use warning;
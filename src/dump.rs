@@ -0,0 +1,237 @@
+//! Dumps of the parser's view of a stream, for diagnosing why a document
+//! boundary landed where it did.
+//!
+//! A dump writes one JSON object per line for each event that libyaml's parser
+//! produces, or for each token that its scanner produces, with its start and
+//! end positions. Tokens are the lower level of the two, and can show what
+//! the scanner made of indentation and stray characters in a document that
+//! doesn't parse. Positions give one-based lines and columns along with
+//! zero-based byte offsets into the stream as UTF-8, which differ from offsets
+//! in the input if it was transcoded.
+
+use std::io::{self, Read, Write};
+
+use crate::json;
+use crate::libyaml::*;
+
+/// Writes the events of the UTF-8 stream from `reader` to `output`, and
+/// returns the number of events written.
+///
+/// A parse error ends the dump after the events before it, so the last line
+/// shows how far the parser got.
+pub(crate) fn events(reader: impl Read, output: &mut dyn Write) -> io::Result<usize> {
+	let mut parser = Parser::new(reader);
+	let mut count = 0;
+	loop {
+		let event = parser.next_event()?;
+		writeln!(output, "{}", event_json(&event))?;
+		count += 1;
+		if event.event_type() == YAML_STREAM_END_EVENT {
+			return Ok(count);
+		}
+	}
+}
+
+/// Writes the tokens of the UTF-8 stream from `reader` to `output`, and
+/// returns the number of tokens written.
+///
+/// As with [`events`], a scan error ends the dump after the tokens before it.
+pub(crate) fn tokens(reader: impl Read, output: &mut dyn Write) -> io::Result<usize> {
+	let mut parser = Parser::new(reader);
+	let mut count = 0;
+	loop {
+		let token = parser.next_token()?;
+		writeln!(output, "{}", token_json(&token))?;
+		count += 1;
+		if token.token_type() == YAML_STREAM_END_TOKEN {
+			return Ok(count);
+		}
+	}
+}
+
+/// Returns a JSON object describing `event`.
+fn event_json(event: &Event) -> String {
+	let name = match event.event_type() {
+		YAML_STREAM_START_EVENT => "stream-start",
+		YAML_STREAM_END_EVENT => "stream-end",
+		YAML_DOCUMENT_START_EVENT => "document-start",
+		YAML_DOCUMENT_END_EVENT => "document-end",
+		YAML_ALIAS_EVENT => "alias",
+		YAML_SCALAR_EVENT => "scalar",
+		YAML_SEQUENCE_START_EVENT => "sequence-start",
+		YAML_SEQUENCE_END_EVENT => "sequence-end",
+		YAML_MAPPING_START_EVENT => "mapping-start",
+		YAML_MAPPING_END_EVENT => "mapping-end",
+		_ => "unknown",
+	};
+	let mut out = format!(
+		"{{\"event\":\"{name}\",\"start\":{start},\"end\":{end}",
+		start = position(event.start_mark(), event.start_offset()),
+		end = position(event.end_mark(), event.end_offset()),
+	);
+	if let Some(anchor) = event.anchor() {
+		out.push_str(",\"anchor\":");
+		json::write_string(&mut out, &anchor);
+	}
+	if let Some(tag) = event.tag() {
+		out.push_str(",\"tag\":");
+		json::write_string(&mut out, &tag);
+	}
+	match event.event_type() {
+		YAML_SCALAR_EVENT => {
+			let (value, style) = event.scalar();
+			out.push_str(",\"value\":");
+			json::write_string(&mut out, &value);
+			out.push_str(&format!(",\"style\":\"{}\"", style_name(style)));
+		}
+		YAML_SEQUENCE_START_EVENT | YAML_MAPPING_START_EVENT => {
+			out.push_str(&format!(",\"flow\":{}", event.is_flow()));
+		}
+		_ => {}
+	}
+	out.push('}');
+	out
+}
+
+/// Returns a JSON object describing `token`.
+fn token_json(token: &Token) -> String {
+	let name = match token.token_type() {
+		YAML_STREAM_START_TOKEN => "stream-start",
+		YAML_STREAM_END_TOKEN => "stream-end",
+		YAML_VERSION_DIRECTIVE_TOKEN => "version-directive",
+		YAML_TAG_DIRECTIVE_TOKEN => "tag-directive",
+		YAML_DOCUMENT_START_TOKEN => "document-start",
+		YAML_DOCUMENT_END_TOKEN => "document-end",
+		YAML_BLOCK_SEQUENCE_START_TOKEN => "block-sequence-start",
+		YAML_BLOCK_MAPPING_START_TOKEN => "block-mapping-start",
+		YAML_BLOCK_END_TOKEN => "block-end",
+		YAML_FLOW_SEQUENCE_START_TOKEN => "flow-sequence-start",
+		YAML_FLOW_SEQUENCE_END_TOKEN => "flow-sequence-end",
+		YAML_FLOW_MAPPING_START_TOKEN => "flow-mapping-start",
+		YAML_FLOW_MAPPING_END_TOKEN => "flow-mapping-end",
+		YAML_BLOCK_ENTRY_TOKEN => "block-entry",
+		YAML_FLOW_ENTRY_TOKEN => "flow-entry",
+		YAML_KEY_TOKEN => "key",
+		YAML_VALUE_TOKEN => "value",
+		YAML_ALIAS_TOKEN => "alias",
+		YAML_ANCHOR_TOKEN => "anchor",
+		YAML_TAG_TOKEN => "tag",
+		YAML_SCALAR_TOKEN => "scalar",
+		_ => "unknown",
+	};
+	let mut out = format!(
+		"{{\"token\":\"{name}\",\"start\":{start},\"end\":{end}",
+		start = position(token.start_mark(), token.start_offset()),
+		end = position(token.end_mark(), token.end_offset()),
+	);
+	if let Some(value) = token.value() {
+		out.push_str(",\"value\":");
+		json::write_string(&mut out, &value);
+	}
+	if let Some(style) = token.scalar_style() {
+		out.push_str(&format!(",\"style\":\"{}\"", style_name(style)));
+	}
+	if let Some((handle, rest)) = token.tag() {
+		out.push_str(",\"handle\":");
+		json::write_string(&mut out, &handle);
+		out.push_str(match token.token_type() {
+			YAML_TAG_TOKEN => ",\"suffix\":",
+			_ => ",\"prefix\":",
+		});
+		json::write_string(&mut out, &rest);
+	}
+	if let Some((major, minor)) = token.version() {
+		out.push_str(&format!(",\"version\":\"{major}.{minor}\""));
+	}
+	out.push('}');
+	out
+}
+
+/// Returns the name of a scalar style, for display to users.
+fn style_name(style: yaml_scalar_style_t) -> &'static str {
+	match style {
+		YAML_PLAIN_SCALAR_STYLE => "plain",
+		YAML_SINGLE_QUOTED_SCALAR_STYLE => "single-quoted",
+		YAML_DOUBLE_QUOTED_SCALAR_STYLE => "double-quoted",
+		YAML_LITERAL_SCALAR_STYLE => "literal",
+		YAML_FOLDED_SCALAR_STYLE => "folded",
+		_ => "any",
+	}
+}
+
+/// Returns a JSON object for a position in the stream.
+fn position(mark: Mark, offset: u64) -> String {
+	format!(
+		"{{\"line\":{line},\"column\":{column},\"offset\":{offset}}}",
+		line = mark.line + 1,
+		column = mark.column + 1,
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn dump_events() {
+		let mut output = vec![];
+		let count = events("- &a !t 'x'\n- *a\n".as_bytes(), &mut output).unwrap();
+		assert_eq!(count, 8);
+		let lines = String::from_utf8(output).unwrap();
+		let lines = lines.lines().collect::<Vec<_>>();
+		assert_eq!(
+			lines[2],
+			r#"{"event":"sequence-start","start":{"line":1,"column":1,"offset":0},"end":{"line":1,"column":1,"offset":0},"flow":false}"#
+		);
+		assert_eq!(
+			lines[3],
+			r#"{"event":"scalar","start":{"line":1,"column":3,"offset":2},"end":{"line":1,"column":12,"offset":11},"anchor":"a","tag":"!t","value":"x","style":"single-quoted"}"#
+		);
+		assert_eq!(
+			lines[4],
+			r#"{"event":"alias","start":{"line":2,"column":3,"offset":14},"end":{"line":2,"column":5,"offset":16},"anchor":"a"}"#
+		);
+
+		let mut output = vec![];
+		assert!(events("a: [b\n".as_bytes(), &mut output).is_err());
+		let output = String::from_utf8(output).unwrap();
+		assert!(output.lines().last().unwrap().contains(r#""value":"b""#));
+	}
+
+	#[test]
+	fn dump_tokens() {
+		let mut output = vec![];
+		let input = "%TAG !e! tag:example.com,2000:\n--- !e!x\nk: &a |\n  v\n";
+		let count = tokens(input.as_bytes(), &mut output).unwrap();
+		let output = String::from_utf8(output).unwrap();
+		let kinds = output
+			.lines()
+			.map(|line| line.split('"').nth(3).unwrap())
+			.collect::<Vec<_>>();
+		assert_eq!(
+			kinds,
+			[
+				"stream-start",
+				"tag-directive",
+				"document-start",
+				"tag",
+				"block-mapping-start",
+				"key",
+				"scalar",
+				"value",
+				"anchor",
+				"scalar",
+				"block-end",
+				"stream-end",
+			]
+		);
+		assert_eq!(count, kinds.len());
+		let lines = output.lines().collect::<Vec<_>>();
+		assert!(lines[1].ends_with(r#""handle":"!e!","prefix":"tag:example.com,2000:"}"#));
+		assert!(lines[3].ends_with(r#""handle":"!e!","suffix":"x"}"#));
+		assert!(lines[9].ends_with(r#""value":"v\n","style":"literal"}"#));
+
+		let mut output = vec![];
+		assert!(tokens("a:\n\tb: 1\n".as_bytes(), &mut output).is_err());
+	}
+}
@@ -35,17 +35,22 @@ use std::fmt::{Debug, Display, LowerHex};
 use std::io::{self, BufRead, Read, Write};
 
 /// The possible text encodings of a valid YAML 1.2 stream.
-pub(super) enum Encoding {
+pub enum Encoding {
+	/// UTF-8.
 	Utf8,
+	/// UTF-16, big-endian.
 	Utf16Big,
+	/// UTF-32, big-endian.
 	Utf32Big,
+	/// UTF-16, little-endian.
 	Utf16Little,
+	/// UTF-32, little-endian.
 	Utf32Little,
 }
 
 impl Encoding {
 	/// The desired length of the prefix for encoding detection.
-	pub(super) const DETECT_LEN: usize = 4;
+	pub const DETECT_LEN: usize = 4;
 
 	/// Detects the text encoding of a YAML 1.2 stream based on its leading
 	/// bytes.
@@ -60,7 +65,7 @@ impl Encoding {
 	/// the detection may be incorrect.
 	///
 	/// [spec]: https://yaml.org/spec/1.2.2/#52-character-encodings
-	pub(super) fn detect(prefix: &[u8]) -> Encoding {
+	pub fn detect(prefix: &[u8]) -> Encoding {
 		if let Some(Ok(prefix)) = prefix.get(0..4).map(TryInto::<[u8; 4]>::try_into) {
 			match prefix {
 				[0, 0, 0xFE, 0xFF] | [0, 0, 0, _] => return Encoding::Utf32Big,
@@ -88,7 +93,11 @@ impl Encoding {
 /// from, improving compatibility with parsers that do not accept the full range
 /// of supported YAML encodings. Otherwise, an `Encoder` can pass through a
 /// UTF-8 stream with little overhead.
-pub(super) struct Encoder<R>(EncoderKind<R>)
+///
+/// Since a YAML stream can't contain U+0000 in any encoding, reading fails
+/// with a [`BinaryInput`] error at the first NUL character, which is the surest
+/// sign that the input isn't text at all.
+pub struct Encoder<R>(EncoderKind<R>)
 where
 	R: BufRead;
 
@@ -96,7 +105,7 @@ enum EncoderKind<R>
 where
 	R: BufRead,
 {
-	Passthrough(R),
+	Passthrough(R, u64),
 	From16(Utf8Encoder<Utf16Decoder<R>>),
 	From32(Utf8Encoder<Utf32Decoder<R>>),
 }
@@ -106,13 +115,13 @@ where
 	R: BufRead,
 {
 	/// Creates an encoder using a known source encoding.
-	pub(super) fn new(reader: R, from: Encoding) -> Self {
+	pub fn new(reader: R, from: Encoding) -> Self {
 		use EncoderKind::*;
 		use Encoding::*;
 		use Endianness::*;
 
 		Self(match from {
-			Utf8 => Passthrough(reader),
+			Utf8 => Passthrough(reader, 0),
 			Utf16Big => From16(Utf8Encoder::new(Utf16Decoder::new(reader, Big))),
 			Utf32Big => From32(Utf8Encoder::new(Utf32Decoder::new(reader, Big))),
 			Utf16Little => From16(Utf8Encoder::new(Utf16Decoder::new(reader, Little))),
@@ -126,14 +135,88 @@ where
 	/// See [`Encoding::detect`] for details of the detection process. Note that
 	/// `from_reader` provides as many prefix bytes to the detector as it needs
 	/// for accurate detection.
-	pub(super) fn from_reader(mut reader: R) -> io::Result<impl Read> {
+	pub fn from_reader(reader: R) -> io::Result<impl Read> {
+		Self::from_reader_with_map(reader).map(|(encoder, _)| encoder)
+	}
+
+	/// Creates an encoder like [`Encoder::from_reader`], along with a map
+	/// from offsets in its output back to offsets in the reader.
+	pub fn from_reader_with_map(mut reader: R) -> io::Result<(impl Read, SourceMap)> {
 		let mut prefix = ArrayBuffer::<{ Encoding::DETECT_LEN }>::new();
 		io::copy(
 			&mut reader.by_ref().take(Encoding::DETECT_LEN as u64),
 			&mut prefix,
 		)?;
+		let map = SourceMap::detect(prefix.unread());
 		let encoding = Encoding::detect(prefix.unread());
-		Ok(Encoder::new(prefix.chain(reader), encoding))
+		Ok((Encoder::new(prefix.chain(reader), encoding), map))
+	}
+}
+
+/// Maps offsets in the UTF-8 output of an [`Encoder`] back to offsets in its
+/// source.
+///
+/// Since the width of every character in each source encoding depends only
+/// on the width of the same character in UTF-8, the map only needs the text
+/// that the encoder produced to translate its length into the source's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceMap {
+	unit_len: u64,
+	bom_len: u64,
+}
+
+impl SourceMap {
+	/// A map for an encoder that passes UTF-8 through unchanged.
+	pub const IDENTITY: SourceMap = SourceMap {
+		unit_len: 1,
+		bom_len: 0,
+	};
+
+	/// Creates the map for an encoder whose source starts with `prefix`, per
+	/// [`Encoding::detect`].
+	fn detect(prefix: &[u8]) -> SourceMap {
+		let (unit_len, bom): (u64, &[u8]) = match Encoding::detect(prefix) {
+			Encoding::Utf8 => return SourceMap::IDENTITY,
+			Encoding::Utf16Big => (2, &[0xFE, 0xFF]),
+			Encoding::Utf16Little => (2, &[0xFF, 0xFE]),
+			Encoding::Utf32Big => (4, &[0, 0, 0xFE, 0xFF]),
+			Encoding::Utf32Little => (4, &[0xFF, 0xFE, 0, 0]),
+		};
+		SourceMap {
+			unit_len,
+			bom_len: match prefix.starts_with(bom) {
+				true => bom.len() as u64,
+				false => 0,
+			},
+		}
+	}
+
+	/// Returns whether output offsets are the same as source offsets.
+	pub(super) fn is_identity(self) -> bool {
+		self == SourceMap::IDENTITY
+	}
+
+	/// Returns the offset in the source of the start of the encoder's output,
+	/// which is past any byte order mark that the encoder skipped.
+	pub(super) fn start(self) -> u64 {
+		self.bom_len
+	}
+
+	/// Returns the length in the source of the complete UTF-8 characters in
+	/// `text`.
+	pub(super) fn source_len(self, text: &[u8]) -> u64 {
+		match self.unit_len {
+			1 => text.len() as u64,
+			2 => text
+				.iter()
+				.map(|b| match b {
+					0x80..=0xBF => 0,
+					0xF0..=0xFF => 4,
+					_ => 2,
+				})
+				.sum(),
+			_ => text.iter().filter(|b| !(0x80..=0xBF).contains(*b)).count() as u64 * 4,
+		}
 	}
 }
 
@@ -143,7 +226,14 @@ where
 {
 	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
 		match &mut self.0 {
-			EncoderKind::Passthrough(r) => r.read(buf),
+			EncoderKind::Passthrough(r, pos) => {
+				let len = r.read(buf)?;
+				if let Some(i) = buf[..len].iter().position(|&b| b == 0) {
+					return Err(BinaryInput(*pos + i as u64).into());
+				}
+				*pos += len as u64;
+				Ok(len)
+			}
 			EncoderKind::From16(r) => r.read(buf),
 			EncoderKind::From32(r) => r.read(buf),
 		}
@@ -306,7 +396,8 @@ where
 			},
 		};
 		match lead {
-			0x0000..=0xD7FF | 0xE000..=0xFFFF => {
+			0x0000 => return Some(Err(BinaryInput(pos).into())),
+			0x0001..=0xD7FF | 0xE000..=0xFFFF => {
 				// SAFETY: This is not a UTF-16 surrogate, which means that the
 				// u16 code unit directly encodes the desired code point.
 				return Some(Ok(unsafe { char::from_u32_unchecked(u32::from(lead)) }));
@@ -384,6 +475,9 @@ where
 		self.pos += next.len() as u64;
 
 		let unit = self.endianness.decode_u32(next);
+		if unit == 0 {
+			return Some(Err(BinaryInput(pos).into()));
+		}
 		Some(match char::from_u32(unit) {
 			Some(ch) => Ok(ch),
 			None => Err(EncodingError::new(unit, pos).into()),
@@ -413,6 +507,35 @@ impl Endianness {
 	}
 }
 
+/// The error for a NUL character in a stream, at a byte offset in the source.
+#[derive(Debug)]
+pub struct BinaryInput(u64);
+
+impl BinaryInput {
+	/// Returns the byte offset of the NUL character in the source.
+	pub fn offset(&self) -> u64 {
+		self.0
+	}
+}
+
+impl From<BinaryInput> for io::Error {
+	fn from(err: BinaryInput) -> Self {
+		io::Error::new(io::ErrorKind::InvalidData, err)
+	}
+}
+
+impl Error for BinaryInput {}
+
+impl Display for BinaryInput {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"input looks like binary data (first NUL at byte {pos})",
+			pos = self.0
+		)
+	}
+}
+
 /// An error in a UTF-16 or UTF-32 stream.
 #[derive(Debug)]
 struct EncodingError<T>
@@ -640,6 +763,27 @@ mod tests {
 		assert_eq!(std::str::from_utf8(&result), Ok("hello 🖥"));
 	}
 
+	#[test]
+	fn encode_binary_input() {
+		let inputs = [
+			(&hex!("61 3a 20 31 0a 00 ff")[..], Encoding::Utf8, 5),
+			(&hex!("00 61 00 00")[..], Encoding::Utf16Big, 2),
+			(
+				&hex!("61 00 00 00 00 00 00 00")[..],
+				Encoding::Utf32Little,
+				4,
+			),
+		];
+		for (input, encoding, pos) in inputs {
+			let mut encoder = Encoder::new(input, encoding);
+			let err = encoder.read_to_end(&mut vec![]).unwrap_err();
+			assert_eq!(
+				err.to_string(),
+				format!("input looks like binary data (first NUL at byte {pos})")
+			);
+		}
+	}
+
 	#[test]
 	fn encode_valid_utf16le_empty() {
 		let mut encoder = Encoder::new(io::empty(), Encoding::Utf16Little);
@@ -752,6 +896,9 @@ mod tests {
 	}
 
 	#[test]
+	// Newer versions of clippy prefer slicing the bytes, but slicing the text
+	// keeps the offset checked against a character boundary.
+	#[allow(unknown_lints, clippy::sliced_string_as_bytes)]
 	fn arraybuffer_set_bufread() {
 		const INPUT: &str = "hello world";
 
@@ -774,4 +921,16 @@ mod tests {
 		let err = buf.write_all(INPUT.as_bytes()).unwrap_err();
 		assert_eq!(err.kind(), io::ErrorKind::WriteZero);
 	}
+
+	#[test]
+	fn source_map_lengths() {
+		let text = "aé€😀".as_bytes();
+		let map = |prefix: &[u8]| SourceMap::detect(prefix);
+		assert_eq!(map(b"ab").source_len(text), 10);
+		assert_eq!(map(&hex!("ff fe 61 00")).start(), 2);
+		assert_eq!(map(&hex!("61 00 62 00")).start(), 0);
+		assert_eq!(map(&hex!("ff fe 61 00")).source_len(text), 10);
+		assert_eq!(map(&hex!("00 00 fe ff")).start(), 4);
+		assert_eq!(map(&hex!("00 00 fe ff")).source_len(text), 16);
+	}
 }
@@ -0,0 +1,265 @@
+//! A C interface to the chunker, for programs in other languages that split
+//! YAML streams.
+//!
+//! The interface is only built with the `ffi` feature. Cargo can't add the
+//! `cdylib` crate type behind a feature, so build the shared library with
+//! `cargo rustc`, which can:
+//!
+//! ```text
+//! cargo rustc --lib --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! `include/yaml_split.h` declares the functions for C. A program opens a
+//! stream with [`ys_open`], calls [`ys_next_doc`] until it returns 0, reading
+//! each document with [`ys_doc_bytes`], and closes the stream with
+//! [`ys_close`]. When a call fails, [`ys_error`] describes why:
+//!
+//! ```c
+//! ys_stream *stream = ys_open("input.yaml");
+//! int status;
+//! while ((status = ys_next_doc(stream)) > 0) {
+//!     size_t len;
+//!     const char *doc = ys_doc_bytes(stream, &len);
+//!     fwrite(doc, 1, len, stdout);
+//! }
+//! if (status < 0) {
+//!     fprintf(stderr, "%s\n", ys_error(stream));
+//! }
+//! ys_close(stream);
+//! ```
+
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+
+use crate::chunker::{Chunker, Document};
+use crate::encoding::Encoder;
+
+/// An open YAML stream, and the document last read from it, which C knows as
+/// the opaque `ys_stream`.
+pub struct Stream {
+	docs: Option<Box<dyn Iterator<Item = io::Result<Document>>>>,
+	doc: Option<Document>,
+	error: Option<CString>,
+}
+
+impl Stream {
+	/// Records `err` as the reason that the stream can't go on.
+	fn fail(&mut self, err: impl ToString) {
+		let message = err.to_string().replace('\0', "\\0");
+		self.docs = None;
+		self.doc = None;
+		self.error = Some(CString::new(message).unwrap());
+	}
+}
+
+/// Opens the YAML stream in the file at `path`, or standard input if `path`
+/// is null, in any of the encodings that the chunker reads.
+///
+/// This never returns null. If the stream can't be opened, the first call to
+/// [`ys_next_doc`] fails with the reason.
+///
+/// # Safety
+///
+/// `path` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ys_open(path: *const c_char) -> *mut Stream {
+	let path = match path.is_null() {
+		true => None,
+		// SAFETY: The caller passes a NUL-terminated string.
+		false => Some(path_from_c(unsafe { CStr::from_ptr(path) })),
+	};
+	let mut stream = Stream {
+		docs: None,
+		doc: None,
+		error: None,
+	};
+	match open(path) {
+		Ok(docs) => stream.docs = Some(docs),
+		Err(err) => stream.fail(err),
+	}
+	Box::into_raw(Box::new(stream))
+}
+
+/// Returns the chunker over the stream in the file at `path`, or standard
+/// input.
+fn open(path: Option<PathBuf>) -> io::Result<Box<dyn Iterator<Item = io::Result<Document>>>> {
+	let input: Box<dyn BufRead> = match path {
+		Some(path) => Box::new(BufReader::new(File::open(path)?)),
+		None => Box::new(io::stdin().lock()),
+	};
+	let (reader, map) = Encoder::from_reader_with_map(input)?;
+	Ok(Box::new(Chunker::new(reader).with_source_map(map)))
+}
+
+#[cfg(unix)]
+fn path_from_c(path: &CStr) -> PathBuf {
+	use std::os::unix::ffi::OsStrExt;
+	std::ffi::OsStr::from_bytes(path.to_bytes()).into()
+}
+
+#[cfg(not(unix))]
+fn path_from_c(path: &CStr) -> PathBuf {
+	path.to_string_lossy().into_owned().into()
+}
+
+/// Reads the next document of `stream`, returning 1 if there is one, 0 at the
+/// end of the stream, and -1 if reading fails.
+///
+/// Once reading fails, every later call fails too, and [`ys_error`] gives the
+/// reason.
+///
+/// # Safety
+///
+/// `stream` must have been returned by [`ys_open`], and not yet closed.
+#[no_mangle]
+pub unsafe extern "C" fn ys_next_doc(stream: *mut Stream) -> c_int {
+	// SAFETY: The caller passes an open stream.
+	let stream = unsafe { &mut *stream };
+	let Some(docs) = &mut stream.docs else {
+		stream.doc = None;
+		return if stream.error.is_some() { -1 } else { 0 };
+	};
+	// The chunker doesn't panic by design, but unwinding out of a C call
+	// would be undefined behavior.
+	match panic::catch_unwind(AssertUnwindSafe(|| docs.next())) {
+		Ok(Some(Ok(doc))) => {
+			stream.doc = Some(doc);
+			1
+		}
+		Ok(None) => {
+			stream.docs = None;
+			stream.doc = None;
+			0
+		}
+		Ok(Some(Err(err))) => {
+			stream.fail(err);
+			-1
+		}
+		Err(_) => {
+			stream.fail("yaml-split panicked while reading the stream");
+			-1
+		}
+	}
+}
+
+/// Returns the text of the document that [`ys_next_doc`] last read, exactly
+/// as the stream gave it in UTF-8, and stores its length in bytes in `len`.
+///
+/// The text isn't NUL-terminated, and stays valid until the next call to
+/// [`ys_next_doc`] or [`ys_close`]. If there is no document, this returns null
+/// and stores 0.
+///
+/// # Safety
+///
+/// `stream` must have been returned by [`ys_open`], and not yet closed, and
+/// `len` must be null or point to a `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn ys_doc_bytes(stream: *const Stream, len: *mut usize) -> *const c_char {
+	// SAFETY: The caller passes an open stream.
+	let stream = unsafe { &*stream };
+	let (bytes, n) = match &stream.doc {
+		Some(doc) => (doc.content().as_ptr().cast(), doc.content().len()),
+		None => (ptr::null(), 0),
+	};
+	if !len.is_null() {
+		// SAFETY: The caller passes null or a valid pointer.
+		unsafe { *len = n };
+	}
+	bytes
+}
+
+/// Returns why `stream` failed, as a NUL-terminated string, or null if it
+/// hasn't.
+///
+/// The string stays valid until [`ys_close`].
+///
+/// # Safety
+///
+/// `stream` must have been returned by [`ys_open`], and not yet closed.
+#[no_mangle]
+pub unsafe extern "C" fn ys_error(stream: *const Stream) -> *const c_char {
+	// SAFETY: The caller passes an open stream.
+	let stream = unsafe { &*stream };
+	stream.error.as_deref().map_or(ptr::null(), CStr::as_ptr)
+}
+
+/// Closes `stream`, freeing it along with its document and error.
+///
+/// # Safety
+///
+/// `stream` must be null, or have been returned by [`ys_open`] and not yet
+/// closed.
+#[no_mangle]
+pub unsafe extern "C" fn ys_close(stream: *mut Stream) {
+	if !stream.is_null() {
+		// SAFETY: The stream came from `Box::into_raw` in `ys_open`.
+		drop(unsafe { Box::from_raw(stream) });
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::fs;
+
+	/// Returns the documents of the stream at `path`, or the error that ends
+	/// it.
+	fn split(path: Option<&CStr>) -> Result<Vec<String>, String> {
+		let mut docs = vec![];
+		// SAFETY: The stream is only used until it's closed.
+		unsafe {
+			let stream = ys_open(path.map_or(ptr::null(), CStr::as_ptr));
+			let status = loop {
+				let status = ys_next_doc(stream);
+				if status <= 0 {
+					break status;
+				}
+				let mut len = 0;
+				let bytes = ys_doc_bytes(stream, &mut len);
+				let bytes = std::slice::from_raw_parts(bytes.cast::<u8>(), len);
+				docs.push(String::from_utf8(bytes.to_vec()).unwrap());
+			};
+			assert!(ys_doc_bytes(stream, ptr::null_mut()).is_null());
+			let result = match status {
+				0 => {
+					assert!(ys_error(stream).is_null());
+					Ok(docs)
+				}
+				_ => {
+					assert_eq!(ys_next_doc(stream), -1);
+					let error = CStr::from_ptr(ys_error(stream));
+					Err(error.to_str().unwrap().to_owned())
+				}
+			};
+			ys_close(stream);
+			result
+		}
+	}
+
+	#[test]
+	fn ffi_splits_files() {
+		let path = std::env::temp_dir().join(format!("yaml-split-ffi-{}", std::process::id()));
+		let c_path = CString::new(path.to_str().unwrap()).unwrap();
+		fs::write(
+			&path,
+			b"\xFF\xFEa\x00:\x00 \x001\x00\n\x00-\x00-\x00-\x00\n\x00b\x00\n\x00",
+		)
+		.unwrap();
+		assert_eq!(split(Some(&c_path)).unwrap(), ["a: 1\n", "---\nb\n"]);
+
+		fs::write(&path, "a: 1\n---\nb: [\n").unwrap();
+		let err = split(Some(&c_path)).unwrap_err();
+		assert!(err.starts_with("did not find expected"), "{err}");
+
+		fs::remove_file(&path).unwrap();
+		let err = split(Some(&c_path)).unwrap_err();
+		assert_eq!(err, File::open(&path).unwrap_err().to_string());
+		// SAFETY: Closing null does nothing.
+		unsafe { ys_close(ptr::null_mut()) };
+	}
+}
@@ -0,0 +1,324 @@
+//! Cryptographic digests of document content.
+//!
+//! yaml-split only ever hashes whole documents that are already in memory, so
+//! these are simple one-shot implementations of each algorithm, written from
+//! their specifications to avoid pulling in a dependency for each. Neither
+//! needs to be especially fast compared to parsing the YAML in the first
+//! place.
+
+use std::fmt::Write;
+
+/// The supported digest algorithms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum Algorithm {
+	/// SHA-256, as defined in FIPS 180-4.
+	Sha256,
+	/// BLAKE3, with its default 256-bit output.
+	Blake3,
+}
+
+impl Algorithm {
+	/// Returns the lowercase name of the algorithm, for display to users.
+	pub(crate) fn name(self) -> &'static str {
+		match self {
+			Algorithm::Sha256 => "sha256",
+			Algorithm::Blake3 => "blake3",
+		}
+	}
+
+	/// Returns the digest of `data` in lowercase hexadecimal.
+	pub(crate) fn hex_digest(self, data: &[u8]) -> String {
+		let digest = match self {
+			Algorithm::Sha256 => sha256(data),
+			Algorithm::Blake3 => blake3(data),
+		};
+		let mut hex = String::with_capacity(digest.len() * 2);
+		for b in digest {
+			let _ = write!(hex, "{b:02x}");
+		}
+		hex
+	}
+}
+
+/// The initial hash value of SHA-256, which BLAKE3 shares as its IV.
+const IV: [u32; 8] = [
+	0x6A09E667, 0xBB67AE85, 0x3C6EF372, 0xA54FF53A, 0x510E527F, 0x9B05688C, 0x1F83D9AB, 0x5BE0CD19,
+];
+
+const SHA256_K: [u32; 64] = [
+	0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+	0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+	0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+	0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+	0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+	0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+	0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+	0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+	let mut padded = data.to_vec();
+	padded.push(0x80);
+	while padded.len() % 64 != 56 {
+		padded.push(0);
+	}
+	padded.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+	let mut h = IV;
+	for block in padded.chunks_exact(64) {
+		let mut w = [0u32; 64];
+		for (i, word) in block.chunks_exact(4).enumerate() {
+			w[i] = u32::from_be_bytes(word.try_into().unwrap());
+		}
+		for i in 16..64 {
+			let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+			let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+			w[i] = w[i - 16]
+				.wrapping_add(s0)
+				.wrapping_add(w[i - 7])
+				.wrapping_add(s1);
+		}
+
+		let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+		for i in 0..64 {
+			let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+			let ch = (e & f) ^ (!e & g);
+			let t1 = hh
+				.wrapping_add(s1)
+				.wrapping_add(ch)
+				.wrapping_add(SHA256_K[i])
+				.wrapping_add(w[i]);
+			let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+			let maj = (a & b) ^ (a & c) ^ (b & c);
+			let t2 = s0.wrapping_add(maj);
+			(hh, g, f, e) = (g, f, e, d.wrapping_add(t1));
+			(d, c, b, a) = (c, b, a, t1.wrapping_add(t2));
+		}
+		for (h, v) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+			*h = h.wrapping_add(v);
+		}
+	}
+
+	let mut digest = [0; 32];
+	for (out, h) in digest.chunks_exact_mut(4).zip(h) {
+		out.copy_from_slice(&h.to_be_bytes());
+	}
+	digest
+}
+
+const BLAKE3_CHUNK_LEN: usize = 1024;
+const BLAKE3_BLOCK_LEN: usize = 64;
+const BLAKE3_CHUNK_START: u32 = 1 << 0;
+const BLAKE3_CHUNK_END: u32 = 1 << 1;
+const BLAKE3_PARENT: u32 = 1 << 2;
+const BLAKE3_ROOT: u32 = 1 << 3;
+const BLAKE3_MSG_PERMUTATION: [usize; 16] = [2, 6, 3, 10, 7, 0, 4, 13, 1, 11, 12, 5, 9, 14, 15, 8];
+
+/// Computes an unkeyed BLAKE3 hash, following the structure of the reference
+/// implementation: each 1 KiB chunk is compressed one block at a time, and the
+/// chaining values of completed chunks are merged into a binary tree as soon
+/// as each subtree is complete.
+fn blake3(data: &[u8]) -> [u8; 32] {
+	let mut stack: Vec<[u32; 8]> = vec![];
+	let mut chunks = data.chunks(BLAKE3_CHUNK_LEN).peekable();
+	let mut counter = 0u64;
+	let mut output = loop {
+		let chunk = chunks.next().unwrap_or_default();
+		let output = blake3_chunk(chunk, counter);
+		if chunks.peek().is_none() {
+			break output;
+		}
+		counter += 1;
+		let mut cv = output.chaining_value();
+		let mut total = counter;
+		while total & 1 == 0 {
+			cv = blake3_parent(stack.pop().unwrap(), cv).chaining_value();
+			total >>= 1;
+		}
+		stack.push(cv);
+	};
+	while let Some(left) = stack.pop() {
+		output = blake3_parent(left, output.chaining_value());
+	}
+
+	let words = output.compress(BLAKE3_ROOT);
+	let mut digest = [0; 32];
+	for (out, word) in digest.chunks_exact_mut(4).zip(words) {
+		out.copy_from_slice(&word.to_le_bytes());
+	}
+	digest
+}
+
+/// The inputs to a BLAKE3 compression whose flags aren't yet final, since
+/// only the root node sets the ROOT flag.
+struct Blake3Output {
+	cv: [u32; 8],
+	block: [u32; 16],
+	counter: u64,
+	block_len: u32,
+	flags: u32,
+}
+
+impl Blake3Output {
+	fn compress(&self, extra_flags: u32) -> [u32; 16] {
+		blake3_compress(
+			&self.cv,
+			&self.block,
+			self.counter,
+			self.block_len,
+			self.flags | extra_flags,
+		)
+	}
+
+	fn chaining_value(&self) -> [u32; 8] {
+		self.compress(0)[..8].try_into().unwrap()
+	}
+}
+
+fn blake3_chunk(chunk: &[u8], counter: u64) -> Blake3Output {
+	let mut cv = IV;
+	let mut blocks = chunk.chunks(BLAKE3_BLOCK_LEN).peekable();
+	let mut flags = BLAKE3_CHUNK_START;
+	loop {
+		let block = blocks.next().unwrap_or_default();
+		let mut bytes = [0; BLAKE3_BLOCK_LEN];
+		bytes[..block.len()].copy_from_slice(block);
+		let words = blake3_words(&bytes);
+		if blocks.peek().is_none() {
+			return Blake3Output {
+				cv,
+				block: words,
+				counter,
+				block_len: block.len() as u32,
+				flags: flags | BLAKE3_CHUNK_END,
+			};
+		}
+		cv = blake3_compress(&cv, &words, counter, BLAKE3_BLOCK_LEN as u32, flags)[..8]
+			.try_into()
+			.unwrap();
+		flags = 0;
+	}
+}
+
+fn blake3_parent(left: [u32; 8], right: [u32; 8]) -> Blake3Output {
+	let mut block = [0; 16];
+	block[..8].copy_from_slice(&left);
+	block[8..].copy_from_slice(&right);
+	Blake3Output {
+		cv: IV,
+		block,
+		counter: 0,
+		block_len: BLAKE3_BLOCK_LEN as u32,
+		flags: BLAKE3_PARENT,
+	}
+}
+
+fn blake3_words(bytes: &[u8; BLAKE3_BLOCK_LEN]) -> [u32; 16] {
+	let mut words = [0; 16];
+	for (word, bytes) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+		*word = u32::from_le_bytes(bytes.try_into().unwrap());
+	}
+	words
+}
+
+fn blake3_compress(
+	cv: &[u32; 8],
+	block: &[u32; 16],
+	counter: u64,
+	block_len: u32,
+	flags: u32,
+) -> [u32; 16] {
+	#[rustfmt::skip]
+	let mut state = [
+		cv[0], cv[1], cv[2], cv[3], cv[4], cv[5], cv[6], cv[7],
+		IV[0], IV[1], IV[2], IV[3],
+		counter as u32, (counter >> 32) as u32, block_len, flags,
+	];
+	let mut m = *block;
+	for _ in 0..7 {
+		blake3_g(&mut state, 0, 4, 8, 12, m[0], m[1]);
+		blake3_g(&mut state, 1, 5, 9, 13, m[2], m[3]);
+		blake3_g(&mut state, 2, 6, 10, 14, m[4], m[5]);
+		blake3_g(&mut state, 3, 7, 11, 15, m[6], m[7]);
+		blake3_g(&mut state, 0, 5, 10, 15, m[8], m[9]);
+		blake3_g(&mut state, 1, 6, 11, 12, m[10], m[11]);
+		blake3_g(&mut state, 2, 7, 8, 13, m[12], m[13]);
+		blake3_g(&mut state, 3, 4, 9, 14, m[14], m[15]);
+		m = BLAKE3_MSG_PERMUTATION.map(|i| m[i]);
+	}
+	for i in 0..8 {
+		state[i] ^= state[i + 8];
+		state[i + 8] ^= cv[i];
+	}
+	state
+}
+
+#[allow(clippy::too_many_arguments)]
+fn blake3_g(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize, mx: u32, my: u32) {
+	s[a] = s[a].wrapping_add(s[b]).wrapping_add(mx);
+	s[d] = (s[d] ^ s[a]).rotate_right(16);
+	s[c] = s[c].wrapping_add(s[d]);
+	s[b] = (s[b] ^ s[c]).rotate_right(12);
+	s[a] = s[a].wrapping_add(s[b]).wrapping_add(my);
+	s[d] = (s[d] ^ s[a]).rotate_right(8);
+	s[c] = s[c].wrapping_add(s[d]);
+	s[b] = (s[b] ^ s[c]).rotate_right(7);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn hash_sha256() {
+		let long = "a".repeat(1000);
+		for (input, expected) in [
+			(
+				"",
+				"e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+			),
+			(
+				"abc",
+				"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+			),
+			(
+				long.as_str(),
+				"41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3",
+			),
+		] {
+			assert_eq!(Algorithm::Sha256.hex_digest(input.as_bytes()), expected);
+		}
+	}
+
+	#[test]
+	fn hash_blake3() {
+		for (input, expected) in [
+			(
+				"",
+				"af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+			),
+			(
+				"abc",
+				"6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+			),
+		] {
+			assert_eq!(Algorithm::Blake3.hex_digest(input.as_bytes()), expected);
+		}
+
+		// Multi-chunk inputs from the official test vectors, which repeat the
+		// bytes 0 through 250.
+		for (len, expected) in [
+			(
+				1025,
+				"d00278ae47eb27b34faecf67b4fe263f82d5412916c1ffd97c8cb7fb814b8444",
+			),
+			(
+				2048,
+				"e776b6028c7cd22a4d0ba182a8bf62205d2ef576467e838ed6f2529b85fba24a",
+			),
+		] {
+			let input = (0..len).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+			assert_eq!(Algorithm::Blake3.hex_digest(&input), expected);
+		}
+	}
+}
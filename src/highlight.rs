@@ -0,0 +1,327 @@
+//! ANSI syntax highlighting for YAML documents.
+//!
+//! The highlighter is purely lexical, and looks at one line at a time with a
+//! little state carried over for block and multi-line quoted scalars. It
+//! doesn't validate anything or understand the full YAML grammar, which keeps
+//! it fast and means it degrades gracefully on inputs that libyaml would
+//! reject. That's the right tradeoff for output meant to be read by humans,
+//! but means the highlighting should never be relied on for correctness.
+
+use std::io::{self, Write};
+
+/// The visual role of a span of highlighted text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Style {
+	Plain,
+	Key,
+	String,
+	Literal,
+	Comment,
+	Marker,
+	Anchor,
+	Tag,
+}
+
+impl Style {
+	/// Returns the SGR parameters for the style, or `None` for plain text.
+	fn sgr(self) -> Option<&'static str> {
+		match self {
+			Style::Plain => None,
+			Style::Key => Some("34"),
+			Style::String => Some("32"),
+			Style::Literal => Some("35"),
+			Style::Comment => Some("90"),
+			Style::Marker => Some("1;35"),
+			Style::Anchor => Some("33"),
+			Style::Tag => Some("36"),
+		}
+	}
+}
+
+/// Headers and other framing around documents.
+pub(crate) const FRAME_SGR: &str = "1;36";
+
+/// Writes `text` to `w` with ANSI escape sequences applied using the given SGR
+/// parameters.
+pub(crate) fn write_styled(w: &mut dyn Write, sgr: &str, text: &str) -> io::Result<()> {
+	write!(w, "\x1b[{sgr}m{text}\x1b[0m")
+}
+
+/// Writes `doc` to `w` with ANSI syntax highlighting.
+pub(crate) fn write_highlighted(w: &mut dyn Write, doc: &str) -> io::Result<()> {
+	let mut highlighter = Highlighter::default();
+	for line in doc.split_inclusive('\n') {
+		let (content, newline) = match line.strip_suffix('\n') {
+			Some(content) => (content, "\n"),
+			None => (line, ""),
+		};
+		for (style, span) in highlighter.line(content) {
+			match style.sgr() {
+				None => w.write_all(span.as_bytes())?,
+				Some(sgr) => write_styled(w, sgr, span)?,
+			}
+		}
+		w.write_all(newline.as_bytes())?;
+	}
+	Ok(())
+}
+
+/// Lexical state carried from one line to the next.
+#[derive(Default)]
+struct Highlighter {
+	/// The indentation of the line that introduced the block scalar whose
+	/// content we're in, if any.
+	block_parent_indent: Option<usize>,
+	/// The quote character of a multi-line quoted scalar we're in, if any.
+	open_quote: Option<char>,
+}
+
+impl Highlighter {
+	/// Splits a single line (without its line break) into styled spans.
+	fn line<'a>(&mut self, line: &'a str) -> Vec<(Style, &'a str)> {
+		let mut spans = vec![];
+		let indent = line.len() - line.trim_start_matches(' ').len();
+
+		if let Some(parent) = self.block_parent_indent {
+			if line.trim().is_empty() || indent > parent {
+				spans.push((Style::String, line));
+				return spans;
+			}
+			self.block_parent_indent = None;
+		}
+
+		let mut rest = line;
+		if let Some(quote) = self.open_quote.take() {
+			let len = quoted_len(rest, quote, 0);
+			spans.push((Style::String, &rest[..len.unwrap_or(rest.len())]));
+			match len {
+				None => {
+					self.open_quote = Some(quote);
+					return spans;
+				}
+				Some(len) => rest = &rest[len..],
+			}
+		} else if line.starts_with('%') {
+			spans.push((Style::Marker, line));
+			return spans;
+		} else if (line.starts_with("---") || line.starts_with("..."))
+			&& line[3..].chars().next().map_or(true, char::is_whitespace)
+		{
+			spans.push((Style::Marker, &line[..3]));
+			rest = &line[3..];
+		}
+
+		let mut flow_depth = 0usize;
+		while !rest.is_empty() {
+			let ws = rest.len() - rest.trim_start().len();
+			if ws > 0 {
+				spans.push((Style::Plain, &rest[..ws]));
+				rest = &rest[ws..];
+				continue;
+			}
+
+			let mut chars = rest.chars();
+			let first = chars.next().unwrap();
+			let next_is_space = chars.next().map_or(true, char::is_whitespace);
+			let (style, len) = match first {
+				'#' => (Style::Comment, rest.len()),
+				'-' | '?' | ':' if next_is_space => (Style::Plain, 1),
+				'[' | '{' => {
+					flow_depth += 1;
+					(Style::Plain, 1)
+				}
+				']' | '}' if flow_depth > 0 => {
+					flow_depth -= 1;
+					(Style::Plain, 1)
+				}
+				',' if flow_depth > 0 => (Style::Plain, 1),
+				'&' | '*' => (Style::Anchor, token_len(rest)),
+				'!' => (Style::Tag, token_len(rest)),
+				'|' | '>' => {
+					let len = token_len(rest);
+					let after = rest[len..].trim_start();
+					if after.is_empty() || after.starts_with('#') {
+						self.block_parent_indent = Some(indent);
+					}
+					(Style::Plain, len)
+				}
+				'"' | '\'' => match quoted_len(rest, first, 1) {
+					Some(len) if is_key_indicator(&rest[len..]) => (Style::Key, len),
+					Some(len) => (Style::String, len),
+					None => {
+						self.open_quote = Some(first);
+						(Style::String, rest.len())
+					}
+				},
+				_ => {
+					let len = plain_len(rest, flow_depth > 0).max(first.len_utf8());
+					let style = if is_key_indicator(&rest[len..]) {
+						Style::Key
+					} else if is_literal(&rest[..len]) {
+						Style::Literal
+					} else {
+						Style::String
+					};
+					(style, len)
+				}
+			};
+			spans.push((style, &rest[..len]));
+			rest = &rest[len..];
+		}
+		spans
+	}
+}
+
+/// Returns the length of an anchor, alias, tag, or block scalar header at the
+/// start of `text`.
+fn token_len(text: &str) -> usize {
+	text.find(|c: char| c.is_whitespace() || matches!(c, ',' | '[' | ']' | '{' | '}'))
+		.unwrap_or(text.len())
+}
+
+/// Returns the length of a single-line plain scalar at the start of `text`,
+/// which ends early at flow indicators if it's inside a flow collection.
+fn plain_len(text: &str, in_flow: bool) -> usize {
+	let bytes = text.as_bytes();
+	for (i, &b) in bytes.iter().enumerate() {
+		let next_is_space = bytes.get(i + 1).map_or(true, u8::is_ascii_whitespace);
+		let prev_is_space = i > 0 && bytes[i - 1].is_ascii_whitespace();
+		match b {
+			b':' if i > 0 && next_is_space => return trim_end_len(&text[..i]),
+			b'#' if prev_is_space => return trim_end_len(&text[..i]),
+			b',' | b']' | b'}' if in_flow => return trim_end_len(&text[..i]),
+			_ => {}
+		}
+	}
+	trim_end_len(text)
+}
+
+fn trim_end_len(text: &str) -> usize {
+	text.trim_end().len()
+}
+
+/// Returns the length of a quoted scalar whose opening quote (if any) ends at
+/// `start`, including the closing quote, or `None` if the scalar doesn't end
+/// on this line.
+fn quoted_len(text: &str, quote: char, start: usize) -> Option<usize> {
+	let mut chars = text[start..].char_indices().peekable();
+	while let Some((i, c)) = chars.next() {
+		match c {
+			'\\' if quote == '"' => {
+				chars.next();
+			}
+			'\'' if quote == '\'' && chars.peek().map(|&(_, c)| c) == Some('\'') => {
+				chars.next();
+			}
+			c if c == quote => return Some(start + i + 1),
+			_ => {}
+		}
+	}
+	None
+}
+
+/// Returns whether `rest` begins with the `:` that makes the scalar just
+/// before it a mapping key.
+fn is_key_indicator(rest: &str) -> bool {
+	let rest = rest.trim_start_matches(' ');
+	rest.starts_with(':') && rest[1..].chars().next().map_or(true, char::is_whitespace)
+}
+
+/// Returns whether a plain scalar looks like a null, boolean, or number under
+/// the YAML 1.2 core schema.
+fn is_literal(text: &str) -> bool {
+	matches!(
+		text,
+		"~" | "null" | "Null" | "NULL" | "true" | "True" | "TRUE" | "false" | "False" | "FALSE"
+	) || text
+		.bytes()
+		.all(|b| b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.' | b'e' | b'E'))
+		&& text.parse::<f64>().is_ok()
+		|| text.starts_with("0x") && i64::from_str_radix(&text[2..], 16).is_ok()
+		|| text.starts_with("0o") && i64::from_str_radix(&text[2..], 8).is_ok()
+		|| matches!(
+			text.trim_start_matches(['+', '-']),
+			".inf" | ".Inf" | ".INF" | ".nan" | ".NaN" | ".NAN"
+		)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use Style::*;
+
+	fn spans(doc: &str) -> Vec<(Style, &str)> {
+		let mut highlighter = Highlighter::default();
+		doc.lines()
+			.flat_map(|line| highlighter.line(line))
+			.filter(|(style, span)| *style != Plain || !span.trim().is_empty())
+			.collect()
+	}
+
+	#[test]
+	fn highlight_mapping() {
+		assert_eq!(
+			spans("--- !config\nname: &n \"web\" # the name\ncount: 3\nalias: *n\n"),
+			&[
+				(Marker, "---"),
+				(Tag, "!config"),
+				(Key, "name"),
+				(Plain, ":"),
+				(Anchor, "&n"),
+				(String, "\"web\""),
+				(Comment, "# the name"),
+				(Key, "count"),
+				(Plain, ":"),
+				(Literal, "3"),
+				(Key, "alias"),
+				(Plain, ":"),
+				(Anchor, "*n"),
+			]
+		);
+	}
+
+	#[test]
+	fn highlight_block_scalar() {
+		assert_eq!(
+			spans("script: |\n  echo # not a comment\n\n  done\nnext: value\n"),
+			&[
+				(Key, "script"),
+				(Plain, ":"),
+				(Plain, "|"),
+				(String, "  echo # not a comment"),
+				(String, ""),
+				(String, "  done"),
+				(Key, "next"),
+				(Plain, ":"),
+				(String, "value"),
+			]
+		);
+	}
+
+	#[test]
+	fn highlight_flow_and_quotes() {
+		assert_eq!(
+			spans("- [a, 'it''s', {k: v}]\n- \"multi\n  line\"\n- a, b]\n"),
+			&[
+				(Plain, "-"),
+				(Plain, "["),
+				(String, "a"),
+				(Plain, ","),
+				(String, "'it''s'"),
+				(Plain, ","),
+				(Plain, "{"),
+				(Key, "k"),
+				(Plain, ":"),
+				(String, "v"),
+				(Plain, "}"),
+				(Plain, "]"),
+				(Plain, "-"),
+				(String, "\"multi"),
+				(String, "  line\""),
+				(Plain, "-"),
+				(String, "a, b]"),
+			]
+		);
+	}
+}
@@ -0,0 +1,272 @@
+//! Indexes of the documents in a stream, for repeated random access to a file
+//! too large to parse each time.
+//!
+//! An index file is text, like a checkpoint. Its first line names the format
+//! and the length of the input it describes, so that an index for another
+//! version of the file is caught. Each later line describes one document by
+//! its start and end byte offsets, the one-based line it starts on, and the
+//! start of the SHA-256 digest of its content, as in:
+//!
+//! ```text
+//! 120 245 9 3f9a2c0b71de4e18
+//! ```
+//!
+//! Offsets are counted in the input file, which must be UTF-8, so a document
+//! can be read back by seeking to it without decoding anything before it.
+
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::hash;
+use crate::transform::Documents;
+
+/// The start of the first line of every index file, with the version of the
+/// format.
+const MAGIC: &str = "yaml-split-index 1";
+
+/// The number of hex digits of each document's digest that an index keeps.
+const DIGEST_LEN: usize = 16;
+
+/// The locations of the documents in an input file.
+///
+/// An index is written by `yaml-split split --index-out` and read back with
+/// [`Index::load`], so that a program can read any of the documents without
+/// parsing the input again:
+///
+/// ```no_run
+/// use std::fs::File;
+/// use yaml_split::index::Index;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let index = Index::load("big.yaml.idx".as_ref())?;
+/// let mut file = File::open("big.yaml")?;
+/// if let Some(content) = index.read(&mut file, index.len() - 1)? {
+///     println!("the last document is {content:?}");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Index {
+	/// The length of the indexed input, in bytes.
+	input_len: u64,
+	entries: Vec<Entry>,
+}
+
+/// The location of a single document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Entry {
+	range: Range<u64>,
+	/// The one-based line on which the document starts.
+	line: u64,
+	digest: String,
+}
+
+impl Index {
+	/// Creates an empty index for an input of `input_len` bytes.
+	pub(crate) fn new(input_len: u64) -> Self {
+		Self {
+			input_len,
+			entries: vec![],
+		}
+	}
+
+	/// Loads the index at `path`.
+	pub fn load(path: &Path) -> io::Result<Self> {
+		let text = fs::read_to_string(path)?;
+		let invalid = |n: usize| {
+			io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!(
+					"{path} is not a yaml-split index: line {n} is invalid",
+					path = path.display()
+				),
+			)
+		};
+		let mut lines = text.lines();
+		let input_len = lines
+			.next()
+			.and_then(|line| {
+				line.strip_prefix(MAGIC)?
+					.strip_prefix(" input ")?
+					.parse()
+					.ok()
+			})
+			.ok_or_else(|| invalid(1))?;
+		let mut entries = vec![];
+		for (i, line) in lines.enumerate() {
+			let fields = line.split(' ').collect::<Vec<_>>();
+			let entry = match fields[..] {
+				[start, end, line, digest]
+					if digest.len() == DIGEST_LEN
+						&& digest.bytes().all(|b| b.is_ascii_hexdigit()) =>
+				{
+					start
+						.parse()
+						.ok()
+						.zip(end.parse().ok())
+						.zip(line.parse().ok())
+						.map(|((start, end), line)| Entry {
+							range: start..end,
+							line,
+							digest: digest.to_owned(),
+						})
+				}
+				_ => None,
+			};
+			entries.push(entry.ok_or_else(|| invalid(i + 2))?);
+		}
+		Ok(Self { input_len, entries })
+	}
+
+	/// Writes the index to `path`, replacing it atomically.
+	pub(crate) fn save(&self, path: &Path) -> io::Result<()> {
+		let mut text = format!("{MAGIC} input {}\n", self.input_len);
+		for entry in &self.entries {
+			text.push_str(&format!(
+				"{start} {end} {line} {digest}\n",
+				start = entry.range.start,
+				end = entry.range.end,
+				line = entry.line,
+				digest = entry.digest,
+			));
+		}
+		let mut name = path.file_name().unwrap_or_default().to_owned();
+		name.push(".tmp");
+		let tmp = path.with_file_name(name);
+		fs::write(&tmp, text)?;
+		fs::rename(&tmp, path)
+	}
+
+	/// Returns the number of documents in the index.
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	/// Returns whether the index has no documents.
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	/// Reads the document at the zero-based `index` from `file`, the input
+	/// that the index describes.
+	///
+	/// This fails if the file has changed length since it was indexed, or if
+	/// the document's content no longer matches its digest.
+	pub fn read(&self, file: &mut File, index: usize) -> io::Result<Option<String>> {
+		let len = file.metadata()?.len();
+		if len != self.input_len {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!(
+					"the index is for an input of {indexed} bytes, not {len}",
+					indexed = self.input_len
+				),
+			));
+		}
+		let Some(entry) = self.entries.get(index) else {
+			return Ok(None);
+		};
+		file.seek(SeekFrom::Start(entry.range.start))?;
+		let mut content = String::new();
+		Read::by_ref(file)
+			.take(entry.range.end - entry.range.start)
+			.read_to_string(&mut content)?;
+		if digest(&content) != entry.digest {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!(
+					"document {index} at line {line} no longer matches the index",
+					line = entry.line
+				),
+			));
+		}
+		Ok(Some(content))
+	}
+}
+
+/// Returns the part of the SHA-256 digest of `content` that an index keeps.
+fn digest(content: &str) -> String {
+	let mut digest = hash::Algorithm::Sha256.hex_digest(content.as_bytes());
+	digest.truncate(DIGEST_LEN);
+	digest
+}
+
+/// Records every document in `index` as it passes through.
+///
+/// Documents must be exactly as read from a UTF-8 input, so that their
+/// content can be found again at their offsets.
+pub(crate) fn record(docs: Documents, index: Rc<RefCell<Index>>) -> Documents {
+	Box::new(docs.map(move |doc| {
+		let doc = doc?;
+		let range = doc.original();
+		if doc.full_len().is_some() || range.end - range.start != doc.content().len() as u64 {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"--index-out requires documents exactly as they appear in a UTF-8 input",
+			));
+		}
+		let line = doc.position().map_or(1, |position| position.start.line + 1);
+		index.borrow_mut().entries.push(Entry {
+			range,
+			line,
+			digest: digest(doc.content()),
+		});
+		Ok(doc)
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::env;
+	use std::process;
+
+	use crate::chunker::Chunker;
+
+	#[test]
+	fn index_round_trip() {
+		const INPUT: &str = "a: 1\n---\nb: 2\n--- c\n";
+		let dir = env::temp_dir();
+		let input = dir.join(format!("yaml-split-index-input-{}", process::id()));
+		let path = dir.join(format!("yaml-split-index-{}", process::id()));
+		fs::write(&input, INPUT).unwrap();
+
+		let recorded = Rc::new(RefCell::new(Index::new(INPUT.len() as u64)));
+		let docs = record(
+			Box::new(Chunker::new(INPUT.as_bytes())),
+			Rc::clone(&recorded),
+		);
+		assert_eq!(docs.count(), 3);
+		recorded.borrow().save(&path).unwrap();
+		let text = fs::read_to_string(&path).unwrap();
+		assert!(text.starts_with("yaml-split-index 1 input 20\n0 5 1 "));
+		assert!(text.lines().nth(2).unwrap().starts_with("5 14 2 "));
+
+		let index = Index::load(&path).unwrap();
+		assert_eq!(index, *recorded.borrow());
+		assert_eq!(index.len(), 3);
+		let mut file = File::open(&input).unwrap();
+		assert_eq!(index.read(&mut file, 1).unwrap().unwrap(), "---\nb: 2\n");
+		assert_eq!(index.read(&mut file, 2).unwrap().unwrap(), "--- c\n");
+		assert_eq!(index.read(&mut file, 3).unwrap(), None);
+
+		fs::write(&input, INPUT.replace('b', "x")).unwrap();
+		let mut file = File::open(&input).unwrap();
+		assert!(index.read(&mut file, 1).is_err());
+		assert!(index.read(&mut file, 0).is_ok());
+		fs::write(&input, "a: 1\n").unwrap();
+		let mut file = File::open(&input).unwrap();
+		assert!(index.read(&mut file, 0).is_err());
+
+		fs::write(&path, "yaml-split-index 1 input 20\n0 5 1\n").unwrap();
+		assert!(Index::load(&path).is_err());
+		fs::remove_file(&path).unwrap();
+		fs::remove_file(&input).unwrap();
+	}
+}
@@ -0,0 +1,319 @@
+//! Enumeration of the input files in a directory tree, for `--recursive` and
+//! glob inputs like `manifests/**/*.yaml`.
+//!
+//! A glob matches a path one component at a time. Within a component, `*`
+//! matches any run of characters, `?` matches any one character, and a class
+//! like `[a-z]` or `[!.]` matches one character in or out of the class. A whole
+//! component of `**` matches any number of components, including none. A glob
+//! without a `/` matches a file's name wherever the file is, as in
+//! `.gitignore`.
+//!
+//! Files are found in sorted order, so that a tree splits the same way every
+//! time. Links to files are followed, but links to directories are not, so a
+//! walk never loops.
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+/// A parsed glob pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Glob {
+	text: String,
+	/// The components of the pattern, each as its characters.
+	segments: Vec<Vec<char>>,
+	/// Whether the pattern matches whole paths, rather than file names.
+	anchored: bool,
+}
+
+impl Glob {
+	/// Returns whether the path of a file, relative to the directory being
+	/// walked, matches the pattern.
+	pub(crate) fn matches(&self, path: &Path) -> bool {
+		let components = components(path);
+		match self.anchored {
+			true => match_components(&self.segments, &components, false),
+			false => components
+				.last()
+				.is_some_and(|name| match_segment(&self.segments[0], name)),
+		}
+	}
+
+	/// Returns whether a file under the directory at `path`, relative to the
+	/// directory being walked, could match the pattern.
+	fn could_contain(&self, path: &Path) -> bool {
+		!self.anchored || match_components(&self.segments, &components(path), true)
+	}
+}
+
+impl FromStr for Glob {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, String> {
+		let trimmed = text.trim_start_matches("./");
+		if trimmed.is_empty() || trimmed.ends_with('/') {
+			return Err(format!("invalid glob {text:?}, expected one like '*.yaml'"));
+		}
+		Ok(Self {
+			text: text.to_owned(),
+			segments: trimmed
+				.split('/')
+				.filter(|segment| !segment.is_empty())
+				.map(|segment| segment.chars().collect())
+				.collect(),
+			anchored: trimmed.contains('/'),
+		})
+	}
+}
+
+/// Returns whether `text` has any of the characters that make a glob.
+pub(crate) fn is_glob(text: &str) -> bool {
+	text.contains(['*', '?', '['])
+}
+
+/// Splits a glob input like `manifests/**/*.yaml` into the directory to walk,
+/// which is the part before the first component with a wildcard, and the glob
+/// for paths relative to it, which matches whole paths even without a `/`.
+pub(crate) fn split_glob(text: &str) -> Result<(PathBuf, Glob), String> {
+	let mut root = PathBuf::new();
+	let mut rest = text;
+	while let Some((component, after)) = rest.split_once('/') {
+		if is_glob(component) {
+			break;
+		}
+		root.push(match component {
+			"" => "/",
+			component => component,
+		});
+		rest = after;
+	}
+	let mut glob = rest.parse::<Glob>()?;
+	glob.anchored = true;
+	Ok((root, glob))
+}
+
+/// Returns the path of every file under `root`, relative to it, that matches
+/// `pattern` if one is given, matches any of `include` if there are any, and
+/// matches none of `exclude`.
+///
+/// A directory that matches one of `exclude` is skipped entirely, as is one
+/// that nothing under could match `pattern`.
+pub(crate) fn find(
+	root: &Path,
+	pattern: Option<&Glob>,
+	include: &[Glob],
+	exclude: &[Glob],
+) -> io::Result<Vec<PathBuf>> {
+	let mut files = vec![];
+	let mut pending = vec![PathBuf::new()];
+	while let Some(dir) = pending.pop() {
+		let path = match dir.as_os_str().is_empty() {
+			true => root.to_owned(),
+			false => root.join(&dir),
+		};
+		let path = match path.as_os_str().is_empty() {
+			true => PathBuf::from("."),
+			false => path,
+		};
+		let mut entries = fs::read_dir(&path)
+			.and_then(|entries| entries.collect::<io::Result<Vec<_>>>())
+			.map_err(|err| {
+				io::Error::new(err.kind(), format!("{path}: {err}", path = path.display()))
+			})?;
+		entries.sort_by_key(|entry| entry.file_name());
+		let mut subdirs = vec![];
+		for entry in entries {
+			let relative = dir.join(entry.file_name());
+			if exclude.iter().any(|glob| glob.matches(&relative)) {
+				continue;
+			}
+			let file_type = entry.file_type()?;
+			let is_file = match file_type.is_symlink() {
+				true => fs::metadata(entry.path()).is_ok_and(|meta| meta.is_file()),
+				false => file_type.is_file(),
+			};
+			if file_type.is_dir() {
+				if pattern.map_or(true, |glob| glob.could_contain(&relative)) {
+					subdirs.push(relative);
+				}
+			} else if is_file
+				&& pattern.map_or(true, |glob| glob.matches(&relative))
+				&& (include.is_empty() || include.iter().any(|glob| glob.matches(&relative)))
+			{
+				files.push(relative);
+			}
+		}
+		// Walk the subdirectories in order, after the files beside them.
+		pending.extend(subdirs.into_iter().rev());
+	}
+	Ok(files)
+}
+
+fn components(path: &Path) -> Vec<String> {
+	path.components()
+		.filter_map(|component| match component {
+			Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+			_ => None,
+		})
+		.collect()
+}
+
+/// Returns whether `components` match `segments`, or with `partial`, whether
+/// they could be the start of a path that does.
+fn match_components(segments: &[Vec<char>], components: &[String], partial: bool) -> bool {
+	let Some((segment, rest)) = segments.split_first() else {
+		return components.is_empty();
+	};
+	if segment.as_slice() == ['*', '*'] {
+		return (0..=components.len())
+			.any(|skip| match_components(rest, &components[skip..], partial));
+	}
+	match components.split_first() {
+		None => partial,
+		Some((component, others)) => {
+			match_segment(segment, component) && match_components(rest, others, partial)
+		}
+	}
+}
+
+/// Returns whether the single path component `name` matches `pattern`.
+fn match_segment(pattern: &[char], name: &str) -> bool {
+	fn matches(pattern: &[char], name: &[char]) -> bool {
+		let Some((&first, rest)) = pattern.split_first() else {
+			return name.is_empty();
+		};
+		match first {
+			'*' => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+			'?' => !name.is_empty() && matches(rest, &name[1..]),
+			'[' => match (class(rest), name.split_first()) {
+				(Some((in_class, rest)), Some((&c, others))) => {
+					in_class(c) && matches(rest, others)
+				}
+				(Some(_), None) => false,
+				// An unclosed class is just a bracket.
+				(None, _) => name.first() == Some(&'[') && matches(rest, &name[1..]),
+			},
+			c => name.first() == Some(&c) && matches(rest, &name[1..]),
+		}
+	}
+	matches(pattern, &name.chars().collect::<Vec<_>>())
+}
+
+/// Parses the class that starts a pattern, after its opening `[`, returning a
+/// test for the characters in it and the rest of the pattern.
+fn class(pattern: &[char]) -> Option<(impl Fn(char) -> bool + '_, &[char])> {
+	let (negated, body) = match pattern.first() {
+		Some('!' | '^') => (true, &pattern[1..]),
+		_ => (false, pattern),
+	};
+	// A `]` right after the opening bracket is part of the class.
+	let end = body.iter().skip(1).position(|&c| c == ']')? + 1;
+	let (members, rest) = (&body[..end], &body[end + 1..]);
+	let test = move |c: char| {
+		let mut i = 0;
+		let mut found = false;
+		while i < members.len() {
+			if i + 2 < members.len() && members[i + 1] == '-' {
+				found |= (members[i]..=members[i + 2]).contains(&c);
+				i += 3;
+			} else {
+				found |= members[i] == c;
+				i += 1;
+			}
+		}
+		found != negated
+	};
+	Some((test, rest))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn glob_matches() {
+		let matches =
+			|glob: &str, path: &str| glob.parse::<Glob>().unwrap().matches(Path::new(path));
+		assert!(matches("*.yaml", "a.yaml"));
+		assert!(matches("*.yaml", "deep/down/a.yaml"));
+		assert!(!matches("*.yaml", "a.yml"));
+		assert!(matches("a/*.yaml", "a/b.yaml"));
+		assert!(!matches("a/*.yaml", "a/b/c.yaml"));
+		assert!(!matches("a/*.yaml", "x/a/b.yaml"));
+		assert!(matches("**/*.yaml", "b.yaml"));
+		assert!(matches("a/**/c.yaml", "a/c.yaml"));
+		assert!(matches("a/**/c.yaml", "a/x/y/c.yaml"));
+		assert!(matches("./a/?.y*ml", "a/b.yml"));
+		assert!(matches("[a-c]x[!.]", "bx1"));
+		assert!(!matches("[a-c]x[!.]", "dx1"));
+		assert!(!matches("[a-c]x[!.]", "ax."));
+		assert!(matches("[]]", "]"));
+		assert!(matches("a[b", "a[b"));
+		assert!(!matches("deploy", "deployment"));
+		for invalid in ["", "a/", "./"] {
+			assert!(invalid.parse::<Glob>().is_err(), "{invalid:?}");
+		}
+	}
+
+	#[test]
+	fn split_globs() {
+		let split = |text: &str| {
+			let (root, glob) = split_glob(text).unwrap();
+			(root, glob.text)
+		};
+		assert_eq!(
+			split("manifests/**/*.yaml"),
+			(PathBuf::from("manifests"), "**/*.yaml".to_owned())
+		);
+		assert_eq!(split("*.yaml"), (PathBuf::new(), "*.yaml".to_owned()));
+		assert_eq!(
+			split("/a/b/c*/d"),
+			(PathBuf::from("/a/b"), "c*/d".to_owned())
+		);
+		assert!(is_glob("a/[b]") && !is_glob("a/b.yaml"));
+	}
+
+	#[test]
+	fn find_files() {
+		let root = std::env::temp_dir().join(format!("yaml-split-inputs-{}", std::process::id()));
+		for file in [
+			"b.yaml",
+			"a/c.yaml",
+			"a/d.yml",
+			"a/e.txt",
+			"vendor/f.yaml",
+			"g.yaml",
+		] {
+			let path = root.join(file);
+			fs::create_dir_all(path.parent().unwrap()).unwrap();
+			fs::write(path, "x\n").unwrap();
+		}
+		let globs = |texts: &[&str]| {
+			texts
+				.iter()
+				.map(|text| text.parse().unwrap())
+				.collect::<Vec<Glob>>()
+		};
+		let found = |pattern: Option<&str>, include: &[&str], exclude: &[&str]| {
+			let pattern = pattern.map(|text| split_glob(text).unwrap().1);
+			find(&root, pattern.as_ref(), &globs(include), &globs(exclude))
+				.unwrap()
+				.iter()
+				.map(|path| path.to_str().unwrap().to_owned())
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(
+			found(None, &["*.yaml", "*.yml"], &["vendor"]),
+			["b.yaml", "g.yaml", "a/c.yaml", "a/d.yml"]
+		);
+		assert_eq!(found(Some("*.yaml"), &[], &[]), ["b.yaml", "g.yaml"]);
+		assert_eq!(
+			found(Some("**/*.yaml"), &[], &["g.yaml"]),
+			["b.yaml", "a/c.yaml", "vendor/f.yaml"]
+		);
+		assert_eq!(found(Some("*/*"), &["*.txt"], &[]), ["a/e.txt"]);
+		fs::remove_dir_all(&root).unwrap();
+		assert!(find(&root, None, &[], &[]).is_err());
+	}
+}
@@ -0,0 +1,139 @@
+//! Graceful handling of Ctrl-C and time limits during a split.
+//!
+//! The first SIGINT doesn't terminate the program. It only sets a flag that
+//! stops the input between documents, so that everything already read is still
+//! written in full, and progress records like checkpoints stay consistent with
+//! the output. The handler is installed with `SA_RESETHAND`, so a second SIGINT
+//! terminates the program as usual, in case the input is blocked on a read
+//! that may never finish.
+//!
+//! On non-Unix systems, installing the handler does nothing, and Ctrl-C keeps
+//! its default behavior.
+//!
+//! A time limit set with [`set_timeout`] stops the input the same way once it
+//! passes, on every system. Since a blocked read can't see the request, the
+//! program exits outright if it hasn't stopped within a grace period after the
+//! limit.
+
+use std::io;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::transform::Documents;
+
+/// The conventional exit code of a program that stopped on SIGINT.
+pub(crate) const EXIT_CODE: u8 = 130;
+
+/// The conventional exit code of a program that ran out of time, as used by
+/// timeout(1).
+pub(crate) const TIMEOUT_EXIT_CODE: u8 = 124;
+
+/// How long after a time limit the program may take to stop on its own.
+const TIMEOUT_GRACE: Duration = Duration::from_secs(5);
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static TIMED_OUT: AtomicBool = AtomicBool::new(false);
+
+/// Replaces the default SIGINT behavior with a request to stop.
+pub(crate) fn install() {
+	#[cfg(unix)]
+	// SAFETY: These are FFI calls to libc, which we assume is implemented
+	// correctly. The handler only stores to an atomic, which is
+	// async-signal-safe.
+	unsafe {
+		let mut action: libc::sigaction = std::mem::zeroed();
+		action.sa_sigaction = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+		action.sa_flags = libc::SA_RESETHAND | libc::SA_RESTART;
+		libc::sigemptyset(&mut action.sa_mask);
+		libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+	}
+}
+
+#[cfg(unix)]
+extern "C" fn handle(_: libc::c_int) {
+	INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+/// Requests a stop once `timeout` has passed.
+///
+/// This fails on WASI, which has no threads to wait for the timeout on.
+pub(crate) fn set_timeout(timeout: Duration) -> io::Result<()> {
+	if cfg!(target_os = "wasi") {
+		return Err(io::Error::new(
+			io::ErrorKind::Unsupported,
+			"--timeout is not supported on WASI",
+		));
+	}
+	thread::spawn(move || {
+		thread::sleep(timeout);
+		TIMED_OUT.store(true, Ordering::Relaxed);
+		thread::sleep(TIMEOUT_GRACE);
+		eprintln!(
+			"Error: timed out after {secs} s without finishing the current document",
+			secs = (timeout + TIMEOUT_GRACE).as_secs(),
+		);
+		process::exit(TIMEOUT_EXIT_CODE.into());
+	});
+	Ok(())
+}
+
+/// Parses a duration like `90s`, `10m`, or `2h`, or a bare number of seconds.
+pub(crate) fn parse_duration(text: &str) -> Result<Duration, String> {
+	let (digits, unit) = match text.as_bytes().last() {
+		Some(b's') => (&text[..text.len() - 1], 1),
+		Some(b'm') => (&text[..text.len() - 1], 60),
+		Some(b'h') => (&text[..text.len() - 1], 60 * 60),
+		_ => (text, 1),
+	};
+	let count = digits
+		.parse::<u64>()
+		.map_err(|_| format!("invalid duration {text:?}"))?;
+	count
+		.checked_mul(unit)
+		.map(Duration::from_secs)
+		.ok_or_else(|| format!("duration {text:?} is too long"))
+}
+
+/// Returns whether the user has asked the program to stop, or its time limit
+/// has passed.
+pub(crate) fn requested() -> bool {
+	INTERRUPTED.load(Ordering::Relaxed) || TIMED_OUT.load(Ordering::Relaxed)
+}
+
+/// Stops producing documents once the user asks the program to stop.
+pub(crate) fn stop_on_request(docs: Documents) -> Documents {
+	Box::new(docs.take_while(|_| !requested()))
+}
+
+/// Returns the error that reports an interrupted or timed out run, after
+/// `count` documents were written in full.
+pub(crate) fn error(count: usize) -> io::Error {
+	match TIMED_OUT.load(Ordering::Relaxed) {
+		true => io::Error::new(
+			io::ErrorKind::TimedOut,
+			format!("timed out after writing {count} documents"),
+		),
+		false => io::Error::new(
+			io::ErrorKind::Interrupted,
+			format!("interrupted after writing {count} documents"),
+		),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_durations() {
+		assert_eq!(parse_duration("45"), Ok(Duration::from_secs(45)));
+		assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+		assert_eq!(parse_duration("10m"), Ok(Duration::from_secs(600)));
+		assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+		for text in ["", "m", "-1s", "1.5m", "1d"] {
+			assert!(parse_duration(text).is_err(), "{text:?} should be invalid");
+		}
+	}
+}
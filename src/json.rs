@@ -0,0 +1,868 @@
+//! Support for reading JSON values as YAML documents, and for writing YAML
+//! nodes as JSON.
+//!
+//! JSON values are nearly YAML documents already, so a JSON input mostly needs
+//! to be split into values. Since concatenated JSON values are not a valid YAML
+//! stream, libyaml can't find the boundaries between them, and this module
+//! scans for them instead.
+//!
+//! The few parts of JSON that libyaml reads differently are rewritten as the
+//! values are split. A UTF-16 surrogate pair escape like `\ud83d\ude00` becomes
+//! the single escape `\U0001F600`, and a key that's more than 1024 bytes from
+//! its `:` or on another line is marked with `?` as an explicit key, since
+//! libyaml only looks that far for the end of an implicit one.
+//!
+//! The reverse is not true, so writing JSON requires a [`Node`] tree. Scalars
+//! are converted according to the YAML 1.2 core schema, and anything JSON
+//! can't represent, like a mapping key that isn't a scalar, is an error.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Read};
+use std::ops::Range;
+
+use crate::chunker::{Document, DocumentKind, LimitExceeded};
+use crate::libyaml::{Mark, YAML_PLAIN_SCALAR_STYLE};
+use crate::node::{self, Content, Node, CORE_TAG_PREFIX};
+
+/// Splits JSON text into one document per value, with each document's content
+/// being the text of the value exactly as it appears in the input.
+///
+/// The text may contain any number of concatenated values, optionally
+/// separated by whitespace. If it contains a single top-level array, each item
+/// of the array becomes a document instead.
+pub(crate) fn documents(text: &str) -> io::Result<Vec<Document>> {
+	let mut scanner = Scanner::new(text);
+	if text.starts_with('\u{FEFF}') {
+		scanner.pos = '\u{FEFF}'.len_utf8();
+	}
+	let mut values = vec![];
+	loop {
+		scanner.skip_whitespace();
+		if scanner.pos == text.len() {
+			break;
+		}
+		let start = scanner.pos;
+		scanner.value()?;
+		values.push(start..scanner.pos);
+	}
+
+	if let [range] = &values[..] {
+		if text[range.clone()].starts_with('[') {
+			scanner.pos = range.start;
+			scanner.edits.clear();
+			values = scanner.items()?;
+		}
+	}
+	Ok(values
+		.into_iter()
+		.enumerate()
+		.map(|(index, range)| document(text, range, 0, &scanner.edits).with_index(index))
+		.collect())
+}
+
+/// Returns an iterator over the documents of newline-delimited JSON, where
+/// each line holds exactly one value.
+///
+/// Unlike [`documents`], this reads the input one line at a time. Blank lines
+/// are skipped. Since each value is on a known line, the documents also have
+/// positions.
+///
+/// If `limit` is given, no line is read past that many bytes, not counting its
+/// line break, and a longer line fails with a [`LimitExceeded`] error. With
+/// `truncate`, a longer line instead becomes a document cut to the limit, as
+/// with [`Chunker::truncate_long_documents`](crate::chunker::Chunker), and the
+/// rest of the line is skipped.
+pub(crate) fn lines<R>(
+	mut reader: R,
+	limit: Option<usize>,
+	truncate: bool,
+) -> impl Iterator<Item = io::Result<Document>>
+where
+	R: BufRead,
+{
+	let mut bytes = vec![];
+	let mut offset = 0;
+	let mut number = 0;
+	let mut count = 0;
+	std::iter::from_fn(move || loop {
+		bytes.clear();
+		let start = offset;
+		// Allow for a CRLF line break after a line of the longest length.
+		let cap = limit.map_or(u64::MAX, |limit| limit as u64 + 2);
+		match Read::by_ref(&mut reader)
+			.take(cap)
+			.read_until(b'\n', &mut bytes)
+		{
+			Ok(0) => return None,
+			Ok(len) => offset += len,
+			Err(err) => return Some(Err(err)),
+		}
+		number += 1;
+		if let Some(limit) = limit.filter(|&limit| text_len(&bytes) > limit) {
+			if !truncate {
+				let err = LimitExceeded {
+					index: count + 1,
+					offset: start as u64,
+					limit,
+				};
+				return Some(Err(io::Error::new(io::ErrorKind::InvalidData, err)));
+			}
+			let doc = truncated_line(&mut reader, &bytes, limit, number, start);
+			if let Ok((_, len)) = &doc {
+				offset += len;
+				count += 1;
+			}
+			return Some(doc.map(|(doc, _)| doc.with_index(count - 1)));
+		}
+		let line = match std::str::from_utf8(&bytes) {
+			Ok(line) => line,
+			Err(err) => {
+				let err = format!("line {number}: {err}");
+				return Some(Err(io::Error::new(io::ErrorKind::InvalidData, err)));
+			}
+		};
+		let text = match number {
+			1 => line.trim_start_matches('\u{FEFF}'),
+			_ => line,
+		};
+		let skipped = line.len() - text.len();
+		if text.trim().is_empty() {
+			continue;
+		}
+		let mut scanner = Scanner::new(text);
+		scanner.skip_whitespace();
+		let value_start = scanner.pos;
+		let result = scanner.value().and_then(|()| {
+			let value_end = scanner.pos;
+			scanner.skip_whitespace();
+			match scanner.pos == text.len() {
+				true => Ok(value_start..value_end),
+				false => Err(scanner.error("expected the end of the line")),
+			}
+		});
+		return Some(match result {
+			Ok(range) => {
+				let mark = |pos: usize| Mark {
+					line: number - 1,
+					column: text[..pos].chars().count() as u64,
+				};
+				let position = mark(range.start)..mark(range.end);
+				count += 1;
+				Ok(document(text, range, start + skipped, &scanner.edits)
+					.with_position(position)
+					.with_index(count - 1))
+			}
+			Err(err) => Err(io::Error::new(err.kind(), format!("line {number}: {err}"))),
+		});
+	})
+}
+
+/// Returns a document for the first `limit` bytes of a line that's longer, and
+/// skips the rest of the line in `reader`, returning the number of bytes it
+/// skipped.
+///
+/// The line is number `number` of the input, starting at byte `start`, and
+/// `bytes` holds what was read of it at first.
+fn truncated_line<R: BufRead>(
+	reader: &mut R,
+	bytes: &[u8],
+	limit: usize,
+	number: u64,
+	start: usize,
+) -> io::Result<(Document, usize)> {
+	let mut skipped = 0;
+	let mut rest = 0;
+	let mut line_ended = bytes.ends_with(b"\n");
+	while !line_ended {
+		let buf = reader.fill_buf()?;
+		if buf.is_empty() {
+			break;
+		}
+		let len = match buf.iter().position(|&b| b == b'\n') {
+			Some(i) => {
+				line_ended = true;
+				rest += i;
+				i + 1
+			}
+			None => {
+				rest += buf.len();
+				buf.len()
+			}
+		};
+		reader.consume(len);
+		skipped += len;
+	}
+	let mut len = limit;
+	while bytes.get(len).is_some_and(|b| b & 0xC0 == 0x80) {
+		len -= 1;
+	}
+	let text = std::str::from_utf8(&bytes[..len]).map_err(|err| {
+		io::Error::new(io::ErrorKind::InvalidData, format!("line {number}: {err}"))
+	})?;
+	let value = text.trim_start_matches('\u{FEFF}').trim_start();
+	let value_start = start + (text.len() - value.len());
+	let kind = match value.as_bytes().first() {
+		Some(b'{') => DocumentKind::Mapping,
+		Some(b'[') => DocumentKind::Sequence,
+		_ => DocumentKind::Scalar,
+	};
+	let full_len = text_len(bytes) + rest;
+	let mark = Mark {
+		line: number - 1,
+		column: 0,
+	};
+	let source = value_start as u64..(start + full_len) as u64;
+	let mut doc = Document::new(value.to_owned(), kind, source).with_position(mark..mark);
+	doc.truncate(limit, (start + full_len - value_start) as u64);
+	Ok((doc, skipped))
+}
+
+/// Returns the length of a line without its line break.
+fn text_len(line: &[u8]) -> usize {
+	let line = line.strip_suffix(b"\n").unwrap_or(line);
+	line.strip_suffix(b"\r").unwrap_or(line).len()
+}
+
+/// Returns a document for the value at `range` of `text`, which itself begins
+/// at byte `base` of the input, with the `edits` that fall in the range made to
+/// its content.
+fn document(text: &str, range: Range<usize>, base: usize, edits: &[Edit]) -> Document {
+	let first = edits.partition_point(|edit| edit.range.start < range.start);
+	let mut content = String::with_capacity(range.len() + 1);
+	let mut pos = range.start;
+	for edit in edits[first..]
+		.iter()
+		.take_while(|edit| edit.range.end <= range.end)
+	{
+		content.push_str(&text[pos..edit.range.start]);
+		content.push_str(&edit.text);
+		pos = edit.range.end;
+	}
+	content.push_str(&text[pos..range.end]);
+	content.push('\n');
+	let kind = match content.as_bytes()[0] {
+		b'{' => DocumentKind::Mapping,
+		b'[' => DocumentKind::Sequence,
+		_ => DocumentKind::Scalar,
+	};
+	let source = (base + range.start) as u64..(base + range.end) as u64;
+	Document::new(content, kind, source)
+}
+
+/// Converts a node to compact JSON text.
+///
+/// Aliases are replaced with the JSON of the nodes they refer to. Aliases to
+/// anchors outside of the node are an error, and should be inlined first.
+pub(crate) fn to_json(node: &Node) -> io::Result<String> {
+	let mut out = String::new();
+	Writer::default().node(&mut out, node)?;
+	Ok(out)
+}
+
+/// Tracks the JSON written for each anchored node, to repeat for its aliases.
+#[derive(Default)]
+struct Writer {
+	anchors: HashMap<String, String>,
+}
+
+impl Writer {
+	fn node(&mut self, out: &mut String, node: &Node) -> io::Result<()> {
+		let start = out.len();
+		match &node.content {
+			Content::Scalar(value, style) => {
+				let plain = *style == YAML_PLAIN_SCALAR_STYLE;
+				out.push_str(&scalar(value, plain, node.tag.as_deref())?);
+			}
+			Content::Alias(name) => out.push_str(self.alias(name)?),
+			Content::Sequence(items, _) => {
+				out.push('[');
+				for (i, item) in items.iter().enumerate() {
+					if i > 0 {
+						out.push(',');
+					}
+					self.node(out, item)?;
+				}
+				out.push(']');
+			}
+			Content::Mapping(entries, _) => {
+				out.push('{');
+				for (i, (key, value)) in entries.iter().enumerate() {
+					if i > 0 {
+						out.push(',');
+					}
+					self.key(out, key)?;
+					out.push(':');
+					self.node(out, value)?;
+				}
+				out.push('}');
+			}
+		}
+		if let Some(anchor) = &node.anchor {
+			self.anchors.insert(anchor.clone(), out[start..].to_owned());
+		}
+		Ok(())
+	}
+
+	/// Writes a mapping key, which JSON requires to be a string. Keys that
+	/// resolve to other scalar types are written as strings of their JSON.
+	fn key(&mut self, out: &mut String, key: &Node) -> io::Result<()> {
+		let mut json = String::new();
+		self.node(&mut json, key)?;
+		if json.starts_with(['{', '[']) {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"cannot convert a mapping with a non-scalar key to JSON",
+			));
+		}
+		match json.starts_with('"') {
+			true => out.push_str(&json),
+			false => write_string(out, &json),
+		}
+		Ok(())
+	}
+
+	fn alias(&self, name: &str) -> io::Result<&str> {
+		self.anchors.get(name).map(String::as_str).ok_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("alias *{name} refers to an undefined anchor"),
+			)
+		})
+	}
+}
+
+/// Converts a scalar to JSON based on its tag, or on its value if it's an
+/// untagged plain scalar.
+///
+/// Scalars with tags outside of the core schema are treated like untagged
+/// scalars of the same style, and those with non-scalar core tags like
+/// `!!binary` become strings.
+fn scalar(value: &str, plain: bool, tag: Option<&str>) -> io::Result<String> {
+	let resolved = match (plain, tag) {
+		(_, Some("!")) | (false, None) => "str",
+		(_, Some(tag)) if tag.starts_with(CORE_TAG_PREFIX) => match &tag[CORE_TAG_PREFIX.len()..] {
+			core @ ("null" | "bool" | "int" | "float") => core,
+			_ => "str",
+		},
+		(true, _) => node::core_scalar_type(value),
+		(false, _) => "str",
+	};
+	let actual = node::core_scalar_type(value);
+	let matches = actual == resolved || (resolved == "float" && actual == "int");
+	if resolved != "str" && !matches {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("cannot convert {value:?} to a JSON {resolved}"),
+		));
+	}
+	Ok(match resolved {
+		"null" => "null".to_owned(),
+		"bool" => value.to_ascii_lowercase(),
+		"int" | "float" => number(value)?,
+		_ => {
+			let mut out = String::new();
+			write_string(&mut out, value);
+			out
+		}
+	})
+}
+
+/// Rewrites a core schema number in JSON syntax.
+fn number(value: &str) -> io::Result<String> {
+	let (negative, unsigned) = match value.strip_prefix('-') {
+		Some(unsigned) => (true, unsigned),
+		None => (false, value.strip_prefix('+').unwrap_or(value)),
+	};
+	let radix = match unsigned.get(..2) {
+		Some("0x") => Some(16),
+		Some("0o") => Some(8),
+		_ => None,
+	};
+	if let Some(radix) = radix {
+		return match u128::from_str_radix(&unsigned[2..], radix) {
+			Ok(n) => Ok(n.to_string()),
+			Err(_) => Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("integer {value} is too large to convert to JSON"),
+			)),
+		};
+	}
+	if unsigned.starts_with('.') && !unsigned[1..].starts_with(|c: char| c.is_ascii_digit()) {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("cannot represent {value} in JSON"),
+		));
+	}
+
+	let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+		Some(i) => unsigned.split_at(i),
+		None => (unsigned, ""),
+	};
+	let (whole, fraction) = match mantissa.split_once('.') {
+		Some((whole, fraction)) => (whole, Some(fraction)),
+		None => (mantissa, None),
+	};
+	let whole = match whole.trim_start_matches('0') {
+		"" => "0",
+		whole => whole,
+	};
+	let mut out = String::new();
+	if negative {
+		out.push('-');
+	}
+	out.push_str(whole);
+	match fraction {
+		Some("") => out.push_str(".0"),
+		Some(fraction) => {
+			out.push('.');
+			out.push_str(fraction);
+		}
+		None => {}
+	}
+	out.push_str(exponent);
+	Ok(out)
+}
+
+/// Writes `s` as a JSON string, with quotes and escapes.
+pub(crate) fn write_string(out: &mut String, s: &str) {
+	out.push('"');
+	for c in s.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\n' => out.push_str("\\n"),
+			'\r' => out.push_str("\\r"),
+			'\t' => out.push_str("\\t"),
+			'\u{08}' => out.push_str("\\b"),
+			'\u{0C}' => out.push_str("\\f"),
+			c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+}
+
+/// The longest that libyaml lets an implicit key be, counting from its start
+/// to the `:` after it.
+const MAX_IMPLICIT_KEY_LEN: usize = 1024;
+
+/// A change to the text of a JSON value that makes libyaml read it the same
+/// as a JSON parser would.
+struct Edit {
+	/// The range of the text to replace, in the text that was scanned.
+	range: Range<usize>,
+	text: String,
+}
+
+/// A validating scanner that finds the boundaries of JSON values, as defined
+/// by [RFC 8259](https://www.rfc-editor.org/rfc/rfc8259).
+struct Scanner<'a> {
+	text: &'a [u8],
+	pos: usize,
+	/// The number of arrays and objects that the scan is inside.
+	depth: usize,
+	/// The edits that the values scanned so far need as YAML, in order.
+	edits: Vec<Edit>,
+}
+
+impl<'a> Scanner<'a> {
+	fn new(text: &'a str) -> Self {
+		Self {
+			text: text.as_bytes(),
+			pos: 0,
+			depth: 0,
+			edits: vec![],
+		}
+	}
+
+	fn peek(&self) -> Option<u8> {
+		self.text.get(self.pos).copied()
+	}
+
+	fn skip_whitespace(&mut self) {
+		while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+			self.pos += 1;
+		}
+	}
+
+	/// Consumes `b` if it is the next byte, and returns whether it was.
+	fn eat(&mut self, b: u8) -> bool {
+		let found = self.peek() == Some(b);
+		if found {
+			self.pos += 1;
+		}
+		found
+	}
+
+	fn expect(&mut self, b: u8) -> io::Result<()> {
+		match self.eat(b) {
+			true => Ok(()),
+			false => Err(self.error(&format!("expected '{}'", char::from(b)))),
+		}
+	}
+
+	fn value(&mut self) -> io::Result<()> {
+		match self.peek() {
+			Some(b'{' | b'[') if self.depth >= node::MAX_DEPTH => Err(self.error(&format!(
+				"arrays and objects nest more than {} levels deep",
+				node::MAX_DEPTH
+			))),
+			Some(b'{') => self.nested(Self::object),
+			Some(b'[') => self.nested(|scanner| scanner.items().map(|_| ())),
+			Some(b'"') => self.string(),
+			Some(b'-' | b'0'..=b'9') => self.number(),
+			Some(b't') => self.literal("true"),
+			Some(b'f') => self.literal("false"),
+			Some(b'n') => self.literal("null"),
+			_ => Err(self.error("expected a value")),
+		}
+	}
+
+	/// Scans an array or object with `scan`, one level deeper.
+	fn nested(&mut self, scan: impl FnOnce(&mut Self) -> io::Result<()>) -> io::Result<()> {
+		self.depth += 1;
+		let result = scan(self);
+		self.depth -= 1;
+		result
+	}
+
+	fn object(&mut self) -> io::Result<()> {
+		self.expect(b'{')?;
+		self.skip_whitespace();
+		if self.eat(b'}') {
+			return Ok(());
+		}
+		loop {
+			self.skip_whitespace();
+			if self.peek() != Some(b'"') {
+				return Err(self.error("expected a string key"));
+			}
+			let key_start = self.pos;
+			let edit = self.edits.len();
+			self.string()?;
+			self.skip_whitespace();
+			let key = &self.text[key_start..self.pos];
+			if key.len() > MAX_IMPLICIT_KEY_LEN || key.contains(&b'\n') || key.contains(&b'\r') {
+				let range = key_start..key_start;
+				let text = "? ".to_owned();
+				self.edits.insert(edit, Edit { range, text });
+			}
+			self.expect(b':')?;
+			self.skip_whitespace();
+			self.value()?;
+			self.skip_whitespace();
+			if self.eat(b'}') {
+				return Ok(());
+			}
+			self.expect(b',')?;
+		}
+	}
+
+	/// Scans an array, returning the range of each of its items.
+	fn items(&mut self) -> io::Result<Vec<Range<usize>>> {
+		let mut items = vec![];
+		self.expect(b'[')?;
+		self.skip_whitespace();
+		if self.eat(b']') {
+			return Ok(items);
+		}
+		loop {
+			self.skip_whitespace();
+			let start = self.pos;
+			self.value()?;
+			items.push(start..self.pos);
+			self.skip_whitespace();
+			if self.eat(b']') {
+				return Ok(items);
+			}
+			self.expect(b',')?;
+		}
+	}
+
+	fn string(&mut self) -> io::Result<()> {
+		self.expect(b'"')?;
+		loop {
+			match self.peek() {
+				Some(b'"') => {
+					self.pos += 1;
+					return Ok(());
+				}
+				Some(b'\\') => {
+					self.pos += 1;
+					match self.peek() {
+						Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => {
+							self.pos += 1
+						}
+						Some(b'u') => self.unicode_escape()?,
+						_ => return Err(self.error("invalid escape sequence")),
+					}
+				}
+				Some(0x00..=0x1F) => return Err(self.error("unescaped control character")),
+				Some(_) => self.pos += 1,
+				None => return Err(self.error("unterminated string")),
+			}
+		}
+	}
+
+	/// Scans a `\\u` escape, just after its backslash.
+	///
+	/// libyaml rejects escapes of UTF-16 surrogates, so a pair of them is
+	/// rewritten as the escape of the character they encode, and a surrogate
+	/// without its other half is an error.
+	fn unicode_escape(&mut self) -> io::Result<()> {
+		let start = self.pos - 1;
+		let high = self.code_unit()?;
+		if !(0xD800..0xE000).contains(&high) {
+			return Ok(());
+		}
+		let low = match self.text[self.pos..].starts_with(b"\\u") && high < 0xDC00 {
+			true => {
+				self.pos += 1;
+				self.code_unit()?
+			}
+			false => 0,
+		};
+		if !(0xDC00..0xE000).contains(&low) {
+			self.pos = start;
+			return Err(self.error("unpaired UTF-16 surrogate escape"));
+		}
+		let c = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+		let range = start..self.pos;
+		let text = format!("\\U{c:08X}");
+		self.edits.push(Edit { range, text });
+		Ok(())
+	}
+
+	/// Scans the `u` and four hex digits of a `\\u` escape, returning the
+	/// UTF-16 code unit that they give.
+	fn code_unit(&mut self) -> io::Result<u32> {
+		self.expect(b'u')?;
+		let mut unit = 0;
+		for _ in 0..4 {
+			let digit = self.peek().and_then(|b| char::from(b).to_digit(16));
+			let Some(digit) = digit else {
+				return Err(self.error("expected a hex digit"));
+			};
+			unit = unit * 16 + digit;
+			self.pos += 1;
+		}
+		Ok(unit)
+	}
+
+	fn number(&mut self) -> io::Result<()> {
+		self.eat(b'-');
+		if !self.eat(b'0') {
+			self.digits()?;
+		}
+		if self.eat(b'.') {
+			self.digits()?;
+		}
+		if self.eat(b'e') || self.eat(b'E') {
+			let _ = self.eat(b'+') || self.eat(b'-');
+			self.digits()?;
+		}
+		self.end_of_token()
+	}
+
+	fn digits(&mut self) -> io::Result<()> {
+		let start = self.pos;
+		while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+			self.pos += 1;
+		}
+		match self.pos > start {
+			true => Ok(()),
+			false => Err(self.error("expected a digit")),
+		}
+	}
+
+	fn literal(&mut self, word: &str) -> io::Result<()> {
+		match self.text[self.pos..].starts_with(word.as_bytes()) {
+			true => {
+				self.pos += word.len();
+				self.end_of_token()
+			}
+			false => Err(self.error("expected a value")),
+		}
+	}
+
+	/// Checks that a number or literal isn't immediately followed by more
+	/// characters that would otherwise start a second value, as in `01`.
+	fn end_of_token(&self) -> io::Result<()> {
+		match self.peek() {
+			Some(b) if b.is_ascii_alphanumeric() || b == b'-' || b == b'.' => {
+				Err(self.error("unexpected character after value"))
+			}
+			_ => Ok(()),
+		}
+	}
+
+	fn error(&self, problem: &str) -> io::Error {
+		io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("invalid JSON at byte {pos}: {problem}", pos = self.pos),
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn contents(text: &str) -> Vec<String> {
+		documents(text)
+			.unwrap()
+			.iter()
+			.map(|doc| doc.content().to_owned())
+			.collect()
+	}
+
+	#[test]
+	fn json_concatenated_values() {
+		assert_eq!(
+			contents("{\"a\": [1, 2]}{\"b\":null}\n\"x\\\"y\" -1.5e+3 true [ ]"),
+			[
+				"{\"a\": [1, 2]}\n",
+				"{\"b\":null}\n",
+				"\"x\\\"y\"\n",
+				"-1.5e+3\n",
+				"true\n",
+				"[ ]\n"
+			]
+		);
+	}
+
+	#[test]
+	fn json_single_array() {
+		let docs = documents("\u{FEFF}[\n  {\"a\": 1},\n  [2],\n  3\n]\n").unwrap();
+		let contents = docs.iter().map(Document::content).collect::<Vec<_>>();
+		assert_eq!(contents, ["{\"a\": 1}\n", "[2]\n", "3\n"]);
+		let kinds = docs.iter().map(Document::kind).collect::<Vec<_>>();
+		assert_eq!(
+			kinds,
+			[
+				DocumentKind::Mapping,
+				DocumentKind::Sequence,
+				DocumentKind::Scalar
+			]
+		);
+		assert_eq!(docs[0].source(), 7..15);
+	}
+
+	#[test]
+	fn json_rewritten_for_yaml() {
+		let long = "k".repeat(MAX_IMPLICIT_KEY_LEN);
+		let text =
+			format!("{{\"e\": \"\\ud83d\\ude00!\"}} {{\"{long}\": 1, \"a\"\n: [2]}} \"\\u00e9\"");
+		assert_eq!(
+			contents(&text),
+			[
+				"{\"e\": \"\\U0001F600!\"}\n".to_owned(),
+				format!("{{? \"{long}\": 1, ? \"a\"\n: [2]}}\n"),
+				"\"\\u00e9\"\n".to_owned(),
+			]
+		);
+		let json = documents(&text)
+			.unwrap()
+			.iter()
+			.map(|doc| to_json(&doc.root().unwrap()).unwrap())
+			.collect::<Vec<_>>();
+		assert_eq!(
+			json,
+			[
+				"{\"e\":\"\u{1F600}!\"}".to_owned(),
+				format!("{{\"{long}\":1,\"a\":[2]}}"),
+				"\"\u{e9}\"".to_owned(),
+			]
+		);
+
+		let docs = lines("{\"\\ud83d\\ude00\": 1}\n".as_bytes(), None, false)
+			.collect::<io::Result<Vec<_>>>()
+			.unwrap();
+		assert_eq!(docs[0].content(), "{\"\\U0001F600\": 1}\n");
+		assert_eq!(docs[0].position().unwrap().end.column, 19);
+
+		for lone in ["\"\\ud83d\"", "\"\\ude00\\ud83d\"", "\"\\ud83d\\u0041\""] {
+			let err = documents(lone).err().unwrap();
+			assert_eq!(
+				err.to_string(),
+				"invalid JSON at byte 1: unpaired UTF-16 surrogate escape"
+			);
+		}
+	}
+
+	#[test]
+	fn json_lines() {
+		let docs = lines("{\"a\": 1}\r\n\n  [2] \n\"x\"".as_bytes(), None, false)
+			.collect::<io::Result<Vec<_>>>()
+			.unwrap();
+		let contents = docs.iter().map(Document::content).collect::<Vec<_>>();
+		assert_eq!(contents, ["{\"a\": 1}\n", "[2]\n", "\"x\"\n"]);
+		assert_eq!(docs[1].source(), 13..16);
+
+		let Some(Err(err)) = lines("1\n2 3\n".as_bytes(), None, false).nth(1) else {
+			panic!("second line should be invalid");
+		};
+		assert!(err.to_string().starts_with("line 2: "), "{err}");
+
+		const LONG: &str = "[1, 2]\r\n\n  [123]\n[1, 2, 3, 4, 5]\n[3]";
+		let mut docs = lines(LONG.as_bytes(), Some(6), false);
+		assert_eq!(docs.next().unwrap().unwrap().content(), "[1, 2]\n");
+		let Some(Err(err)) = docs.next() else {
+			panic!("second document should be too long");
+		};
+		assert_eq!(
+			err.to_string(),
+			"document 2 at byte 9 is longer than the limit of 6 bytes"
+		);
+
+		let docs = lines(LONG.as_bytes(), Some(6), true)
+			.collect::<io::Result<Vec<_>>>()
+			.unwrap();
+		let docs = docs
+			.iter()
+			.map(|doc| (doc.content(), doc.full_len(), doc.source()))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			docs,
+			[
+				("[1, 2]\n", None, 0..6),
+				("[123", Some(5), 11..16),
+				("[1, 2,", Some(15), 17..32),
+				("[3]\n", None, 33..36),
+			]
+		);
+	}
+
+	#[test]
+	fn json_from_nodes() {
+		let convert = |text: &str| to_json(&node::parse(text).unwrap()[0]);
+		assert_eq!(
+			convert("a: &x [1, +2.50, .5, 1., 0x1F, -007, 1e3]\nb: *x\n").unwrap(),
+			r#"{"a":[1,2.50,0.5,1.0,31,-7,1e3],"b":[1,2.50,0.5,1.0,31,-7,1e3]}"#
+		);
+		assert_eq!(
+			convert("? 1\n: [~, True, '1', !!str 2, !!float 3, !custom x]\n\"q\\\"\\t\": yes")
+				.unwrap(),
+			r#"{"1":[null,true,"1","2",3,"x"],"q\"\t":"yes"}"#
+		);
+		for text in ["[.inf]", "!!int abc", "{[1]: 2}", "!!bool 1"] {
+			assert!(convert(text).is_err(), "{text:?} should not convert");
+		}
+	}
+
+	#[test]
+	fn json_invalid() {
+		for text in ["{a: 1}", "[1, 2", "\"\t\"", "01", "nul", "1.", "{\"a\" 1}"] {
+			assert!(documents(text).is_err(), "{text:?} should be invalid");
+		}
+	}
+
+	#[test]
+	fn json_depth_limit() {
+		let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+		assert!(documents(&nested(node::MAX_DEPTH)).is_ok());
+		let err = documents(&nested(100_000)).err().unwrap();
+		assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+		assert!(documents(&"{\"a\":".repeat(100_000)).is_err());
+	}
+}
@@ -0,0 +1,454 @@
+//! Splitting of YAML streams on their document markers alone, without parsing
+//! them, for `split --lexical`.
+//!
+//! A line that starts with `---` or `...` followed by a space or the end of
+//! the line always starts or ends a document, since YAML forbids such a line
+//! inside any scalar, whether in a block scalar or in quotes. A line that
+//! starts with `%` is always a directive to libyaml, and so starts the next
+//! document. A stream that's known to be well-formed can be split on those
+//! lines alone, many times faster than parsing it.
+//!
+//! Documents have the same content and locations as those from the full
+//! [`Chunker`](crate::chunker::Chunker), but nothing else about them is
+//! checked. The kind of each document's root is guessed from its first line
+//! of content, and the tags and features that a parser would find aren't
+//! known at all.
+
+use std::io::{self, BufRead};
+use std::mem;
+
+use crate::chunker::{Chunking, Document, DocumentKind, LimitExceeded};
+use crate::encoding::SourceMap;
+use crate::libyaml::Mark;
+
+/// An iterator over the documents of a UTF-8 YAML stream, found from their
+/// markers alone.
+pub(crate) struct LexicalChunker<R> {
+	reader: R,
+	source_map: SourceMap,
+	/// The offset of the start of the next line in the stream.
+	offset: u64,
+	/// The offset of the start of the next line in the original input.
+	source_offset: u64,
+	/// The zero-based number of the next line.
+	line: u64,
+	limit: Option<usize>,
+	truncate: bool,
+	/// The number of documents produced so far.
+	documents: usize,
+	current: Option<Current>,
+	/// An error to return after the document before it.
+	error: Option<io::Error>,
+	done: bool,
+	buf: Vec<u8>,
+}
+
+/// A document that's still being read.
+struct Current {
+	content: String,
+	start: u64,
+	source_start: u64,
+	start_mark: Mark,
+	/// Whether the document has started, with `---` or content, rather than
+	/// only having directives so far.
+	started: bool,
+	kind: Option<DocumentKind>,
+	/// Whether the document's root has a tag or anchor but no content so far.
+	has_properties: bool,
+	/// The length of the document so far, including any of it that was
+	/// dropped for being past the limit.
+	len: u64,
+}
+
+/// What the first line of a document's content shows about its root.
+enum Guess {
+	Kind(DocumentKind),
+	/// A tag or anchor with nothing after it, so the content is on a later
+	/// line if there is any.
+	Properties,
+	Nothing,
+}
+
+impl<R> LexicalChunker<R>
+where
+	R: BufRead,
+{
+	/// Creates a new chunker for the UTF-8 YAML stream produced by the reader,
+	/// reporting offsets in the original input per `source_map`.
+	pub(crate) fn new(reader: R, source_map: SourceMap) -> Self {
+		Self {
+			reader,
+			source_map,
+			offset: 0,
+			source_offset: source_map.start(),
+			line: 0,
+			limit: None,
+			truncate: false,
+			documents: 0,
+			current: None,
+			error: None,
+			done: false,
+			buf: vec![],
+		}
+	}
+
+	/// Handles the next line of the stream, and returns the document that it
+	/// ends, if any.
+	fn read_line(&mut self, line: &str) -> io::Result<Option<Document>> {
+		// A byte order mark at the start of the stream is skipped, like the
+		// whitespace before the first token.
+		let body = line.trim_start_matches('\u{feff}');
+		let skip = line.len() - body.len();
+		if let Some(rest) = after_marker(body, "---") {
+			let finished = match &self.current {
+				Some(current) if current.started => self.finish(line, 0),
+				_ => None,
+			};
+			if self.current.is_none() {
+				self.begin(line, skip);
+			}
+			let pushed = self.push(&line[skip..]);
+			let current = self.current.as_mut().expect("a document was begun");
+			current.started = true;
+			current.observe(rest);
+			return self.after_finishing(finished, pushed);
+		}
+		if after_marker(body, "...").is_some() {
+			if self.current.is_none() {
+				return Ok(None);
+			}
+			self.push("...")?;
+			return Ok(self.finish(line, skip + 3));
+		}
+		if body.starts_with('%') {
+			let finished = match &self.current {
+				Some(current) if current.started => self.finish(line, 0),
+				_ => None,
+			};
+			if self.current.is_none() {
+				self.begin(line, skip);
+			}
+			let pushed = self.push(&line[skip..]);
+			return self.after_finishing(finished, pushed);
+		}
+		let text = body.trim_start_matches([' ', '\t']);
+		let insignificant = text.trim().is_empty() || text.starts_with('#');
+		if self.current.is_none() {
+			if insignificant {
+				return Ok(None);
+			}
+			self.begin(line, line.len() - text.len());
+		}
+		match self.current.as_ref().expect("a document was begun") {
+			current if current.content.is_empty() => self.push(text)?,
+			_ => self.push(line)?,
+		}
+		let current = self.current.as_mut().expect("a document was begun");
+		if !insignificant {
+			current.started = true;
+			if current.kind.is_none() {
+				current.observe(text);
+			}
+		}
+		Ok(None)
+	}
+
+	/// Returns the document that a line `finished`, if any, before the error
+	/// from starting the next document with the line, which waits its turn.
+	fn after_finishing(
+		&mut self,
+		finished: Option<Document>,
+		pushed: io::Result<()>,
+	) -> io::Result<Option<Document>> {
+		match (finished, pushed) {
+			(finished, Ok(())) => Ok(finished),
+			(None, Err(err)) => Err(err),
+			(Some(doc), Err(err)) => {
+				self.error = Some(err);
+				Ok(Some(doc))
+			}
+		}
+	}
+
+	/// Begins a document at byte `at` of `line`, the next line of the stream.
+	fn begin(&mut self, line: &str, at: usize) {
+		self.current = Some(Current {
+			content: String::new(),
+			start: self.offset + at as u64,
+			source_start: self.source_offset + self.source_map.source_len(&line.as_bytes()[..at]),
+			start_mark: Mark {
+				line: self.line,
+				column: line[..at].chars().count() as u64,
+			},
+			started: false,
+			kind: None,
+			has_properties: false,
+			len: 0,
+		});
+	}
+
+	/// Adds `text` to the current document, enforcing the length limit.
+	fn push(&mut self, text: &str) -> io::Result<()> {
+		let current = self.current.as_mut().expect("a document was begun");
+		current.len += text.len() as u64;
+		match self.limit {
+			Some(limit) if current.len > limit as u64 => {
+				if !self.truncate {
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						LimitExceeded {
+							index: self.documents + 1,
+							offset: current.source_start,
+							limit,
+						},
+					));
+				}
+				// Keep only enough to cut at the limit later.
+				if current.content.len() <= limit {
+					current.content.push_str(text);
+				}
+			}
+			_ => current.content.push_str(text),
+		}
+		Ok(())
+	}
+
+	/// Ends the current document `at` bytes into `line`, the next line of the
+	/// stream, if there is a current document.
+	fn finish(&mut self, line: &str, at: usize) -> Option<Document> {
+		let current = self.current.take()?;
+		self.documents += 1;
+		let line = &line[..at];
+		let end = self.offset + at as u64;
+		let source_end = self.source_offset + self.source_map.source_len(line.as_bytes());
+		let end_mark = Mark {
+			line: self.line,
+			column: line.chars().count() as u64,
+		};
+		let mut doc = Document::unparsed(
+			current.content,
+			current.kind.unwrap_or(DocumentKind::Scalar),
+			current.kind.is_none() && !current.has_properties,
+			self.documents - 1,
+			current.start..end,
+			current.source_start..source_end,
+			current.start_mark..end_mark,
+		);
+		if let Some(limit) = self.limit.filter(|&limit| current.len > limit as u64) {
+			doc.truncate(limit, current.len);
+		}
+		Some(doc)
+	}
+}
+
+impl Current {
+	/// Guesses the kind of the document's root from `text`, the rest of a line
+	/// that may start its content.
+	fn observe(&mut self, text: &str) {
+		match guess(text) {
+			Guess::Kind(kind) => self.kind = Some(kind),
+			Guess::Properties => self.has_properties = true,
+			Guess::Nothing => {}
+		}
+	}
+}
+
+impl<R> Chunking for LexicalChunker<R>
+where
+	R: BufRead,
+{
+	fn limit_document_len(mut self, limit: usize, truncate: bool) -> Self {
+		self.limit = Some(limit);
+		self.truncate = truncate;
+		self
+	}
+}
+
+impl<R> Iterator for LexicalChunker<R>
+where
+	R: BufRead,
+{
+	type Item = io::Result<Document>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		while !self.done {
+			if let Some(err) = self.error.take() {
+				self.done = true;
+				return Some(Err(err));
+			}
+			self.buf.clear();
+			let line = match self.reader.read_until(b'\n', &mut self.buf) {
+				Ok(0) => {
+					self.done = true;
+					return self.finish("", 0).map(Ok);
+				}
+				Ok(_) => match String::from_utf8(mem::take(&mut self.buf)) {
+					Ok(line) => line,
+					Err(err) => {
+						self.done = true;
+						return Some(Err(io::Error::new(io::ErrorKind::InvalidData, err)));
+					}
+				},
+				Err(err) => {
+					self.done = true;
+					return Some(Err(err));
+				}
+			};
+			let result = self.read_line(&line);
+			self.offset += line.len() as u64;
+			self.source_offset += self.source_map.source_len(line.as_bytes());
+			self.line += 1;
+			// Keep the line's buffer for reading the next one.
+			self.buf = line.into_bytes();
+			match result {
+				Ok(None) => {}
+				Ok(Some(doc)) => return Some(Ok(doc)),
+				Err(err) => {
+					self.done = true;
+					return Some(Err(err));
+				}
+			}
+		}
+		None
+	}
+}
+
+/// Returns the rest of `line` after `marker`, if the line starts with the
+/// marker as a document marker rather than as part of a longer token.
+fn after_marker<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+	let rest = line.strip_prefix(marker)?;
+	match rest.chars().next() {
+		None | Some(' ' | '\t' | '\r' | '\n') => Some(rest),
+		_ => None,
+	}
+}
+
+/// Guesses what the root of a document is from `text`, the first text on a
+/// line that may begin its content.
+fn guess(text: &str) -> Guess {
+	let mut text = text.trim_start();
+	let mut properties = false;
+	while text.starts_with(['&', '!']) {
+		properties = true;
+		text = text.find([' ', '\t', '\n']).map_or("", |i| &text[i..]);
+		text = text.trim_start();
+	}
+	let text = text.trim_end();
+	let mut chars = text.chars();
+	let kind = match (chars.next(), chars.next()) {
+		(None | Some('#'), _) if properties => return Guess::Properties,
+		(None | Some('#'), _) => return Guess::Nothing,
+		(Some('-'), None | Some(' ' | '\t')) => DocumentKind::Sequence,
+		(Some('['), _) => DocumentKind::Sequence,
+		(Some('{'), _) => DocumentKind::Mapping,
+		(Some('?'), None | Some(' ' | '\t')) => DocumentKind::Mapping,
+		(Some('|' | '>'), _) => DocumentKind::Scalar,
+		(Some(quote @ ('"' | '\'')), _) => {
+			let rest = after_quoted(&text[1..], quote);
+			match rest.is_some_and(|rest| rest.trim_start().starts_with(':')) {
+				true => DocumentKind::Mapping,
+				false => DocumentKind::Scalar,
+			}
+		}
+		_ => {
+			let text = text.split(" #").next().unwrap_or_default();
+			match text.contains(": ") || text.contains(":\t") || text.ends_with(':') {
+				true => DocumentKind::Mapping,
+				false => DocumentKind::Scalar,
+			}
+		}
+	};
+	Guess::Kind(kind)
+}
+
+/// Returns the rest of `text` after the end of a scalar quoted by `quote`,
+/// whose opening quote comes just before `text`, if it ends on this line.
+fn after_quoted(text: &str, quote: char) -> Option<&str> {
+	let mut chars = text.char_indices();
+	while let Some((i, c)) = chars.next() {
+		match c {
+			'\\' if quote == '"' => {
+				chars.next();
+			}
+			c if c == quote => {
+				// A single-quoted scalar escapes a quote by doubling it.
+				if quote == '\'' && text[i + 1..].starts_with('\'') {
+					chars.next();
+					continue;
+				}
+				return Some(&text[i + 1..]);
+			}
+			_ => {}
+		}
+	}
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::chunker::Chunker;
+
+	/// Returns the content, kind, emptiness, and locations of each document
+	/// that a backend finds.
+	fn describe(docs: impl Chunking) -> Vec<String> {
+		docs.map(|doc| {
+			let doc = doc.unwrap();
+			let position = doc.position().unwrap();
+			format!(
+				"{index:?} {content:?} {kind} {empty} {source:?} {original:?} {start}..{end}",
+				index = doc.index(),
+				content = doc.content(),
+				kind = doc.kind().name(),
+				empty = doc.is_empty(),
+				source = doc.source(),
+				original = doc.original(),
+				start = position.start,
+				end = position.end,
+			)
+		})
+		.collect()
+	}
+
+	#[test]
+	fn lexical_matches_parsed_boundaries() {
+		for input in [
+			"# lead\na: 1 # c\n\n# between\n---\nb: |\n  --- not\n  ...\n...\n# after end\n%YAML 1.2\n--- c\n",
+			"  - a\n  - b\n---\n--- !t\n---\n'k': v\n--- \"x\"\n--- &a [1]\n",
+			"%YAML 1.2\n%TAG ! tag:example.com,2000:\n---\n? a\n: b\n",
+			"\u{feff}a\n---\n",
+			"\"k\\\"\": 1\n--- >\n  folded\n--- 'it''s'\n",
+			"",
+			"# only comments\n",
+			"a: 1\n# trailing\n\n",
+		] {
+			let parsed = describe(Chunker::new(input.as_bytes()));
+			let lexical = describe(LexicalChunker::new(input.as_bytes(), SourceMap::IDENTITY));
+			assert_eq!(lexical, parsed, "{input:?}");
+		}
+	}
+
+	#[test]
+	fn backends_limit_alike() {
+		fn limited(docs: impl Chunking, truncate: bool) -> Vec<io::Result<Document>> {
+			docs.limit_document_len(8, truncate).collect()
+		}
+
+		let input = "a: 1\n--- [1, 2, 3, 4]\n--- b\n";
+		let lexical = || LexicalChunker::new(input.as_bytes(), SourceMap::IDENTITY);
+		let parsed = || Chunker::new(input.as_bytes());
+		for results in [limited(lexical(), false), limited(parsed(), false)] {
+			assert!(results[0].is_ok());
+			let err = results[1].as_ref().err().unwrap();
+			assert!(err.to_string().contains("document 2 at byte 5"));
+		}
+
+		for results in [limited(lexical(), true), limited(parsed(), true)] {
+			let docs = results.into_iter().collect::<io::Result<Vec<_>>>().unwrap();
+			assert_eq!(docs[1].content(), "--- [1, ");
+			assert_eq!(docs[1].full_len(), Some(17));
+			assert_eq!(docs[2].content(), "--- b\n");
+		}
+	}
+}
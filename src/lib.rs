@@ -0,0 +1,91 @@
+//! Splits YAML streams into their documents without parsing them fully.
+//!
+//! [`chunker::Chunker`] reads a UTF-8 stream and yields each of its documents
+//! with the exact text that the stream gave it, along with its index in the
+//! stream and the bytes and lines it came from. A stream in UTF-16 or UTF-32
+//! can be read through an [`encoding::Encoder`] first, which re-encodes it as
+//! UTF-8:
+//!
+//! ```
+//! use yaml_split::chunker::Chunker;
+//! use yaml_split::encoding::Encoder;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let input = "a: 1\n---\nb: 2\n".as_bytes();
+//! let (reader, map) = Encoder::from_reader_with_map(input)?;
+//! for doc in Chunker::new(reader).with_source_map(map) {
+//!     let doc = doc?;
+//!     let (index, lines) = (doc.index().unwrap(), doc.lines().unwrap());
+//!     println!("document {index} on lines {lines:?}: {:?}", doc.content());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`writer::DocumentWriter`] does the reverse, joining documents back into a
+//! single stream, and an [`index::Index`] written by `split --index-out`
+//! reads any one document of a large file back without parsing the rest.
+//! Output to a pipe can go through a [`pipecheck::Writer`] to exit quietly
+//! when the pipe breaks, as `yaml-split` does.
+//!
+//! Errors are [`std::io::Error`]s, since most come from reading the stream.
+//! Those that yaml-split detects itself carry one of the error types of this
+//! crate, such as [`chunker::LimitExceeded`], which can be recovered with
+//! [`std::io::Error::get_ref`] and `downcast_ref`.
+//!
+//! The rest of the crate implements the `yaml-split` command, and is not part
+//! of the API. In particular:
+//!
+//! - The parsed value of a document stays internal. It's a minimal tree that
+//!   the command's queries need, tied to libyaml's scalar styles, and no
+//!   substitute for a YAML library's value type, so parse
+//!   [`chunker::Document::content`] with one of those instead.
+//! - The C interface of the `ffi` feature is for building this crate as a C
+//!   library, as the `ffi` module describes, and not for Rust programs,
+//!   which should use [`chunker::Chunker`] directly.
+
+#![deny(unsafe_op_in_unsafe_fn)]
+#![warn(missing_docs)]
+
+mod budget;
+mod checkpoint;
+#[allow(dead_code)]
+pub mod chunker;
+#[doc(hidden)]
+pub mod cli;
+mod cmd;
+#[cfg(feature = "compression")]
+mod compression;
+mod config;
+mod dedup;
+mod diagnostics;
+mod dump;
+#[allow(dead_code)]
+pub mod encoding;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod hash;
+mod highlight;
+pub mod index;
+mod inputs;
+mod interrupt;
+mod json;
+mod lexical;
+mod libyaml;
+mod memory;
+mod node;
+mod output;
+mod pager;
+mod parallel;
+pub mod pipecheck;
+mod query;
+mod rate;
+mod regex;
+#[cfg(all(target_os = "linux", feature = "sandbox"))]
+mod sandbox;
+mod select;
+#[cfg(unix)]
+mod terminal;
+mod textdiff;
+mod transform;
+pub mod writer;
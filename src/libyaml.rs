@@ -0,0 +1,16 @@
+//! Minimal safe abstractions over the venerable [libyaml].
+//!
+//! These wrap a pure-Rust translation of the common [libyaml] library, and
+//! largely expose that library's types and values when it's safe to do so.
+//! The [`Parser`] is the foundation of the chunker, while the [`Emitter`]
+//! backs the output modes that re-serialize documents rather than copying
+//! them from the input.
+//!
+//! [libyaml]: https://pyyaml.org/wiki/LibYAML
+
+mod emitter;
+mod parser;
+
+pub(crate) use self::emitter::*;
+pub use self::parser::Mark;
+pub(crate) use self::parser::*;
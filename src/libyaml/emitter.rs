@@ -0,0 +1,323 @@
+//! A minimal safe abstraction over libyaml's emitter.
+//!
+//! [`Emitter`] writes a UTF-8 encoded YAML stream to a Rust writer from a
+//! sequence of events. Unlike the parser, the emitter takes the content of
+//! each event directly through its methods rather than through an event type,
+//! since nothing outside this module ever needs to hold on to an event.
+
+use std::ffi::{c_void, CString};
+use std::io::{self, Write};
+use std::mem::MaybeUninit;
+use std::ptr;
+
+use unsafe_libyaml::{
+	yaml_alias_event_initialize, yaml_document_end_event_initialize,
+	yaml_document_start_event_initialize, yaml_emitter_delete, yaml_emitter_emit,
+	yaml_emitter_initialize, yaml_emitter_set_canonical, yaml_emitter_set_indent,
+	yaml_emitter_set_output, yaml_emitter_set_unicode, yaml_emitter_set_width, yaml_emitter_t,
+	yaml_event_t, yaml_mapping_end_event_initialize, yaml_mapping_start_event_initialize,
+	yaml_scalar_event_initialize, yaml_sequence_end_event_initialize,
+	yaml_sequence_start_event_initialize, yaml_stream_end_event_initialize,
+	yaml_stream_start_event_initialize, YAML_UTF8_ENCODING,
+};
+
+pub(crate) use unsafe_libyaml::yaml_mapping_style_t::{self, *};
+pub(crate) use unsafe_libyaml::yaml_scalar_style_t::{self, *};
+pub(crate) use unsafe_libyaml::yaml_sequence_style_t::{self, *};
+
+/// Formatting options for an [`Emitter`].
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct EmitterOptions {
+	/// Write the stream in libyaml's canonical form, with every tag and
+	/// structure spelled out explicitly.
+	pub(crate) canonical: bool,
+	/// The number of spaces to indent nested block collections by.
+	pub(crate) indent: i32,
+	/// The preferred line width, or `None` for no limit.
+	pub(crate) width: Option<i32>,
+}
+
+impl Default for EmitterOptions {
+	fn default() -> Self {
+		Self {
+			canonical: false,
+			indent: 2,
+			width: Some(80),
+		}
+	}
+}
+
+pub(crate) struct Emitter<W>
+where
+	W: Write,
+{
+	emitter: Box<yaml_emitter_t>,
+	write_state: *mut WriteState<W>, // See Parser::new() for details.
+}
+
+struct WriteState<W>
+where
+	W: Write,
+{
+	writer: W,
+	error: Option<io::Error>,
+}
+
+impl<W> Emitter<W>
+where
+	W: Write,
+{
+	pub(crate) fn new(writer: W, options: EmitterOptions) -> Emitter<W> {
+		let mut emitter: Box<yaml_emitter_t>;
+
+		// SAFETY: As in Parser::new(), we trust libyaml to initialize the
+		// emitter properly given uninitialized memory.
+		unsafe {
+			let mut uninit = Box::new(MaybeUninit::<yaml_emitter_t>::uninit());
+			if yaml_emitter_initialize(uninit.as_mut_ptr()).ok {
+				emitter = Box::from_raw(Box::into_raw(uninit).cast());
+			} else {
+				panic!("out of memory for yaml_emitter_initialize");
+			}
+		}
+
+		// As with the parser's read state, this stays raw for its entire life
+		// to avoid invalidating the pointer that libyaml holds.
+		let write_state = Box::into_raw(Box::new(WriteState {
+			writer,
+			error: None,
+		}));
+
+		// SAFETY: We know the emitter is initialized because we didn't panic
+		// above, and trust libyaml to implement these correctly.
+		unsafe {
+			yaml_emitter_set_output(
+				&mut *emitter,
+				Self::write_handler,
+				write_state.cast::<c_void>(),
+			);
+			yaml_emitter_set_unicode(&mut *emitter, true);
+			yaml_emitter_set_canonical(&mut *emitter, options.canonical);
+			yaml_emitter_set_indent(&mut *emitter, options.indent);
+			yaml_emitter_set_width(&mut *emitter, options.width.unwrap_or(-1));
+		}
+
+		Emitter {
+			emitter,
+			write_state,
+		}
+	}
+
+	pub(crate) fn stream_start(&mut self) -> io::Result<()> {
+		// SAFETY: For this and the other event methods, we trust libyaml to
+		// initialize the event when it reports success, and to copy any
+		// strings we pass rather than holding on to them.
+		self.emit(|event| unsafe {
+			yaml_stream_start_event_initialize(event, YAML_UTF8_ENCODING).ok
+		})
+	}
+
+	pub(crate) fn stream_end(&mut self) -> io::Result<()> {
+		self.emit(|event| unsafe { yaml_stream_end_event_initialize(event).ok })
+	}
+
+	/// Starts a document, with an explicit `---` marker unless `implicit` is
+	/// set and libyaml can leave it out.
+	pub(crate) fn document_start(&mut self, implicit: bool) -> io::Result<()> {
+		self.emit(|event| unsafe {
+			yaml_document_start_event_initialize(
+				event,
+				ptr::null_mut(),
+				ptr::null_mut(),
+				ptr::null_mut(),
+				implicit,
+			)
+			.ok
+		})
+	}
+
+	/// Ends a document, with an explicit `...` marker unless `implicit` is set.
+	pub(crate) fn document_end(&mut self, implicit: bool) -> io::Result<()> {
+		self.emit(|event| unsafe { yaml_document_end_event_initialize(event, implicit).ok })
+	}
+
+	pub(crate) fn alias(&mut self, anchor: &str) -> io::Result<()> {
+		let anchor = c_string(anchor)?;
+		self.emit(|event| unsafe { yaml_alias_event_initialize(event, anchor.as_ptr().cast()).ok })
+	}
+
+	/// Writes a scalar, preferring the given style if libyaml can represent
+	/// the value in it. A scalar without a tag is written so that it resolves
+	/// the same way it did when parsed, quoting it if necessary.
+	pub(crate) fn scalar(
+		&mut self,
+		anchor: Option<&str>,
+		tag: Option<&str>,
+		value: &str,
+		style: yaml_scalar_style_t,
+	) -> io::Result<()> {
+		let anchor = anchor.map(c_string).transpose()?;
+		let tag = tag.map(c_string).transpose()?;
+		let Ok(length) = value.len().try_into() else {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"scalar is too long to emit",
+			));
+		};
+		let implicit = tag.is_none();
+		self.emit(|event| unsafe {
+			yaml_scalar_event_initialize(
+				event,
+				opt_ptr(&anchor),
+				opt_ptr(&tag),
+				value.as_ptr(),
+				length,
+				implicit,
+				implicit,
+				style,
+			)
+			.ok
+		})
+	}
+
+	pub(crate) fn sequence_start(
+		&mut self,
+		anchor: Option<&str>,
+		tag: Option<&str>,
+		style: yaml_sequence_style_t,
+	) -> io::Result<()> {
+		let anchor = anchor.map(c_string).transpose()?;
+		let tag = tag.map(c_string).transpose()?;
+		let implicit = tag.is_none();
+		self.emit(|event| unsafe {
+			yaml_sequence_start_event_initialize(
+				event,
+				opt_ptr(&anchor),
+				opt_ptr(&tag),
+				implicit,
+				style,
+			)
+			.ok
+		})
+	}
+
+	pub(crate) fn sequence_end(&mut self) -> io::Result<()> {
+		self.emit(|event| unsafe { yaml_sequence_end_event_initialize(event).ok })
+	}
+
+	pub(crate) fn mapping_start(
+		&mut self,
+		anchor: Option<&str>,
+		tag: Option<&str>,
+		style: yaml_mapping_style_t,
+	) -> io::Result<()> {
+		let anchor = anchor.map(c_string).transpose()?;
+		let tag = tag.map(c_string).transpose()?;
+		let implicit = tag.is_none();
+		self.emit(|event| unsafe {
+			yaml_mapping_start_event_initialize(
+				event,
+				opt_ptr(&anchor),
+				opt_ptr(&tag),
+				implicit,
+				style,
+			)
+			.ok
+		})
+	}
+
+	pub(crate) fn mapping_end(&mut self) -> io::Result<()> {
+		self.emit(|event| unsafe { yaml_mapping_end_event_initialize(event).ok })
+	}
+
+	/// Initializes an event with `init` and passes it to libyaml.
+	fn emit<F>(&mut self, init: F) -> io::Result<()>
+	where
+		F: FnOnce(*mut yaml_event_t) -> bool,
+	{
+		let mut event = MaybeUninit::<yaml_event_t>::uninit();
+		if !init(event.as_mut_ptr()) {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"invalid content for YAML event",
+			));
+		}
+		// SAFETY: init reported success, so the event is initialized.
+		// yaml_emitter_emit takes ownership of the event whether or not it
+		// succeeds, so we must not delete it ourselves.
+		let emitted = unsafe { yaml_emitter_emit(&mut *self.emitter, event.as_mut_ptr()) };
+		if emitted.ok {
+			return Ok(());
+		}
+		// SAFETY: As in Parser::read_state_mut(), nobody is running the
+		// emitter while we hold &mut self.
+		let state = unsafe { &mut *self.write_state };
+		Err(state.error.take().unwrap_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"libyaml failed to emit an event",
+			)
+		}))
+	}
+
+	/// A callback for libyaml to write to a Rust Write impl.
+	///
+	/// # Safety
+	///
+	/// The data pointer provided to `yaml_emitter_set_output` must be a valid
+	/// `WriteState<W>` pointer, and `buffer` must be valid for reads of `size`
+	/// bytes.
+	unsafe fn write_handler(write_state: *mut c_void, buffer: *mut u8, size: u64) -> i32 {
+		const WRITE_SUCCESS: i32 = 1;
+		const WRITE_FAILURE: i32 = 0;
+
+		if write_state.is_null() || buffer.is_null() {
+			return WRITE_FAILURE;
+		}
+		let Ok(size) = usize::try_from(size) else {
+			return WRITE_FAILURE;
+		};
+
+		// SAFETY: As in Parser::read_handler(), this is the only live reference
+		// to the write state while the emitter runs. The caller guarantees the
+		// validity of the buffer, which libyaml fully initializes up to size.
+		let write_state = unsafe { &mut *write_state.cast::<WriteState<W>>() };
+		let buffer = unsafe { std::slice::from_raw_parts(buffer, size) };
+
+		match write_state.writer.write_all(buffer) {
+			Ok(()) => WRITE_SUCCESS,
+			Err(err) => {
+				write_state.error = Some(err);
+				WRITE_FAILURE
+			}
+		}
+	}
+}
+
+impl<W> Drop for Emitter<W>
+where
+	W: Write,
+{
+	fn drop(&mut self) {
+		// SAFETY: Emitter::new panics if libyaml fails to initialize the
+		// emitter, so we know it's logically valid here. As with the parser,
+		// we destroy the emitter before the write state it points to.
+		unsafe {
+			yaml_emitter_delete(&mut *self.emitter);
+			drop(Box::from_raw(self.write_state));
+		}
+	}
+}
+
+fn c_string(s: &str) -> io::Result<CString> {
+	CString::new(s).map_err(|_| {
+		io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"anchors and tags may not contain NUL characters",
+		)
+	})
+}
+
+fn opt_ptr(s: &Option<CString>) -> *const u8 {
+	s.as_ref().map_or(ptr::null(), |s| s.as_ptr().cast())
+}
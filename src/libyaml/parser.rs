@@ -1,12 +1,10 @@
-//! A minimal safe abstraction over the venerable [libyaml].
+//! A minimal safe abstraction over libyaml's parser.
 //!
 //! [`Parser`] reads a UTF-8 encoded YAML stream and exposes [`Event`]s that
-//! indicate the start and end positions of various document features. It's
-//! based on a pure-Rust translation of the common [libyaml] library, and
-//! largely exposes that library's types and values when it's safe to do so.
-//!
-//! [libyaml]: https://pyyaml.org/wiki/LibYAML
+//! indicate the start and end positions of various document features, along
+//! with the content of the nodes they represent.
 
+use std::borrow::Cow;
 use std::error::Error;
 use std::ffi::{c_char, c_void, CStr};
 use std::fmt::Display;
@@ -16,13 +14,15 @@ use std::ptr;
 
 use unsafe_libyaml::{
 	yaml_encoding_t::YAML_UTF8_ENCODING, yaml_event_delete, yaml_event_t, yaml_event_type_t,
-	yaml_mark_t, yaml_parser_delete, yaml_parser_initialize, yaml_parser_parse,
-	yaml_parser_set_encoding, yaml_parser_set_input, yaml_parser_t,
+	yaml_mark_t, yaml_parser_delete, yaml_parser_initialize, yaml_parser_parse, yaml_parser_scan,
+	yaml_parser_set_encoding, yaml_parser_set_input, yaml_parser_t, yaml_scalar_style_t,
+	yaml_token_delete, yaml_token_t, yaml_token_type_t,
 };
 
-pub(super) use unsafe_libyaml::yaml_event_type_t::*;
+pub(crate) use unsafe_libyaml::yaml_event_type_t::*;
+pub(crate) use unsafe_libyaml::yaml_token_type_t::*;
 
-pub(super) struct Parser<R>
+pub(crate) struct Parser<R>
 where
 	R: Read,
 {
@@ -43,7 +43,7 @@ impl<R> Parser<R>
 where
 	R: Read,
 {
-	pub(super) fn new(reader: R) -> Parser<R> {
+	pub(crate) fn new(reader: R) -> Parser<R> {
 		let mut parser: Box<yaml_parser_t>;
 
 		// SAFETY: This comes from libyaml, which we assume is implemented
@@ -98,11 +98,11 @@ where
 		unsafe { &mut *self.read_state }
 	}
 
-	pub(super) fn reader_mut(&mut self) -> &mut R {
+	pub(crate) fn reader_mut(&mut self) -> &mut R {
 		&mut self.read_state_mut().reader
 	}
 
-	pub(super) fn next_event(&mut self) -> Result<Event, io::Error> {
+	pub(crate) fn next_event(&mut self) -> Result<Event, io::Error> {
 		Event::parse_next(&mut self.parser).map_err(|err| {
 			self.read_state_mut()
 				.error
@@ -111,6 +111,19 @@ where
 		})
 	}
 
+	/// Returns the next token from the scanner that underlies the parser.
+	///
+	/// A parser should be used either for events or for tokens, but not both,
+	/// as the two share the scanner's state.
+	pub(crate) fn next_token(&mut self) -> Result<Token, io::Error> {
+		Token::scan_next(&mut self.parser).map_err(|err| {
+			self.read_state_mut()
+				.error
+				.take()
+				.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidData, err))
+		})
+	}
+
 	/// A callback for libyaml to read from a Rust Read impl.
 	///
 	/// # Safety
@@ -209,7 +222,34 @@ where
 	}
 }
 
-pub(super) struct Event(yaml_event_t);
+pub(crate) struct Event(yaml_event_t);
+
+/// A position in a YAML stream, by zero-based line and column.
+///
+/// Columns count characters rather than bytes. A mark displays in the one-based
+/// `line:column` form that editors and compilers use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Mark {
+	/// The zero-based line.
+	pub line: u64,
+	/// The zero-based column, in characters.
+	pub column: u64,
+}
+
+impl From<yaml_mark_t> for Mark {
+	fn from(mark: yaml_mark_t) -> Self {
+		Mark {
+			line: mark.line,
+			column: mark.column,
+		}
+	}
+}
+
+impl Display for Mark {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}:{}", self.line + 1, self.column + 1)
+	}
+}
 
 impl Event {
 	fn parse_next(parser: &mut yaml_parser_t) -> Result<Event, ParserError> {
@@ -226,17 +266,94 @@ impl Event {
 		}
 	}
 
-	pub(super) fn event_type(&self) -> yaml_event_type_t {
+	pub(crate) fn event_type(&self) -> yaml_event_type_t {
 		self.0.type_
 	}
 
-	pub(super) fn start_offset(&self) -> u64 {
+	pub(crate) fn start_offset(&self) -> u64 {
 		self.0.start_mark.index
 	}
 
-	pub(super) fn end_offset(&self) -> u64 {
+	pub(crate) fn end_offset(&self) -> u64 {
 		self.0.end_mark.index
 	}
+
+	pub(crate) fn start_mark(&self) -> Mark {
+		self.0.start_mark.into()
+	}
+
+	pub(crate) fn end_mark(&self) -> Mark {
+		self.0.end_mark.into()
+	}
+
+	/// Returns the anchor of a node, or the anchor referenced by an alias.
+	pub(crate) fn anchor(&self) -> Option<Cow<'_, str>> {
+		// SAFETY: The union field we read matches the event type, which libyaml
+		// initialized along with the rest of the event. Anchors are either null
+		// or valid C strings, and live as long as the event.
+		unsafe {
+			let anchor = match self.0.type_ {
+				YAML_ALIAS_EVENT => self.0.data.alias.anchor,
+				YAML_SCALAR_EVENT => self.0.data.scalar.anchor,
+				YAML_SEQUENCE_START_EVENT => self.0.data.sequence_start.anchor,
+				YAML_MAPPING_START_EVENT => self.0.data.mapping_start.anchor,
+				_ => return None,
+			};
+			(!anchor.is_null()).then(|| CStr::from_ptr(anchor.cast()).to_string_lossy())
+		}
+	}
+
+	/// Returns the tag of a node, if it has one.
+	///
+	/// Tags are fully resolved by the parser, so that the shorthand `!!str`
+	/// appears as `tag:yaml.org,2002:str`.
+	pub(crate) fn tag(&self) -> Option<Cow<'_, str>> {
+		// SAFETY: As in anchor().
+		unsafe {
+			let tag = match self.0.type_ {
+				YAML_SCALAR_EVENT => self.0.data.scalar.tag,
+				YAML_SEQUENCE_START_EVENT => self.0.data.sequence_start.tag,
+				YAML_MAPPING_START_EVENT => self.0.data.mapping_start.tag,
+				_ => return None,
+			};
+			(!tag.is_null()).then(|| CStr::from_ptr(tag.cast()).to_string_lossy())
+		}
+	}
+
+	/// Returns the value and style of a scalar.
+	///
+	/// # Panics
+	///
+	/// Panics if this is not a scalar event.
+	pub(crate) fn scalar(&self) -> (Cow<'_, str>, yaml_scalar_style_t) {
+		assert_eq!(self.0.type_, YAML_SCALAR_EVENT, "not a scalar event");
+		// SAFETY: We checked that this is a scalar event, so libyaml
+		// initialized the scalar data, whose value is valid for reads of its
+		// length (which may include NULs) for as long as the event lives.
+		unsafe {
+			let scalar = self.0.data.scalar;
+			let value = std::slice::from_raw_parts(scalar.value, scalar.length as usize);
+			(String::from_utf8_lossy(value), scalar.style)
+		}
+	}
+
+	/// Returns whether a sequence or mapping is written in flow style.
+	pub(crate) fn is_flow(&self) -> bool {
+		use unsafe_libyaml::yaml_mapping_style_t::YAML_FLOW_MAPPING_STYLE;
+		use unsafe_libyaml::yaml_sequence_style_t::YAML_FLOW_SEQUENCE_STYLE;
+		// SAFETY: As in anchor().
+		unsafe {
+			match self.0.type_ {
+				YAML_SEQUENCE_START_EVENT => {
+					self.0.data.sequence_start.style == YAML_FLOW_SEQUENCE_STYLE
+				}
+				YAML_MAPPING_START_EVENT => {
+					self.0.data.mapping_start.style == YAML_FLOW_MAPPING_STYLE
+				}
+				_ => false,
+			}
+		}
+	}
 }
 
 impl Drop for Event {
@@ -249,6 +366,111 @@ impl Drop for Event {
 	}
 }
 
+pub(crate) struct Token(yaml_token_t);
+
+impl Token {
+	fn scan_next(parser: &mut yaml_parser_t) -> Result<Token, ParserError> {
+		let mut token = MaybeUninit::uninit();
+		// SAFETY: As in Event::parse_next, for yaml_parser_scan.
+		unsafe {
+			if yaml_parser_scan(parser, token.as_mut_ptr()).ok {
+				Ok(Token(token.assume_init()))
+			} else {
+				Err(ParserError::new(parser))
+			}
+		}
+	}
+
+	pub(crate) fn token_type(&self) -> yaml_token_type_t {
+		self.0.type_
+	}
+
+	pub(crate) fn start_offset(&self) -> u64 {
+		self.0.start_mark.index
+	}
+
+	pub(crate) fn end_offset(&self) -> u64 {
+		self.0.end_mark.index
+	}
+
+	pub(crate) fn start_mark(&self) -> Mark {
+		self.0.start_mark.into()
+	}
+
+	pub(crate) fn end_mark(&self) -> Mark {
+		self.0.end_mark.into()
+	}
+
+	/// Returns the name of an anchor or alias, or the value of a scalar.
+	pub(crate) fn value(&self) -> Option<Cow<'_, str>> {
+		// SAFETY: The union field we read matches the token type, which libyaml
+		// initialized along with the rest of the token. Names are valid C
+		// strings, and scalar values are valid for reads of their length. All
+		// of them live as long as the token.
+		unsafe {
+			let name = match self.0.type_ {
+				YAML_ALIAS_TOKEN => self.0.data.alias.value,
+				YAML_ANCHOR_TOKEN => self.0.data.anchor.value,
+				YAML_SCALAR_TOKEN => {
+					let scalar = self.0.data.scalar;
+					let value = std::slice::from_raw_parts(scalar.value, scalar.length as usize);
+					return Some(String::from_utf8_lossy(value));
+				}
+				_ => return None,
+			};
+			Some(CStr::from_ptr(name.cast()).to_string_lossy())
+		}
+	}
+
+	/// Returns the style of a scalar token.
+	pub(crate) fn scalar_style(&self) -> Option<yaml_scalar_style_t> {
+		// SAFETY: As in value().
+		match self.0.type_ {
+			YAML_SCALAR_TOKEN => Some(unsafe { self.0.data.scalar.style }),
+			_ => None,
+		}
+	}
+
+	/// Returns the handle and suffix of a tag, or the handle and prefix of a
+	/// `%TAG` directive, as written.
+	pub(crate) fn tag(&self) -> Option<(Cow<'_, str>, Cow<'_, str>)> {
+		// SAFETY: As in value(). Both parts are valid C strings.
+		unsafe {
+			let (handle, rest) = match self.0.type_ {
+				YAML_TAG_TOKEN => (self.0.data.tag.handle, self.0.data.tag.suffix),
+				YAML_TAG_DIRECTIVE_TOKEN => (
+					self.0.data.tag_directive.handle,
+					self.0.data.tag_directive.prefix,
+				),
+				_ => return None,
+			};
+			Some((
+				CStr::from_ptr(handle.cast()).to_string_lossy(),
+				CStr::from_ptr(rest.cast()).to_string_lossy(),
+			))
+		}
+	}
+
+	/// Returns the major and minor version of a `%YAML` directive.
+	pub(crate) fn version(&self) -> Option<(i32, i32)> {
+		// SAFETY: As in value().
+		(self.0.type_ == YAML_VERSION_DIRECTIVE_TOKEN).then(|| unsafe {
+			let version = self.0.data.version_directive;
+			(version.major, version.minor)
+		})
+	}
+}
+
+impl Drop for Token {
+	fn drop(&mut self) {
+		// SAFETY: Token::scan_next returns an error if libyaml fails to
+		// initialize the token, so we know it's logically valid here.
+		unsafe {
+			yaml_token_delete(&mut self.0);
+		};
+	}
+}
+
 #[derive(Debug)]
 struct ParserError {
 	problem: Option<LocatedError>,
@@ -0,0 +1,182 @@
+//! Measurement of the program's memory use, for reporting with `--stats`.
+//!
+//! The peak resident set size comes from the operating system, and is only
+//! available on Unix-like systems. Heap usage needs a counting global
+//! allocator, which adds a little overhead to every allocation, so it's only
+//! tracked when yaml-split is built with the `alloc-stats` feature.
+
+use std::fmt::{self, Display};
+
+/// The memory used by the program so far.
+pub(crate) struct Usage {
+	/// The peak resident set size in bytes, if the system reports it.
+	pub(crate) peak_rss: Option<u64>,
+	/// The peak number of bytes allocated on the heap at once, and the total
+	/// number of allocations, if allocations are counted.
+	pub(crate) heap: Option<(usize, usize)>,
+}
+
+/// Returns the memory used by the program so far.
+pub(crate) fn usage() -> Usage {
+	Usage {
+		peak_rss: peak_rss(),
+		heap: heap(),
+	}
+}
+
+impl Display for Usage {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.peak_rss {
+			Some(rss) => write!(f, "peak RSS {}", Size(rss))?,
+			None => f.write_str("peak RSS unknown")?,
+		}
+		if let Some((peak, count)) = self.heap {
+			write!(
+				f,
+				", peak heap {} in {count} allocations",
+				Size(peak as u64)
+			)?;
+		}
+		Ok(())
+	}
+}
+
+/// A byte count, displayed in the largest binary unit that keeps it above 1.
+struct Size(u64);
+
+impl Display for Size {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+		if self.0 < 1024 {
+			return write!(f, "{} bytes", self.0);
+		}
+		let mut size = self.0 as f64 / 1024.0;
+		let mut unit = 0;
+		while size >= 1024.0 && unit < UNITS.len() - 1 {
+			size /= 1024.0;
+			unit += 1;
+		}
+		write!(f, "{size:.1} {}", UNITS[unit])
+	}
+}
+
+#[cfg(unix)]
+fn peak_rss() -> Option<u64> {
+	let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+	// SAFETY: This is an FFI call to libc, which we assume is implemented
+	// correctly, with a pointer to memory large enough for the result.
+	let usage = unsafe {
+		if libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) != 0 {
+			return None;
+		}
+		usage.assume_init()
+	};
+	let maxrss = u64::try_from(usage.ru_maxrss).ok()?;
+	// macOS reports the size in bytes, where other systems use kilobytes.
+	match cfg!(target_os = "macos") {
+		true => Some(maxrss),
+		false => Some(maxrss * 1024),
+	}
+}
+
+#[cfg(not(unix))]
+fn peak_rss() -> Option<u64> {
+	None
+}
+
+#[cfg(feature = "alloc-stats")]
+fn heap() -> Option<(usize, usize)> {
+	use std::sync::atomic::Ordering;
+	Some((
+		counting::PEAK.load(Ordering::Relaxed),
+		counting::COUNT.load(Ordering::Relaxed),
+	))
+}
+
+#[cfg(not(feature = "alloc-stats"))]
+fn heap() -> Option<(usize, usize)> {
+	None
+}
+
+#[cfg(feature = "alloc-stats")]
+mod counting {
+	use std::alloc::{GlobalAlloc, Layout, System};
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	pub(super) static CURRENT: AtomicUsize = AtomicUsize::new(0);
+	pub(super) static PEAK: AtomicUsize = AtomicUsize::new(0);
+	pub(super) static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+	/// The system allocator, counting the bytes it has allocated.
+	struct Counting;
+
+	#[global_allocator]
+	static ALLOCATOR: Counting = Counting;
+
+	fn allocated(size: usize) {
+		let current = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+		PEAK.fetch_max(current, Ordering::Relaxed);
+		COUNT.fetch_add(1, Ordering::Relaxed);
+	}
+
+	// SAFETY: Every call is forwarded to the system allocator unchanged.
+	unsafe impl GlobalAlloc for Counting {
+		unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+			// SAFETY: The caller upholds the contract of GlobalAlloc::alloc.
+			let ptr = unsafe { System.alloc(layout) };
+			if !ptr.is_null() {
+				allocated(layout.size());
+			}
+			ptr
+		}
+
+		unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+			// SAFETY: The caller upholds the contract of
+			// GlobalAlloc::alloc_zeroed.
+			let ptr = unsafe { System.alloc_zeroed(layout) };
+			if !ptr.is_null() {
+				allocated(layout.size());
+			}
+			ptr
+		}
+
+		unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+			// SAFETY: The caller upholds the contract of GlobalAlloc::dealloc.
+			unsafe { System.dealloc(ptr, layout) };
+			CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+		}
+
+		unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+			// SAFETY: The caller upholds the contract of GlobalAlloc::realloc.
+			let new = unsafe { System.realloc(ptr, layout, new_size) };
+			if !new.is_null() {
+				CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+				allocated(new_size);
+			}
+			new
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn size_display() {
+		let sizes = [0, 1023, 1024, 1536, 5 << 20, 3 << 40, 5000 << 40];
+		let text = sizes.map(|size| Size(size).to_string());
+		assert_eq!(
+			text,
+			[
+				"0 bytes",
+				"1023 bytes",
+				"1.0 KiB",
+				"1.5 KiB",
+				"5.0 MiB",
+				"3.0 TiB",
+				"5000.0 TiB"
+			]
+		);
+	}
+}
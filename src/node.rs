@@ -0,0 +1,469 @@
+//! An in-memory tree of the nodes in a YAML document.
+//!
+//! The chunker never needs one of these, and copies documents out of the
+//! input as-is. Output modes that restructure documents instead parse each
+//! chunk a second time into a [`Node`] tree, and emit the result through
+//! libyaml. That loses comments and the original formatting, but none of the
+//! content.
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::chunker::DocumentKind;
+use crate::libyaml::{
+	Emitter, EmitterOptions, Event, Parser, YAML_ALIAS_EVENT, YAML_ANY_MAPPING_STYLE,
+	YAML_ANY_SEQUENCE_STYLE, YAML_DOCUMENT_END_EVENT, YAML_DOCUMENT_START_EVENT,
+	YAML_FLOW_MAPPING_STYLE, YAML_FLOW_SEQUENCE_STYLE, YAML_MAPPING_END_EVENT,
+	YAML_MAPPING_START_EVENT, YAML_SCALAR_EVENT, YAML_SEQUENCE_END_EVENT,
+	YAML_SEQUENCE_START_EVENT, YAML_STREAM_END_EVENT,
+};
+
+pub(crate) use crate::libyaml::yaml_scalar_style_t as ScalarStyle;
+use crate::libyaml::YAML_PLAIN_SCALAR_STYLE;
+
+/// The prefix of the tags in the YAML 1.2 core schema, which the `!!` handle
+/// expands to.
+pub(crate) const CORE_TAG_PREFIX: &str = "tag:yaml.org,2002:";
+
+/// The deepest that collections may nest in a document that's parsed into a
+/// tree, or in JSON input, since working with a tree recurses once per level.
+/// This is far deeper than any real document, but shallow enough for the
+/// stacks of worker threads.
+pub(crate) const MAX_DEPTH: usize = 512;
+
+/// Returns the error for a document that nests deeper than [`MAX_DEPTH`].
+pub(crate) fn too_deep() -> io::Error {
+	io::Error::new(
+		io::ErrorKind::InvalidData,
+		format!("document nests collections more than {MAX_DEPTH} levels deep"),
+	)
+}
+
+/// A node in a YAML document, along with its properties.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Node {
+	pub(crate) anchor: Option<String>,
+	/// The fully resolved tag of the node, if it has an explicit one.
+	pub(crate) tag: Option<String>,
+	pub(crate) content: Content,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Content {
+	Scalar(String, ScalarStyle),
+	/// A sequence, with whether it's written in flow style.
+	Sequence(Vec<Node>, bool),
+	/// A mapping, with whether it's written in flow style.
+	Mapping(Vec<(Node, Node)>, bool),
+	/// A reference to the most recent node with the given anchor.
+	Alias(String),
+}
+
+impl Node {
+	/// Creates a node with the given content and no properties.
+	pub(crate) fn new(content: Content) -> Node {
+		Node {
+			anchor: None,
+			tag: None,
+			content,
+		}
+	}
+
+	/// Returns the kind of document that this node would be the root of.
+	///
+	/// An alias is considered a scalar, as it is with no other information
+	/// about the node it refers to.
+	pub(crate) fn kind(&self) -> DocumentKind {
+		match self.content {
+			Content::Scalar(..) | Content::Alias(_) => DocumentKind::Scalar,
+			Content::Sequence(..) => DocumentKind::Sequence,
+			Content::Mapping(..) => DocumentKind::Mapping,
+		}
+	}
+
+	/// Replaces aliases to anchors defined outside of this node with copies of
+	/// the nodes they refer to, so that the node can stand on its own as the
+	/// root of a document.
+	///
+	/// `anchors` holds the anchored nodes that precede this one in the
+	/// original document, and is updated with those defined inside this node
+	/// so that it can be passed along to the next one.
+	pub(crate) fn inline_aliases(&mut self, anchors: &mut HashMap<String, Node>) -> io::Result<()> {
+		self.inline_aliases_within(anchors, &mut vec![])
+	}
+
+	fn inline_aliases_within(
+		&mut self,
+		anchors: &mut HashMap<String, Node>,
+		local: &mut Vec<String>,
+	) -> io::Result<()> {
+		match &mut self.content {
+			Content::Alias(name) if !local.contains(name) => {
+				let Some(node) = anchors.get(name) else {
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						format!("alias *{name} refers to an undefined anchor"),
+					));
+				};
+				*self = node.clone();
+				self.collect_anchors(local);
+			}
+			Content::Alias(_) | Content::Scalar(..) => {}
+			Content::Sequence(items, _) => {
+				for item in items {
+					item.inline_aliases_within(anchors, local)?;
+				}
+			}
+			Content::Mapping(entries, _) => {
+				for (key, value) in entries {
+					key.inline_aliases_within(anchors, local)?;
+					value.inline_aliases_within(anchors, local)?;
+				}
+			}
+		}
+		if let Some(anchor) = &self.anchor {
+			anchors.insert(anchor.clone(), self.clone());
+			local.push(anchor.clone());
+		}
+		Ok(())
+	}
+
+	/// Replaces every alias in the node with a copy of the node it refers to,
+	/// including aliases to anchors defined inside of this node.
+	pub(crate) fn expand_aliases(&mut self) -> io::Result<()> {
+		self.expand_aliases_within(&mut HashMap::new())
+	}
+
+	fn expand_aliases_within(&mut self, anchors: &mut HashMap<String, Node>) -> io::Result<()> {
+		match &mut self.content {
+			Content::Alias(name) => {
+				let Some(node) = anchors.get(name) else {
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						format!("alias *{name} refers to an undefined anchor"),
+					));
+				};
+				*self = node.clone();
+				return Ok(());
+			}
+			Content::Scalar(..) => {}
+			Content::Sequence(items, _) => {
+				for item in items {
+					item.expand_aliases_within(anchors)?;
+				}
+			}
+			Content::Mapping(entries, _) => {
+				for (key, value) in entries {
+					key.expand_aliases_within(anchors)?;
+					value.expand_aliases_within(anchors)?;
+				}
+			}
+		}
+		if let Some(anchor) = &self.anchor {
+			anchors.insert(anchor.clone(), self.clone());
+		}
+		Ok(())
+	}
+
+	/// Sets every collection in the node to flow style, or to block style
+	/// where libyaml allows it.
+	pub(crate) fn set_flow(&mut self, flow: bool) {
+		match &mut self.content {
+			Content::Scalar(..) | Content::Alias(_) => {}
+			Content::Sequence(items, is_flow) => {
+				*is_flow = flow;
+				items.iter_mut().for_each(|item| item.set_flow(flow));
+			}
+			Content::Mapping(entries, is_flow) => {
+				*is_flow = flow;
+				for (key, value) in entries {
+					key.set_flow(flow);
+					value.set_flow(flow);
+				}
+			}
+		}
+	}
+
+	/// Gives every node without a specific tag the tag it resolves to under
+	/// the YAML 1.2 core schema.
+	pub(crate) fn resolve_tags(&mut self) {
+		let resolved = match &mut self.content {
+			Content::Alias(_) => return,
+			Content::Scalar(value, style) => match (*style, self.tag.as_deref()) {
+				(YAML_PLAIN_SCALAR_STYLE, None) => core_scalar_type(value),
+				_ => "str",
+			},
+			Content::Sequence(items, _) => {
+				items.iter_mut().for_each(Node::resolve_tags);
+				"seq"
+			}
+			Content::Mapping(entries, _) => {
+				for (key, value) in entries {
+					key.resolve_tags();
+					value.resolve_tags();
+				}
+				"map"
+			}
+		};
+		if matches!(self.tag.as_deref(), None | Some("!")) {
+			self.tag = Some(format!("{CORE_TAG_PREFIX}{resolved}"));
+		}
+	}
+
+	fn collect_anchors(&self, anchors: &mut Vec<String>) {
+		if let Some(anchor) = &self.anchor {
+			anchors.push(anchor.clone());
+		}
+		match &self.content {
+			Content::Scalar(..) | Content::Alias(_) => {}
+			Content::Sequence(items, _) => {
+				items.iter().for_each(|item| item.collect_anchors(anchors))
+			}
+			Content::Mapping(entries, _) => entries.iter().for_each(|(key, value)| {
+				key.collect_anchors(anchors);
+				value.collect_anchors(anchors);
+			}),
+		}
+	}
+
+	/// Serializes the node as the root of a single YAML document.
+	///
+	/// The document has no start or end markers unless libyaml requires them.
+	pub(crate) fn to_document(&self, options: EmitterOptions) -> io::Result<String> {
+		let mut output = vec![];
+		let mut emitter = Emitter::new(&mut output, options);
+		emitter.stream_start()?;
+		emitter.document_start(true)?;
+		self.emit(&mut emitter)?;
+		emitter.document_end(true)?;
+		emitter.stream_end()?;
+		drop(emitter);
+		Ok(String::from_utf8(output).expect("libyaml emits UTF-8"))
+	}
+
+	fn emit<W: io::Write>(&self, emitter: &mut Emitter<W>) -> io::Result<()> {
+		let anchor = self.anchor.as_deref();
+		let tag = self.tag.as_deref();
+		match &self.content {
+			Content::Scalar(value, style) => emitter.scalar(anchor, tag, value, *style),
+			Content::Alias(name) => emitter.alias(name),
+			Content::Sequence(items, flow) => {
+				let style = match flow {
+					true => YAML_FLOW_SEQUENCE_STYLE,
+					false => YAML_ANY_SEQUENCE_STYLE,
+				};
+				emitter.sequence_start(anchor, tag, style)?;
+				for item in items {
+					item.emit(emitter)?;
+				}
+				emitter.sequence_end()
+			}
+			Content::Mapping(entries, flow) => {
+				let style = match flow {
+					true => YAML_FLOW_MAPPING_STYLE,
+					false => YAML_ANY_MAPPING_STYLE,
+				};
+				emitter.mapping_start(anchor, tag, style)?;
+				for (key, value) in entries {
+					key.emit(emitter)?;
+					value.emit(emitter)?;
+				}
+				emitter.mapping_end()
+			}
+		}
+	}
+}
+
+/// Returns the name of the type that an untagged plain scalar resolves to under
+/// the YAML 1.2 core schema: `null`, `bool`, `int`, `float`, or `str`.
+pub(crate) fn core_scalar_type(value: &str) -> &'static str {
+	let unsigned = value.strip_prefix(['+', '-']).unwrap_or(value);
+	let is_digits = |s: &str, radix| !s.is_empty() && s.chars().all(|c| c.is_digit(radix));
+	match value {
+		"" | "~" | "null" | "Null" | "NULL" => "null",
+		"true" | "True" | "TRUE" | "false" | "False" | "FALSE" => "bool",
+		".nan" | ".NaN" | ".NAN" => "float",
+		_ if matches!(unsigned, ".inf" | ".Inf" | ".INF") => "float",
+		_ if is_digits(unsigned, 10) => "int",
+		_ if value.strip_prefix("0o").is_some_and(|s| is_digits(s, 8)) => "int",
+		_ if value.strip_prefix("0x").is_some_and(|s| is_digits(s, 16)) => "int",
+		_ if is_core_float(unsigned) => "float",
+		_ => "str",
+	}
+}
+
+/// Returns whether an unsigned value matches the core schema's pattern for
+/// finite floats, `(\.[0-9]+|[0-9]+(\.[0-9]*)?)([eE][-+]?[0-9]+)?`.
+fn is_core_float(value: &str) -> bool {
+	let (mantissa, exponent) = match value.find(['e', 'E']) {
+		Some(i) => (&value[..i], Some(&value[i + 1..])),
+		None => (value, None),
+	};
+	let (whole, fraction) = match mantissa.split_once('.') {
+		Some((whole, fraction)) => (whole, Some(fraction)),
+		None => (mantissa, None),
+	};
+	let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+	let mantissa_ok = match fraction {
+		None => !whole.is_empty() && digits(whole),
+		Some(fraction) => {
+			digits(whole) && digits(fraction) && !(whole.is_empty() && fraction.is_empty())
+		}
+	};
+	let exponent_ok = exponent.map_or(true, |e| {
+		let e = e.strip_prefix(['+', '-']).unwrap_or(e);
+		!e.is_empty() && digits(e)
+	});
+	mantissa_ok && exponent_ok
+}
+
+/// Parses the root nodes of every document in a UTF-8 YAML stream.
+pub(crate) fn parse(text: &str) -> io::Result<Vec<Node>> {
+	let mut parser = Parser::new(text.as_bytes());
+	let mut roots = vec![];
+	loop {
+		let event = parser.next_event()?;
+		match event.event_type() {
+			YAML_STREAM_END_EVENT => return Ok(roots),
+			YAML_DOCUMENT_START_EVENT => {
+				let root = parser.next_event()?;
+				roots.push(build(&mut parser, root, 0)?);
+				let end = parser.next_event()?;
+				debug_assert_eq!(end.event_type(), YAML_DOCUMENT_END_EVENT);
+			}
+			_ => {}
+		}
+	}
+}
+
+/// Builds the node that starts with `event`, nested in `depth` collections,
+/// reading any further events that make up its content.
+fn build(parser: &mut Parser<&[u8]>, event: Event, depth: usize) -> io::Result<Node> {
+	let is_collection = matches!(
+		event.event_type(),
+		YAML_SEQUENCE_START_EVENT | YAML_MAPPING_START_EVENT
+	);
+	if is_collection && depth >= MAX_DEPTH {
+		return Err(too_deep());
+	}
+	let content = match event.event_type() {
+		YAML_ALIAS_EVENT => {
+			let name = event.anchor().unwrap_or_default().into_owned();
+			return Ok(Node::new(Content::Alias(name)));
+		}
+		YAML_SCALAR_EVENT => {
+			let (value, style) = event.scalar();
+			Content::Scalar(value.into_owned(), style)
+		}
+		YAML_SEQUENCE_START_EVENT => {
+			let mut items = vec![];
+			loop {
+				let next = parser.next_event()?;
+				if next.event_type() == YAML_SEQUENCE_END_EVENT {
+					break;
+				}
+				items.push(build(parser, next, depth + 1)?);
+			}
+			Content::Sequence(items, event.is_flow())
+		}
+		YAML_MAPPING_START_EVENT => {
+			let mut entries = vec![];
+			loop {
+				let next = parser.next_event()?;
+				if next.event_type() == YAML_MAPPING_END_EVENT {
+					break;
+				}
+				let key = build(parser, next, depth + 1)?;
+				let value = parser.next_event()?;
+				entries.push((key, build(parser, value, depth + 1)?));
+			}
+			Content::Mapping(entries, event.is_flow())
+		}
+		other => unreachable!("unexpected {other:?} in a node"),
+	};
+	Ok(Node {
+		anchor: event.anchor().map(|a| a.into_owned()),
+		tag: event.tag().map(|t| t.into_owned()),
+		content,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn node_round_trip() {
+		const INPUT: &str = "a: &x [1, 'two']\nb: *x\nc: !!str 3\n";
+		let roots = parse(INPUT).unwrap();
+		assert_eq!(roots.len(), 1);
+		assert_eq!(roots[0].kind(), DocumentKind::Mapping);
+		assert_eq!(
+			roots[0].to_document(EmitterOptions::default()).unwrap(),
+			"a: &x [1, 'two']\nb: *x\nc: !!str 3\n"
+		);
+	}
+
+	#[test]
+	fn node_depth_limit() {
+		let nested = |depth: usize| format!("{}{}\n", "[".repeat(depth), "]".repeat(depth));
+		assert!(parse(&nested(MAX_DEPTH)).is_ok());
+		let err = parse(&nested(20_000)).unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+		let block = (0..=MAX_DEPTH)
+			.map(|depth| format!("{}- \n", "  ".repeat(depth)))
+			.collect::<String>();
+		assert!(parse(&format!("{block}{}x\n", "  ".repeat(MAX_DEPTH + 1))).is_err());
+	}
+
+	#[test]
+	fn node_inline_aliases() {
+		let roots = parse("- &a {k: v}\n- *a\n- [&b x, *b]\n").unwrap();
+		let Content::Sequence(items, _) = &roots[0].content else {
+			panic!("expected a sequence");
+		};
+		let mut anchors = HashMap::new();
+		let items = items
+			.iter()
+			.map(|item| {
+				let mut item = item.clone();
+				item.inline_aliases(&mut anchors).unwrap();
+				item.to_document(EmitterOptions::default()).unwrap()
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(items, ["&a {k: v}\n", "&a {k: v}\n", "[&b x, *b]\n"]);
+	}
+
+	#[test]
+	fn node_expand_aliases() {
+		let mut root = parse("- &a {k: v}\n- [&b x, *b, *a]\n").unwrap().remove(0);
+		root.expand_aliases().unwrap();
+		assert_eq!(
+			root.to_document(EmitterOptions::default()).unwrap(),
+			"- &a {k: v}\n- [&b x, &b x, &a {k: v}]\n"
+		);
+	}
+
+	#[test]
+	fn node_core_scalar_types() {
+		for (value, expected) in [
+			("", "null"),
+			("~", "null"),
+			("True", "bool"),
+			("-12", "int"),
+			("0o17", "int"),
+			("0xFf", "int"),
+			("1.5e-3", "float"),
+			(".5", "float"),
+			("2.", "float"),
+			("-.inf", "float"),
+			(".NaN", "float"),
+			(".", "str"),
+			("1e", "str"),
+			("0b1", "str"),
+			("yes", "str"),
+		] {
+			assert_eq!(core_scalar_type(value), expected, "{value:?}");
+		}
+	}
+}
@@ -0,0 +1,355 @@
+//! Names for the files that documents are written to, one file per document.
+//!
+//! A template is a file name with placeholders in braces that are filled in
+//! from each document:
+//!
+//! - `{index}`: the number of the document among those written, from 1
+//! - `{file_index}`: the number of the document among those written from its
+//!   input, from 1
+//! - `{total}`: the number of documents in the input, from `--total-counts`
+//! - `{line}`: the one-based line of the input that the document starts on
+//! - `{hash}`: the SHA-256 digest of the document's content in hex
+//! - `{name}`: the name of the document, such as one given by
+//!   `--name-from-path`, with any `/` or control character replaced by `_`
+//! - `{id}`: the document's stable ID from `--ids`
+//! - `{source}`: the file name of the document's input, without its directory
+//!   or extension
+//!
+//! A number may be padded with zeros to a width, as in `{index:04}`, and a
+//! digest cut to a length, as in `{hash:12}`. Literal braces are written
+//! doubled, as `{{` and `}}`.
+
+use std::fmt::{self, Display};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::chunker::Document;
+use crate::hash;
+
+/// Where a document falls among those written, for filling in a template.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Place<'a> {
+	/// The document's number among those written, from one.
+	pub(crate) index: usize,
+	/// The document's number among those written from its input, from one.
+	pub(crate) file_index: usize,
+	/// The number of documents in the input, if they were counted first.
+	pub(crate) total: Option<usize>,
+	/// The name of the input, for a document that doesn't record its own.
+	pub(crate) source: Option<&'a str>,
+}
+
+/// A parsed file name template.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Template {
+	text: String,
+	pieces: Vec<Piece>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Piece {
+	Text(String),
+	/// The document's number, padded with zeros to a width.
+	Index(usize),
+	/// The document's number within its input, padded with zeros to a width.
+	FileIndex(usize),
+	/// The number of documents, padded with zeros to a width.
+	Total(usize),
+	/// The document's line in the input, padded with zeros to a width.
+	Line(usize),
+	/// The document's digest, cut to a length if one is given.
+	Hash(Option<usize>),
+	Name,
+	Id,
+	Source,
+}
+
+impl Template {
+	/// Returns a template for names made of `prefix`, then the document's
+	/// number padded with zeros to `width` digits, then `suffix`.
+	pub(crate) fn numbered(prefix: &str, width: usize, suffix: &str) -> Self {
+		let escape = |text: &str| text.replace('{', "{{").replace('}', "}}");
+		Self {
+			text: format!("{}{{index:0{width}}}{}", escape(prefix), escape(suffix)),
+			pieces: vec![
+				Piece::Text(prefix.to_owned()),
+				Piece::Index(width),
+				Piece::Text(suffix.to_owned()),
+			],
+		}
+	}
+
+	/// Returns a template for names made of the document's name, then
+	/// `suffix`.
+	pub(crate) fn named(suffix: &str) -> Self {
+		Self {
+			text: format!("{{name}}{}", suffix.replace('{', "{{").replace('}', "}}")),
+			pieces: vec![Piece::Name, Piece::Text(suffix.to_owned())],
+		}
+	}
+
+	/// Returns the file name for `doc`, which is at `place` among the
+	/// documents written.
+	///
+	/// This fails if the template needs something the document or its place
+	/// doesn't have, such as a line, name, ID, input, or total, or if the name
+	/// would be `.` or `..`.
+	pub(crate) fn render(&self, place: Place<'_>, doc: &Document) -> io::Result<String> {
+		let index = place.index;
+		let missing = |what: &str| {
+			io::Error::new(
+				io::ErrorKind::InvalidInput,
+				format!("{self} needs {what} of document {index}, which is not known"),
+			)
+		};
+		let mut name = String::new();
+		for piece in &self.pieces {
+			match *piece {
+				Piece::Text(ref text) => name.push_str(text),
+				Piece::Index(width) => name.push_str(&format!("{index:0width$}")),
+				Piece::FileIndex(width) => {
+					name.push_str(&format!("{:0width$}", place.file_index));
+				}
+				Piece::Total(width) => {
+					let Some(total) = place.total else {
+						return Err(io::Error::new(
+							io::ErrorKind::InvalidInput,
+							format!(
+								"{self} needs the total number of documents; pass --total-counts"
+							),
+						));
+					};
+					name.push_str(&format!("{total:0width$}"));
+				}
+				Piece::Line(width) => {
+					let Some(position) = doc.position() else {
+						return Err(missing("the line"));
+					};
+					let line = position.start.line + 1;
+					name.push_str(&format!("{line:0width$}"));
+				}
+				Piece::Hash(len) => {
+					let mut digest = hash::Algorithm::Sha256.hex_digest(doc.content().as_bytes());
+					if let Some(len) = len {
+						digest.truncate(len);
+					}
+					name.push_str(&digest);
+				}
+				Piece::Name => {
+					let Some(doc_name) = doc.name() else {
+						return Err(io::Error::new(
+							io::ErrorKind::InvalidInput,
+							format!("{self} needs a name for document {index}, which has none"),
+						));
+					};
+					name.extend(doc_name.chars().map(safe));
+				}
+				Piece::Id => {
+					let Some(id) = doc.id() else {
+						return Err(missing("the ID, from --ids,"));
+					};
+					name.push_str(id);
+				}
+				Piece::Source => {
+					let source = doc
+						.input()
+						.map(|input| input.name.as_str())
+						.or(place.source);
+					let Some(stem) = source.and_then(|name| Path::new(name).file_stem()) else {
+						return Err(missing("the input"));
+					};
+					name.extend(stem.to_string_lossy().chars().map(safe));
+				}
+			}
+		}
+		if name.is_empty() || name == "." || name == ".." {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				format!("{self} gives document {index} the file name {name:?}"),
+			));
+		}
+		Ok(name)
+	}
+}
+
+/// Replaces a character that can't be part of a file name, or shouldn't be,
+/// with `_`.
+fn safe(c: char) -> char {
+	match c {
+		'/' | '\\' => '_',
+		c if c.is_control() => '_',
+		c => c,
+	}
+}
+
+impl FromStr for Template {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		if text.contains('/') {
+			return Err("a file name template can't contain '/'".to_owned());
+		}
+		let mut pieces = vec![];
+		let mut literal = String::new();
+		let mut rest = text;
+		while let Some(i) = rest.find(['{', '}']) {
+			literal.push_str(&rest[..i]);
+			let after = &rest[i + 1..];
+			if rest[i..].starts_with("{{") || rest[i..].starts_with("}}") {
+				literal.push_str(&rest[i..i + 1]);
+				rest = &after[1..];
+				continue;
+			}
+			if rest[i..].starts_with('}') {
+				return Err("unmatched '}' in template; write '}}' for a brace".to_owned());
+			}
+			let Some((placeholder, after)) = after.split_once('}') else {
+				return Err("unclosed '{' in template".to_owned());
+			};
+			let (name, spec) = match placeholder.split_once(':') {
+				None => (placeholder, None),
+				Some((name, spec)) => {
+					let n = spec
+						.parse::<usize>()
+						.map_err(|_| format!("invalid width {spec:?} in {{{placeholder}}}"))?;
+					(name, Some(n))
+				}
+			};
+			if !literal.is_empty() {
+				pieces.push(Piece::Text(std::mem::take(&mut literal)));
+			}
+			pieces.push(match name {
+				"index" => Piece::Index(spec.unwrap_or(0)),
+				"file_index" => Piece::FileIndex(spec.unwrap_or(0)),
+				"total" => Piece::Total(spec.unwrap_or(0)),
+				"line" => Piece::Line(spec.unwrap_or(0)),
+				"hash" => Piece::Hash(spec),
+				"name" if spec.is_none() => Piece::Name,
+				"id" if spec.is_none() => Piece::Id,
+				"source" if spec.is_none() => Piece::Source,
+				_ => return Err(format!("unknown placeholder {{{placeholder}}} in template")),
+			});
+			rest = after;
+		}
+		literal.push_str(rest);
+		if !literal.is_empty() {
+			pieces.push(Piece::Text(literal));
+		}
+		if !pieces.iter().any(|piece| !matches!(piece, Piece::Text(_))) {
+			return Err("a file name template needs a placeholder like {index}".to_owned());
+		}
+		Ok(Self {
+			text: text.to_owned(),
+			pieces,
+		})
+	}
+}
+
+impl Display for Template {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.text)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::chunker::{Chunker, DocumentKind, Input};
+
+	/// Returns the place of document `index` of a single input whose total
+	/// isn't known.
+	fn nth(index: usize) -> Place<'static> {
+		Place {
+			index,
+			file_index: index,
+			total: None,
+			source: None,
+		}
+	}
+
+	#[test]
+	fn template_render() {
+		let docs = Chunker::new("a: 1\n---\nb: 2\n".as_bytes())
+			.collect::<io::Result<Vec<_>>>()
+			.unwrap();
+		let render = |text: &str, index, doc: &Document| {
+			text.parse::<Template>()
+				.unwrap()
+				.render(nth(index), doc)
+				.unwrap()
+		};
+		assert_eq!(render("cm-{index}.yaml", 1, &docs[0]), "cm-1.yaml");
+		assert_eq!(
+			render("{index:04}-{line:3}.yaml", 12, &docs[1]),
+			"0012-002.yaml"
+		);
+		assert_eq!(render("{hash:8}.yaml", 1, &docs[0]), "37b128c5.yaml");
+		assert_eq!(render("{{{index}}}", 2, &docs[0]), "{2}");
+		assert_eq!(
+			Template::numbered("a{", 4, ".yaml")
+				.render(nth(3), &docs[0])
+				.unwrap(),
+			"a{0003.yaml"
+		);
+		let named = Document::new("a: 1\n".to_owned(), DocumentKind::Mapping, 0..5)
+			.with_name("a/b\n".to_owned());
+		assert_eq!(
+			Template::named(".yaml").render(nth(1), &named).unwrap(),
+			"a_b_.yaml"
+		);
+		assert!(Template::named("").render(nth(1), &docs[0]).is_err());
+		let dots = Document::new("a: 1\n".to_owned(), DocumentKind::Mapping, 0..5)
+			.with_name("..".to_owned());
+		assert!(Template::named("").render(nth(1), &dots).is_err());
+		let unplaced = Document::new("--- a\n".to_owned(), DocumentKind::Scalar, 0..6);
+		let line = "{line}.yaml".parse::<Template>().unwrap();
+		assert!(line.render(nth(1), &unplaced).is_err());
+
+		let counted = "{file_index:2}-of-{total:2}.yaml"
+			.parse::<Template>()
+			.unwrap();
+		let place = Place {
+			index: 7,
+			file_index: 3,
+			total: Some(12),
+			source: None,
+		};
+		assert_eq!(counted.render(place, &docs[0]).unwrap(), "03-of-12.yaml");
+		assert!(counted.render(nth(3), &docs[0]).is_err());
+		let sourced = Document::new("a: 1\n".to_owned(), DocumentKind::Mapping, 0..5)
+			.with_id("1a2b".to_owned())
+			.with_input(Input {
+				name: "dir/app.yaml".to_owned(),
+				index: 0,
+			});
+		let render = |text: &str, doc| text.parse::<Template>().unwrap().render(nth(1), doc);
+		assert_eq!(
+			render("{source}-{id}.yaml", &sourced).unwrap(),
+			"app-1a2b.yaml"
+		);
+		assert!(render("{id}", &docs[0]).is_err());
+		assert!(render("{source}", &docs[0]).is_err());
+		let place = Place {
+			source: Some("in.yml"),
+			..nth(1)
+		};
+		let source = "{source}.yaml".parse::<Template>().unwrap();
+		assert_eq!(source.render(place, &docs[0]).unwrap(), "in.yaml");
+
+		for invalid in [
+			"doc.yaml",
+			"{index",
+			"a}",
+			"{size}",
+			"{index:x}",
+			"{name:2}",
+			"{id:4}",
+			"{source:4}",
+			"a/{index}",
+		] {
+			assert!(invalid.parse::<Template>().is_err(), "{invalid}");
+		}
+	}
+}
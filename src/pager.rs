@@ -0,0 +1,106 @@
+//! Paging of interactive output, in the style of git.
+//!
+//! When enabled, output bound for a terminal is piped through the command in
+//! `PAGER`, or `less` if that isn't set. As with git, `LESS` defaults to `FRX`
+//! so that `less` exits right away when the output fits on one screen, passes
+//! through the escape sequences of colored output, and leaves the output on
+//! the screen after exiting.
+
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use crate::diagnostics::warning;
+use crate::pipecheck;
+
+/// A writer that feeds a running pager.
+///
+/// Dropping a `Pager` closes its input and waits for it to exit, so that the
+/// user can continue reading the output before yaml-split returns control to
+/// the shell.
+pub(crate) struct Pager {
+	child: Child,
+	stdin: Option<pipecheck::Writer<ChildStdin>>,
+}
+
+impl Pager {
+	/// Starts the user's pager, or returns `None` if paging is disabled by an
+	/// empty `PAGER` or the pager is not available.
+	pub(crate) fn spawn() -> Option<Pager> {
+		// WASI has no way to start another process, so there's nothing to try.
+		if cfg!(target_os = "wasi") {
+			return None;
+		}
+
+		let pager = env::var_os("PAGER").unwrap_or_else(|| OsString::from("less"));
+		if pager.is_empty() || pager == "cat" {
+			return None;
+		}
+
+		let mut cmd = shell_command(pager);
+		cmd.stdin(Stdio::piped());
+		if env::var_os("LESS").is_none() {
+			cmd.env("LESS", "FRX");
+		}
+		match cmd.spawn() {
+			Ok(mut child) => {
+				let stdin = child.stdin.take().map(pipecheck::Writer::new);
+				Some(Pager { child, stdin })
+			}
+			Err(err) => {
+				warning!("failed to start pager: {err}");
+				None
+			}
+		}
+	}
+
+	fn stdin(&mut self) -> &mut pipecheck::Writer<ChildStdin> {
+		self.stdin.as_mut().expect("pager input is open until drop")
+	}
+}
+
+/// Builds a command that runs a user's command line, like `pager`, which may
+/// include arguments.
+#[cfg(unix)]
+pub(crate) fn shell_command(line: OsString) -> Command {
+	let mut cmd = Command::new("sh");
+	cmd.arg("-c").arg(line);
+	cmd
+}
+
+/// Builds a command that runs a user's command line, like `pager`, which may
+/// include arguments.
+#[cfg(not(unix))]
+pub(crate) fn shell_command(line: OsString) -> Command {
+	let line = line.to_string_lossy();
+	let mut words = line.split_whitespace();
+	let mut cmd = Command::new(words.next().unwrap_or_default());
+	cmd.args(words);
+	cmd
+}
+
+impl Write for Pager {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.stdin().write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.stdin().flush()
+	}
+
+	fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+		self.stdin().write_all(buf)
+	}
+
+	fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+		self.stdin().write_vectored(bufs)
+	}
+}
+
+impl Drop for Pager {
+	fn drop(&mut self) {
+		drop(self.stdin.take());
+		let _ = self.child.wait();
+	}
+}
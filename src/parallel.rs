@@ -0,0 +1,178 @@
+//! Parallel processing of documents, for work on each document that would
+//! otherwise hold up the rest of the run behind it.
+//!
+//! Documents still come from the chunker one at a time, since finding where
+//! one ends means parsing it. The work on each document after that is handed
+//! to a pool of worker threads, and the results are put back in the order of
+//! their documents before anything downstream sees them. Only a few documents
+//! per worker are in flight at once, so memory use stays bounded however long
+//! the stream.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::chunker::Document;
+use crate::transform::Documents;
+
+/// The number of documents in flight per worker, enough that workers rarely
+/// wait on a slow document ahead of them.
+const IN_FLIGHT_PER_JOB: usize = 4;
+
+/// Applies `f` to each document on `jobs` worker threads, and returns the
+/// results in the order of the documents.
+///
+/// With a single job, `f` runs on the calling thread as each result is taken,
+/// exactly as a plain map would. An error reading the documents comes after
+/// the results for every document before it. A panic in `f` is resumed on the
+/// thread taking the results.
+pub(crate) fn map<T, F>(
+	docs: Documents,
+	jobs: usize,
+	f: F,
+) -> Box<dyn Iterator<Item = io::Result<T>>>
+where
+	T: Send + 'static,
+	F: Fn(Document) -> io::Result<T> + Send + Sync + 'static,
+{
+	// WASI has no threads to start, so documents are handled one at a time.
+	if jobs <= 1 || cfg!(target_os = "wasi") {
+		return Box::new(docs.map(move |doc| doc.and_then(&f)));
+	}
+	let window = jobs * IN_FLIGHT_PER_JOB;
+	let (job_tx, job_rx) = mpsc::sync_channel::<(usize, Document)>(window);
+	let (result_tx, result_rx) = mpsc::channel();
+	let job_rx = Arc::new(Mutex::new(job_rx));
+	let f = Arc::new(f);
+	// Workers exit once the jobs run out, and nothing waits for them, since
+	// the results of any still running when the consumer stops aren't wanted.
+	for _ in 0..jobs {
+		let job_rx = Arc::clone(&job_rx);
+		let result_tx = result_tx.clone();
+		let f = Arc::clone(&f);
+		thread::spawn(move || loop {
+			let job = job_rx.lock().unwrap_or_else(|err| err.into_inner()).recv();
+			let Ok((seq, doc)) = job else {
+				return;
+			};
+			let result = panic::catch_unwind(AssertUnwindSafe(|| f(doc)));
+			if result_tx.send((seq, result)).is_err() {
+				return;
+			}
+		});
+	}
+	Box::new(Ordered {
+		docs,
+		jobs: Some(job_tx),
+		results: result_rx,
+		done: BTreeMap::new(),
+		sent: 0,
+		next: 0,
+		window,
+	})
+}
+
+/// The results of a pool of workers, in the order of their documents.
+struct Ordered<T> {
+	docs: Documents,
+	/// The queue of documents for the workers, until the documents run out.
+	jobs: Option<SyncSender<(usize, Document)>>,
+	results: Receiver<(usize, thread::Result<io::Result<T>>)>,
+	/// Results that came back before the results of earlier documents.
+	done: BTreeMap<usize, io::Result<T>>,
+	/// The number of documents taken from `docs`.
+	sent: usize,
+	/// The number of the next document whose result is due.
+	next: usize,
+	window: usize,
+}
+
+impl<T> Iterator for Ordered<T> {
+	type Item = io::Result<T>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		while self.sent - self.next < self.window {
+			let Some(jobs) = &self.jobs else {
+				break;
+			};
+			match self.docs.next() {
+				Some(Ok(doc)) => {
+					jobs.send((self.sent, doc))
+						.expect("workers run until the queue closes");
+				}
+				Some(Err(err)) => {
+					self.done.insert(self.sent, Err(err));
+					self.jobs = None;
+				}
+				None => {
+					self.jobs = None;
+					break;
+				}
+			}
+			self.sent += 1;
+		}
+		loop {
+			if self.next == self.sent {
+				return None;
+			}
+			if let Some(result) = self.done.remove(&self.next) {
+				self.next += 1;
+				return Some(result);
+			}
+			let (seq, result) = self
+				.results
+				.recv()
+				.expect("workers finish every document they take");
+			match result {
+				Ok(result) => self.done.insert(seq, result),
+				Err(payload) => panic::resume_unwind(payload),
+			};
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::time::Duration;
+
+	use crate::chunker::Chunker;
+
+	#[test]
+	fn map_keeps_document_order() {
+		let input = (0..50).map(|i| format!("--- {i}\n")).collect::<String>();
+		let contents = |jobs| {
+			let docs = Box::new(Chunker::new(io::Cursor::new(input.clone())));
+			map(docs, jobs, |doc| {
+				// Later documents finish first, to be put back in order.
+				let n = doc.content()[4..].trim().parse::<u64>().unwrap();
+				thread::sleep(Duration::from_micros(50 - n));
+				Ok(doc.content().to_owned())
+			})
+			.collect::<io::Result<Vec<_>>>()
+			.unwrap()
+		};
+		let sequential = contents(1);
+		assert_eq!(sequential.len(), 50);
+		assert_eq!(contents(4), sequential);
+	}
+
+	#[test]
+	fn map_reports_errors_in_order() {
+		let docs: Documents = Box::new(Chunker::new("--- a\n--- b\n--- [\n".as_bytes()));
+		let results = map(docs, 3, |doc| match doc.content() {
+			"--- b\n" => Err(io::Error::new(io::ErrorKind::Other, "b")),
+			content => Ok(content.to_owned()),
+		})
+		.map(|result| result.map_err(|err| err.kind()))
+		.collect::<Vec<_>>();
+		assert_eq!(results.len(), 3);
+		assert_eq!(results[0].as_deref(), Ok("--- a\n"));
+		assert_eq!(results[1], Err(io::ErrorKind::Other));
+		assert!(results[2].is_err());
+	}
+}
@@ -1,13 +1,37 @@
 //! Cross-platform Unix-style handling of broken pipe errors.
 
 use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static EXIT_SUCCESS: AtomicBool = AtomicBool::new(false);
+
+/// The status that Unix shells report for a program terminated by SIGPIPE,
+/// which is also how other systems exit on a broken pipe.
+pub(crate) const EXIT_CODE: u8 = 141;
+
+/// The ways that a program can exit on a broken pipe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum PipeExit {
+	/// Terminate by SIGPIPE, which shells report as status 141. Non-Unix
+	/// systems exit with status 141 directly.
+	Signal,
+	/// Exit with status 0, as if all output had been written.
+	Success,
+}
+
+/// Sets how every [`Writer`] exits the program on a broken pipe.
+pub fn set_exit(exit: PipeExit) {
+	EXIT_SUCCESS.store(exit == PipeExit::Success, Ordering::Relaxed);
+}
 
 /// A writer that silently terminates the program on broken pipe errors.
 ///
 /// When any call to its underlying writer returns a [`BrokenPipe`](io::ErrorKind::BrokenPipe)
 /// error, a `Writer` will immediately terminate the program without returning
 /// from the call. On Unix(-like) systems, the program will be terminated by a
-/// SIGPIPE signal. Otherwise, it will exit with code 1.
+/// SIGPIPE signal. Otherwise, it will exit with code 141, the code that Unix
+/// shells report for SIGPIPE. Either way, [`set_exit`] can make it exit with
+/// code 0 instead.
 ///
 /// # Why is this useful?
 ///
@@ -42,7 +66,7 @@ use std::io::{self, Write};
 /// - <https://github.com/rust-lang/rust/issues/62569>
 /// - <https://stackoverflow.com/a/65760807>
 /// - <https://github.com/BurntSushi/ripgrep/issues/200#issuecomment-616884727>
-pub(crate) struct Writer<W>(W)
+pub struct Writer<W>(W)
 where
 	W: Write;
 
@@ -50,47 +74,73 @@ impl<W> Writer<W>
 where
 	W: Write,
 {
-	pub(crate) fn new(w: W) -> Writer<W> {
+	/// Wraps `w` to handle its broken pipe errors.
+	pub fn new(w: W) -> Writer<W> {
 		Writer(w)
 	}
 }
 
+/// In addition to handling broken pipes, `Writer` retries every call that
+/// fails with [`Interrupted`](io::ErrorKind::Interrupted), as when a signal
+/// arrives during a write. Writes are only retried when nothing was written,
+/// so a retried write never repeats output.
+///
+/// `write_all` and `write_fmt` are built on `write` rather than forwarded to
+/// the underlying writer, so that they follow the same rules regardless of how
+/// the underlying writer implements them.
 impl<W> Write for Writer<W>
 where
 	W: Write,
 {
 	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-		check_for_broken_pipe(self.0.write(buf))
+		check_for_broken_pipe(|| self.0.write(buf))
 	}
 
 	fn flush(&mut self) -> io::Result<()> {
-		check_for_broken_pipe(self.0.flush())
+		check_for_broken_pipe(|| self.0.flush())
 	}
 
 	// NOTE: This covers additional trait methods that are stable in Rust 1.70
 	// (xt's MSRV as of this writing). More may be added in the future.
 
-	fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
-		check_for_broken_pipe(self.0.write_all(buf))
-	}
-
-	fn write_fmt(&mut self, fmt: std::fmt::Arguments<'_>) -> io::Result<()> {
-		check_for_broken_pipe(self.0.write_fmt(fmt))
+	fn write_all(&mut self, mut buf: &[u8]) -> io::Result<()> {
+		while !buf.is_empty() {
+			match self.write(buf)? {
+				0 => {
+					return Err(io::Error::new(
+						io::ErrorKind::WriteZero,
+						"failed to write whole buffer",
+					))
+				}
+				n => buf = &buf[n..],
+			}
+		}
+		Ok(())
 	}
 
 	fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
-		check_for_broken_pipe(self.0.write_vectored(bufs))
+		check_for_broken_pipe(|| self.0.write_vectored(bufs))
 	}
 }
 
-fn check_for_broken_pipe<T>(result: io::Result<T>) -> io::Result<T> {
-	match result {
-		Err(err) if err.kind() == io::ErrorKind::BrokenPipe => exit_for_broken_pipe(),
-		result => result,
+fn check_for_broken_pipe<T, F>(mut op: F) -> io::Result<T>
+where
+	F: FnMut() -> io::Result<T>,
+{
+	loop {
+		match op() {
+			Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+			Err(err) if err.kind() == io::ErrorKind::BrokenPipe => exit_for_broken_pipe(),
+			result => return result,
+		}
 	}
 }
 
 fn exit_for_broken_pipe() -> ! {
+	if EXIT_SUCCESS.load(Ordering::Relaxed) {
+		std::process::exit(0);
+	}
+
 	#[cfg(unix)]
 	// SAFETY: These are FFI calls to libc, which we assume is implemented
 	// correctly. Because everything in the block comes from libc, there are no
@@ -100,7 +150,46 @@ fn exit_for_broken_pipe() -> ! {
 		libc::raise(libc::SIGPIPE);
 	}
 
-	// Non-Unix systems fall back to a normal silent exit (and Unix systems
-	// should not reach this line).
-	std::process::exit(1);
+	// Non-Unix systems fall back to a normal silent exit with the same status
+	// (and Unix systems should not reach this line).
+	std::process::exit(EXIT_CODE.into());
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A writer that accepts at most 2 bytes per call, and fails every other
+	/// call as if interrupted by a signal.
+	struct FlakyWriter {
+		written: Vec<u8>,
+		fail: bool,
+	}
+
+	impl Write for FlakyWriter {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			self.fail = !self.fail;
+			if self.fail {
+				return Err(io::ErrorKind::Interrupted.into());
+			}
+			let len = buf.len().min(2);
+			self.written.extend_from_slice(&buf[..len]);
+			Ok(len)
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn writer_retries_interrupted_writes() {
+		let mut writer = Writer::new(FlakyWriter {
+			written: vec![],
+			fail: false,
+		});
+		let (greeting, name) = ("hello", "world");
+		write!(writer, "{greeting}, {name}!").unwrap();
+		assert_eq!(writer.0.written, b"hello, world!");
+	}
 }
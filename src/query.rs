@@ -0,0 +1,216 @@
+//! Paths that select a single node from a document, like `.metadata.name`.
+//!
+//! The syntax is a small subset of jq's: a path starts with `.`, and continues
+//! with any number of `.key`, `."quoted key"`, and `[index]` segments. A path
+//! of just `.` selects the root of the document.
+//!
+//! A condition compares the scalar at a path with a value, as in
+//! `.kind=ConfigMap` or `.kind!=Secret`.
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::node::{Content, Node};
+
+/// A parsed path to a node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Query {
+	text: String,
+	segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+	/// The value for a scalar key in a mapping.
+	Key(String),
+	/// An item of a sequence, by its zero-based index.
+	Index(usize),
+}
+
+impl Query {
+	/// Returns the node at the end of the path, if every segment of the path
+	/// exists.
+	///
+	/// Aliases are not followed, so the node should have none; see
+	/// [`Node::expand_aliases`].
+	pub(crate) fn get<'a>(&self, mut node: &'a Node) -> Option<&'a Node> {
+		for segment in &self.segments {
+			node = match (segment, &node.content) {
+				(Segment::Key(key), Content::Mapping(entries, _)) => entries
+					.iter()
+					.find(|(k, _)| matches!(&k.content, Content::Scalar(k, _) if k == key))
+					.map(|(_, value)| value)?,
+				(Segment::Index(i), Content::Sequence(items, _)) => items.get(*i)?,
+				_ => return None,
+			};
+		}
+		Some(node)
+	}
+}
+
+impl FromStr for Query {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let Some(mut rest) = text.strip_prefix('.') else {
+			return Err("a path must start with '.'".to_owned());
+		};
+		let mut segments = vec![];
+		let mut dotted = true;
+		while !rest.is_empty() {
+			if let Some(after) = rest.strip_prefix('[') {
+				let Some((index, after)) = after.split_once(']') else {
+					return Err("unclosed '[' in path".to_owned());
+				};
+				let index = index
+					.parse()
+					.map_err(|_| format!("invalid index [{index}] in path"))?;
+				segments.push(Segment::Index(index));
+				rest = after;
+			} else if !dotted {
+				let Some(after) = rest.strip_prefix('.') else {
+					return Err(format!("expected '.' or '[' before {rest:?} in path"));
+				};
+				rest = after;
+				dotted = true;
+				continue;
+			} else if let Some(after) = rest.strip_prefix('"') {
+				let Some((key, after)) = after.split_once('"') else {
+					return Err("unclosed '\"' in path".to_owned());
+				};
+				segments.push(Segment::Key(key.to_owned()));
+				rest = after;
+			} else {
+				let end = rest.find(['.', '[']).unwrap_or(rest.len());
+				if end == 0 {
+					return Err("empty key in path".to_owned());
+				}
+				segments.push(Segment::Key(rest[..end].to_owned()));
+				rest = &rest[end..];
+			}
+			dotted = false;
+		}
+		if dotted && !segments.is_empty() {
+			return Err("path must not end with '.'".to_owned());
+		}
+		Ok(Query {
+			text: text.to_owned(),
+			segments,
+		})
+	}
+}
+
+impl Display for Query {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.text)
+	}
+}
+
+/// A parsed comparison of the scalar at a path with a value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Condition {
+	query: Query,
+	value: String,
+	/// Whether the condition holds when the scalar is not the value.
+	negated: bool,
+}
+
+impl Condition {
+	/// Returns whether the condition holds for the document with `root`.
+	///
+	/// A scalar is compared by its text, whatever its type, so `.replicas=3`
+	/// matches both `replicas: 3` and `replicas: "3"`. A path that is missing
+	/// or ends at a collection never equals the value.
+	pub(crate) fn matches(&self, root: &Node) -> bool {
+		let equal = match self.query.get(root).map(|node| &node.content) {
+			Some(Content::Scalar(value, _)) => *value == self.value,
+			_ => false,
+		};
+		equal != self.negated
+	}
+}
+
+impl FromStr for Condition {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		// A quoted key in the path may contain '=' itself.
+		let mut quoted = false;
+		let equals = text.char_indices().find_map(|(i, c)| match c {
+			'"' => {
+				quoted = !quoted;
+				None
+			}
+			'=' if !quoted => Some(i),
+			_ => None,
+		});
+		let Some(equals) = equals else {
+			return Err("a condition must be like .kind=ConfigMap or .kind!=Secret".to_owned());
+		};
+		let (path, negated) = match text[..equals].strip_suffix('!') {
+			Some(path) => (path, true),
+			None => (&text[..equals], false),
+		};
+		Ok(Condition {
+			query: path.parse()?,
+			value: text[equals + 1..].to_owned(),
+			negated,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::node;
+
+	#[test]
+	fn query_get() {
+		const INPUT: &str = "metadata: {name: app, \"a.b\": 1}\nitems: [[x, y]]\n";
+		let root = &node::parse(INPUT).unwrap()[0];
+		let get = |path: &str| match path.parse::<Query>().unwrap().get(root) {
+			Some(Node {
+				content: Content::Scalar(value, _),
+				..
+			}) => Some(value.as_str()),
+			Some(_) => Some("<collection>"),
+			None => None,
+		};
+		assert_eq!(get("."), Some("<collection>"));
+		assert_eq!(get(".metadata.name"), Some("app"));
+		assert_eq!(get(".metadata.\"a.b\""), Some("1"));
+		assert_eq!(get(".items[0][1]"), Some("y"));
+		assert_eq!(get(".items.[0].[0]"), Some("x"));
+		assert_eq!(get(".metadata.missing"), None);
+		assert_eq!(get(".items[3]"), None);
+		assert_eq!(get(".metadata[0]"), None);
+	}
+
+	#[test]
+	fn condition_matches() {
+		const INPUT: &str = "kind: ConfigMap\nspec: {replicas: '3', \"a=b\": c}\n";
+		let root = &node::parse(INPUT).unwrap()[0];
+		let matches = |text: &str| text.parse::<Condition>().unwrap().matches(root);
+		assert!(matches(".kind=ConfigMap"));
+		assert!(!matches(".kind=Secret"));
+		assert!(matches(".kind!=Secret"));
+		assert!(matches(".spec.replicas=3"));
+		assert!(matches(".spec.\"a=b\"=c"));
+		assert!(!matches(".spec=x"));
+		assert!(!matches(".missing="));
+		assert!(matches(".missing!=x"));
+		for text in ["", ".kind", "kind=a", "=a", ".\"a=b"] {
+			assert!(
+				text.parse::<Condition>().is_err(),
+				"{text:?} should be invalid"
+			);
+		}
+	}
+
+	#[test]
+	fn query_invalid() {
+		for path in ["", "a", ".a.", ".a..b", ".[x]", ".[1", ".\"a"] {
+			assert!(path.parse::<Query>().is_err(), "{path:?} should be invalid");
+		}
+	}
+}
@@ -0,0 +1,101 @@
+//! Throttling of output to a fixed rate, for consumers that can't keep up.
+//!
+//! A rate counts either documents or bytes of document content per second.
+//! The first document passes immediately, and each later one waits until the
+//! documents before it have used up their share of time, so the average rate
+//! over any stretch of the run never exceeds the limit.
+
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::budget;
+use crate::transform::Documents;
+
+/// A limit on how fast documents are produced.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Rate {
+	/// Documents per second.
+	Documents(f64),
+	/// Bytes of document content per second.
+	Bytes(f64),
+}
+
+impl FromStr for Rate {
+	type Err = String;
+
+	/// Parses a rate like `10/s` for documents, or `1MB/s` for bytes, where the
+	/// byte count takes the suffixes of [`budget::parse_size`].
+	fn from_str(text: &str) -> Result<Self, String> {
+		let invalid = || format!("invalid rate {text:?}, expected one like 10/s or 1MB/s");
+		let amount = text.strip_suffix("/s").ok_or_else(invalid)?;
+		let rate = match amount.strip_suffix(['B', 'b']) {
+			Some("") => return Err(invalid()),
+			Some(size) if size.ends_with(['K', 'k', 'M', 'm', 'G', 'g']) => {
+				Rate::Bytes(budget::parse_size(size).map_err(|_| invalid())? as f64)
+			}
+			Some(size) => Rate::Bytes(size.parse::<u64>().map_err(|_| invalid())? as f64),
+			None => Rate::Documents(amount.parse().map_err(|_| invalid())?),
+		};
+		match rate {
+			Rate::Documents(n) | Rate::Bytes(n) if n.is_finite() && n > 0.0 => Ok(rate),
+			_ => Err(format!("rate {text:?} must be more than zero")),
+		}
+	}
+}
+
+impl Rate {
+	/// Returns how long after the first document the next one may pass, once
+	/// `sent` units have passed before it.
+	fn due(self, sent: f64) -> Duration {
+		let per_second = match self {
+			Rate::Documents(n) | Rate::Bytes(n) => n,
+		};
+		Duration::try_from_secs_f64(sent / per_second).unwrap_or(Duration::MAX)
+	}
+}
+
+/// Produces documents no faster than `rate`, sleeping as needed.
+pub(crate) fn throttle(docs: Documents, rate: Rate) -> Documents {
+	let mut started = None;
+	let mut sent = 0.0;
+	Box::new(docs.map(move |doc| {
+		let doc = doc?;
+		let started = *started.get_or_insert_with(Instant::now);
+		let wait = rate.due(sent).saturating_sub(started.elapsed());
+		if !wait.is_zero() {
+			thread::sleep(wait);
+		}
+		sent += match rate {
+			Rate::Documents(_) => 1.0,
+			Rate::Bytes(_) => doc.content().len() as f64,
+		};
+		Ok(doc)
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_rates() {
+		assert_eq!("10/s".parse(), Ok(Rate::Documents(10.0)));
+		assert_eq!("0.5/s".parse(), Ok(Rate::Documents(0.5)));
+		assert_eq!("800B/s".parse(), Ok(Rate::Bytes(800.0)));
+		assert_eq!("1MB/s".parse(), Ok(Rate::Bytes((1 << 20) as f64)));
+		assert_eq!("64kb/s".parse(), Ok(Rate::Bytes((64 << 10) as f64)));
+		for text in [
+			"", "10", "/s", "B/s", "MB/s", "0/s", "-1/s", "inf/s", "1.5MB/s", "1TB/s",
+		] {
+			assert!(text.parse::<Rate>().is_err(), "{text:?} should be invalid");
+		}
+	}
+
+	#[test]
+	fn rate_due() {
+		assert_eq!(Rate::Documents(10.0).due(0.0), Duration::ZERO);
+		assert_eq!(Rate::Documents(10.0).due(5.0), Duration::from_millis(500));
+		assert_eq!(Rate::Bytes(1000.0).due(2500.0), Duration::from_millis(2500));
+	}
+}
@@ -0,0 +1,688 @@
+//! Regular expressions for matching the text of documents, without a
+//! dependency for something this small.
+//!
+//! The syntax is a common subset of what most engines accept:
+//!
+//! - literal characters, and `\` before any punctuation to match it literally
+//! - `.` for any character but a newline
+//! - classes like `[a-z_]` and `[^,]`, and `\d`, `\w`, and `\s` with their
+//!   negations `\D`, `\W`, and `\S`, in classes or out
+//! - `^` and `$` for the start and end of a line, and `\b` for the boundary
+//!   of a word
+//! - groups like `(a|b)`, which capture, and `(?:a|b)`, which don't
+//! - the repetitions `*`, `+`, `?`, `{n}`, `{n,}`, and `{n,m}`, which match as
+//!   much as possible, or as little with a `?` after them
+//!
+//! Matching follows every way that the pattern could match at once, rather
+//! than backtracking, so it takes time in proportion to the length of the
+//! text no matter how the pattern nests its repetitions, and never recurses
+//! over the text.
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// The most instructions that a pattern may compile to, which only a
+/// repetition with large counts, like `(a{1000}){1000}`, comes near.
+const MAX_PROGRAM_LEN: usize = 1 << 16;
+
+/// A compiled regular expression.
+#[derive(Clone, Debug)]
+pub(crate) struct Regex {
+	text: String,
+	program: Vec<Inst>,
+	groups: usize,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+	Char(char),
+	/// Any character but a newline.
+	Any,
+	Class(Class),
+	LineStart,
+	LineEnd,
+	WordBoundary,
+	/// A group, with its number if it captures.
+	Group(Box<Node>, Option<usize>),
+	Concat(Vec<Node>),
+	Alternation(Vec<Node>),
+	Repeat {
+		node: Box<Node>,
+		min: usize,
+		max: Option<usize>,
+		greedy: bool,
+	},
+}
+
+/// An instruction of a compiled pattern, which the matcher runs for every
+/// position of the text at once.
+#[derive(Clone, Debug)]
+enum Inst {
+	/// Consumes a character that the node, which matches one, matches.
+	Step(Node),
+	/// Continues only if the anchor node holds at the current position.
+	Assert(Node),
+	/// Continues at both instructions, preferring the first.
+	Split(usize, usize),
+	Jump(usize),
+	/// Records the current position in a capture slot.
+	Save(usize),
+	Match,
+}
+
+#[derive(Clone, Debug)]
+struct Class {
+	items: Vec<ClassItem>,
+	negated: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ClassItem {
+	Range(char, char),
+	/// One of the classes written with a backslash, or its negation.
+	Named(Named, bool),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Named {
+	Digit,
+	Word,
+	Space,
+}
+
+impl Named {
+	fn matches(self, c: char) -> bool {
+		match self {
+			Named::Digit => c.is_ascii_digit(),
+			Named::Word => is_word(c),
+			Named::Space => c.is_whitespace(),
+		}
+	}
+}
+
+impl Class {
+	fn matches(&self, c: char) -> bool {
+		let found = self.items.iter().any(|item| match *item {
+			ClassItem::Range(low, high) => (low..=high).contains(&c),
+			ClassItem::Named(named, negated) => named.matches(c) != negated,
+		});
+		found != self.negated
+	}
+}
+
+fn is_word(c: char) -> bool {
+	c.is_alphanumeric() || c == '_'
+}
+
+impl Regex {
+	/// Returns whether the pattern matches anywhere in `text`.
+	pub(crate) fn is_match(&self, text: &str) -> bool {
+		self.find(text).is_some()
+	}
+
+	/// Returns the text of each capture group in the first match in `text`,
+	/// starting with that of the whole match, or `None` if there is no match.
+	///
+	/// A group that took no part in the match is `None` in turn.
+	pub(crate) fn captures<'t>(&self, text: &'t str) -> Option<Vec<Option<&'t str>>> {
+		let matcher = self.find(text)?;
+		let captures = matcher.captures.iter();
+		Some(
+			captures
+				.map(|c| c.map(|(start, end)| &text[start..end]))
+				.collect(),
+		)
+	}
+
+	/// Returns the capture slots of the first match in `text`, if there is
+	/// one.
+	///
+	/// Threads of the match are kept in order of preference, so that the
+	/// first to reach the end of the pattern is the match that a backtracking
+	/// engine would find, and every thread after it can be dropped.
+	fn find(&self, text: &str) -> Option<Matcher> {
+		let slots = 2 * (self.groups + 1);
+		let mut current = Threads::new(self.program.len());
+		let mut next = Threads::new(self.program.len());
+		let mut found = None;
+		let mut pos = 0;
+		loop {
+			// A match starting here is preferred less than any that started
+			// earlier, and not tried at all once one has been found.
+			if found.is_none() {
+				self.add(&mut current, text, pos, 0, vec![None; slots]);
+			}
+			if current.list.is_empty() && found.is_some() {
+				break;
+			}
+			let c = text[pos..].chars().next();
+			let after = pos + c.map_or(0, char::len_utf8);
+			for (pc, captures) in current.list.drain(..) {
+				match &self.program[pc] {
+					Inst::Match => {
+						found = Some(captures);
+						break;
+					}
+					Inst::Step(node) => {
+						if c.is_some_and(|c| node_matches(node, c)) {
+							self.add(&mut next, text, after, pc + 1, captures);
+						}
+					}
+					_ => unreachable!("threads only wait on steps and matches"),
+				}
+			}
+			if c.is_none() {
+				break;
+			}
+			std::mem::swap(&mut current, &mut next);
+			next.clear();
+			pos = after;
+		}
+		found.map(|slots| Matcher {
+			captures: slots.chunks(2).map(|pair| pair[0].zip(pair[1])).collect(),
+		})
+	}
+
+	/// Adds a thread at `pc` to `threads`, following every instruction that
+	/// doesn't consume a character, in order of preference.
+	fn add(
+		&self,
+		threads: &mut Threads,
+		text: &str,
+		pos: usize,
+		pc: usize,
+		captures: Vec<Option<usize>>,
+	) {
+		let mut pending = vec![(pc, captures)];
+		while let Some((pc, mut captures)) = pending.pop() {
+			if !threads.visit(pc) {
+				continue;
+			}
+			match &self.program[pc] {
+				Inst::Step(_) | Inst::Match => threads.list.push((pc, captures)),
+				Inst::Assert(node) => {
+					if holds(node, text, pos) {
+						pending.push((pc + 1, captures));
+					}
+				}
+				Inst::Split(first, second) => {
+					pending.push((*second, captures.clone()));
+					pending.push((*first, captures));
+				}
+				Inst::Jump(to) => pending.push((*to, captures)),
+				Inst::Save(slot) => {
+					captures[*slot] = Some(pos);
+					pending.push((pc + 1, captures));
+				}
+			}
+		}
+	}
+}
+
+/// The threads of a match at one position of the text.
+struct Threads {
+	/// The instruction that each thread waits on, with its capture slots.
+	list: Vec<(usize, Vec<Option<usize>>)>,
+	/// The generation in which a thread last reached each instruction, since
+	/// only the first thread to reach one at a position matters.
+	visited: Vec<usize>,
+	/// The generation of the threads at this position.
+	generation: usize,
+}
+
+impl Threads {
+	fn new(len: usize) -> Self {
+		Self {
+			list: vec![],
+			visited: vec![0; len],
+			generation: 1,
+		}
+	}
+
+	/// Marks `pc` as reached, returning whether it wasn't already.
+	fn visit(&mut self, pc: usize) -> bool {
+		std::mem::replace(&mut self.visited[pc], self.generation) != self.generation
+	}
+
+	fn clear(&mut self) {
+		self.list.clear();
+		self.generation += 1;
+	}
+}
+
+/// The result of a match.
+struct Matcher {
+	/// The start and end of what each group captured.
+	captures: Vec<Option<(usize, usize)>>,
+}
+
+/// Returns whether `node`, which matches a single character, matches `c`.
+fn node_matches(node: &Node, c: char) -> bool {
+	match node {
+		Node::Char(expected) => c == *expected,
+		Node::Any => c != '\n',
+		Node::Class(class) => class.matches(c),
+		_ => unreachable!("only single characters are stepped over"),
+	}
+}
+
+/// Returns whether the anchor `node` holds at `pos` in `text`.
+fn holds(node: &Node, text: &str, pos: usize) -> bool {
+	match node {
+		Node::LineStart => pos == 0 || text[..pos].ends_with('\n'),
+		Node::LineEnd => pos == text.len() || text[pos..].starts_with('\n'),
+		Node::WordBoundary => {
+			let before = text[..pos].chars().next_back().is_some_and(is_word);
+			let after = text[pos..].chars().next().is_some_and(is_word);
+			before != after
+		}
+		_ => unreachable!("only anchors are asserted"),
+	}
+}
+
+/// Compiles parsed patterns into instructions.
+struct Compiler {
+	program: Vec<Inst>,
+}
+
+impl Compiler {
+	fn emit(&mut self, inst: Inst) -> Result<usize, String> {
+		if self.program.len() >= MAX_PROGRAM_LEN {
+			return Err("pattern repeats too much to compile".to_owned());
+		}
+		self.program.push(inst);
+		Ok(self.program.len() - 1)
+	}
+
+	fn compile(&mut self, node: &Node) -> Result<(), String> {
+		match node {
+			Node::Char(_) | Node::Any | Node::Class(_) => {
+				self.emit(Inst::Step(node.clone()))?;
+			}
+			Node::LineStart | Node::LineEnd | Node::WordBoundary => {
+				self.emit(Inst::Assert(node.clone()))?;
+			}
+			Node::Group(inner, None) => self.compile(inner)?,
+			Node::Group(inner, Some(group)) => {
+				self.emit(Inst::Save(2 * group))?;
+				self.compile(inner)?;
+				self.emit(Inst::Save(2 * group + 1))?;
+			}
+			Node::Concat(nodes) => {
+				for node in nodes {
+					self.compile(node)?;
+				}
+			}
+			Node::Alternation(branches) => {
+				let mut jumps = vec![];
+				for (i, branch) in branches.iter().enumerate() {
+					if i + 1 == branches.len() {
+						self.compile(branch)?;
+						break;
+					}
+					let split = self.emit(Inst::Split(0, 0))?;
+					self.compile(branch)?;
+					jumps.push(self.emit(Inst::Jump(0))?);
+					let len = self.program.len();
+					self.program[split] = Inst::Split(split + 1, len);
+				}
+				let end = self.program.len();
+				for jump in jumps {
+					self.program[jump] = Inst::Jump(end);
+				}
+			}
+			Node::Repeat {
+				node,
+				min,
+				max,
+				greedy,
+			} => {
+				for _ in 0..*min {
+					self.compile(node)?;
+				}
+				match max {
+					None => {
+						let split = self.emit(Inst::Split(0, 0))?;
+						self.compile(node)?;
+						self.emit(Inst::Jump(split))?;
+						let end = self.program.len();
+						self.program[split] = self.choice(split + 1, end, *greedy);
+					}
+					Some(max) => {
+						let mut splits = vec![];
+						for _ in *min..*max {
+							splits.push(self.emit(Inst::Split(0, 0))?);
+							self.compile(node)?;
+						}
+						let end = self.program.len();
+						for split in splits {
+							self.program[split] = self.choice(split + 1, end, *greedy);
+						}
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Returns a split between repeating again at `again` and going on at
+	/// `end`, preferring to repeat if `greedy`.
+	fn choice(&self, again: usize, end: usize, greedy: bool) -> Inst {
+		match greedy {
+			true => Inst::Split(again, end),
+			false => Inst::Split(end, again),
+		}
+	}
+}
+
+impl FromStr for Regex {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let mut parser = RegexParser {
+			chars: text.chars().collect(),
+			pos: 0,
+			groups: 0,
+		};
+		let root = parser.alternation()?;
+		if parser.pos < parser.chars.len() {
+			return Err("unmatched ')' in pattern".to_owned());
+		}
+		let mut compiler = Compiler { program: vec![] };
+		compiler.compile(&Node::Group(Box::new(root), Some(0)))?;
+		compiler.emit(Inst::Match)?;
+		Ok(Self {
+			text: text.to_owned(),
+			program: compiler.program,
+			groups: parser.groups,
+		})
+	}
+}
+
+impl Display for Regex {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.text)
+	}
+}
+
+struct RegexParser {
+	chars: Vec<char>,
+	pos: usize,
+	/// The number of capture groups so far.
+	groups: usize,
+}
+
+impl RegexParser {
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.pos).copied()
+	}
+
+	fn eat(&mut self, c: char) -> bool {
+		let found = self.peek() == Some(c);
+		if found {
+			self.pos += 1;
+		}
+		found
+	}
+
+	fn alternation(&mut self) -> Result<Node, String> {
+		let mut branches = vec![self.concat()?];
+		while self.eat('|') {
+			branches.push(self.concat()?);
+		}
+		Ok(match branches.len() {
+			1 => branches.pop().unwrap(),
+			_ => Node::Alternation(branches),
+		})
+	}
+
+	fn concat(&mut self) -> Result<Node, String> {
+		let mut nodes = vec![];
+		while let Some(c) = self.peek() {
+			if c == '|' || c == ')' {
+				break;
+			}
+			let atom = self.atom()?;
+			nodes.push(self.repetition(atom)?);
+		}
+		Ok(Node::Concat(nodes))
+	}
+
+	fn atom(&mut self) -> Result<Node, String> {
+		let c = self
+			.peek()
+			.expect("atoms are only parsed before a character");
+		self.pos += 1;
+		Ok(match c {
+			'(' => {
+				let group = match self.eat('?') {
+					true if self.eat(':') => None,
+					true => return Err("unsupported group syntax '(?' in pattern".to_owned()),
+					false => {
+						self.groups += 1;
+						Some(self.groups)
+					}
+				};
+				let inner = self.alternation()?;
+				if !self.eat(')') {
+					return Err("unclosed '(' in pattern".to_owned());
+				}
+				Node::Group(Box::new(inner), group)
+			}
+			'[' => Node::Class(self.class()?),
+			'.' => Node::Any,
+			'^' => Node::LineStart,
+			'$' => Node::LineEnd,
+			'\\' => match self.escape()? {
+				Escaped::Char(c) => Node::Char(c),
+				Escaped::Named(named, negated) => Node::Class(Class {
+					items: vec![ClassItem::Named(named, negated)],
+					negated: false,
+				}),
+				Escaped::WordBoundary => Node::WordBoundary,
+			},
+			'*' | '+' | '?' => return Err(format!("nothing for '{c}' to repeat in pattern")),
+			c => Node::Char(c),
+		})
+	}
+
+	/// Parses any repetition of `atom` that follows it.
+	fn repetition(&mut self, atom: Node) -> Result<Node, String> {
+		let (min, max) = match self.peek() {
+			Some('{') => match self.counts() {
+				Some(counts) => counts,
+				None => return Ok(atom),
+			},
+			Some(c @ ('*' | '+' | '?')) => {
+				self.pos += 1;
+				match c {
+					'*' => (0, None),
+					'+' => (1, None),
+					_ => (0, Some(1)),
+				}
+			}
+			_ => return Ok(atom),
+		};
+		if matches!(atom, Node::LineStart | Node::LineEnd | Node::WordBoundary) {
+			return Err("an anchor can't be repeated in pattern".to_owned());
+		}
+		if max.is_some_and(|max| max < min) {
+			return Err(format!("repetition {{{min},{}}} is backward", max.unwrap()));
+		}
+		let greedy = !self.eat('?');
+		if matches!(self.peek(), Some('*' | '+' | '?')) {
+			return Err("a repetition can't be repeated in pattern".to_owned());
+		}
+		Ok(Node::Repeat {
+			node: Box::new(atom),
+			min,
+			max,
+			greedy,
+		})
+	}
+
+	/// Parses counts like `{2,5}`, consuming them only if they're complete,
+	/// since a `{` that doesn't start them is an ordinary character.
+	fn counts(&mut self) -> Option<(usize, Option<usize>)> {
+		let rest = &self.chars[self.pos + 1..];
+		let close = rest.iter().position(|&c| c == '}')?;
+		let inside = rest[..close].iter().collect::<String>();
+		let counts = match inside.split_once(',') {
+			None => {
+				let n = inside.parse().ok()?;
+				(n, Some(n))
+			}
+			Some((min, "")) => (min.parse().ok()?, None),
+			Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+		};
+		self.pos += close + 2;
+		Some(counts)
+	}
+
+	fn class(&mut self) -> Result<Class, String> {
+		let negated = self.eat('^');
+		let mut items = vec![];
+		let mut first = true;
+		loop {
+			let Some(c) = self.peek() else {
+				return Err("unclosed '[' in pattern".to_owned());
+			};
+			self.pos += 1;
+			if c == ']' && !first {
+				return Ok(Class { items, negated });
+			}
+			first = false;
+			let low = match c {
+				'\\' => match self.escape()? {
+					Escaped::Char(c) => c,
+					Escaped::Named(named, negated) => {
+						items.push(ClassItem::Named(named, negated));
+						continue;
+					}
+					Escaped::WordBoundary => return Err("'\\b' can't be in a class".to_owned()),
+				},
+				c => c,
+			};
+			let ranged = self.peek() == Some('-')
+				&& !matches!(self.chars.get(self.pos + 1), None | Some(']'));
+			if !ranged {
+				items.push(ClassItem::Range(low, low));
+				continue;
+			}
+			self.pos += 1;
+			let high = match self.chars[self.pos] {
+				'\\' => {
+					self.pos += 1;
+					match self.escape()? {
+						Escaped::Char(c) => c,
+						_ => return Err("a range in a class must end in a character".to_owned()),
+					}
+				}
+				c => {
+					self.pos += 1;
+					c
+				}
+			};
+			if high < low {
+				return Err(format!("range {low}-{high} in a class is backward"));
+			}
+			items.push(ClassItem::Range(low, high));
+		}
+	}
+
+	/// Parses what follows a backslash.
+	fn escape(&mut self) -> Result<Escaped, String> {
+		let Some(c) = self.peek() else {
+			return Err("pattern ends with '\\'".to_owned());
+		};
+		self.pos += 1;
+		Ok(match c {
+			'd' => Escaped::Named(Named::Digit, false),
+			'D' => Escaped::Named(Named::Digit, true),
+			'w' => Escaped::Named(Named::Word, false),
+			'W' => Escaped::Named(Named::Word, true),
+			's' => Escaped::Named(Named::Space, false),
+			'S' => Escaped::Named(Named::Space, true),
+			'b' => Escaped::WordBoundary,
+			'n' => Escaped::Char('\n'),
+			'r' => Escaped::Char('\r'),
+			't' => Escaped::Char('\t'),
+			c if c.is_alphanumeric() => return Err(format!("unknown escape '\\{c}' in pattern")),
+			c => Escaped::Char(c),
+		})
+	}
+}
+
+/// What a backslash and the character after it stand for.
+enum Escaped {
+	Char(char),
+	Named(Named, bool),
+	WordBoundary,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn captures<'t>(pattern: &str, text: &'t str) -> Option<Vec<Option<&'t str>>> {
+		pattern.parse::<Regex>().unwrap().captures(text)
+	}
+
+	#[test]
+	fn regex_matches() {
+		let first = |pattern, text| captures(pattern, text).map(|c| c[0].unwrap());
+		assert_eq!(first("b+", "abbbc"), Some("bbb"));
+		assert_eq!(first("b+?", "abbbc"), Some("b"));
+		assert_eq!(first("a.c", "a\nc abc"), Some("abc"));
+		assert_eq!(first("\\S+$", "name: web\n"), Some("web"));
+		assert_eq!(
+			first("^kind: (\\w+)", "a: 1\nkind: Pod\n"),
+			Some("kind: Pod")
+		);
+		assert_eq!(first("\\d{2,3}", "1 1234"), Some("123"));
+		assert_eq!(first("x{,2}", "x{,2}"), Some("x{,2}"));
+		assert_eq!(first("\\bcat\\b", "concat cat"), Some("cat"));
+		assert_eq!(first("(?:ab)+c", "abababc"), Some("abababc"));
+		assert_eq!(first("[a-c-]+", "x-b-a"), Some("-b-a"));
+		assert_eq!(first("(a|ab)(c|bcd)", "abcd"), Some("abcd"));
+		assert_eq!(first("(a*)*b", "aaab"), Some("aaab"));
+		assert_eq!(first("$", "ab"), Some(""));
+		assert_eq!(first("é+", "café"), Some("é"));
+		assert_eq!(first("z", "abc"), None);
+
+		let groups = captures("name: (\\S+)|id: (\\d+)", "id: 42").unwrap();
+		assert_eq!(groups, [Some("id: 42"), None, Some("42")]);
+		let kind = "^kind: (Pod|Job)$".parse::<Regex>().unwrap();
+		assert!(kind.is_match("a: 1\nkind: Job\n"));
+		assert!(!kind.is_match("kind: Jobs\n"));
+		// A repetition over a single character doesn't recurse per character.
+		let long = "a".repeat(1 << 20);
+		assert!(".*a$".parse::<Regex>().unwrap().captures(&long).is_some());
+	}
+
+	#[test]
+	fn regex_matches_long_text() {
+		// Neither repeating a group nor nesting repetitions recurses over the
+		// text or backtracks through it.
+		let long = "ab".repeat(100_000);
+		let first = |pattern: &str, text: &str| {
+			let regex = pattern.parse::<Regex>().unwrap();
+			regex.captures(text).map(|c| c[0].unwrap().len())
+		};
+		assert_eq!(first("(ab)+$", &long), Some(long.len()));
+		assert_eq!(first("^(a|b)*c", &long), None);
+		assert_eq!(first("(a*)*b", &"a".repeat(10_000)), None);
+		assert_eq!(first("(?:a?){30}a{30}", &"a".repeat(30)), Some(30));
+		assert_eq!(first("(a+?)(b)", "aab"), Some(3));
+		assert!("(a{1000}){1000}".parse::<Regex>().is_err());
+	}
+
+	#[test]
+	fn regex_errors() {
+		for invalid in [
+			"(a", "a)", "[a", "*a", "a**", "\\", "\\q", "[z-a]", "a{3,1}", "(?=a)", "^*",
+		] {
+			assert!(invalid.parse::<Regex>().is_err(), "{invalid}");
+		}
+	}
+}
@@ -0,0 +1,270 @@
+//! Restriction of the process before it reads untrusted input, for `--sandbox`.
+//!
+//! Landlock denies opening any file, except for creating and replacing files
+//! in the directories that the run still has to write to, like the one for a
+//! checkpoint, and reading the inputs that are only opened in turn, like those
+//! of `--recursive`. Files opened before entering the sandbox, like the input
+//! and the output, stay usable. A seccomp filter then denies creating sockets and
+//! running other programs, so that nothing the parser might be tricked into
+//! doing reaches the network.
+//!
+//! Both restrictions are permanent for the process and can't be lifted. Since
+//! the point is to be safe, entering the sandbox fails outright on systems
+//! that can't provide either of them, rather than running without them.
+
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Landlock access rights for files, from the first version of its ABI.
+mod access {
+	pub(super) const EXECUTE: u64 = 1 << 0;
+	pub(super) const WRITE_FILE: u64 = 1 << 1;
+	pub(super) const READ_FILE: u64 = 1 << 2;
+	pub(super) const READ_DIR: u64 = 1 << 3;
+	pub(super) const REMOVE_DIR: u64 = 1 << 4;
+	pub(super) const REMOVE_FILE: u64 = 1 << 5;
+	pub(super) const MAKE_CHAR: u64 = 1 << 6;
+	pub(super) const MAKE_DIR: u64 = 1 << 7;
+	pub(super) const MAKE_REG: u64 = 1 << 8;
+	pub(super) const MAKE_SOCK: u64 = 1 << 9;
+	pub(super) const MAKE_FIFO: u64 = 1 << 10;
+	pub(super) const MAKE_BLOCK: u64 = 1 << 11;
+	pub(super) const MAKE_SYM: u64 = 1 << 12;
+
+	/// Every right, so that the ruleset denies whatever it doesn't allow.
+	pub(super) const ALL: u64 = EXECUTE
+		| WRITE_FILE
+		| READ_FILE
+		| READ_DIR
+		| REMOVE_DIR
+		| REMOVE_FILE
+		| MAKE_CHAR
+		| MAKE_DIR
+		| MAKE_REG
+		| MAKE_SOCK
+		| MAKE_FIFO
+		| MAKE_BLOCK
+		| MAKE_SYM;
+
+	/// The rights to create a file, write it, and rename it over another.
+	pub(super) const REPLACE_FILE: u64 = WRITE_FILE | MAKE_REG | REMOVE_FILE;
+}
+
+/// The files that a run may still open once it's in the sandbox.
+#[derive(Default)]
+pub(crate) struct Allowed<'a> {
+	/// Files to create or replace, which may be any file in the same
+	/// directory.
+	pub(crate) writable: Vec<&'a Path>,
+	/// Directories to create files and directories anywhere beneath.
+	pub(crate) trees: Vec<&'a Path>,
+	/// Files to read, which are the files themselves if they're links.
+	pub(crate) readable: Vec<&'a Path>,
+}
+
+const LANDLOCK_CREATE_RULESET_VERSION: libc::c_uint = 1 << 0;
+const LANDLOCK_RULE_PATH_BENEATH: libc::c_int = 1;
+
+#[repr(C)]
+struct RulesetAttr {
+	handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+	allowed_access: u64,
+	parent_fd: libc::c_int,
+}
+
+/// The architecture that seccomp reports for this build, from the kernel's
+/// `linux/audit.h`.
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xC000_003E;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xC000_00B7;
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const AUDIT_ARCH: u32 = 0;
+
+/// The system calls that the seccomp filter denies.
+const DENIED_SYSCALLS: [libc::c_long; 9] = [
+	libc::SYS_socket,
+	libc::SYS_socketpair,
+	libc::SYS_connect,
+	libc::SYS_bind,
+	libc::SYS_listen,
+	libc::SYS_accept,
+	libc::SYS_accept4,
+	libc::SYS_execve,
+	libc::SYS_execveat,
+];
+
+/// Restricts the process for the rest of its life, allowing it to open only
+/// the files that `allowed` gives.
+pub(crate) fn enter(allowed: &Allowed) -> io::Result<()> {
+	if AUDIT_ARCH == 0 {
+		return Err(io::Error::new(
+			io::ErrorKind::Unsupported,
+			"--sandbox is not supported on this architecture",
+		));
+	}
+	// SAFETY: PR_SET_NO_NEW_PRIVS takes no pointers, and is required before
+	// an unprivileged process may restrict itself.
+	if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+		return Err(io::Error::last_os_error());
+	}
+	restrict_files(allowed)?;
+	deny_syscalls()
+}
+
+fn restrict_files(allowed: &Allowed) -> io::Result<()> {
+	// SAFETY: Querying the ABI version takes no attributes.
+	let abi = unsafe {
+		libc::syscall(
+			libc::SYS_landlock_create_ruleset,
+			std::ptr::null::<RulesetAttr>(),
+			0,
+			LANDLOCK_CREATE_RULESET_VERSION,
+		)
+	};
+	if abi < 1 {
+		return Err(io::Error::new(
+			io::ErrorKind::Unsupported,
+			"--sandbox requires Landlock, which this system does not provide",
+		));
+	}
+	let attr = RulesetAttr {
+		handled_access_fs: access::ALL,
+	};
+	// SAFETY: The attributes are valid for reads of the given size.
+	let ruleset = unsafe {
+		libc::syscall(
+			libc::SYS_landlock_create_ruleset,
+			&attr,
+			mem::size_of::<RulesetAttr>(),
+			0,
+		)
+	};
+	if ruleset < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	let ruleset = ruleset as libc::c_int;
+	let result = add_rules(ruleset, allowed).and_then(|()| {
+		// SAFETY: ruleset is a Landlock ruleset that we own.
+		match unsafe { libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0) } {
+			0 => Ok(()),
+			_ => Err(io::Error::last_os_error()),
+		}
+	});
+	// SAFETY: ruleset is a file descriptor that we own and no longer need.
+	unsafe { libc::close(ruleset) };
+	result
+}
+
+fn add_rules(ruleset: libc::c_int, allowed: &Allowed) -> io::Result<()> {
+	for path in &allowed.writable {
+		let dir = match path.parent() {
+			Some(dir) if dir != Path::new("") => dir,
+			_ => Path::new("."),
+		};
+		add_rule(ruleset, dir, access::REPLACE_FILE)?;
+	}
+	for dir in &allowed.trees {
+		add_rule(ruleset, dir, access::REPLACE_FILE | access::MAKE_DIR)?;
+	}
+	for path in &allowed.readable {
+		add_rule(ruleset, path, access::READ_FILE)?;
+	}
+	Ok(())
+}
+
+/// Allows `access` beneath `path`, or to `path` itself if it's a file.
+fn add_rule(ruleset: libc::c_int, path: &Path, access: u64) -> io::Result<()> {
+	let path = CString::new(path.as_os_str().as_bytes())?;
+	// SAFETY: path is a valid C string.
+	let fd = unsafe { libc::open(path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+	if fd < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	let attr = PathBeneathAttr {
+		allowed_access: access,
+		parent_fd: fd,
+	};
+	// SAFETY: The attributes are valid for reads for the duration of the
+	// call, and fd is a file descriptor that we own.
+	let result = unsafe {
+		let result = libc::syscall(
+			libc::SYS_landlock_add_rule,
+			ruleset,
+			LANDLOCK_RULE_PATH_BENEATH,
+			&attr,
+			0,
+		);
+		libc::close(fd);
+		result
+	};
+	match result {
+		0 => Ok(()),
+		_ => Err(io::Error::last_os_error()),
+	}
+}
+
+fn deny_syscalls() -> io::Result<()> {
+	let stmt = |code: u32, k: u32| libc::sock_filter {
+		code: code as u16,
+		jt: 0,
+		jf: 0,
+		k,
+	};
+	let jump = |op: u32, k: u32, jt: u8, jf: u8| libc::sock_filter {
+		code: (libc::BPF_JMP | op | libc::BPF_K) as u16,
+		jt,
+		jf,
+		k,
+	};
+	const LOAD: u32 = libc::BPF_LD | libc::BPF_W | libc::BPF_ABS;
+	const RETURN: u32 = libc::BPF_RET | libc::BPF_K;
+	// The offsets of the system call number and architecture in the kernel's
+	// struct seccomp_data.
+	const NR_OFFSET: u32 = 0;
+	const ARCH_OFFSET: u32 = 4;
+
+	// The x32 ABI of x86-64 sets this bit in its system call numbers.
+	const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+	// A call made through another architecture's numbering can't be checked
+	// against ours, so it kills the process.
+	let mut filter = vec![
+		stmt(LOAD, ARCH_OFFSET),
+		jump(libc::BPF_JEQ, AUDIT_ARCH, 1, 0),
+		stmt(RETURN, libc::SECCOMP_RET_KILL_PROCESS),
+		stmt(LOAD, NR_OFFSET),
+		jump(libc::BPF_JGE, X32_SYSCALL_BIT, 0, 1),
+		stmt(RETURN, libc::SECCOMP_RET_KILL_PROCESS),
+	];
+	for nr in DENIED_SYSCALLS {
+		filter.push(jump(libc::BPF_JEQ, nr as u32, 0, 1));
+		filter.push(stmt(RETURN, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32));
+	}
+	filter.push(stmt(RETURN, libc::SECCOMP_RET_ALLOW));
+	let program = libc::sock_fprog {
+		len: filter.len() as u16,
+		filter: filter.as_mut_ptr(),
+	};
+	// SAFETY: The program points to a filter of the given length, which the
+	// kernel copies before returning.
+	let result = unsafe {
+		libc::syscall(
+			libc::SYS_seccomp,
+			libc::SECCOMP_SET_MODE_FILTER,
+			libc::SECCOMP_FILTER_FLAG_TSYNC,
+			&program,
+		)
+	};
+	match result {
+		0 => Ok(()),
+		_ => Err(io::Error::last_os_error()),
+	}
+}
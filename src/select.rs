@@ -0,0 +1,240 @@
+//! Selection of documents by their position in the stream, as with
+//! `--select 0,3,10-20,-1`.
+//!
+//! Positions count from zero, and a negative position counts back from the
+//! end, so that `-1` is the last document. A range like `10-20` includes both
+//! ends, and one like `10-` runs to the end of the stream.
+//!
+//! Documents are always produced in their original order, each at most once,
+//! however the selection lists them. Once every document the selection could
+//! match has passed, no more are read. Positions from the end can't be known
+//! until the stream ends, so a selection with any of them holds that many of
+//! the latest documents in memory.
+
+use std::collections::VecDeque;
+use std::io;
+use std::str::FromStr;
+
+use crate::budget::Claim;
+use crate::chunker::Document;
+use crate::transform::Documents;
+
+/// A parsed list of positions and ranges.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Selection {
+	/// Ranges of positions from the start, with `usize::MAX` as the end of a
+	/// range that runs to the end of the stream.
+	ranges: Vec<(usize, usize)>,
+	/// Positions from the end, with the last document at 1.
+	from_end: Vec<usize>,
+}
+
+impl Selection {
+	fn contains(&self, position: usize) -> bool {
+		self.ranges
+			.iter()
+			.any(|&(start, end)| (start..=end).contains(&position))
+	}
+
+	/// Returns the last position from the start that the selection could
+	/// match, or `None` if it could match any position.
+	fn last(&self) -> Option<usize> {
+		match self.from_end.is_empty() {
+			true => self.ranges.iter().map(|&(_, end)| end).max(),
+			false => None,
+		}
+	}
+
+	/// Returns the number of the latest documents the selection needs to hold
+	/// until the end of the stream is known.
+	fn window(&self) -> usize {
+		self.from_end.iter().copied().max().unwrap_or(0)
+	}
+}
+
+impl FromStr for Selection {
+	type Err = String;
+
+	/// Parses comma-separated positions like `3` or `-1`, and ranges like
+	/// `10-20` or `10-`.
+	fn from_str(text: &str) -> Result<Self, String> {
+		let mut selection = Selection {
+			ranges: vec![],
+			from_end: vec![],
+		};
+		for item in text.split(',') {
+			let invalid =
+				|| format!("invalid position {item:?}, expected one like 3, -1, or 10-20");
+			let number = |digits: &str| match digits.is_empty()
+				|| !digits.bytes().all(|b| b.is_ascii_digit())
+			{
+				true => Err(invalid()),
+				false => digits.parse::<usize>().map_err(|_| invalid()),
+			};
+			if let Some(digits) = item.strip_prefix('-') {
+				match number(digits)? {
+					0 => {
+						return Err(format!(
+							"position {item:?} is before the end; the last is -1"
+						))
+					}
+					n => selection.from_end.push(n),
+				}
+				continue;
+			}
+			let range = match item.split_once('-') {
+				None => {
+					let position = number(item)?;
+					(position, position)
+				}
+				Some((start, "")) => (number(start)?, usize::MAX),
+				Some((start, end)) => (number(start)?, number(end)?),
+			};
+			if range.1 < range.0 {
+				return Err(format!("range {item:?} ends before it starts"));
+			}
+			selection.ranges.push(range);
+		}
+		Ok(selection)
+	}
+}
+
+/// Produces only the documents at the positions in `selection`.
+pub(crate) fn select(docs: Documents, selection: Selection) -> Documents {
+	Box::new(Select {
+		docs: Some(docs),
+		last: selection.last(),
+		window: selection.window(),
+		selection,
+		read: 0,
+		held: VecDeque::new(),
+		held_len: 0,
+		claim: Claim::default(),
+	})
+}
+
+struct Select {
+	/// The documents to select from, until they run out or no more could be
+	/// selected.
+	docs: Option<Documents>,
+	selection: Selection,
+	last: Option<usize>,
+	window: usize,
+	/// The number of documents read, which is the position of the next one.
+	read: usize,
+	/// The latest documents and their positions, which could still be
+	/// selected by a position from the end.
+	held: VecDeque<(usize, Document)>,
+	/// The total length of the content of the held documents.
+	held_len: usize,
+	claim: Claim,
+}
+
+impl Select {
+	fn release(&mut self) -> Option<(usize, Document)> {
+		let (position, doc) = self.held.pop_front()?;
+		self.held_len -= doc.content().len();
+		self.claim.shrink_to(self.held_len);
+		Some((position, doc))
+	}
+}
+
+impl Iterator for Select {
+	type Item = io::Result<Document>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			let Some(docs) = &mut self.docs else {
+				// The stream has ended, so the held documents' positions from
+				// the end are known.
+				let (position, doc) = self.release()?;
+				let from_end = self.read - position;
+				if self.selection.contains(position) || self.selection.from_end.contains(&from_end)
+				{
+					return Some(Ok(doc));
+				}
+				continue;
+			};
+			if self.last.is_some_and(|last| self.read > last) {
+				self.docs = None;
+				continue;
+			}
+			let doc = match docs.next() {
+				None => {
+					self.docs = None;
+					continue;
+				}
+				Some(Err(err)) => return Some(Err(err)),
+				Some(Ok(doc)) => doc,
+			};
+			let position = self.read;
+			self.read += 1;
+			if self.window == 0 {
+				if self.selection.contains(position) {
+					return Some(Ok(doc));
+				}
+				continue;
+			}
+			self.held_len += doc.content().len();
+			self.held.push_back((position, doc));
+			let what = format!("holding the last {} documents for --select", self.window);
+			if let Err(err) = self.claim.grow_to(self.held_len, &what) {
+				return Some(Err(err));
+			}
+			if self.held.len() > self.window {
+				let (position, doc) = self.release().expect("a document is held");
+				if self.selection.contains(position) {
+					return Some(Ok(doc));
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::chunker::Chunker;
+
+	fn selected(input: &'static str, selection: &str) -> Vec<String> {
+		let docs = Box::new(Chunker::new(input.as_bytes()));
+		select(docs, selection.parse().unwrap())
+			.map(|doc| doc.unwrap().content().trim().to_owned())
+			.collect()
+	}
+
+	#[test]
+	fn select_positions() {
+		const INPUT: &str = "0\n--- 1\n--- 2\n--- 3\n--- 4\n--- 5\n";
+		assert_eq!(selected(INPUT, "0"), ["0"]);
+		assert_eq!(selected(INPUT, "4,1,1"), ["--- 1", "--- 4"]);
+		assert_eq!(selected(INPUT, "2-3,9"), ["--- 2", "--- 3"]);
+		assert_eq!(selected(INPUT, "4-"), ["--- 4", "--- 5"]);
+		assert_eq!(selected(INPUT, "-1"), ["--- 5"]);
+		assert_eq!(selected(INPUT, "-2,0,5"), ["0", "--- 4", "--- 5"]);
+		assert_eq!(selected(INPUT, "-6,-9,1"), ["0", "--- 1"]);
+		assert!(selected(INPUT, "6-,-7").is_empty());
+		assert_eq!(selected("a\n", "-1,-3"), ["a"]);
+	}
+
+	#[test]
+	fn select_stops_reading() {
+		// The third document is invalid, but is never read.
+		let docs = Box::new(Chunker::new("--- a\n--- b\n--- [\n".as_bytes()));
+		let docs = select(docs, "0-1".parse().unwrap());
+		assert_eq!(docs.map(Result::unwrap).count(), 2);
+		let docs = Box::new(Chunker::new("--- a\n--- b\n--- [\n".as_bytes()));
+		assert!(select(docs, "-1".parse().unwrap()).any(|doc| doc.is_err()));
+	}
+
+	#[test]
+	fn select_invalid() {
+		for text in ["", "a", "1,", "-0", "--1", "3-1", "1-2-3", "+1", " 1", "-"] {
+			assert!(
+				text.parse::<Selection>().is_err(),
+				"{text:?} should be invalid"
+			);
+		}
+	}
+}
@@ -0,0 +1,169 @@
+//! Minimal raw mode terminal handling for interactive commands.
+//!
+//! This covers exactly what yaml-split's interactive features need, and no
+//! more: switching the controlling terminal into raw mode on the alternate
+//! screen, querying its size, and decoding the handful of keys we respond to.
+//! The terminal is always accessed through `/dev/tty`, so that interactive
+//! commands work even when standard input carries the YAML stream.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::mem::MaybeUninit;
+use std::os::fd::AsRawFd;
+
+/// A key press read from the terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Key {
+	Up,
+	Down,
+	PageUp,
+	PageDown,
+	Home,
+	End,
+	Enter,
+	Escape,
+	Backspace,
+	/// Ctrl-C, which raw mode delivers as a key rather than a signal.
+	Interrupt,
+	Char(char),
+	Unknown,
+}
+
+/// The controlling terminal in raw mode on the alternate screen.
+///
+/// Dropping a `Terminal` restores the original screen and terminal settings.
+pub(crate) struct Terminal {
+	tty: File,
+	original: libc::termios,
+	/// Input that has been read but not yet decoded into keys.
+	pending: Vec<u8>,
+}
+
+impl Terminal {
+	/// Opens the controlling terminal and prepares it for interactive use.
+	pub(crate) fn open() -> io::Result<Terminal> {
+		let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+		let fd = tty.as_raw_fd();
+
+		let mut original = MaybeUninit::<libc::termios>::uninit();
+		// SAFETY: tcgetattr fully initializes the termios struct when it
+		// succeeds, which we check before assuming initialization.
+		let original = unsafe {
+			if libc::tcgetattr(fd, original.as_mut_ptr()) != 0 {
+				return Err(io::Error::last_os_error());
+			}
+			original.assume_init()
+		};
+
+		let mut raw = original;
+		// SAFETY: cfmakeraw only modifies the flags of the struct it's given,
+		// which we own. tcsetattr only reads from the struct.
+		unsafe {
+			libc::cfmakeraw(&mut raw);
+			if libc::tcsetattr(fd, libc::TCSAFLUSH, &raw) != 0 {
+				return Err(io::Error::last_os_error());
+			}
+		}
+
+		let mut terminal = Terminal {
+			tty,
+			original,
+			pending: vec![],
+		};
+		// Enter the alternate screen and hide the cursor.
+		terminal.tty.write_all(b"\x1b[?1049h\x1b[?25l")?;
+		Ok(terminal)
+	}
+
+	/// Returns the size of the terminal as a number of rows and columns.
+	pub(crate) fn size(&self) -> (usize, usize) {
+		let mut size = MaybeUninit::<libc::winsize>::zeroed();
+		// SAFETY: TIOCGWINSZ writes a winsize struct through the pointer, which
+		// is valid for writes of that size. The zeroed struct is a valid
+		// fallback if the call fails.
+		let size = unsafe {
+			libc::ioctl(self.tty.as_raw_fd(), libc::TIOCGWINSZ, size.as_mut_ptr());
+			size.assume_init()
+		};
+		match (size.ws_row, size.ws_col) {
+			(0, _) | (_, 0) => (24, 80),
+			(rows, cols) => (rows.into(), cols.into()),
+		}
+	}
+
+	/// Blocks until the next key press.
+	pub(crate) fn read_key(&mut self) -> io::Result<Key> {
+		if self.pending.is_empty() {
+			let mut buf = [0u8; 64];
+			let len = loop {
+				match self.tty.read(&mut buf) {
+					Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+					Ok(len) => break len,
+					Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+					Err(err) => return Err(err),
+				}
+			};
+			self.pending.extend_from_slice(&buf[..len]);
+		}
+
+		// Escape sequences for special keys generally arrive in a single read,
+		// which is how we tell them apart from a bare press of Escape. Anything
+		// else may arrive several keys at a time, as when text is pasted.
+		if self.pending[0] == b'\x1b' {
+			let key = match &self.pending[..] {
+				b"\x1b[A" | b"\x1bOA" => Key::Up,
+				b"\x1b[B" | b"\x1bOB" => Key::Down,
+				b"\x1b[5~" => Key::PageUp,
+				b"\x1b[6~" => Key::PageDown,
+				b"\x1b[H" | b"\x1bOH" | b"\x1b[1~" => Key::Home,
+				b"\x1b[F" | b"\x1bOF" | b"\x1b[4~" => Key::End,
+				b"\x1b" => Key::Escape,
+				_ => Key::Unknown,
+			};
+			self.pending.clear();
+			return Ok(key);
+		}
+
+		let len = match self.pending[0] {
+			0x00..=0x7f => 1,
+			0xc0..=0xdf => 2,
+			0xe0..=0xef => 3,
+			_ => 4,
+		}
+		.min(self.pending.len());
+		let bytes: Vec<u8> = self.pending.drain(..len).collect();
+		Ok(match &bytes[..] {
+			b"\r" | b"\n" => Key::Enter,
+			b"\x7f" | b"\x08" => Key::Backspace,
+			b"\x03" => Key::Interrupt,
+			bytes => match std::str::from_utf8(bytes)
+				.ok()
+				.and_then(|s| s.chars().next())
+			{
+				Some(c) if !c.is_control() => Key::Char(c),
+				_ => Key::Unknown,
+			},
+		})
+	}
+}
+
+impl Write for Terminal {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.tty.write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.tty.flush()
+	}
+}
+
+impl Drop for Terminal {
+	fn drop(&mut self) {
+		// Show the cursor and leave the alternate screen.
+		let _ = self.tty.write_all(b"\x1b[?25h\x1b[?1049l");
+		// SAFETY: original came from a successful tcgetattr on this terminal.
+		unsafe {
+			libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSAFLUSH, &self.original);
+		}
+	}
+}
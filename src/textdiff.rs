@@ -0,0 +1,173 @@
+//! Line-by-line differences between texts, in the unified format of diff(1).
+//!
+//! The differences come from Myers' algorithm, which finds a shortest edit
+//! script in time proportional to the length of the texts times the number of
+//! edits, so it stays fast for documents that differ in only a few lines.
+
+use std::fmt::Write;
+
+/// How a line of one text relates to the other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Edit {
+	/// The line is in both texts.
+	Same,
+	/// The line is only in the old text.
+	Removed,
+	/// The line is only in the new text.
+	Added,
+}
+
+/// Returns the hunks of a unified diff between two texts, with `context`
+/// unchanged lines around each change.
+///
+/// Line numbers in hunk headers count from `old_line` and `new_line`, the
+/// one-based numbers of the first lines of the texts in their files. The
+/// result is empty if the texts have the same lines.
+pub(crate) fn unified(
+	old: &str,
+	new: &str,
+	old_line: usize,
+	new_line: usize,
+	context: usize,
+) -> String {
+	let old = old.split_inclusive('\n').collect::<Vec<_>>();
+	let new = new.split_inclusive('\n').collect::<Vec<_>>();
+	let edits = edits(&old, &new);
+
+	// The indexes of the old and new lines at the start of each edit.
+	let mut positions = Vec::with_capacity(edits.len() + 1);
+	let (mut i, mut j) = (0, 0);
+	for edit in &edits {
+		positions.push((i, j));
+		match edit {
+			Edit::Same => (i, j) = (i + 1, j + 1),
+			Edit::Removed => i += 1,
+			Edit::Added => j += 1,
+		}
+	}
+	positions.push((i, j));
+
+	let mut out = String::new();
+	let changes = (0..edits.len()).filter(|&e| edits[e] != Edit::Same);
+	let mut hunks: Vec<(usize, usize)> = vec![];
+	for e in changes {
+		let start = e.saturating_sub(context);
+		let end = (e + 1 + context).min(edits.len());
+		match hunks.last_mut() {
+			Some(hunk) if start <= hunk.1 => hunk.1 = end,
+			_ => hunks.push((start, end)),
+		}
+	}
+	for (start, end) in hunks {
+		let (old_start, new_start) = positions[start];
+		let (old_end, new_end) = positions[end];
+		let range = |start: usize, len: usize, first: usize| match len {
+			// An empty range names the line before it, as diff(1) does.
+			0 => format!("{},0", start + first - 1),
+			1 => format!("{}", start + first),
+			_ => format!("{},{len}", start + first),
+		};
+		let _ = writeln!(
+			out,
+			"@@ -{} +{} @@",
+			range(old_start, old_end - old_start, old_line),
+			range(new_start, new_end - new_start, new_line),
+		);
+		for (edit, &(i, j)) in edits[start..end].iter().zip(&positions[start..end]) {
+			let (prefix, line) = match edit {
+				Edit::Same => (' ', old[i]),
+				Edit::Removed => ('-', old[i]),
+				Edit::Added => ('+', new[j]),
+			};
+			out.push(prefix);
+			out.push_str(line);
+			if !line.ends_with('\n') {
+				out.push_str("\n\\ No newline at end of file\n");
+			}
+		}
+	}
+	out
+}
+
+/// Returns a shortest sequence of edits that turns `old` into `new`.
+fn edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+	let (n, m) = (old.len() as isize, new.len() as isize);
+	let max = n + m;
+	// The furthest old index reached on each diagonal k = x - y, offset so
+	// that the diagonals on either side of the outermost are in bounds.
+	let index = |k: isize| (k + max + 1) as usize;
+	let mut v = vec![0; 2 * max as usize + 3];
+	let mut trace = vec![];
+	'search: for d in 0..=max {
+		trace.push(v.clone());
+		for k in (-d..=d).step_by(2) {
+			let mut x = match k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+				true => v[index(k + 1)],
+				false => v[index(k - 1)] + 1,
+			};
+			let mut y = x - k;
+			while x < n && y < m && old[x as usize] == new[y as usize] {
+				(x, y) = (x + 1, y + 1);
+			}
+			v[index(k)] = x;
+			if x >= n && y >= m {
+				break 'search;
+			}
+		}
+	}
+
+	let mut edits = vec![];
+	let (mut x, mut y) = (n, m);
+	for (d, v) in trace.iter().enumerate().rev() {
+		let d = d as isize;
+		let k = x - y;
+		let prev_k = match k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+			true => k + 1,
+			false => k - 1,
+		};
+		let prev_x = v[index(prev_k)];
+		let prev_y = prev_x - prev_k;
+		while x > prev_x && y > prev_y {
+			edits.push(Edit::Same);
+			(x, y) = (x - 1, y - 1);
+		}
+		if d > 0 {
+			match x == prev_x {
+				true => edits.push(Edit::Added),
+				false => edits.push(Edit::Removed),
+			}
+			(x, y) = (prev_x, prev_y);
+		}
+	}
+	edits.reverse();
+	edits
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn unified_diffs() {
+		assert_eq!(unified("a\nb\n", "a\nb\n", 1, 1, 3), "");
+		assert_eq!(
+			unified("a\nb\nc\nd\ne\nf\ng\n", "a\nc\nd\nX\ne\nf\ng\n", 10, 20, 1),
+			"@@ -10,5 +20,5 @@\n a\n-b\n c\n d\n+X\n e\n"
+		);
+		assert_eq!(
+			unified(
+				"1\n2\n3\n4\nb\n5\n6\n7\n8\n",
+				"1\n2\n3\n4\n5\n6\n7\nc\n8\n",
+				1,
+				1,
+				1
+			),
+			"@@ -4,3 +4,2 @@\n 4\n-b\n 5\n@@ -8,2 +7,3 @@\n 7\n+c\n 8\n"
+		);
+		assert_eq!(
+			unified("a\nb\n", "c", 1, 1, 3),
+			"@@ -1,2 +1 @@\n-a\n-b\n+c\n\\ No newline at end of file\n"
+		);
+		assert_eq!(unified("", "a\n", 1, 5, 3), "@@ -0,0 +5 @@\n+a\n");
+	}
+}
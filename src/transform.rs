@@ -0,0 +1,1165 @@
+//! Transformations that restructure the documents of a stream.
+//!
+//! Each transformation adapts an iterator of documents, as produced by the
+//! chunker, into another. Documents that a transformation doesn't apply to
+//! pass through untouched, with their original text. Those it does apply to
+//! are re-serialized through a [`Node`] tree, and lose their comments and
+//! formatting in the process.
+
+use std::collections::HashMap;
+use std::io;
+use std::iter;
+
+use crate::budget::Claim;
+use crate::chunker::{Document, DocumentKind};
+use crate::hash;
+use crate::libyaml::{EmitterOptions, Parser, YAML_STREAM_END_TOKEN};
+use crate::node::{Content, Node};
+use crate::parallel;
+use crate::query::{Condition, Query};
+use crate::regex::Regex;
+use crate::writer::{is_insignificant, is_marker};
+
+/// A boxed iterator of documents, as passed between transformations.
+pub(crate) type Documents = Box<dyn Iterator<Item = io::Result<Document>>>;
+
+/// Splits each document whose root is a sequence into one document per item.
+///
+/// Aliases within an item to anchors defined outside of it are replaced with
+/// copies of the anchored nodes, so that every new document stands alone.
+pub(crate) fn explode_sequences(docs: Documents) -> Documents {
+	replace_kind(docs, DocumentKind::Sequence, explode)
+}
+
+/// Replaces each document of the given kind with the documents that `f`
+/// produces from it.
+fn replace_kind(
+	docs: Documents,
+	kind: DocumentKind,
+	f: fn(&Document) -> io::Result<Vec<Document>>,
+) -> Documents {
+	Box::new(docs.flat_map(move |doc| {
+		let result = doc.and_then(|doc| match doc.kind() == kind {
+			true => f(&doc),
+			false => Ok(vec![doc]),
+		});
+		match result {
+			Ok(docs) => docs.into_iter().map(Ok).collect::<Vec<_>>(),
+			Err(err) => vec![Err(err)],
+		}
+	}))
+}
+
+/// Returns the error for a document whose content turns out not to have the
+/// kind of root that it was read with.
+fn not_kind(doc: &Document, kind: DocumentKind) -> io::Error {
+	let doc = match doc.index() {
+		Some(index) => format!("document {}", index + 1),
+		None => "a document".to_owned(),
+	};
+	io::Error::new(
+		io::ErrorKind::InvalidData,
+		format!(
+			"{doc} no longer has a {kind} at its root",
+			kind = kind.name()
+		),
+	)
+}
+
+fn explode(doc: &Document) -> io::Result<Vec<Document>> {
+	let Node {
+		content: Content::Sequence(items, _),
+		..
+	} = doc.root()?
+	else {
+		return Err(not_kind(doc, DocumentKind::Sequence));
+	};
+	let mut anchors = HashMap::new();
+	items
+		.into_iter()
+		.map(|mut item| {
+			item.inline_aliases(&mut anchors)?;
+			let content = item.to_document(EmitterOptions::default())?;
+			Ok(doc.derive(content, item.kind()))
+		})
+		.collect()
+}
+
+/// Splits each document whose root is a mapping into one document per entry,
+/// named by the entry's key if the key is a scalar.
+///
+/// As with [`explode_sequences`], aliases to anchors outside of an entry are
+/// replaced with copies of the anchored nodes.
+pub(crate) fn split_by_key(docs: Documents) -> Documents {
+	replace_kind(docs, DocumentKind::Mapping, by_key)
+}
+
+fn by_key(doc: &Document) -> io::Result<Vec<Document>> {
+	let Node {
+		content: Content::Mapping(entries, flow),
+		..
+	} = doc.root()?
+	else {
+		return Err(not_kind(doc, DocumentKind::Mapping));
+	};
+	let mut anchors = HashMap::new();
+	entries
+		.into_iter()
+		.map(|(mut key, mut value)| {
+			key.inline_aliases(&mut anchors)?;
+			value.inline_aliases(&mut anchors)?;
+			let name = match &key.content {
+				Content::Scalar(name, _) => Some(name.clone()),
+				_ => None,
+			};
+			let root = Node::new(Content::Mapping(vec![(key, value)], flow));
+			let content = root.to_document(EmitterOptions::default())?;
+			let doc = doc.derive(content, root.kind());
+			Ok(match name {
+				Some(name) => doc.with_name(name),
+				None => doc,
+			})
+		})
+		.collect()
+}
+
+/// Reverses the order of the documents.
+///
+/// Every document is held in memory until the end of the stream, since the
+/// last document must be written first.
+pub(crate) fn reverse(docs: Documents) -> Documents {
+	match collect(docs) {
+		Ok((docs, claim)) => release_as_taken(docs.into_iter().rev(), claim),
+		Err(err) => Box::new(iter::once(Err(err))),
+	}
+}
+
+/// Shuffles the documents into an order determined entirely by `seed`.
+///
+/// The shuffle uses its own small generator rather than depending on any
+/// external source of randomness, so that a given seed produces the same
+/// order on every platform and in every version of yaml-split.
+pub(crate) fn shuffle(docs: Documents, seed: u64) -> Documents {
+	let (mut docs, claim) = match collect(docs) {
+		Ok(collected) => collected,
+		Err(err) => return Box::new(iter::once(Err(err))),
+	};
+	let mut rng = SplitMix64(seed);
+	for i in (1..docs.len()).rev() {
+		let j = (rng.next() % (i as u64 + 1)) as usize;
+		docs.swap(i, j);
+	}
+	release_as_taken(docs.into_iter(), claim)
+}
+
+/// Reads every document into memory, with a claim on the memory budget for
+/// their content.
+fn collect(docs: Documents) -> io::Result<(Vec<Document>, Claim)> {
+	let mut claim = Claim::default();
+	let mut len = 0;
+	let mut collected = vec![];
+	for doc in docs {
+		let doc = doc?;
+		len += doc.content().len();
+		claim.grow_to(len, "holding every document in memory")?;
+		collected.push(doc);
+	}
+	Ok((collected, claim))
+}
+
+/// Returns the documents one by one, shrinking `claim` as each one is taken.
+fn release_as_taken<I>(docs: I, mut claim: Claim) -> Documents
+where
+	I: Iterator<Item = Document> + 'static,
+{
+	let mut len = claim.len();
+	Box::new(docs.map(move |doc| {
+		len -= doc.content().len();
+		claim.shrink_to(len);
+		Ok(doc)
+	}))
+}
+
+/// The SplitMix64 generator, which is tiny, fast, and good enough for
+/// shuffling.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+	fn next(&mut self) -> u64 {
+		self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+		let mut z = self.0;
+		z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+		z ^ (z >> 31)
+	}
+}
+
+/// How [`reserialize`] lays out the collections of a document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Layout {
+	/// Keep the style of each collection from the input.
+	Preserve,
+	/// Write every collection in block style, where libyaml allows it.
+	Block,
+	/// Write every collection in flow style.
+	Flow,
+}
+
+/// Parses and re-serializes every document with the given layout and emitter
+/// options, on `jobs` threads.
+///
+/// Scalars keep their original styles, since changing a quoted scalar to a
+/// plain one could change how it resolves. In canonical form, every node is
+/// given an explicit tag from the YAML 1.2 core schema unless it already has
+/// one.
+pub(crate) fn reserialize(
+	docs: Documents,
+	layout: Layout,
+	options: EmitterOptions,
+	jobs: usize,
+) -> Documents {
+	parallel::map(docs, jobs, move |doc| {
+		let mut root = doc.root()?;
+		match layout {
+			Layout::Preserve => {}
+			Layout::Block => root.set_flow(false),
+			Layout::Flow => root.set_flow(true),
+		}
+		if options.canonical {
+			root.resolve_tags();
+		}
+		let content = root.to_document(options)?;
+		Ok(doc.with_content(content))
+	})
+}
+
+/// Removes every comment from the documents, leaving the rest of their text
+/// exactly as it was, on `jobs` threads.
+///
+/// Comments are found from the gaps between the tokens that libyaml scans,
+/// rather than by guessing at the syntax, so a `#` inside a scalar is never
+/// mistaken for one. Lines left blank by the removal of a comment are removed
+/// too.
+pub(crate) fn strip_comments(docs: Documents, jobs: usize) -> Documents {
+	parallel::map(docs, jobs, |doc| {
+		let content = without_comments(doc.content())?;
+		Ok(doc.with_content(content))
+	})
+}
+
+fn without_comments(text: &str) -> io::Result<String> {
+	let mut tokens = vec![];
+	let mut parser = Parser::new(text.as_bytes());
+	loop {
+		let token = parser.next_token()?;
+		if token.token_type() == YAML_STREAM_END_TOKEN {
+			break;
+		}
+		tokens.push(token.start_offset() as usize..token.end_offset() as usize);
+	}
+
+	let mut output = String::with_capacity(text.len());
+	let mut copied = 0;
+	let mut gap_start = 0;
+	for token in tokens.into_iter().chain(iter::once(text.len()..text.len())) {
+		let gap_end = token.start.max(gap_start);
+		let mut search = gap_start;
+		while let Some(i) = text[search..gap_end].find('#') {
+			let hash = search + i;
+			let eol = text[hash..].find('\n').map_or(text.len(), |i| hash + i);
+			let line_start = text[..hash].rfind('\n').map_or(0, |i| i + 1);
+			let (cut_start, cut_end) = match text[line_start..hash].trim().is_empty() {
+				true => (line_start, (eol + 1).min(text.len())),
+				false => (text[..hash].trim_end().len(), eol),
+			};
+			output.push_str(&text[copied..cut_start.max(copied)]);
+			copied = copied.max(cut_end);
+			search = eol.min(gap_end);
+		}
+		gap_start = token.end.max(gap_start);
+	}
+	output.push_str(&text[copied..]);
+	Ok(output)
+}
+
+/// The number of hex digits of a document's hash to use in its ID.
+const ID_LEN: usize = 12;
+
+/// Gives each document a short ID derived from a hash of its content, so that
+/// the same document gets the same ID no matter where it appears.
+///
+/// Documents with identical content would otherwise share an ID, so every
+/// repeat after the first gets a numeric suffix in order of appearance, as in
+/// `3f9a2c0b71de-2`. Hashes are computed on `jobs` threads, and suffixes
+/// assigned in order afterward.
+pub(crate) fn assign_ids(docs: Documents, jobs: usize) -> Documents {
+	let mut seen = HashMap::<String, usize>::new();
+	let hashed = parallel::map(docs, jobs, |doc| {
+		let mut id = hash::Algorithm::Sha256.hex_digest(doc.content().as_bytes());
+		id.truncate(ID_LEN);
+		Ok((doc, id))
+	});
+	Box::new(hashed.map(move |hashed| {
+		let (doc, mut id) = hashed?;
+		let repeats = seen.entry(id.clone()).or_default();
+		*repeats += 1;
+		if *repeats > 1 {
+			id = format!("{id}-{repeats}");
+		}
+		Ok(doc.with_id(id))
+	}))
+}
+
+/// Adds a comment to the start of each document with its position in the
+/// output, and the range of the input it came from.
+///
+/// `input` names the input in the comment, and is typically its path.
+/// Documents are numbered from one, after the `first` that were already
+/// written, as when resuming from a checkpoint. If the `total` number of
+/// documents is known ahead of time, the comment includes it.
+///
+/// A document that records its own input is named by that input instead, along
+/// with the input's one-based position among all of them. With `per_input`,
+/// such documents are numbered from one within each input, rather than across
+/// the whole output.
+pub(crate) fn annotate(
+	docs: Documents,
+	input: String,
+	first: usize,
+	total: Option<usize>,
+	per_input: bool,
+) -> Documents {
+	let mut counts = vec![];
+	Box::new(docs.zip(first + 1..).map(move |(doc, index)| {
+		let doc = doc?;
+		let range = doc.original();
+		let (index, source) = match doc.input() {
+			None => (index, input.clone()),
+			Some(from) => {
+				if counts.len() <= from.index {
+					counts.resize(from.index + 1, 0);
+				}
+				counts[from.index] += 1;
+				let index = if per_input { counts[from.index] } else { index };
+				(index, format!("{}, input {}", from.name, from.index + 1))
+			}
+		};
+		let position = match total {
+			None => format!("document {index}"),
+			Some(total) => format!("document {index} of {total}"),
+		};
+		let content = format!(
+			"# {position} (bytes {start}..{end} of {source})\n{content}",
+			start = range.start,
+			end = range.end,
+			content = doc.content(),
+		);
+		Ok(doc.with_content(content))
+	}))
+}
+
+/// The width that [`number_lines`] pads line numbers to, as `nl` does.
+const LINE_NUMBER_WIDTH: usize = 6;
+
+/// Prefixes each line of each document with its one-based number within the
+/// document, right-aligned and followed by a tab, like `nl -b a`.
+///
+/// Blank lines are numbered too, so that a number always matches the line of
+/// the document an editor shows. The result is no longer YAML.
+pub(crate) fn number_lines(docs: Documents) -> Documents {
+	Box::new(docs.map(|doc| {
+		let doc = doc?;
+		let mut content = String::with_capacity(doc.content().len() * 2);
+		for (n, line) in doc.content().split_inclusive('\n').enumerate() {
+			content.push_str(&format!("{:>LINE_NUMBER_WIDTH$}\t{line}", n + 1));
+		}
+		Ok(doc.with_content(content))
+	}))
+}
+
+/// Keeps only the documents whose root node has one of `tags`, as resolved by
+/// the chunker.
+///
+/// Documents derived from others have no tag, so this must come before any
+/// transformation that restructures them.
+pub(crate) fn tagged(docs: Documents, tags: Vec<String>) -> Documents {
+	Box::new(docs.filter(move |doc| match doc {
+		Ok(doc) => doc.tag().is_some_and(|tag| tags.iter().any(|t| t == tag)),
+		Err(_) => true,
+	}))
+}
+
+/// Keeps only the documents that have a node at the end of every one of
+/// `paths`, whatever its value.
+pub(crate) fn having(docs: Documents, paths: Vec<Query>) -> Documents {
+	Box::new(docs.filter_map(move |doc| {
+		let has_paths = |doc: &Document| -> io::Result<bool> {
+			let root = doc.value()?;
+			Ok(paths.iter().all(|path| path.get(root).is_some()))
+		};
+		match doc.and_then(|doc| Ok((has_paths(&doc)?, doc))) {
+			Ok((true, doc)) => Some(Ok(doc)),
+			Ok((false, _)) => None,
+			Err(err) => Some(Err(err)),
+		}
+	}))
+}
+
+/// Keeps only the documents for which every one of `conditions` holds.
+pub(crate) fn satisfying(docs: Documents, conditions: Vec<Condition>) -> Documents {
+	Box::new(docs.filter_map(move |doc| {
+		let holds = |doc: &Document| -> io::Result<bool> {
+			let root = doc.value()?;
+			Ok(conditions.iter().all(|condition| condition.matches(root)))
+		};
+		match doc.and_then(|doc| Ok((holds(&doc)?, doc))) {
+			Ok((true, doc)) => Some(Ok(doc)),
+			Ok((false, _)) => None,
+			Err(err) => Some(Err(err)),
+		}
+	}))
+}
+
+/// Keeps only the documents whose text matches every one of `patterns`, or
+/// with `invert`, only those whose text matches none of them.
+pub(crate) fn matching(docs: Documents, patterns: Vec<Regex>, invert: bool) -> Documents {
+	Box::new(docs.filter(move |doc| match doc {
+		Ok(doc) => match invert {
+			false => patterns.iter().all(|regex| regex.is_match(doc.content())),
+			true => !patterns.iter().any(|regex| regex.is_match(doc.content())),
+		},
+		Err(_) => true,
+	}))
+}
+
+/// Names each document after the scalars at `paths`, joined by hyphens, as in
+/// `Deployment-frontend`.
+///
+/// A document without a scalar at every one of the paths is named instead
+/// after the first match of `regex` in its text, or the first capture group of
+/// the match if it has any, so that `kind: (\w+)` names a document after its
+/// kind. A document that neither names keeps any name it had.
+pub(crate) fn named(docs: Documents, paths: Vec<Query>, regex: Option<Regex>) -> Documents {
+	Box::new(docs.map(move |doc| {
+		let doc = doc?;
+		let mut name = None;
+		if !paths.is_empty() {
+			let root = doc.value()?;
+			let values = paths
+				.iter()
+				.map(|path| match path.get(root).map(|node| &node.content) {
+					Some(Content::Scalar(value, _)) => Some(value.as_str()),
+					_ => None,
+				})
+				.collect::<Option<Vec<_>>>();
+			name = values.map(|values| values.join("-"));
+		}
+		if let (None, Some(regex)) = (&name, &regex) {
+			name = regex.captures(doc.content()).and_then(|captures| {
+				let matched = captures.get(1).copied().flatten().or(captures[0]);
+				matched.map(str::to_owned)
+			});
+		}
+		Ok(match name {
+			Some(name) => doc.with_name(name),
+			None => doc,
+		})
+	}))
+}
+
+/// Removes the `---` marker that starts each document and the `...` marker
+/// that ends it, along with any line left blank by their removal.
+///
+/// A document with directives keeps its `---` marker, which the directives
+/// need to end them. A document left with nothing but comments, like `---`
+/// alone, ends with `~` instead, so that it still holds the null it did.
+pub(crate) fn strip_markers(docs: Documents) -> Documents {
+	Box::new(docs.map(|doc| {
+		let doc = doc?;
+		let mut lines = doc.content().split_inclusive('\n').collect::<Vec<_>>();
+		let first = lines.iter().position(|line| !is_insignificant(line));
+		let last = lines.iter().rposition(|line| !is_insignificant(line));
+		for (i, marker) in [(first, "---"), (last, "...")] {
+			if let Some(i) = i.filter(|&i| is_marker(lines[i], marker)) {
+				let rest = lines[i][marker.len()..].trim_start_matches([' ', '\t']);
+				lines[i] = match rest.trim_end().is_empty() {
+					true => "",
+					false => rest,
+				};
+			}
+		}
+		let mut content = lines.concat();
+		if content.lines().all(is_insignificant) {
+			content.push_str("~\n");
+		}
+		Ok(doc.with_content(content))
+	}))
+}
+
+/// Starts each document that has no `---` marker with one, so that every
+/// document has one after any directives.
+pub(crate) fn normalize_markers(docs: Documents) -> Documents {
+	Box::new(docs.map(|doc| {
+		let doc = doc?;
+		let first = doc.content().lines().find(|line| !is_insignificant(line));
+		if first.is_some_and(|line| line.starts_with('%') || is_marker(line, "---")) {
+			return Ok(doc);
+		}
+		let content = format!("---\n{}", doc.content());
+		Ok(doc.with_content(content))
+	}))
+}
+
+/// Returns the comments in the text of a document, each on its own line.
+fn comments(text: &str) -> String {
+	let mut comments = String::new();
+	for line in text.lines() {
+		let rest = line
+			.strip_prefix("---")
+			.or_else(|| line.strip_prefix("..."))
+			.unwrap_or(line)
+			.trim_start();
+		if rest.starts_with('#') {
+			comments.push_str(rest);
+			comments.push('\n');
+		}
+	}
+	comments
+}
+
+/// Returns whether a document has nothing but comments, along with markers
+/// and directives.
+fn is_comment_only(doc: &Document) -> bool {
+	doc.is_empty() && doc.full_len().is_none() && !comments(doc.content()).is_empty()
+}
+
+/// Removes every document that has nothing but comments.
+pub(crate) fn skip_comment_only(docs: Documents) -> Documents {
+	Box::new(docs.filter(|doc| !doc.as_ref().is_ok_and(is_comment_only)))
+}
+
+/// Moves the comments of every document that has nothing but comments to the
+/// start of the next document, which then spans both.
+///
+/// A document at the end of the stream has nothing to attach to, and is kept
+/// as it is.
+pub(crate) fn attach_comment_only(docs: Documents) -> Documents {
+	let mut docs = docs;
+	let mut pending: Option<(Document, String)> = None;
+	Box::new(iter::from_fn(move || loop {
+		let doc = match docs.next() {
+			Some(Ok(doc)) => doc,
+			Some(Err(err)) => return Some(Err(err)),
+			None => return pending.take().map(|(doc, _)| Ok(doc)),
+		};
+		if is_comment_only(&doc) {
+			pending = Some(match pending.take() {
+				None => {
+					let comments = comments(doc.content());
+					(doc, comments)
+				}
+				Some((earlier, held)) => {
+					let comments = held + &comments(doc.content());
+					(doc.extended_back(&earlier), comments)
+				}
+			});
+			continue;
+		}
+		return Some(Ok(match pending.take() {
+			None => doc,
+			Some((earlier, comments)) => {
+				let content = comments + doc.content();
+				doc.extended_back(&earlier).with_content(content)
+			}
+		}));
+	}))
+}
+
+/// Combines every document into a single document, whose root is a sequence
+/// of the original roots.
+///
+/// Anchors keep their meaning, since an alias always refers to the most recent
+/// node with its anchor, and those from the alias's original document are
+/// always more recent than those from any earlier one.
+pub(crate) fn implode(docs: Documents) -> Documents {
+	Box::new(iter::once_with(move || {
+		let mut roots = vec![];
+		let mut span: Option<Document> = None;
+		for doc in docs {
+			let doc = doc?;
+			roots.push(doc.root()?);
+			span = Some(match span {
+				None => doc.derive(String::new(), DocumentKind::Sequence),
+				Some(span) => span.spanning(&doc),
+			});
+		}
+		let root = Node::new(Content::Sequence(roots, false));
+		let content = root.to_document(EmitterOptions::default())?;
+		Ok(match span {
+			None => Document::new(content, DocumentKind::Sequence, 0..0),
+			Some(span) => span.with_content(content),
+		})
+	}))
+}
+
+/// The name of the document that [`trailer`] adds.
+pub(crate) const TRAILER_NAME: &str = "trailer";
+
+/// Adds a final document to the stream that records what came before it: the
+/// number of documents, their total length in bytes, and the digest of each
+/// with `algorithm`, so that a consumer can check that it got the whole stream
+/// intact.
+///
+/// The trailer follows the last document even if there are none, but not an
+/// error, since the stream is already known to be incomplete.
+pub(crate) fn trailer(docs: Documents, algorithm: hash::Algorithm) -> Documents {
+	let mut docs = docs;
+	let mut digests = vec![];
+	let mut bytes = 0;
+	let mut done = false;
+	Box::new(iter::from_fn(move || {
+		if done {
+			return None;
+		}
+		match docs.next() {
+			Some(Ok(doc)) => {
+				let content = doc.content().as_bytes();
+				bytes += content.len();
+				digests.push(algorithm.hex_digest(content));
+				Some(Ok(doc))
+			}
+			Some(Err(err)) => {
+				done = true;
+				Some(Err(err))
+			}
+			None => {
+				done = true;
+				let mut content = format!(
+					"--- # yaml-split trailer\ndocuments: {count}\nbytes: {bytes}\n",
+					count = digests.len(),
+				);
+				match digests.is_empty() {
+					true => content.push_str("hashes: []\n"),
+					false => content.push_str("hashes:\n"),
+				}
+				for digest in &digests {
+					content.push_str(&format!("- {name}:{digest}\n", name = algorithm.name()));
+				}
+				let doc = Document::new(content, DocumentKind::Mapping, 0..0);
+				Some(Ok(doc.with_name(TRAILER_NAME.to_owned())))
+			}
+		}
+	}))
+}
+
+/// Passes on the documents of `docs`, which the backend `backend` found in a
+/// stream, checking each against the one that `other` found in the same
+/// stream through `others`.
+///
+/// Backends should find every stream's documents at the same bytes, so any
+/// difference between them is an error, as is a failure of `other` where
+/// `backend` succeeded.
+pub(crate) fn cross_checked(
+	docs: Documents,
+	backend: &'static str,
+	others: Documents,
+	other: &'static str,
+) -> Documents {
+	let (mut docs, mut others) = (docs, others);
+	let mut n = 0;
+	let mut done = false;
+	Box::new(iter::from_fn(move || {
+		if done {
+			return None;
+		}
+		n += 1;
+		let found = |doc: &Document| {
+			let original = doc.original();
+			format!("bytes {}..{}", original.start, original.end)
+		};
+		let problem = match (docs.next(), others.next()) {
+			(None, None) => return None,
+			(Some(Err(err)), _) => {
+				done = true;
+				return Some(Err(err));
+			}
+			(Some(Ok(doc)), Some(Ok(theirs))) if doc.original() == theirs.original() => {
+				return Some(Ok(doc));
+			}
+			(Some(Ok(doc)), Some(Ok(theirs))) => {
+				format!(
+					"{backend} found {}, {other} found {}",
+					found(&doc),
+					found(&theirs)
+				)
+			}
+			(_, Some(Err(err))) => format!("{other} failed where {backend} didn't: {err}"),
+			(Some(Ok(doc)), None) => {
+				format!(
+					"{backend} found {}, {other} found no more documents",
+					found(&doc)
+				)
+			}
+			(None, Some(Ok(theirs))) => {
+				format!(
+					"{other} found {}, {backend} found no more documents",
+					found(&theirs)
+				)
+			}
+		};
+		done = true;
+		Some(Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("the {backend} and {other} backends disagree on document {n}: {problem}"),
+		)))
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::chunker::{Chunker, Input};
+	use crate::encoding::SourceMap;
+	use crate::lexical::LexicalChunker;
+
+	fn transform(input: &'static str, f: impl FnOnce(Documents) -> Documents) -> Vec<String> {
+		f(Box::new(Chunker::new(input.as_bytes())))
+			.map(|doc| doc.unwrap().content().to_owned())
+			.collect()
+	}
+
+	#[test]
+	fn explode_sequences_splits_items() {
+		assert_eq!(
+			transform(
+				"a: 1\n---\n- &x one # comment\n- [*x, two]\n--- []\n--- other\n",
+				explode_sequences
+			),
+			["a: 1\n", "&x one\n", "[&x one, two]\n", "--- other\n"]
+		);
+	}
+
+	#[test]
+	fn truncated_documents_are_not_parsed() {
+		let truncated = |input: &'static str, limit| -> Documents {
+			let chunker = Chunker::new(input.as_bytes())
+				.max_document_len(limit)
+				.truncate_long_documents();
+			Box::new(chunker)
+		};
+		let error = |mut docs: Documents| docs.next().unwrap().err().unwrap().to_string();
+		let cut = "document 1 was cut to the size limit, so it can't be parsed";
+		let input = "--- # a long comment here\n- a\n- b\n";
+		assert_eq!(error(explode_sequences(truncated(input, 10))), cut);
+		let input = "--- # a long comment here\na: 1\n";
+		assert_eq!(error(split_by_key(truncated(input, 10))), cut);
+		let options = EmitterOptions::default();
+		for layout in [Layout::Preserve, Layout::Block, Layout::Flow] {
+			let docs = reserialize(truncated("- a\n", 0), layout, options, 1);
+			assert_eq!(error(docs), cut);
+		}
+		assert_eq!(error(implode(truncated("- a\n", 0))), cut);
+		let doc = truncated("- a\n", 0).next().unwrap().unwrap();
+		assert_eq!(doc.value().err().unwrap().to_string(), cut);
+	}
+
+	#[test]
+	fn cross_checked_backends_agree() {
+		let docs = |sources: &[std::ops::Range<u64>]| -> Documents {
+			let docs = sources
+				.iter()
+				.map(|source| {
+					Ok(Document::new(
+						String::new(),
+						DocumentKind::Scalar,
+						source.clone(),
+					))
+				})
+				.collect::<Vec<_>>();
+			Box::new(docs.into_iter())
+		};
+		let check = |ours: &[_], theirs: &[_]| {
+			cross_checked(docs(ours), "ours", docs(theirs), "theirs")
+				.map(|doc| doc.map(|doc| doc.original()).map_err(|err| err.to_string()))
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(check(&[0..4, 4..9], &[0..4, 4..9]), [Ok(0..4), Ok(4..9)]);
+		let disagree = "the ours and theirs backends disagree on document 2: ";
+		assert_eq!(
+			check(&[0..4, 4..9, 9..12], &[0..4, 4..12]),
+			[
+				Ok(0..4),
+				Err(format!(
+					"{disagree}ours found bytes 4..9, theirs found bytes 4..12"
+				))
+			]
+		);
+		assert_eq!(
+			check(&[0..4, 4..9], &[0..4, 4..9, 9..12]),
+			[
+				Ok(0..4),
+				Ok(4..9),
+				Err("the ours and theirs backends disagree on document 3: \
+					 theirs found bytes 9..12, ours found no more documents"
+					.to_owned())
+			]
+		);
+
+		let input = "--- |\n%x\n";
+		let lexical = LexicalChunker::new(input.as_bytes(), SourceMap::IDENTITY);
+		let libyaml = Chunker::new(input.as_bytes());
+		let mut docs = cross_checked(Box::new(lexical), "lexical", Box::new(libyaml), "libyaml");
+		let err = docs.next().unwrap().err().unwrap().to_string();
+		assert!(
+			err.starts_with("the lexical and libyaml backends disagree on document 1: libyaml failed where lexical didn't: "),
+			"{err}"
+		);
+		assert!(docs.next().is_none());
+	}
+
+	#[test]
+	fn implode_wraps_documents() {
+		assert_eq!(
+			transform(
+				"a: &x 1\nb: *x\n--- [&x 2, *x] # c\n...\n--- three\n",
+				implode
+			),
+			["- a: &x 1\n  b: *x\n- [&x 2, *x]\n- three\n"]
+		);
+		assert_eq!(transform("", implode), ["[]\n"]);
+	}
+
+	#[test]
+	fn split_by_key_splits_entries() {
+		let docs = split_by_key(Box::new(Chunker::new(
+			"a: &x 1\nb: [*x]\n? [c]\n: 3\n--- {}\n--- [d]\n".as_bytes(),
+		)))
+		.map(|doc| {
+			let doc = doc.unwrap();
+			(doc.name().map(str::to_owned), doc.content().to_owned())
+		})
+		.collect::<Vec<_>>();
+		assert_eq!(
+			docs,
+			[
+				(Some("a".to_owned()), "a: &x 1\n".to_owned()),
+				(Some("b".to_owned()), "b: [&x 1]\n".to_owned()),
+				(None, "? [c]\n: 3\n".to_owned()),
+				(None, "--- [d]\n".to_owned()),
+			]
+		);
+	}
+
+	#[test]
+	fn reverse_documents() {
+		assert_eq!(
+			transform("a\n--- b\n--- c\n", reverse),
+			["--- c\n", "--- b\n", "a\n"]
+		);
+	}
+
+	#[test]
+	fn shuffle_is_deterministic() {
+		const INPUT: &str = "--- a\n--- b\n--- c\n--- d\n--- e\n";
+		let shuffled = transform(INPUT, |docs| shuffle(docs, 42));
+		assert_eq!(shuffled, transform(INPUT, |docs| shuffle(docs, 42)));
+		assert_ne!(shuffled, transform(INPUT, |docs| docs));
+		let mut sorted = shuffled.clone();
+		sorted.sort();
+		assert_eq!(sorted, transform(INPUT, |docs| docs));
+	}
+
+	#[test]
+	fn reserialize_layouts() {
+		const INPUT: &str = "a: {b: [1, '2']}   # c\nd: |\n  text\n";
+		let block = EmitterOptions {
+			indent: 4,
+			..EmitterOptions::default()
+		};
+		assert_eq!(
+			transform(INPUT, |docs| reserialize(docs, Layout::Block, block, 1)),
+			["a:\n    b:\n    - 1\n    - '2'\nd: |\n    text\n"]
+		);
+		let flow = EmitterOptions {
+			width: None,
+			..EmitterOptions::default()
+		};
+		assert_eq!(
+			transform(INPUT, |docs| reserialize(docs, Layout::Flow, flow, 2)),
+			["{a: {b: [1, '2']}, d: \"text\\n\"}\n"]
+		);
+	}
+
+	#[test]
+	fn reserialize_canonical() {
+		let canonical = EmitterOptions {
+			canonical: true,
+			..EmitterOptions::default()
+		};
+		assert_eq!(
+			transform("a: [1, '2', ~]\n", |docs| reserialize(
+				docs,
+				Layout::Preserve,
+				canonical,
+				1
+			)),
+			["---\n!!map {\n  ? !!str \"a\"\n  : !!seq [\n    !!int \"1\",\n    !!str \"2\",\n    !!null \"~\",\n  ],\n}\n"]
+		);
+	}
+
+	#[test]
+	fn strip_comments_keeps_everything_else() {
+		assert_eq!(
+			transform(
+				"--- # start\n# whole line\na: 1 # trailing\nb: \"# not\" #x\nc: |\n  # kept\n  text\n# after\nd: e#f\n",
+				|docs| strip_comments(docs, 1)
+			),
+			["---\na: 1\nb: \"# not\"\nc: |\n  # kept\n  text\nd: e#f\n"]
+		);
+	}
+
+	#[test]
+	fn assign_ids_to_documents() {
+		let docs = assign_ids(
+			Box::new(Chunker::new("--- a\n--- b\n--- a\n".as_bytes())),
+			2,
+		)
+		.collect::<io::Result<Vec<_>>>()
+		.unwrap();
+		let ids = docs.iter().map(|doc| doc.id().unwrap()).collect::<Vec<_>>();
+		assert_eq!(ids[0].len(), ID_LEN);
+		assert_ne!(ids[0], ids[1]);
+		assert_eq!(ids[2], format!("{}-2", ids[0]));
+	}
+
+	#[test]
+	fn trailer_summarizes_documents() {
+		let docs = transform("--- a\n--- bc\n", |docs| {
+			trailer(docs, hash::Algorithm::Sha256)
+		});
+		assert_eq!(docs.len(), 3);
+		assert_eq!(
+			docs[2],
+			format!(
+				"--- # yaml-split trailer\ndocuments: 2\nbytes: 13\nhashes:\n- sha256:{a}\n- sha256:{bc}\n",
+				a = hash::Algorithm::Sha256.hex_digest(b"--- a\n"),
+				bc = hash::Algorithm::Sha256.hex_digest(b"--- bc\n"),
+			)
+		);
+		assert_eq!(
+			transform("", |docs| trailer(docs, hash::Algorithm::Sha256)),
+			["--- # yaml-split trailer\ndocuments: 0\nbytes: 0\nhashes: []\n"]
+		);
+	}
+
+	#[test]
+	fn named_documents() {
+		const INPUT: &str = "kind: Pod\nmetadata: {name: web}\n--- {kind: Job}\n--- x\n";
+		let names = |paths: &[&str], regex: Option<&str>| {
+			let docs = Chunker::new(INPUT.as_bytes())
+				.map(|doc| doc.map(|doc| doc.with_name("old".to_owned())));
+			let paths = paths.iter().map(|path| path.parse().unwrap()).collect();
+			let regex = regex.map(|regex| regex.parse().unwrap());
+			named(Box::new(docs), paths, regex)
+				.map(|doc| doc.unwrap().name().unwrap().to_owned())
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(
+			names(&[".kind", ".metadata.name"], None),
+			["Pod-web", "old", "old"]
+		);
+		assert_eq!(
+			names(&[".metadata.name"], Some("kind: (\\w+)")),
+			["web", "Job", "old"]
+		);
+		assert_eq!(names(&[], Some("^--- .")), ["old", "--- {", "--- x"]);
+	}
+
+	#[test]
+	fn matching_documents() {
+		const INPUT: &str = "kind: Pod\n--- {kind: Job}\n---\nkind: Service\n";
+		let grep = |patterns: &[&str], invert| {
+			let patterns = patterns.iter().map(|p| p.parse().unwrap()).collect();
+			transform(INPUT, |docs| matching(docs, patterns, invert))
+		};
+		assert_eq!(
+			grep(&["Job|Pod"], false),
+			["kind: Pod\n", "--- {kind: Job}\n"]
+		);
+		assert_eq!(grep(&["^kind", "o"], false), ["kind: Pod\n"]);
+		assert_eq!(grep(&["Job", "Pod"], true), ["---\nkind: Service\n"]);
+	}
+
+	#[test]
+	fn numbered_lines() {
+		assert_eq!(
+			transform("a: 1\n\nb: 2\n--- c", number_lines),
+			["     1\ta: 1\n     2\t\n     3\tb: 2\n", "     1\t--- c",]
+		);
+	}
+
+	#[test]
+	fn document_markers() {
+		const INPUT: &str =
+			"a: 1\n--- b\n...\n---\n# c\nd: |\n  ...\n--- # e\nf\n... # g\n%YAML 1.2\n---\nh\n";
+		assert_eq!(
+			transform(INPUT, strip_markers),
+			[
+				"a: 1\n",
+				"b\n",
+				"# c\nd: |\n  ...\n",
+				"# e\nf\n",
+				"%YAML 1.2\n---\nh\n"
+			]
+		);
+		assert_eq!(
+			transform("---\n--- # i\n...\n", strip_markers),
+			["~\n", "# i\n~\n"]
+		);
+		assert_eq!(
+			transform(INPUT, normalize_markers),
+			[
+				"---\na: 1\n",
+				"--- b\n...",
+				"---\n# c\nd: |\n  ...\n",
+				"--- # e\nf\n...",
+				"%YAML 1.2\n---\nh\n"
+			]
+		);
+	}
+
+	#[test]
+	fn comment_only_documents() {
+		const INPUT: &str = "# a\n---\n# b\n--- # c\n...\n--- d\n--- # e\n";
+		assert_eq!(transform(INPUT, skip_comment_only), ["--- d\n"]);
+		let docs = attach_comment_only(Box::new(Chunker::new(INPUT.as_bytes())))
+			.collect::<io::Result<Vec<_>>>()
+			.unwrap();
+		let docs = docs
+			.iter()
+			.map(|doc| (doc.content(), doc.source()))
+			.collect::<Vec<_>>();
+		assert_eq!(docs, [("# b\n# c\n--- d\n", 4..30), ("--- # e\n", 30..38)]);
+	}
+
+	#[test]
+	fn tagged_documents() {
+		const INPUT: &str = "--- !A 1\n--- !B [2]\n--- 3\n--- !A {a: 4}\n";
+		assert_eq!(
+			transform(INPUT, |docs| tagged(docs, vec!["!A".to_owned()])),
+			["--- !A 1\n", "--- !A {a: 4}\n"]
+		);
+		assert_eq!(
+			transform(INPUT, |docs| tagged(
+				docs,
+				vec!["!B".to_owned(), "!C".to_owned()]
+			)),
+			["--- !B [2]\n"]
+		);
+	}
+
+	#[test]
+	fn having_paths() {
+		const INPUT: &str =
+			"a: {b: null}\n--- {a: 1}\n--- [x, {b: 2}]\n--- {c: &x {b: 3}, a: *x}\n";
+		let paths = |paths: &[&str]| paths.iter().map(|path| path.parse().unwrap()).collect();
+		assert_eq!(
+			transform(INPUT, |docs| having(docs, paths(&[".a.b"]))),
+			["a: {b: null}\n", "--- {c: &x {b: 3}, a: *x}\n"]
+		);
+		assert_eq!(
+			transform(INPUT, |docs| having(docs, paths(&[".[1].b"]))),
+			["--- [x, {b: 2}]\n"]
+		);
+		assert_eq!(
+			transform(INPUT, |docs| having(docs, paths(&[".a", ".c"]))),
+			["--- {c: &x {b: 3}, a: *x}\n"]
+		);
+	}
+
+	#[test]
+	fn satisfying_conditions() {
+		const INPUT: &str = "kind: Pod\n--- {kind: ConfigMap, data: {a: 1}}\n--- [kind]\n";
+		let conditions = |texts: &[&str]| texts.iter().map(|text| text.parse().unwrap()).collect();
+		assert_eq!(
+			transform(INPUT, |docs| satisfying(
+				docs,
+				conditions(&[".kind=ConfigMap"])
+			)),
+			["--- {kind: ConfigMap, data: {a: 1}}\n"]
+		);
+		assert_eq!(
+			transform(INPUT, |docs| satisfying(
+				docs,
+				conditions(&[".kind!=Pod", ".data.a!=2"])
+			)),
+			["--- {kind: ConfigMap, data: {a: 1}}\n", "--- [kind]\n"]
+		);
+	}
+
+	#[test]
+	fn annotate_documents() {
+		assert_eq!(
+			transform("a: 1\n--- b\n", |docs| annotate(
+				docs,
+				"in.yaml".to_owned(),
+				0,
+				None,
+				false
+			)),
+			[
+				"# document 1 (bytes 0..5 of in.yaml)\na: 1\n",
+				"# document 2 (bytes 5..11 of in.yaml)\n--- b\n"
+			]
+		);
+		assert_eq!(
+			transform("a\n", |docs| annotate(
+				docs,
+				"in.yaml".to_owned(),
+				2,
+				Some(3),
+				false
+			)),
+			["# document 3 of 3 (bytes 0..2 of in.yaml)\na\n"]
+		);
+		let read = |text: &'static str, name: &str, index| {
+			let input = Input {
+				name: name.to_owned(),
+				index,
+			};
+			Chunker::new(text.as_bytes())
+				.map(move |doc| doc.map(|doc| doc.with_input(input.clone())))
+		};
+		let interleave = |per_input| {
+			let mut a = read("--- a1\n--- a2\n", "a.yaml", 0);
+			let b = read("b1\n", "b.yaml", 1);
+			let docs = a.next().into_iter().chain(b).chain(a).collect::<Vec<_>>();
+			annotate(
+				Box::new(docs.into_iter()),
+				"-".to_owned(),
+				0,
+				None,
+				per_input,
+			)
+			.map(|doc| doc.unwrap().content().lines().next().unwrap().to_owned())
+			.collect::<Vec<_>>()
+		};
+		assert_eq!(
+			interleave(false),
+			[
+				"# document 1 (bytes 0..7 of a.yaml, input 1)",
+				"# document 2 (bytes 0..3 of b.yaml, input 2)",
+				"# document 3 (bytes 7..14 of a.yaml, input 1)",
+			]
+		);
+		assert_eq!(
+			interleave(true),
+			[
+				"# document 1 (bytes 0..7 of a.yaml, input 1)",
+				"# document 1 (bytes 0..3 of b.yaml, input 2)",
+				"# document 2 (bytes 7..14 of a.yaml, input 1)",
+			]
+		);
+	}
+}
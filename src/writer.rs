@@ -0,0 +1,243 @@
+//! Writing documents back into a single YAML stream, the inverse of
+//! splitting one.
+//!
+//! [`DocumentWriter`] appends the text of each document to the stream as-is,
+//! except that it adds whatever markers are needed to keep each document
+//! separate from those around it, and makes sure every document ends with a
+//! line break so the next marker starts on its own line:
+//!
+//! ```
+//! use yaml_split::writer::DocumentWriter;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let mut writer = DocumentWriter::new(vec![]);
+//! writer.write_document("a: 1")?;
+//! writer.write_document("%YAML 1.2\n--- b\n")?;
+//! let stream = writer.into_inner();
+//! assert_eq!(stream, b"a: 1\n...\n%YAML 1.2\n--- b\n");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// How much of the end of an existing stream to read at a time, looking for
+/// the last line that isn't blank or a comment.
+const TAIL_LEN: u64 = 64 << 10;
+
+/// A writer of documents into a single stream.
+///
+/// Each call to [`DocumentWriter::write_document`] takes the text of one
+/// file's worth of YAML, which may already start with directives or a `---`
+/// marker, or hold several documents or none. Only the markers needed
+/// between them are added, which the writer tells from how the stream so far
+/// ends.
+pub struct DocumentWriter<W> {
+	inner: W,
+	/// Whether any text so far has contained a document.
+	has_documents: bool,
+	/// Whether the last document so far is still open, meaning that it wasn't
+	/// ended by a `...` marker.
+	open: bool,
+	/// Whether the stream so far ends with a bare `---` marker, which starts
+	/// a document that the next text can fill in without a marker of its own.
+	separated: bool,
+	/// Whether the stream so far is missing the line break at its end.
+	needs_newline: bool,
+}
+
+impl<W: Write> DocumentWriter<W> {
+	/// Returns a writer that starts a new stream in `inner`.
+	pub fn new(inner: W) -> Self {
+		Self {
+			inner,
+			has_documents: false,
+			open: false,
+			separated: false,
+			needs_newline: false,
+		}
+	}
+
+	/// Returns a writer that continues the existing stream at `path` in
+	/// `inner`, which should append to it. The stream need not exist yet.
+	///
+	/// Only the end of the stream is read, back to its last line that isn't
+	/// blank or a comment, since that's all that decides the next marker.
+	pub(crate) fn appending(inner: W, path: &Path) -> io::Result<Self> {
+		let mut writer = Self::new(inner);
+		let mut file = match File::open(path) {
+			Ok(file) => file,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(writer),
+			Err(err) => return Err(err),
+		};
+		let len = file.metadata()?.len();
+		let mut tail_len = TAIL_LEN;
+		loop {
+			let start = len.saturating_sub(tail_len);
+			let mut tail = vec![];
+			file.seek(SeekFrom::Start(start))?;
+			Read::by_ref(&mut file)
+				.take(len - start)
+				.read_to_end(&mut tail)?;
+			let tail = String::from_utf8_lossy(&tail);
+			// The first line may have started before the tail did.
+			let whole = match start {
+				0 => &tail[..],
+				_ => tail.split_once('\n').map_or("", |(_, rest)| rest),
+			};
+			let last = whole.lines().rev().find(|line| !is_insignificant(line));
+			if last.is_some() || start == 0 {
+				writer.has_documents = last.is_some();
+				writer.open = last.is_some_and(|line| !is_marker(line, "..."));
+				writer.separated = last.is_some_and(is_bare_marker);
+				writer.needs_newline = !tail.is_empty() && !tail.ends_with('\n');
+				return Ok(writer);
+			}
+			tail_len *= 2;
+		}
+	}
+
+	/// Appends `content` to the stream, after any markers it needs to be kept
+	/// apart from the documents before it.
+	pub fn write_document(&mut self, content: &str) -> io::Result<()> {
+		if self.needs_newline {
+			self.inner.write_all(b"\n")?;
+			self.needs_newline = false;
+		}
+		let mut significant = content
+			.trim_start_matches('\u{FEFF}')
+			.lines()
+			.filter(|line| !is_insignificant(line));
+		let Some(first) = significant.next() else {
+			// Text of only comments and blank lines adds no documents, so it
+			// can follow anything without a marker.
+			return self.write_with_newline(content);
+		};
+
+		if first.starts_with('%') {
+			// Directives are only allowed after the previous document ends.
+			if self.open {
+				self.inner.write_all(b"...\n")?;
+			}
+		} else if !is_marker(first, "---") && self.has_documents && !self.separated {
+			self.inner.write_all(b"---\n")?;
+		}
+		self.write_with_newline(content)?;
+
+		let last = significant.next_back().unwrap_or(first);
+		self.has_documents = true;
+		self.open = !is_marker(last, "...");
+		self.separated = is_bare_marker(last);
+		Ok(())
+	}
+
+	/// Flushes the underlying writer.
+	pub fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()
+	}
+
+	/// Returns a mutable reference to the underlying writer.
+	pub(crate) fn get_mut(&mut self) -> &mut W {
+		&mut self.inner
+	}
+
+	/// Returns the underlying writer.
+	pub fn into_inner(self) -> W {
+		self.inner
+	}
+
+	fn write_with_newline(&mut self, content: &str) -> io::Result<()> {
+		self.inner.write_all(content.as_bytes())?;
+		if !content.is_empty() && !content.ends_with('\n') {
+			self.inner.write_all(b"\n")?;
+		}
+		Ok(())
+	}
+}
+
+/// Returns whether `line` is blank or only a comment.
+pub(crate) fn is_insignificant(line: &str) -> bool {
+	let line = line.trim_start();
+	line.is_empty() || line.starts_with('#')
+}
+
+/// Returns whether `line` starts with the document marker `marker`.
+pub(crate) fn is_marker(line: &str, marker: &str) -> bool {
+	line.strip_prefix(marker)
+		.is_some_and(|rest| rest.chars().next().map_or(true, char::is_whitespace))
+}
+
+/// Returns whether `line` is a `---` marker with nothing after it but perhaps
+/// a comment.
+fn is_bare_marker(line: &str) -> bool {
+	is_marker(line, "---") && is_insignificant(&line[3..])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::fs;
+
+	fn join(files: &[&str]) -> String {
+		let mut writer = DocumentWriter::new(vec![]);
+		for file in files {
+			writer.write_document(file).unwrap();
+		}
+		String::from_utf8(writer.into_inner()).unwrap()
+	}
+
+	#[test]
+	fn join_adds_only_needed_markers() {
+		assert_eq!(
+			join(&["a: 1", "# comment\n", "---\nb: 2\n", "c: 3\n"]),
+			"a: 1\n# comment\n---\nb: 2\n---\nc: 3\n"
+		);
+		assert_eq!(join(&["a: 1\n---\n", "b: 2\n"]), "a: 1\n---\nb: 2\n");
+	}
+
+	#[test]
+	fn join_resumes_existing_streams() {
+		let path = std::env::temp_dir().join(format!("yaml-split-join-{}", std::process::id()));
+		let resumed = |existing: &str, file: &str| {
+			fs::write(&path, existing).unwrap();
+			let mut writer = DocumentWriter::appending(vec![], &path).unwrap();
+			writer.write_document(file).unwrap();
+			String::from_utf8(writer.into_inner()).unwrap()
+		};
+		assert_eq!(resumed("", "a: 1\n"), "a: 1\n");
+		assert_eq!(resumed("# only a comment\n", "a: 1\n"), "a: 1\n");
+		assert_eq!(resumed("a: 1", "b: 2\n"), "\n---\nb: 2\n");
+		assert_eq!(resumed("a: 1\n---\n", "b: 2\n"), "b: 2\n");
+		assert_eq!(resumed("a: 1\n--- # next\n\n", "b: 2\n"), "b: 2\n");
+		assert_eq!(resumed("a: 1\n--- b\n", "c\n"), "---\nc\n");
+		assert_eq!(
+			resumed("a: 1\n...\n# end\n", "%YAML 1.2\n--- b\n"),
+			"%YAML 1.2\n--- b\n"
+		);
+		assert_eq!(
+			resumed("a: 1\n", "%YAML 1.2\n--- b\n"),
+			"...\n%YAML 1.2\n--- b\n"
+		);
+		let long = format!("a: 1\n{}", "#\n".repeat(TAIL_LEN as usize));
+		assert_eq!(resumed(&long, "b\n"), "---\nb\n");
+		fs::remove_file(&path).unwrap();
+		let writer = DocumentWriter::appending(vec![], &path).unwrap();
+		assert!(!writer.has_documents && !writer.needs_newline);
+	}
+
+	#[test]
+	fn join_ends_documents_before_directives() {
+		assert_eq!(
+			join(&[
+				"%YAML 1.2\n---\na: 1\n",
+				"%YAML 1.2\n--- b\n",
+				"c\n...\n",
+				"%TAG ! x:\n--- d\n"
+			]),
+			"%YAML 1.2\n---\na: 1\n...\n%YAML 1.2\n--- b\n---\nc\n...\n%TAG ! x:\n--- d\n"
+		);
+	}
+}
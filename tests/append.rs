@@ -0,0 +1,51 @@
+//! Tests that `split --raw --append` continues the YAML stream in its output
+//! file, however the stream ends.
+
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Appends the split documents of `input` to a file that holds `existing`,
+/// and returns what the file holds then.
+fn append(existing: &str, input: &str) -> String {
+	let path = std::env::temp_dir().join(format!(
+		"yaml-split-append-{}-{}.yaml",
+		std::process::id(),
+		existing.len()
+	));
+	fs::write(&path, existing).unwrap();
+	let mut child = Command::new(env!("CARGO_BIN_EXE_yaml-split"))
+		.args(["split", "--raw", "--append", "-o"])
+		.arg(&path)
+		.stdin(Stdio::piped())
+		.spawn()
+		.unwrap();
+	child
+		.stdin
+		.take()
+		.unwrap()
+		.write_all(input.as_bytes())
+		.unwrap();
+	assert!(child.wait().unwrap().success());
+	let stream = fs::read_to_string(&path).unwrap();
+	fs::remove_file(&path).unwrap();
+	stream
+}
+
+#[test]
+fn append_after_missing_line_break() {
+	assert_eq!(append("x: 1", "a: 1\n"), "x: 1\n---\na: 1\n");
+}
+
+#[test]
+fn append_after_open_document() {
+	assert_eq!(
+		append("x: 1\n", "a: 1\n---\nb: 2\n"),
+		"x: 1\n---\na: 1\n---\nb: 2\n"
+	);
+}
+
+#[test]
+fn append_after_ended_document() {
+	assert_eq!(append("x: 1\n...\n", "a: 1\n"), "x: 1\n...\n---\na: 1\n");
+}
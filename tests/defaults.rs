@@ -0,0 +1,81 @@
+//! Tests of flag defaults from the environment and the config file, which run
+//! the binary since the defaults come from its environment.
+
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Splits `input` with `args` and the environment variables `env`, leaving
+/// out any config file of the user running the tests.
+fn split(input: &str, args: &[&str], env: &[(&str, &str)]) -> Output {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_yaml-split"))
+		.arg("split")
+		.args(args)
+		.env("YAML_SPLIT_CONFIG", "/nonexistent/config.toml")
+		.envs(env.iter().copied())
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.unwrap();
+	// A split that fails on its arguments exits without reading its input.
+	let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+	child.wait_with_output().unwrap()
+}
+
+#[test]
+fn raw_and_delimiter_defaults() {
+	let env = [("YAML_SPLIT_RAW", "true"), ("YAML_SPLIT_DELIMITER", "#\n")];
+	let output = split("a: 1\n---\nb: 2\n", &[], &env);
+	assert!(output.status.success(), "{output:?}");
+	assert_eq!(output.stdout, b"a: 1\n#\n---\nb: 2\n");
+
+	// Neither default applies where the flag couldn't be given.
+	let output = split("a: 1\n", &["--get", ".a"], &env);
+	assert!(output.status.success(), "{output:?}");
+	assert_eq!(output.stdout, b"1\n");
+	let output = split("a: 1\n", &["--print0"], &env);
+	assert!(output.status.success(), "{output:?}");
+	assert_eq!(output.stdout, b"a: 1\n\0");
+	let output = split("a: 1\n", &[], &[("YAML_SPLIT_DELIMITER", "#\n")]);
+	assert!(output.status.success(), "{output:?}");
+	assert!(output.stdout.starts_with(b">>> START CHUNK"));
+
+	// On the command line, they're still checked.
+	let output = split("a: 1\n", &["--raw", "--get", ".a"], &[]);
+	assert!(!output.status.success());
+	let output = split("a: 1\n", &["--delimiter", "#"], &[]);
+	assert!(!output.status.success());
+}
+
+#[test]
+fn limit_defaults_from_config_file() {
+	let path = std::env::temp_dir().join(format!("yaml-split-config-{}.toml", std::process::id()));
+	fs::write(&path, "max-doc-bytes = 5\nwarn-doc-bytes = 3\n").unwrap();
+	let config = path.to_str().unwrap();
+	let output = split("a: 1\n---\nb: 123\n", &[], &[("YAML_SPLIT_CONFIG", config)]);
+	fs::remove_file(&path).unwrap();
+	assert!(!output.status.success());
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	assert!(stderr.contains("document 1 is 5 bytes"), "{stderr}");
+	assert!(stderr.contains("limit of 5 bytes"), "{stderr}");
+}
+
+#[test]
+fn lexical_backend_default() {
+	let env = [("YAML_SPLIT_BACKEND", "lexical")];
+	let output = split("a: 1\n---\nb: 2\n", &["--raw"], &env);
+	assert!(output.status.success(), "{output:?}");
+	assert_eq!(output.stdout, b"a: 1\n---\nb: 2\n");
+
+	// The default gives way to libyaml where only libyaml can be used.
+	let output = split("a: 1\n", &["--report", "tags"], &env);
+	assert!(output.status.success(), "{output:?}");
+	let output = split("a: 1\n", &["--backend", "lexical", "--report", "tags"], &[]);
+	assert!(!output.status.success());
+
+	let output = split("a: 1\n", &["--lexical", "--raw"], &[]);
+	assert!(output.status.success(), "{output:?}");
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	assert!(stderr.contains("--lexical is deprecated"), "{stderr}");
+}
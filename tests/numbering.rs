@@ -0,0 +1,104 @@
+//! Tests that the diagnostics about documents number them by their place in
+//! the input, even after others were skipped before them.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Splits `input` with `args`, and returns the standard error and whether the
+/// split succeeded.
+fn split(input: &str, args: &[&str]) -> (String, bool) {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_yaml-split"))
+		.arg("split")
+		.args(args)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::null())
+		.stderr(Stdio::piped())
+		.spawn()
+		.unwrap();
+	child
+		.stdin
+		.take()
+		.unwrap()
+		.write_all(input.as_bytes())
+		.unwrap();
+	let output = child.wait_with_output().unwrap();
+	(
+		String::from_utf8(output.stderr).unwrap(),
+		output.status.success(),
+	)
+}
+
+#[test]
+fn empty_documents_after_skipped_ones() {
+	let (stderr, ok) = split(
+		"a: 1\n---\n# c\n---\n---\n",
+		&["--comment-only-docs", "skip", "--empty-docs", "error"],
+	);
+	assert!(!ok);
+	assert!(stderr.contains("document 3 is empty"), "{stderr}");
+}
+
+#[test]
+fn large_documents_after_skipped_ones() {
+	let (stderr, ok) = split(
+		"a: 1\n---\nb: 12345678901234\n---\nc: 12345\n",
+		&[
+			"--max-doc-bytes",
+			"15",
+			"--oversize",
+			"skip",
+			"--warn-doc-bytes",
+			"5",
+		],
+	);
+	assert!(ok, "{stderr}");
+	assert!(stderr.contains("skipped document 2,"), "{stderr}");
+	assert!(stderr.contains("document 3 is 13 bytes"), "{stderr}");
+}
+
+#[test]
+fn oversize_documents_after_skipped_ones() {
+	let (stderr, ok) = split(
+		"a: 1\n---\n# c\n---\nb: 12345678901234\n",
+		&[
+			"--comment-only-docs",
+			"skip",
+			"--max-doc-bytes",
+			"10",
+			"--oversize",
+			"truncate",
+		],
+	);
+	assert!(ok, "{stderr}");
+	assert!(stderr.contains("truncated document 3 "), "{stderr}");
+}
+
+#[test]
+fn unsorted_documents_after_skipped_ones() {
+	let (stderr, ok) = split(
+		"k: 1\n---\n# c\n---\nk: 3\n---\nk: 2\n",
+		&["--comment-only-docs", "skip", "--assert-sorted", ".k"],
+	);
+	assert!(!ok);
+	assert!(
+		stderr.contains("document 4 is out of order by .k: \"2\" sorts before \"3\" of document 3"),
+		"{stderr}"
+	);
+}
+
+#[test]
+fn json_lines_after_blank_ones() {
+	let (stderr, ok) = split(
+		"{\"a\":1}\n\n{\"b\":12345678901}\n",
+		&[
+			"--from",
+			"ndjson",
+			"--max-doc-bytes",
+			"10",
+			"--oversize",
+			"skip",
+		],
+	);
+	assert!(ok, "{stderr}");
+	assert!(stderr.contains("skipped document 2,"), "{stderr}");
+}
@@ -0,0 +1,62 @@
+//! Tests of `--sandbox`, which run the binary since entering the sandbox is
+//! permanent for the process.
+
+#![cfg(all(target_os = "linux", feature = "sandbox"))]
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn split(dir: &Path, args: &[&str]) -> Output {
+	Command::new(env!("CARGO_BIN_EXE_yaml-split"))
+		.current_dir(dir)
+		.arg("split")
+		.arg("--sandbox")
+		.args(args)
+		.output()
+		.unwrap()
+}
+
+/// Returns whether the system can't provide the sandbox at all, in which case
+/// there's nothing to test.
+fn unsupported(output: &Output) -> bool {
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	!output.status.success() && stderr.contains("--sandbox requires")
+}
+
+#[test]
+fn sandbox_reads_recursive_inputs() {
+	let dir = std::env::temp_dir().join(format!("yaml-split-sandbox-{}", std::process::id()));
+	for (file, content) in [
+		("tree/b.yaml", "b: 1\n"),
+		("tree/a/c.yaml", "c: 1\n---\nd: 2\n"),
+	] {
+		let path = dir.join(file);
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		fs::write(path, content).unwrap();
+	}
+
+	let output = split(&dir, &["--raw", "--recursive", "tree"]);
+	if unsupported(&output) {
+		fs::remove_dir_all(&dir).unwrap();
+		return;
+	}
+	assert!(output.status.success(), "{output:?}");
+	assert_eq!(
+		String::from_utf8(output.stdout).unwrap(),
+		"b: 1\nc: 1\n---\nd: 2\n"
+	);
+
+	let output = split(&dir, &["--recursive", "tree", "--output-dir", "out"]);
+	assert!(output.status.success(), "{output:?}");
+	assert_eq!(
+		String::from_utf8(output.stdout).unwrap(),
+		"out/b/doc-0001.yaml\nout/a/c/doc-0001.yaml\nout/a/c/doc-0002.yaml\n"
+	);
+	assert_eq!(
+		fs::read_to_string(dir.join("out/a/c/doc-0002.yaml")).unwrap(),
+		"---\nd: 2\n"
+	);
+
+	fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,42 @@
+//! Tests that `--total-counts` counts the documents that the split writes,
+//! by running the binary on files, which the count has to read twice.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn split(dir: &Path, args: &[&str]) -> String {
+	let output = Command::new(env!("CARGO_BIN_EXE_yaml-split"))
+		.current_dir(dir)
+		.arg("split")
+		.args(args)
+		.output()
+		.unwrap();
+	assert!(output.status.success(), "{output:?}");
+	String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn total_counts_skip_documents_seen_before() {
+	let dir = std::env::temp_dir().join(format!("yaml-split-total-{}", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	fs::write(dir.join("seen.yaml"), "--- b\n").unwrap();
+	fs::write(dir.join("input.yaml"), "--- a\n--- b\n--- a\n--- c\n").unwrap();
+
+	split(&dir, &["seen.yaml", "--raw", "--dedup-state", "state"]);
+	let args = [
+		"input.yaml",
+		"--raw",
+		"--annotate",
+		"--total-counts",
+		"--dedup-state",
+		"state",
+	];
+	let output = split(&dir, &args);
+	fs::remove_dir_all(&dir).unwrap();
+	assert_eq!(
+		output,
+		"# document 1 of 2 (bytes 0..6 of input.yaml)\n--- a\n\
+		 # document 2 of 2 (bytes 18..24 of input.yaml)\n--- c\n"
+	);
+}